@@ -0,0 +1,59 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use libp2p::PeerId;
+use p2pchat::app::ChatMessage;
+use p2pchat::conversation::Conversation;
+use p2pchat::protocol::{self, Envelope, Payload};
+
+// The paths every chat message goes through, run with `cargo bench`
+
+fn chat_message(clock: u64) -> ChatMessage {
+    let mut message = ChatMessage::new(
+        Some(PeerId::random()),
+        Some(String::from("bench")),
+        format!(
+            "message {} with a bit of text, like most chat messages",
+            clock
+        ),
+    );
+    message.clock = clock;
+    message
+}
+
+fn envelope(c: &mut Criterion) {
+    let envelope = Envelope::new(Payload::Chat(chat_message(1)));
+    let data = envelope.encode().unwrap();
+
+    c.bench_function("envelope encode", |b| {
+        b.iter(|| black_box(&envelope).encode().unwrap())
+    });
+    c.bench_function("envelope decode", |b| {
+        b.iter(|| protocol::decode(black_box(&data)).unwrap())
+    });
+}
+
+fn history(c: &mut Criterion) {
+    let messages = (1..=1000).map(chat_message).collect::<Vec<ChatMessage>>();
+
+    c.bench_function("history insert 1000 in order", |b| {
+        b.iter(|| {
+            let mut conversation = Conversation::default();
+            for message in messages.iter() {
+                conversation.push_message(message.clone());
+            }
+            conversation
+        })
+    });
+    // Gossipsub doesn't keep the order, late messages are inserted in between
+    c.bench_function("history insert 1000 reversed", |b| {
+        b.iter(|| {
+            let mut conversation = Conversation::default();
+            for message in messages.iter().rev() {
+                conversation.push_message(message.clone());
+            }
+            conversation
+        })
+    });
+}
+
+criterion_group!(benches, envelope, history);
+criterion_main!(benches);
@@ -0,0 +1,111 @@
+use libp2p::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
+
+use crate::app::App;
+use crate::i18n::{tr, trf, Msg};
+use crate::invite;
+use crate::transport;
+use crate::utils;
+
+// What is wrong with the typed address and how to fix it, shown while typing. None if it can be
+// dialed or nothing was typed yet
+pub fn hint(app: &App) -> Option<String> {
+    let input = app.ui.addr_input.as_str().trim();
+    if input.is_empty() || invite::is_invite(input) {
+        return None;
+    }
+    let addr = match transport::parse_addr(input) {
+        Ok(addr) => addr,
+        Err(_) => return Some(parse_hint(input).to_string()),
+    };
+
+    let mut protocols = addr.iter();
+    match protocols.next() {
+        // Only the peer, without where to reach it
+        Some(Protocol::P2p(_)) => return Some(tr(Msg::AddrHintMissingTransport).to_string()),
+        Some(
+            Protocol::Ip4(_)
+            | Protocol::Ip6(_)
+            | Protocol::Dns(_)
+            | Protocol::Dns4(_)
+            | Protocol::Dns6(_),
+        ) => {
+            if !matches!(protocols.next(), Some(Protocol::Tcp(_))) {
+                return Some(tr(Msg::AddrHintMissingPort).to_string());
+            }
+        }
+        _ => {}
+    }
+
+    // Peers we know the address of are better dialed with their id, so the connection is
+    // authenticated as them
+    if peer_id_of(&addr).is_none() {
+        if let Some(peer_id) = known_peer(&addr, app) {
+            return Some(trf(
+                Msg::AddrHintMissingPeerId,
+                &[&utils::short_peer_id(&peer_id)],
+            ));
+        }
+    }
+    None
+}
+
+fn parse_hint(input: &str) -> &'static str {
+    if input.contains("/p2p/") {
+        tr(Msg::AddrHintInvalidPeerId)
+    } else if !input.starts_with('/') && input.contains(':') {
+        // e.g. `example.com:4001`
+        tr(Msg::AddrHintHostPort)
+    } else if !input.starts_with('/') {
+        tr(Msg::AddrHintLeadingSlash)
+    } else {
+        tr(Msg::AddrHintInvalid)
+    }
+}
+
+fn peer_id_of(addr: &Multiaddr) -> Option<PeerId> {
+    addr.iter().find_map(|protocol| match protocol {
+        Protocol::P2p(hash) => PeerId::from_multihash(hash).ok(),
+        _ => None,
+    })
+}
+
+// The peer listening on or dialed at the address
+fn known_peer(addr: &Multiaddr, app: &App) -> Option<PeerId> {
+    let peer_addrs = app
+        .connection
+        .peer_addrs
+        .iter()
+        .flat_map(|(peer_id, addrs)| addrs.iter().map(move |addr| (peer_id, addr)));
+    let dialed_addrs = app.connection.dialed_addrs.iter();
+    peer_addrs
+        .chain(dialed_addrs)
+        .find(|(_, known)| *known == addr)
+        .map(|(peer_id, _)| *peer_id)
+}
+
+// The rest of the most recent address that starts with what was typed, shown after the cursor
+pub fn completion(app: &App) -> Option<String> {
+    let input = app.ui.addr_input.as_str();
+    if input.is_empty() || !app.ui.addr_input.is_cursor_at_end() {
+        return None;
+    }
+    app.recent_addrs
+        .all()
+        .into_iter()
+        .map(|addr| addr.to_string())
+        .find(|candidate| candidate.len() > input.len() && candidate.starts_with(input))
+        .map(|candidate| candidate[input.len()..].to_string())
+}
+
+// Takes the shown completion into the field. Returns whether there was one
+pub fn complete(app: &mut App) -> bool {
+    match completion(app) {
+        Some(rest) => {
+            let text = format!("{}{}", app.ui.addr_input.as_str(), rest);
+            app.ui.addr_input.set(text);
+            true
+        }
+        None => false,
+    }
+}
@@ -1,51 +1,385 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::io::Stdout;
+use std::mem;
+use std::time::{Duration, Instant};
 
-use crate::connection::{self, Connection};
+use crate::audit::{self, AuditAction, AuditLog};
+use crate::away::{self, AutoAway};
+use crate::capture::TerminalSnapshot;
+use crate::config::{Config, TransformDirection};
+use crate::connection::{self, Connection, ConnectionCommand};
+use crate::conversation::{Conversation, ConversationId, Draft, ScrollPosition};
+use crate::crdt::{Pin, TopicState};
+use crate::dedup::SeenIds;
+use crate::drafts::{self, Drafts};
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::events;
+use crate::filters::{ContentFilter, Filtered};
+use crate::flood::{self, Flood};
+use crate::i18n::{tr, trf, Msg};
 use crate::input::{self, InputTask};
-use crate::ui::{self, Ui};
+use crate::kdf::{self, Kdf};
+use crate::missed::{self, MissedSummary};
+use crate::moderation::Moderation;
+use crate::nickcert::NickBindings;
+use crate::nicks::NickRegistry;
+use crate::notify::{self, Attention};
+use crate::patch::{self, Git, Patch};
+use crate::protocol::{Capabilities, Envelope, Payload, StatusCard};
+use crate::receipts::ReceiptLog;
+use crate::recentaddrs::RecentAddrs;
+use crate::roster::Roster;
+use crate::schedule::Schedule;
+use crate::session;
+use crate::snapshot;
+use crate::stats::{self, Stats};
+use crate::tick::{self, Ticks};
+use crate::topickeys;
+use crate::transcript::Transcript;
+use crate::transforms::{Transformed, Transforms};
+use crate::ui::{self, PageFocus, Ui};
+use crate::update::UpdateCheck;
+use crate::utils;
+use crate::validation;
+use crate::vault::DerivedKey;
+use crate::voice::{self, Recorder, VoiceMemo};
 
 use anyhow::Context;
-use crossterm::event::EventStream;
-use futures::{select, StreamExt};
+use chrono::{DateTime, Local};
+use crossterm::event::{Event, EventStream};
+use futures::{select, FutureExt, StreamExt};
 use libp2p::PeerId;
 use serde::{Deserialize, Serialize};
 use tui::backend::CrosstermBackend;
 use tui::Terminal;
+use uuid::Uuid;
+
+// How often expired messages are dropped
+pub const RETENTION_INTERVAL: Duration = Duration::from_secs(60);
+// At most this many queued input events are handled between two redraws
+const INPUT_BATCH: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    Chat,
+    // A peer joined or left a topic
+    Membership,
+    // A connection event, e.g. a failed dial
+    System,
+}
+
+impl Default for MessageKind {
+    fn default() -> Self {
+        Self::Chat
+    }
+}
+
+// How far one of our own messages got
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    Sending,
+    Sent,
+    Failed,
+}
+
+// What sending a payload resulted in. Both are sent once the event loop gets to them, a message
+// to a topic is published then and a direct message is delivered once the peer acknowledges the
+// request. The id finds the message in `pending_publishes` or `pending_sends`
+pub enum Outgoing {
+    Queued(u64),
+    Direct(u64),
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
+    // Messages from peers before ids were introduced get a random one
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
     #[serde(skip)]
     pub source_peer_id: Option<PeerId>,
     pub nick: Option<String>,
     pub text: String,
+    // The message this one replies to
+    #[serde(default)]
+    pub reply_to: Option<Uuid>,
+    // Reactions and the peers who sent them
+    #[serde(skip)]
+    pub reactions: BTreeMap<String, BTreeSet<PeerId>>,
+    #[serde(skip)]
+    pub kind: MessageKind,
+    // Only set for our own messages
+    #[serde(skip)]
+    pub delivery: Option<Delivery>,
+    // When we sent or received the message
+    #[serde(skip, default = "Local::now")]
+    pub received_at: DateTime<Local>,
+    // The author's Lamport clock when sending, 0 from peers without one
+    #[serde(default)]
+    pub clock: u64,
+    // The author's time of sending in milliseconds since the epoch
+    #[serde(default)]
+    pub sent_at: i64,
+    // Seconds after sending when the message disappears for everyone, set by the author in
+    // topics with disappearing messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_in: Option<u64>,
+    // The options of a poll, the text is its question
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll: Option<Vec<String>>,
+    // The option of the poll each peer voted for, stored and synced with `SyncedMessage`
+    #[serde(skip)]
+    pub votes: BTreeMap<PeerId, usize>,
+    // A clip recorded with /voice, the text says what it is for peers that can't play it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voice_memo: Option<VoiceMemo>,
+    // Taken with /snapshot, viewed in a popup so its layout stays intact
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<TerminalSnapshot>,
+    // Made with /patch, the receiver can apply it with `git am`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patch: Option<Patch>,
+    // Sent with /clip, the text is the clipboard contents
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub clip: bool,
+    // Matched a highlighting filter rule
+    #[serde(skip)]
+    pub highlighted: bool,
+    // Came with a history sync, nothing proves the author really sent it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub synced: bool,
 }
 
 impl ChatMessage {
     pub fn new(source_peer_id: Option<PeerId>, nick: Option<String>, text: String) -> Self {
+        let now = Local::now();
         Self {
+            id: Uuid::new_v4(),
             source_peer_id,
             nick,
             text,
+            reply_to: None,
+            reactions: BTreeMap::new(),
+            kind: MessageKind::Chat,
+            delivery: None,
+            received_at: now,
+            clock: 0,
+            sent_at: now.timestamp_millis(),
+            expires_in: None,
+            poll: None,
+            votes: BTreeMap::new(),
+            voice_memo: None,
+            snapshot: None,
+            patch: None,
+            clip: false,
+            highlighted: false,
+            synced: false,
+        }
+    }
+
+    // Histories are sorted by this, so every peer shows messages in the same order no matter
+    // in which order gossipsub delivered them
+    pub fn order_key(&self) -> (u64, i64, Option<PeerId>) {
+        (self.clock, self.sent_at, self.source_peer_id)
+    }
+
+    // Whether a disappearing message is gone. Counted from when it was sent so every peer removes
+    // it at the same time, but never from later than we got it
+    pub fn is_expired(&self, now: DateTime<Local>) -> bool {
+        let expires_in = match self.expires_in {
+            Some(expires_in) => i64::try_from(expires_in).unwrap_or(i64::MAX),
+            None => return false,
+        };
+        let received_at = self.received_at.timestamp_millis();
+        let sent_at = if self.sent_at > 0 {
+            self.sent_at.min(received_at)
+        } else {
+            received_at
+        };
+        now.timestamp_millis() >= sent_at.saturating_add(expires_in.saturating_mul(1000))
+    }
+
+    // How many peers voted for each option of a poll
+    pub fn tally(&self) -> Vec<usize> {
+        let mut tally = vec![0; self.poll.as_ref().map_or(0, |options| options.len())];
+        for option in self.votes.values() {
+            if let Some(count) = tally.get_mut(*option) {
+                *count += 1;
+            }
+        }
+        tally
+    }
+
+    // A notice that a peer joined or left a topic
+    pub fn membership(peer_id: PeerId, text: String) -> Self {
+        Self {
+            kind: MessageKind::Membership,
+            ..Self::new(Some(peer_id), None, text)
+        }
+    }
+
+    // A notice about the connection, shown dimmed in the history
+    pub fn system(text: String) -> Self {
+        Self {
+            kind: MessageKind::System,
+            ..Self::new(None, None, text)
+        }
+    }
+
+    // The sender as shown in the history, the nick if there is one
+    pub fn author(&self) -> String {
+        match (self.nick.as_ref(), self.source_peer_id.as_ref()) {
+            (Some(nick), _) => nick.clone(),
+            (None, Some(source_peer_id)) => utils::short_peer_id(source_peer_id),
+            (None, None) => String::from("unknown source"),
         }
     }
 }
 
 pub struct App {
+    pub config: Config,
     pub ui: Ui,
-    pub history: Vec<ChatMessage>,
+    pub conversations: BTreeMap<ConversationId, Conversation>,
     pub connection: Connection,
+    pub moderation: Moderation,
+    // Our notes and tags on peers
+    pub roster: Roster,
+    // Offered under the address input
+    pub recent_addrs: RecentAddrs,
+    pub nicks: NickRegistry,
+    // The nicks bound to a peer with a certificate
+    pub nick_bindings: NickBindings,
+    pub started: Instant,
+    pub transcript: Option<Transcript>,
+    // Lamport clock, kept ahead of every message we have seen so ours are ordered after them
+    pub clock: u64,
+    pub seen: HashMap<ConversationId, SeenIds>,
+    // The shared description and pin of each topic
+    pub topic_states: HashMap<String, TopicState>,
+    // Who got each of our messages sent during this run
+    pub receipts: ReceiptLog,
+    pub filter: ContentFilter,
+    pub transforms: Transforms,
+    pub git: Git,
+    pub kdf: Kdf,
+    // Encrypts the session when `encrypt_session` is set, entered on startup
+    pub session_passphrase: Option<String>,
+    // Derived from it once, so saving doesn't derive it again each time
+    pub session_key: Option<DerivedKey>,
+    // The saved session is encrypted and the passphrase wasn't entered yet
+    pub session_locked: bool,
+    pub away: AutoAway,
+    pub stats: Stats,
+    // Messages queued with /schedule, /in and /at
+    pub schedule: Schedule,
+    // When the periodic work of the event loop ran last
+    pub ticks: Ticks,
+    // The drafts as last written to disk
+    pub saved_drafts: Drafts,
+    // Set with `--flood`
+    pub flood: Option<Flood>,
+    // Peers whose last presence said they are away
+    pub away_peers: HashSet<PeerId>,
+    // What peers said they are up to with their last presence
+    pub status_cards: HashMap<PeerId, StatusCard>,
+    pub recorder: Recorder,
+    // The newer p2pchat versions peers run
+    pub update: UpdateCheck,
+    pub missed: MissedSummary,
+    // What the moderation layer did and why
+    pub audit: AuditLog,
+    // The size of the message being composed and what it was computed for, see `compose_size`
+    compose_size: Option<(ComposeKey, usize)>,
 }
 
+// The conversation, text, reply and nick a composed message is made of
+type ComposeKey = (ConversationId, String, Option<Uuid>, String);
+
 // Starting in IdleState
 impl App {
-    pub async fn new() -> Result<Self, anyhow::Error> {
-        Ok(Self {
-            ui: Ui::new(),
-            history: vec![],
-            connection: Connection::new()
-                .await
-                .context("Connection::new() failed in App::new()")?,
-        })
+    pub async fn new(config: Config) -> Result<Self, anyhow::Error> {
+        let connection = Connection::new(&config)
+            .await
+            .context("Connection::new() failed in App::new()")?;
+
+        let filter = ContentFilter::new(&config.filters)?;
+        let transforms = Transforms::new(&config.transforms)?;
+        let current_conversation = ConversationId::Topic(config.default_topic.clone());
+        let mut conversations = BTreeMap::new();
+        conversations.insert(current_conversation.clone(), Conversation::default());
+
+        let mut ui = Ui::new(current_conversation);
+        if let Some(nick) = config.nick.as_ref() {
+            ui.nick_input.set(nick.clone());
+        }
+        ui.split_view = config.split_view;
+
+        let mut app = Self {
+            config,
+            ui,
+            conversations,
+            connection,
+            moderation: Moderation::load()?,
+            roster: Roster::load()?,
+            recent_addrs: RecentAddrs::load()?,
+            nicks: NickRegistry::default(),
+            nick_bindings: NickBindings::load()?,
+            started: Instant::now(),
+            transcript: None,
+            clock: 0,
+            seen: HashMap::new(),
+            topic_states: HashMap::new(),
+            receipts: ReceiptLog::default(),
+            filter,
+            transforms,
+            git: Git::default(),
+            kdf: Kdf::default(),
+            session_passphrase: None,
+            session_key: None,
+            session_locked: false,
+            away: AutoAway::default(),
+            stats: Stats::default(),
+            schedule: Schedule::default(),
+            ticks: Ticks::default(),
+            saved_drafts: Drafts::default(),
+            flood: None,
+            away_peers: HashSet::new(),
+            status_cards: HashMap::new(),
+            recorder: Recorder::default(),
+            update: UpdateCheck::default(),
+            missed: MissedSummary::default(),
+            audit: AuditLog::default(),
+            compose_size: None,
+        };
+        if let Err(e) = app.audit.open() {
+            app.connection.push_log(
+                LogLevel::Error,
+                LogCategory::General,
+                &trf(Msg::LogAuditFailed, &[&e]),
+            );
+        }
+        // Snapshots are only taken with `resume_session`, which resumes the last one after a crash
+        let crashed = snapshot::start();
+        if app.config.resume_session {
+            if let Err(e) = session::start(&mut app) {
+                app.connection.push_log(
+                    LogLevel::Error,
+                    LogCategory::General,
+                    &trf(Msg::LogResumeSessionFailed, &[&e]),
+                );
+            }
+        }
+        if app.config.resume_session && crashed {
+            app.push_system_message(None, tr(Msg::SystemSessionRestored).to_string());
+        }
+        if let Err(e) = drafts::restore(&mut app) {
+            app.connection.push_log(
+                LogLevel::Error,
+                LogCategory::General,
+                &trf(Msg::LogRestoreDraftsFailed, &[&e]),
+            );
+        }
+
+        Ok(app)
     }
 
     pub async fn run(
@@ -53,43 +387,1044 @@ impl App {
         terminal: &mut Terminal<CrosstermBackend<Stdout>>,
     ) -> Result<(), anyhow::Error> {
         let mut input_eventstream = EventStream::new().fuse();
+        let mut tick_timer = tokio::time::interval(tick::INTERVAL);
+        let mut flood_timer = tokio::time::interval(flood::interval(self.flood.as_ref()));
 
         loop {
             select! {
                 input_event = &mut input_eventstream.select_next_some() => {
-                     match input_event {
-                        Ok(input_event) => {
-                            match input::handle_input_event(input_event, &mut self) {
-                                Ok(input_task) => match input_task {
-                                    InputTask::Continue => (),
-                                    InputTask::Quit => break,
-                                },
-                                Err(e) => {
-                                    log::error!("handle_input_event() failed with Err `{}`", e);
-                                }
-                            }
+                    let mut input_task = self.handle_input(input_event);
+                    // Key repeat and pastes arrive in bursts, handle everything that is already
+                    // queued before drawing once. The stream is polled by this task, so it wakes
+                    // the loop for events that arrive after the timeout
+                    for _ in 1..INPUT_BATCH {
+                        if matches!(input_task, InputTask::Quit) {
+                            break;
                         }
-                        Err(e) => {
-                            log::error!("input_event is Err `{}`", e);
+                        match tokio::time::timeout(Duration::ZERO, input_eventstream.next()).await {
+                            Ok(Some(input_event)) => input_task = self.handle_input(input_event),
+                            _ => break,
                         }
                     }
+                    if matches!(input_task, InputTask::Quit) {
+                        break;
+                    }
                 },
                 connection_event = self.connection.swarm.select_next_some() => match connection::handle_connection_event(connection_event, &mut self) {
                     Ok(()) => {}
                     Err(e) => {
                         log::error!("handle_connection_event() failed with Err `{}`", e);
                     }
+                },
+                port_mapping_event = self.connection.port_mapper.events_rx.select_next_some() => {
+                    connection::handle_port_mapping_event(port_mapping_event, &mut self);
+                }
+                command = self.connection.outbox.rx.select_next_some() => {
+                    if let InputTask::Quit = connection::handle_connection_command(command, &mut self) {
+                        break;
+                    }
+                }
+                _ = tick_timer.tick().fuse() => {
+                    tick::tick(&mut self);
+                }
+                _ = flood_timer.tick().fuse() => {
+                    flood::send(&mut self);
+                }
+                transformed = self.transforms.done_rx.select_next_some() => {
+                    self.finish_transform(transformed);
+                }
+                git_done = self.git.done_rx.select_next_some() => {
+                    patch::finish(git_done, &mut self);
+                }
+                kdf_done = self.kdf.done_rx.select_next_some() => {
+                    kdf::finish(kdf_done, &mut self);
                 }
             }
 
+            events::dispatch(&mut self);
+            self.view_current_conversation();
             ui::draw_ui(&mut self, terminal)?;
         }
+
+        drafts::save(&mut self);
+        session::save(&self);
+        // The seen addresses are only saved with the dialed ones otherwise
+        if let Err(e) = self.recent_addrs.save() {
+            log::error!("saving the recent addresses failed with Err `{}`", e);
+        }
+        snapshot::finish();
+        Ok(())
+    }
+
+    fn handle_input(&mut self, input_event: crossterm::Result<Event>) -> InputTask {
+        match input_event {
+            Ok(input_event) => {
+                // Switching to another window isn't activity
+                if !matches!(input_event, Event::FocusLost) {
+                    away::handle_activity(self);
+                }
+                match input::handle_input_event(input_event, self) {
+                    Ok(input_task) => input_task,
+                    Err(e) => {
+                        log::error!("handle_input_event() failed with Err `{}`", e);
+                        InputTask::Continue
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("input_event is Err `{}`", e);
+                InputTask::Continue
+            }
+        }
+    }
+
+    pub fn conversation_mut(&mut self, id: &ConversationId) -> &mut Conversation {
+        self.conversations.entry(id.clone()).or_default()
+    }
+
+    pub fn current_conversation(&self) -> Option<&Conversation> {
+        self.conversations.get(&self.ui.current_conversation)
+    }
+
+    // Switches to a conversation, keeping the typed input and scroll position of the previous one
+    pub fn open_conversation(&mut self, id: ConversationId) {
+        if self.ui.current_conversation != id {
+            let draft = Draft {
+                input: mem::take(&mut self.ui.chat_input),
+                reply_to: self.ui.reply_to.take(),
+            };
+            let selected = self.ui.chat_history_selected;
+            let offset = self.ui.chat_history_offset;
+            // The previous conversation might have just been left
+            if let Some(previous) = self.conversations.get_mut(&self.ui.current_conversation) {
+                previous.draft = draft;
+                previous.scroll = ScrollPosition {
+                    selected: selected
+                        .and_then(|selected| previous.history.get(selected))
+                        .map(|message| message.id),
+                    offset,
+                };
+            }
+
+            let conversation = self.conversation_mut(&id);
+            let draft = mem::take(&mut conversation.draft);
+            let scroll = mem::take(&mut conversation.scroll);
+            let selected = scroll.selected.and_then(|selected| {
+                conversation
+                    .history
+                    .iter()
+                    .position(|message| message.id == selected)
+            });
+            self.ui.chat_input = draft.input;
+            self.ui.reply_to = draft.reply_to;
+            self.ui.chat_history_selected = selected;
+            self.ui.chat_history_anchor = None;
+            self.ui.chat_history_offset = scroll.offset;
+        }
+        self.conversation_mut(&id).mark_read();
+        self.ui.current_conversation = id;
+    }
+
+    pub fn is_viewing(&self, id: &ConversationId) -> bool {
+        // The split view shows the chat next to the focused connection page
+        let chat_shown = self.ui.page_focus == PageFocus::Chat
+            || (self.ui.split_shown && self.ui.page_focus == PageFocus::Connection);
+        self.ui.terminal_focused && chat_shown && self.ui.current_conversation == *id
+    }
+
+    // Returns whether a message wasn't in a conversation before, remembering it if so
+    pub fn is_new_message(&mut self, id: &ConversationId, message_id: Uuid) -> bool {
+        self.seen.entry(id.clone()).or_default().insert(message_id)
+    }
+
+    // Like `is_new_message`, without remembering the message. For messages from a history sync,
+    // whose ids mustn't keep the real message out
+    pub fn has_seen(&self, id: &ConversationId, message_id: Uuid) -> bool {
+        self.seen
+            .get(id)
+            .map_or(false, |seen| seen.contains(message_id))
+    }
+
+    // Adds one of our own messages to a conversation
+    pub fn push_message(&mut self, id: &ConversationId, message: ChatMessage) {
+        self.is_new_message(id, message.id);
+        self.write_transcript(id, &message);
+        self.conversation_mut(id).push_message(message);
+        self.apply_retention(id);
+    }
+
+    // Drops the messages of a conversation its retention doesn't keep anymore. Returns how many
+    // were dropped
+    pub fn apply_retention(&mut self, id: &ConversationId) -> usize {
+        let retention = self.config.retention(id);
+        let selection = self.selection_ids();
+        let conversation = match self.conversations.get_mut(id) {
+            Some(conversation) => conversation,
+            None => return 0,
+        };
+        let dropped = conversation.apply_retention(&retention);
+        if dropped.is_empty() {
+            return 0;
+        }
+        // The stored pages are older still
+        conversation.stored_pages = 0;
+        if *id == self.ui.current_conversation {
+            self.reselect(selection);
+        }
+        voice::remove(&dropped, self);
+        dropped.len()
+    }
+
+    // Messages also expire while nothing new arrives, so this runs regularly
+    pub fn apply_retention_all(&mut self) {
+        let ids = self
+            .conversations
+            .keys()
+            .cloned()
+            .collect::<Vec<ConversationId>>();
+        for id in ids.iter() {
+            self.apply_retention(id);
+        }
+    }
+
+    // Removes the disappearing messages whose time is up, from every conversation
+    pub fn remove_expired(&mut self) {
+        let now = Local::now();
+        let selection = self.selection_ids();
+        let expired = self
+            .conversations
+            .values_mut()
+            .flat_map(|conversation| conversation.remove_expired(now))
+            .collect::<Vec<ChatMessage>>();
+        if !expired.is_empty() {
+            self.reselect(selection);
+        }
+        voice::remove(&expired, self);
+    }
+
+    // The messages selected in the current conversation, found again by `reselect` once other
+    // messages were removed and the indices moved
+    fn selection_ids(&self) -> (Option<Uuid>, Option<Uuid>) {
+        let id_at = |index: Option<usize>| {
+            let conversation = self.conversations.get(&self.ui.current_conversation)?;
+            conversation.history.get(index?).map(|message| message.id)
+        };
+        (
+            id_at(self.ui.chat_history_selected),
+            id_at(self.ui.chat_history_anchor),
+        )
+    }
+
+    // The selection is cleared if the selected message is gone, the anchor if its message is
+    fn reselect(&mut self, (selected, anchor): (Option<Uuid>, Option<Uuid>)) {
+        let position = |message_id: Option<Uuid>| {
+            let conversation = self.conversations.get(&self.ui.current_conversation)?;
+            let message_id = message_id?;
+            conversation
+                .history
+                .iter()
+                .position(|message| message.id == message_id)
+        };
+        let selected = position(selected);
+        let anchor = position(anchor).filter(|_| selected.is_some());
+        self.ui.chat_history_selected = selected;
+        self.ui.chat_history_anchor = anchor;
+    }
+
+    // Disappearing messages are never written down
+    fn write_transcript(&mut self, id: &ConversationId, message: &ChatMessage) {
+        if message.kind != MessageKind::Chat || message.expires_in.is_some() {
+            return;
+        }
+        let author = self.nicks.author(message);
+        let result = match self.transcript.as_mut() {
+            Some(transcript) => transcript.write(id, message, &author),
+            None => return,
+        };
+        if let Err(e) = result {
+            self.connection.push_log(
+                LogLevel::Error,
+                LogCategory::Messages,
+                &trf(Msg::LogTranscriptFailed, &[&e]),
+            );
+        }
+    }
+
+    // The envelope a payload is sent in to a conversation. Encrypted topics and DMs with peers
+    // that sent their key only send sealed payloads
+    pub fn outgoing_envelope(
+        &mut self,
+        id: &ConversationId,
+        payload: Payload,
+    ) -> Result<Envelope, anyhow::Error> {
+        let payload = match id {
+            ConversationId::Topic(topic) => self.seal_for_topic(topic, payload)?,
+            ConversationId::Direct(peer_id) => {
+                match self.connection.dm_crypto.session_mut(peer_id) {
+                    // Keys start sessions, they can't be sealed themselves
+                    Some(session) if !matches!(payload, Payload::DmKey(_)) => {
+                        session.seal(&payload)?
+                    }
+                    _ => payload,
+                }
+            }
+        };
+        Ok(Envelope::new(payload))
+    }
+
+    fn seal_for_topic(&self, topic: &str, payload: Payload) -> Result<Payload, anyhow::Error> {
+        match self.config.topic_settings(topic).keys.iter().last() {
+            Some((epoch, key)) => topickeys::seal(*epoch, key, &payload),
+            None => Ok(payload),
+        }
+    }
+
+    // How many bytes a chat message with this text would take on the wire. Topic messages include
+    // the gossipsub framing, so the size compares to `max_transmit_size`
+    pub fn outgoing_size(&self, id: &ConversationId, text: &str) -> usize {
+        self.outgoing_size_with(id, text, |_| {})
+    }
+
+    // `outgoing_size` of the message being composed. Building and sealing it for every frame
+    // would take longer than drawing, so it is only computed again once the input changed
+    pub fn compose_size(&mut self) -> usize {
+        let key = (
+            self.ui.current_conversation.clone(),
+            self.ui.chat_input.as_str().to_string(),
+            self.ui.reply_to,
+            self.ui.nick_input.as_str().to_string(),
+        );
+        match &self.compose_size {
+            Some((computed_for, bytes)) if *computed_for == key => *bytes,
+            _ => {
+                let bytes = self.outgoing_size(&key.0, &key.1);
+                self.compose_size = Some((key, bytes));
+                bytes
+            }
+        }
+    }
+
+    // Like `outgoing_size`, `extend` adds what makes the message more than text
+    fn outgoing_size_with(
+        &self,
+        id: &ConversationId,
+        text: &str,
+        extend: impl FnOnce(&mut ChatMessage),
+    ) -> usize {
+        let nick = Some(self.ui.nick_input.as_str().to_string()).filter(|nick| !nick.is_empty());
+        let mut chat_message = ChatMessage::new(
+            Some(*self.connection.swarm.local_peer_id()),
+            nick,
+            text.to_string(),
+        );
+        chat_message.clock = self.clock.saturating_add(1);
+        chat_message.reply_to = self.ui.reply_to;
+        extend(&mut chat_message);
+
+        let payload = Payload::Chat(chat_message);
+        let payload = match id {
+            ConversationId::Topic(topic) => self.seal_for_topic(topic, payload),
+            // With a copy of the session, estimating doesn't use up a message key
+            ConversationId::Direct(peer_id) => match self.connection.dm_crypto.session(peer_id) {
+                Some(session) => session.clone().seal(&payload),
+                None => Ok(payload),
+            },
+        };
+        let bytes = payload
+            .and_then(|payload| Envelope::new(payload).encode())
+            .map_or(0, |data| data.len());
+        match id {
+            ConversationId::Topic(topic) => {
+                bytes + connection::gossipsub_overhead(&self.connection.topics.hash(topic))
+            }
+            ConversationId::Direct(_) => bytes,
+        }
+    }
+
+    // Queues for publishing to a topic or sends directly to the peer of a DM, depending on the
+    // conversation
+    pub fn send_payload(
+        &mut self,
+        id: &ConversationId,
+        payload: Payload,
+    ) -> Result<Outgoing, anyhow::Error> {
+        let envelope = self.outgoing_envelope(id, payload)?;
+
+        match id {
+            ConversationId::Topic(topic) => {
+                let data = envelope.encode()?;
+                let publish_id = self.connection.outbox.publish(topic, data)?;
+                Ok(Outgoing::Queued(publish_id))
+            }
+            ConversationId::Direct(peer_id) => {
+                let send_id = self.connection.outbox.send(*peer_id, envelope)?;
+                Ok(Outgoing::Direct(send_id))
+            }
+        }
+    }
+
+    // Sends a payload whose delivery isn't shown, only logging failures
+    pub fn send_payload_logged(&mut self, id: &ConversationId, payload: Payload) {
+        if let Err(e) = self.send_payload(id, payload) {
+            self.connection.push_log(
+                LogLevel::Error,
+                LogCategory::Messages,
+                &trf(Msg::LogSendFailed, &[id, &e]),
+            );
+        }
+    }
+
+    // Sends a message with our nick to the current conversation, as a reply if one was chosen.
+    // It is shown right away as sending, and updated once we know whether it got out
+    pub fn send_chat_message(&mut self, text: String) {
+        let conversation_id = self.ui.current_conversation.clone();
+        let reply_to = self.ui.reply_to.take();
+        self.send_chat_message_to(conversation_id, text, reply_to);
+    }
+
+    pub fn send_chat_message_to(
+        &mut self,
+        conversation_id: ConversationId,
+        text: String,
+        reply_to: Option<Uuid>,
+    ) {
+        self.send_new_message(conversation_id, text, |message| message.reply_to = reply_to);
+    }
+
+    // Sends a poll to the current conversation. Peers vote with the number keys on it
+    pub fn send_poll(&mut self, question: String, options: Vec<String>) {
+        let conversation_id = self.ui.current_conversation.clone();
+        self.send_new_message(conversation_id, question, |message| {
+            message.poll = Some(options)
+        });
+    }
+
+    // Sends a recorded voice memo, the id is the one its clip was saved with
+    pub fn send_voice_memo(
+        &mut self,
+        conversation_id: ConversationId,
+        message_id: Uuid,
+        memo: VoiceMemo,
+    ) {
+        let text = trf(
+            Msg::VoiceMemoText,
+            &[&voice::format_duration(memo.duration_secs)],
+        );
+        self.send_new_message(conversation_id, text, |message| {
+            message.id = message_id;
+            message.voice_memo = Some(memo);
+        });
+    }
+
+    pub fn send_terminal_snapshot(
+        &mut self,
+        conversation_id: ConversationId,
+        snapshot: TerminalSnapshot,
+    ) {
+        let text = trf(Msg::SnapshotText, &[&snapshot.width, &snapshot.height]);
+        self.send_new_message(conversation_id, text, |message| {
+            message.snapshot = Some(snapshot)
+        });
+    }
+
+    // Escaping and sealing make the message larger than the mailbox, in topics it still has to
+    // fit into one gossipsub message
+    pub fn send_patch(
+        &mut self,
+        conversation_id: ConversationId,
+        patch: Patch,
+    ) -> Result<(), anyhow::Error> {
+        let text = trf(Msg::PatchText, &[&patch.range, &patch.commits]);
+        if let ConversationId::Topic(_) = conversation_id {
+            let size = self.outgoing_size_with(&conversation_id, &text, |message| {
+                message.patch = Some(patch.clone())
+            });
+            let max_size = self.config.gossipsub.max_transmit_size;
+            anyhow::ensure!(
+                size <= max_size,
+                "the patch takes {} as a message, at most {} can be sent",
+                stats::format_bytes(size as u64),
+                stats::format_bytes(max_size as u64)
+            );
+        }
+        self.send_new_message(conversation_id, text, |message| message.patch = Some(patch));
         Ok(())
     }
 
+    pub fn send_clip(&mut self, topic: String, text: String) {
+        self.send_new_message(ConversationId::Topic(topic), text, |message| {
+            message.clip = true
+        });
+    }
+
+    // `extend` adds what makes the message more than text
+    fn send_new_message(
+        &mut self,
+        conversation_id: ConversationId,
+        text: String,
+        extend: impl FnOnce(&mut ChatMessage),
+    ) {
+        if !self.may_post(&conversation_id) {
+            return;
+        }
+        let nick = if self.ui.nick_input.is_empty() {
+            None
+        } else {
+            Some(self.ui.nick_input.as_str().to_string())
+        };
+        let local_peer_id = *self.connection.swarm.local_peer_id();
+        if let Some(nick) = nick.as_ref() {
+            self.record_nick(local_peer_id, nick);
+        }
+        let mut chat_message = ChatMessage::new(Some(local_peer_id), nick, text);
+        if let ConversationId::Topic(topic) = &conversation_id {
+            chat_message.expires_in = self.config.topic_settings(topic).disappear_after;
+        }
+        self.clock = self.clock.saturating_add(1);
+        chat_message.clock = self.clock;
+        extend(&mut chat_message);
+
+        if self.transforms.runs_programs(TransformDirection::Outgoing) {
+            self.transforms
+                .spawn(TransformDirection::Outgoing, conversation_id, chat_message);
+        } else {
+            let text = self.transforms.outgoing(&chat_message.text);
+            self.send_transformed(&conversation_id, chat_message, text);
+        }
+    }
+
+    // Shows and sends one of our messages once the outgoing transforms are done with its text
+    fn send_transformed(
+        &mut self,
+        id: &ConversationId,
+        mut message: ChatMessage,
+        text: Result<String, anyhow::Error>,
+    ) {
+        match text {
+            Ok(text) => message.text = text,
+            Err(e) => self.connection.push_log(
+                LogLevel::Warn,
+                LogCategory::Messages,
+                &trf(Msg::LogTransformFailed, &[&e]),
+            ),
+        }
+        self.push_message(id, message.clone());
+        self.deliver(id, message);
+    }
+
+    // Continues with a message whose transforms ran programs
+    pub fn finish_transform(&mut self, transformed: Transformed) {
+        let Transformed {
+            direction,
+            conversation_id,
+            message,
+            text,
+        } = transformed;
+        if direction == TransformDirection::Outgoing {
+            self.send_transformed(&conversation_id, message, text);
+        } else if message.synced {
+            let messages = self
+                .filter_received(&conversation_id, message, text)
+                .into_iter()
+                .collect();
+            self.merge_synced(&conversation_id, messages);
+        } else {
+            self.receive_transformed(&conversation_id, message, text);
+        }
+    }
+
+    // Whether we may post to a conversation, logging why not. Only the announcers of an
+    // announcement topic may post to it
+    pub fn may_post(&mut self, id: &ConversationId) -> bool {
+        let topic = match id {
+            ConversationId::Topic(topic) => topic,
+            ConversationId::Direct(_) => return true,
+        };
+        let local_peer_id = *self.connection.swarm.local_peer_id();
+        if self.config.may_publish(topic, Some(&local_peer_id)) {
+            return true;
+        }
+        self.connection.push_log(
+            LogLevel::Warn,
+            LogCategory::Messages,
+            &trf(Msg::LogReadOnlyTopic, &[topic]),
+        );
+        false
+    }
+
+    // Sends one of our own messages that failed to go out again
+    pub fn retry_message(&mut self, id: &ConversationId, message_id: Uuid) {
+        let message = match self
+            .conversations
+            .get(id)
+            .and_then(|conversation| conversation.message(message_id))
+        {
+            Some(message) if message.delivery == Some(Delivery::Failed) => message.clone(),
+            _ => return,
+        };
+        self.deliver(id, message);
+    }
+
+    fn deliver(&mut self, id: &ConversationId, mut message: ChatMessage) {
+        let message_id = message.id;
+        self.set_delivery(id, message_id, Delivery::Sending);
+        // Without the clip peers still see that there was a memo
+        if let Err(e) = voice::attach(&mut message) {
+            self.connection.push_log(
+                LogLevel::Error,
+                LogCategory::Messages,
+                &trf(Msg::LogVoiceMemoFailed, &[&e]),
+            );
+        }
+
+        let sent_to = match id {
+            // Flood publishing hands our own messages to every peer in the topic, not only the mesh
+            ConversationId::Topic(topic) if self.config.gossipsub.flood_publish => {
+                self.connection.topic_peers(topic)
+            }
+            ConversationId::Topic(topic) => self.connection.mesh_peers(topic),
+            ConversationId::Direct(peer_id) => vec![*peer_id],
+        };
+        let delivery = match self.send_payload(id, Payload::Chat(message)) {
+            Ok(outgoing) => {
+                self.receipts.sent(message_id, sent_to);
+                self.stats.record_sent(id);
+                match outgoing {
+                    Outgoing::Queued(publish_id) => {
+                        self.connection
+                            .pending_publishes
+                            .insert(publish_id, (id.clone(), message_id));
+                        Delivery::Sending
+                    }
+                    Outgoing::Direct(send_id) => {
+                        self.connection
+                            .pending_sends
+                            .insert(send_id, (id.clone(), message_id));
+                        Delivery::Sending
+                    }
+                }
+            }
+            Err(e) => {
+                self.connection.push_log(
+                    LogLevel::Error,
+                    LogCategory::Messages,
+                    &trf(Msg::LogSendFailed, &[id, &e]),
+                );
+                Delivery::Failed
+            }
+        };
+        self.set_delivery(id, message_id, delivery);
+    }
+
+    // A peer showed it got one of our messages
+    pub fn confirm_receipt(&mut self, message_id: Uuid, peer_id: PeerId) {
+        self.receipts.confirm(message_id, peer_id);
+    }
+
+    // The conversation might have been left in the meantime
+    pub fn set_delivery(&mut self, id: &ConversationId, message_id: Uuid, delivery: Delivery) {
+        if let Some(message) = self
+            .conversations
+            .get_mut(id)
+            .and_then(|conversation| conversation.message_mut(message_id))
+        {
+            message.delivery = Some(delivery);
+        }
+    }
+
+    // Removes one of our own messages and asks peers to do the same
+    pub fn delete_message(&mut self, id: &ConversationId, message_id: Uuid) {
+        let local_peer_id = *self.connection.swarm.local_peer_id();
+        if let Some(message) = self
+            .conversation_mut(id)
+            .remove_message(message_id, &local_peer_id)
+        {
+            voice::remove(&[message], self);
+            self.send_payload_logged(id, Payload::Delete { message_id });
+        }
+    }
+
+    pub fn react(&mut self, id: &ConversationId, message_id: Uuid, reaction: String) {
+        let local_peer_id = *self.connection.swarm.local_peer_id();
+        self.conversation_mut(id)
+            .add_reaction(message_id, reaction.clone(), local_peer_id);
+        self.send_payload_logged(
+            id,
+            Payload::Reaction {
+                message_id,
+                reaction,
+            },
+        );
+    }
+
+    // Votes for an option of a poll, replacing our earlier vote
+    pub fn vote(&mut self, id: &ConversationId, message_id: Uuid, option: usize) {
+        let local_peer_id = *self.connection.swarm.local_peer_id();
+        if !self
+            .conversation_mut(id)
+            .add_vote(message_id, option, local_peer_id)
+        {
+            return;
+        }
+        self.send_payload_logged(id, Payload::Vote { message_id, option });
+    }
+
+    // Shows a connection event in the DM with the peer it concerns if there is one, otherwise
+    // in the current conversation
+    pub fn push_system_message(&mut self, peer_id: Option<PeerId>, text: String) {
+        let id = match peer_id.map(ConversationId::Direct) {
+            Some(id) if self.conversations.contains_key(&id) => id,
+            _ => self.ui.current_conversation.clone(),
+        };
+        self.receive_message(&id, ChatMessage::system(text));
+    }
+
+    pub fn set_topic_description(&mut self, topic: &str, description: String) {
+        self.clock = self.clock.saturating_add(1);
+        let (clock, local_peer_id) = (self.clock, *self.connection.swarm.local_peer_id());
+        self.topic_states
+            .entry(topic.to_string())
+            .or_default()
+            .description
+            .set(description, clock, &local_peer_id);
+        self.share_topic_state(topic);
+    }
+
+    pub fn set_topic_pin(&mut self, topic: &str, pin: Option<Pin>) {
+        self.clock = self.clock.saturating_add(1);
+        let (clock, local_peer_id) = (self.clock, *self.connection.swarm.local_peer_id());
+        self.topic_states
+            .entry(topic.to_string())
+            .or_default()
+            .pinned
+            .set(pin, clock, &local_peer_id);
+        self.share_topic_state(topic);
+    }
+
+    fn share_topic_state(&mut self, topic: &str) {
+        if let Some(state) = self.topic_states.get(topic).cloned() {
+            self.send_payload_logged(
+                &ConversationId::Topic(topic.to_string()),
+                Payload::TopicState(state),
+            );
+        }
+    }
+
+    // Merges the topic state we got from another peer. States with a clock far ahead of ours are
+    // dropped, and a pin only shows the author and text of the message in our own history.
+    // Returns whether anything changed
+    pub fn merge_topic_state(
+        &mut self,
+        topic: &str,
+        source: PeerId,
+        mut state: TopicState,
+    ) -> bool {
+        if !validation::is_valid_clock(state.clock(), self.clock) {
+            return false;
+        }
+        self.clock = self.clock.max(state.clock());
+        state.attribute_to(&source);
+
+        let conversation_id = ConversationId::Topic(topic.to_string());
+        let pinned = state.pinned.value.as_ref().map(|pin| {
+            self.conversations
+                .get(&conversation_id)
+                .and_then(|conversation| conversation.message(pin.message_id))
+                .map(|message| (self.nicks.author(message), message.text.clone()))
+        });
+        match (state.pinned.value.as_mut(), pinned) {
+            (Some(pin), Some(Some((author, text)))) => {
+                pin.author = author;
+                pin.text = text;
+            }
+            // We can't check a pin of a message we don't have, so we keep ours
+            (Some(_), _) => {
+                state.pinned = self
+                    .topic_states
+                    .get(topic)
+                    .cloned()
+                    .unwrap_or_default()
+                    .pinned;
+            }
+            (None, _) => {}
+        }
+
+        self.topic_states
+            .entry(topic.to_string())
+            .or_default()
+            .merge(&state)
+    }
+
+    // Moves our clock past a message's. Notices, messages from peers without a clock and direct
+    // messages with a clock far ahead of ours are ordered as if they were sent now
+    pub fn observe_clock(&mut self, message: &mut ChatMessage) {
+        if message.clock == 0 || !validation::is_valid_clock(message.clock, self.clock) {
+            message.clock = self.clock;
+            message.sent_at = message.received_at.timestamp_millis();
+        } else {
+            self.clock = self.clock.max(message.clock);
+        }
+    }
+
+    // Remembers the nick a peer uses, warning when another peer already uses it
+    pub fn record_nick(&mut self, peer_id: PeerId, nick: &str) {
+        for other in self.nicks.record(peer_id, nick) {
+            let shown_as = self.nicks.display(&peer_id, nick);
+            self.connection.push_log(
+                LogLevel::Warn,
+                LogCategory::Messages,
+                &trf(Msg::LogNickConflict, &[&peer_id, &nick, &other, &shown_as]),
+            );
+        }
+    }
+
+    // Hides everything from a peer, including the messages already received
+    pub fn block_peer(&mut self, peer_id: PeerId) {
+        self.moderation.block(peer_id);
+        self.connection
+            .outbox
+            .push(ConnectionCommand::Disconnect(peer_id));
+        let removed = self
+            .conversations
+            .values_mut()
+            .flat_map(|conversation| {
+                conversation.remove_where(|message| message.source_peer_id == Some(peer_id))
+            })
+            .collect::<Vec<ChatMessage>>();
+        voice::remove(&removed, self);
+    }
+
+    // Adds a message from a peer to a conversation, counting it as unread if the conversation
+    // isn't currently viewed
+    pub fn receive_message(&mut self, id: &ConversationId, mut message: ChatMessage) {
+        // E.g. from a peer that was offline for a while
+        if message.is_expired(Local::now()) {
+            return;
+        }
+        self.observe_clock(&mut message);
+        if message.kind != MessageKind::Chat {
+            let text = message.text.clone();
+            self.receive_transformed(id, message, Ok(text));
+        } else if self.transforms.runs_programs(TransformDirection::Incoming) {
+            self.transforms
+                .spawn(TransformDirection::Incoming, id.clone(), message);
+        } else {
+            let text = self.transforms.incoming(&message.text);
+            self.receive_transformed(id, message, text);
+        }
+    }
+
+    // What every chat message from a peer goes through, live or synced, once the incoming
+    // transforms are done with its text. Returns `None` if a filter hides it
+    fn filter_received(
+        &mut self,
+        id: &ConversationId,
+        mut message: ChatMessage,
+        text: Result<String, anyhow::Error>,
+    ) -> Option<ChatMessage> {
+        match text {
+            Ok(text) => message.text = text,
+            Err(e) => self.connection.push_log(
+                LogLevel::Warn,
+                LogCategory::Messages,
+                &trf(Msg::LogTransformFailed, &[&e]),
+            ),
+        }
+        match self.filter.apply(&message.text) {
+            Filtered::Hidden { pattern } => {
+                self.connection
+                    .push_log_with(LogLevel::Debug, LogCategory::Messages, || {
+                        trf(Msg::LogMessageFiltered, &[id, &message.author()])
+                    });
+                audit::record(
+                    self,
+                    AuditAction::Filtered,
+                    message.source_peer_id,
+                    &trf(Msg::AuditReasonFilter, &[id, &pattern]),
+                );
+                return None;
+            }
+            Filtered::Shown { highlight } => message.highlighted = highlight,
+        }
+        // Clips are only stored for new messages and for a message replacing its synced copy,
+        // otherwise anyone could replace the clip of a memo by sending its id again
+        let replaces_synced = self
+            .conversations
+            .get(id)
+            .and_then(|conversation| conversation.message(message.id))
+            .map(|known| known.synced && !message.synced);
+        let stored = match replaces_synced {
+            Some(false) => {
+                voice::discard(&mut message);
+                Ok(())
+            }
+            replaces_synced => voice::store(&mut message, replaces_synced.is_some()),
+        };
+        if let Err(e) = stored {
+            self.connection.push_log(
+                LogLevel::Error,
+                LogCategory::Messages,
+                &trf(Msg::LogVoiceMemoFailed, &[&e]),
+            );
+        }
+        Some(message)
+    }
+
+    // Adds the messages of a topic another peer sent us with a history sync, after the same
+    // transforms and filters as live ones. Returns how many were added right away, the others
+    // follow once their transforms are done
+    pub fn receive_synced(&mut self, id: &ConversationId, messages: Vec<ChatMessage>) -> usize {
+        let mut ready = vec![];
+        for message in messages {
+            if self.transforms.runs_programs(TransformDirection::Incoming) {
+                self.transforms
+                    .spawn(TransformDirection::Incoming, id.clone(), message);
+            } else {
+                let text = self.transforms.incoming(&message.text);
+                ready.extend(self.filter_received(id, message, text));
+            }
+        }
+        self.merge_synced(id, ready)
+    }
+
+    fn merge_synced(&mut self, id: &ConversationId, messages: Vec<ChatMessage>) -> usize {
+        if let ConversationId::Topic(topic) = id {
+            missed::record(self, topic, &messages);
+        }
+        let count = self.conversation_mut(id).merge_messages(messages);
+        self.apply_retention(id);
+        count
+    }
+
+    // Continues with a message from a peer once the incoming transforms are done with its text
+    fn receive_transformed(
+        &mut self,
+        id: &ConversationId,
+        mut message: ChatMessage,
+        text: Result<String, anyhow::Error>,
+    ) {
+        if message.kind == MessageKind::Chat {
+            message = match self.filter_received(id, message, text) {
+                Some(message) => message,
+                None => return,
+            };
+        }
+        let viewing = self.is_viewing(id);
+        // Muted peers' messages are kept, but hidden and never counted
+        let muted = message
+            .source_peer_id
+            .map_or(false, |peer_id| self.moderation.is_muted(&peer_id));
+        let attention = if muted {
+            Attention::Ignore
+        } else {
+            match notify::attention(&self.config, id, &message, self.ui.nick_input.as_str()) {
+                Attention::Unread if message.highlighted => Attention::Mention,
+                attention => attention,
+            }
+        };
+        if message.kind == MessageKind::Chat {
+            self.stats.record_received(id);
+        }
+        self.write_transcript(id, &message);
+        // No need to ring for what is already on screen
+        if !viewing && notify::rings_bell(&self.config, id, attention) {
+            notify::ring_bell();
+        }
+        let conversation = self.conversation_mut(id);
+
+        conversation.push_message(message);
+        if !viewing {
+            if attention >= Attention::Silent {
+                conversation.unread += 1;
+            }
+            if attention == Attention::Mention {
+                conversation.mentions += 1;
+            }
+        }
+        self.apply_retention(id);
+    }
+
+    // All conversations, the most recently active first
+    pub fn sorted_conversation_ids(&self) -> Vec<ConversationId> {
+        let mut conversations = self
+            .conversations
+            .iter()
+            .collect::<Vec<(&ConversationId, &Conversation)>>();
+        conversations.sort_by(|(_, a), (_, b)| b.last_activity.cmp(&a.last_activity));
+
+        conversations
+            .into_iter()
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    // Switch to the next or previous conversation in the sidebar, wrapping around
+    pub fn cycle_conversation(&mut self, forward: bool) {
+        let ids = self.sorted_conversation_ids();
+        let current = ids
+            .iter()
+            .position(|id| *id == self.ui.current_conversation)
+            .unwrap_or(0);
+
+        let next = if forward {
+            (current + 1) % ids.len()
+        } else {
+            (current + ids.len() - 1) % ids.len()
+        };
+        self.open_conversation(ids[next].clone());
+    }
+
+    // Resets the unread count of the viewed conversation and sends read markers for it
+    pub fn view_current_conversation(&mut self) {
+        // Nothing is read while the terminal is in the background
+        if self.ui.page_focus != PageFocus::Chat || !self.ui.terminal_focused {
+            return;
+        }
+        let id = self.ui.current_conversation.clone();
+        self.conversation_mut(&id).mark_read();
+
+        self.send_read_marker();
+    }
+
+    // Tell the peer of the currently viewed DM conversation what we have read, if it supports
+    // read receipts
+    pub fn send_read_marker(&mut self) {
+        if self.ui.page_focus != PageFocus::Chat || !self.ui.terminal_focused {
+            return;
+        }
+        let peer_id = match self.ui.current_conversation {
+            ConversationId::Direct(peer_id) => peer_id,
+            ConversationId::Topic(_) => return,
+        };
+        let supports_receipts = self
+            .connection
+            .peer_protocols
+            .get(&peer_id)
+            .map_or(false, |peer_protocol| {
+                peer_protocol.supports(Capabilities::READ_RECEIPTS)
+            });
+        if !supports_receipts {
+            return;
+        }
+
+        let conversation = self.conversation_mut(&ConversationId::Direct(peer_id));
+        let up_to = match conversation.last_message_id() {
+            Some(up_to) if conversation.read_marker_sent != Some(up_to) => up_to,
+            _ => return,
+        };
+        conversation.read_marker_sent = Some(up_to);
+
+        self.connection
+            .outbox
+            .send(peer_id, Envelope::new(Payload::ReadMarker { up_to }));
+    }
+
     // Select the next item. This will not be reflected until the widget is drawn in the
     // `Terminal::draw` callback using `Frame::render_stateful_widget`.
     pub fn connection_log_next(&mut self) {
+        if self.connection.log.is_empty() {
+            return;
+        }
         let i = match self.ui.connection_log_liststate.selected() {
             Some(i) => {
                 if i >= self.connection.log.len() - 1 {
@@ -119,9 +1454,124 @@ impl App {
         self.ui.connection_log_liststate.select(Some(i));
     }
 
-    // Unselect the currently selected item if any. The implementation of `ListState` makes
-    // sure that the stored offset is also reset.
+    // Select the newest entry, scrolling it into view
+    pub fn connection_log_newest(&mut self) {
+        if let Some(last) = self.connection.log.len().checked_sub(1) {
+            self.ui.connection_log_liststate.select(Some(last));
+        }
+    }
+
+    // Unselect the currently selected item if any and scroll back to the top
     pub fn connection_log_unselect(&mut self) {
         self.ui.connection_log_liststate.select(None);
+        self.ui.connection_log_offset = 0;
+    }
+
+    // Grows the selected range of messages by moving the selection, starting a range at the
+    // selected message
+    pub fn chat_history_extend_selection(&mut self, forward: bool) {
+        if self.ui.chat_history_anchor.is_none() {
+            self.ui.chat_history_anchor = self.ui.chat_history_selected;
+        }
+        if forward {
+            self.chat_history_next();
+        } else {
+            self.chat_history_previous();
+        }
+        if self.ui.chat_history_anchor.is_none() {
+            self.ui.chat_history_anchor = self.ui.chat_history_selected;
+        }
+    }
+
+    // The first and last index of the selected messages, a single one without a range
+    pub fn chat_history_selection(&self) -> Option<(usize, usize)> {
+        let selected = self.ui.chat_history_selected?;
+        let anchor = self.ui.chat_history_anchor.unwrap_or(selected);
+
+        Some((anchor.min(selected), anchor.max(selected)))
+    }
+
+    // Puts the selected messages onto the clipboard, one line each with time and author
+    pub fn copy_chat_history_selection(&mut self) -> Result<(), anyhow::Error> {
+        let (first, last) = match self.chat_history_selection() {
+            Some(selection) => selection,
+            None => return Ok(()),
+        };
+        let lines = match self.current_conversation() {
+            Some(conversation) => conversation
+                .history
+                .iter()
+                .skip(first)
+                .take(last + 1 - first)
+                .map(|message| self.format_for_copy(message))
+                .collect::<Vec<String>>(),
+            None => return Ok(()),
+        };
+
+        let count = lines.len();
+        utils::copy_to_clipboard(lines.join("\n"))?;
+        self.connection.push_log(
+            LogLevel::Info,
+            LogCategory::General,
+            &trf(Msg::LogCopiedMessages, &[&count]),
+        );
+        Ok(())
+    }
+
+    fn format_for_copy(&self, message: &ChatMessage) -> String {
+        let time = message.received_at.format("%Y-%m-%d %H:%M");
+        match message.kind {
+            MessageKind::Chat => format!(
+                "[{}] {}: {}",
+                time,
+                self.nicks.author(message),
+                message.text
+            ),
+            MessageKind::Membership => {
+                format!("[{}] {} {}", time, self.nicks.author(message), message.text)
+            }
+            MessageKind::System => format!("[{}] -- {}", time, message.text),
+        }
+    }
+
+    // Select the next message in the current conversation's history
+    pub fn chat_history_next(&mut self) {
+        let len = self.current_conversation().map_or(0, |c| c.history.len());
+        if len == 0 {
+            return;
+        }
+        let i = match self.ui.chat_history_selected {
+            Some(i) => (i + 1).min(len - 1),
+            None => 0,
+        };
+        self.ui.chat_history_selected = Some(i);
+    }
+
+    // Select the previous message in the current conversation's history
+    pub fn chat_history_previous(&mut self) {
+        let len = self.current_conversation().map_or(0, |c| c.history.len());
+        if len == 0 {
+            return;
+        }
+        let i = match self.ui.chat_history_selected {
+            // Past the top, load the previous page of the stored history if there is one
+            Some(0) => {
+                let id = self.ui.current_conversation.clone();
+                match session::load_older(&id, self) {
+                    Ok(added) => added.saturating_sub(1),
+                    Err(e) => {
+                        self.connection.push_log(
+                            LogLevel::Error,
+                            LogCategory::Messages,
+                            &trf(Msg::LogLoadOlderFailed, &[&e]),
+                        );
+                        0
+                    }
+                }
+            }
+            Some(i) => i - 1,
+            None => len - 1,
+        };
+        self.ui.chat_history_selected = Some(i);
     }
 }
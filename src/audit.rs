@@ -0,0 +1,137 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use libp2p::PeerId;
+
+use crate::app::App;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::i18n::{trf, Msg};
+use crate::profile;
+
+// How many entries are kept in memory for the Status page, the file keeps all of them
+const MAX_ENTRIES: usize = 500;
+
+// What the moderation layer did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    Blocked,
+    Unblocked,
+    Muted,
+    Unmuted,
+    // A message hidden by a filter rule
+    Filtered,
+    // A topic message dropped by the validation
+    Dropped,
+    // A connection of a blocked peer that was closed right away
+    ConnectionRejected,
+}
+
+impl AuditAction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Blocked => "blocked",
+            Self::Unblocked => "unblocked",
+            Self::Muted => "muted",
+            Self::Unmuted => "unmuted",
+            Self::Filtered => "filtered",
+            Self::Dropped => "dropped",
+            Self::ConnectionRejected => "connection rejected",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub time: DateTime<Local>,
+    pub action: AuditAction,
+    pub peer_id: Option<PeerId>,
+    pub reason: String,
+}
+
+impl fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let peer = self
+            .peer_id
+            .map(|peer_id| peer_id.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        write!(
+            f,
+            "[{}] {} {}: {}",
+            self.time.format("%Y-%m-%d %H:%M:%S %:z"),
+            self.action.label(),
+            peer,
+            self.reason
+        )
+    }
+}
+
+// Everything the moderation layer did and why, so operators of public topics can review it.
+// Appended to `audit.log` in the profile directory as it happens
+#[derive(Debug, Default)]
+pub struct AuditLog {
+    entries: VecDeque<AuditEntry>,
+    file: Option<File>,
+}
+
+impl AuditLog {
+    pub fn path() -> Option<PathBuf> {
+        profile::dir().map(|dir| dir.join("audit.log"))
+    }
+
+    // Without a profile directory the entries are only kept in memory
+    pub fn open(&mut self) -> Result<(), anyhow::Error> {
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("opening audit log {:?} failed", path))?;
+        self.file = Some(file);
+        Ok(())
+    }
+
+    pub fn entries(&self) -> impl ExactSizeIterator<Item = &AuditEntry> {
+        self.entries.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn push(&mut self, entry: AuditEntry) -> Result<(), anyhow::Error> {
+        let result = match self.file.as_mut() {
+            Some(file) => writeln!(file, "{}", entry).context("appending to the audit log failed"),
+            None => Ok(()),
+        };
+        if self.entries.len() >= MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+        result
+    }
+}
+
+// Records a moderation action in the audit log
+pub fn record(app: &mut App, action: AuditAction, peer_id: Option<PeerId>, reason: &str) {
+    let entry = AuditEntry {
+        time: Local::now(),
+        action,
+        peer_id,
+        reason: reason.to_string(),
+    };
+    if let Err(e) = app.audit.push(entry) {
+        app.connection.push_log(
+            LogLevel::Error,
+            LogCategory::General,
+            &trf(Msg::LogAuditFailed, &[&e]),
+        );
+    }
+}
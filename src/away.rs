@@ -0,0 +1,108 @@
+use std::time::{Duration, Instant};
+
+use libp2p::PeerId;
+
+use crate::app::App;
+use crate::conversation::ConversationId;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::i18n::{tr, Msg};
+use crate::protocol::{Payload, Status};
+
+// How often inactivity is checked
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+// Status changes are sent at most this often, so typing now and then while away doesn't flood
+// peers with presence
+const MIN_BROADCAST_INTERVAL: Duration = Duration::from_secs(30);
+
+// Our status, switched to away after `auto_away_minutes` without input
+#[derive(Debug)]
+pub struct AutoAway {
+    pub status: Status,
+    last_input: Instant,
+    last_broadcast: Option<Instant>,
+    // A change that was held back by the rate limit
+    pending: bool,
+}
+
+impl Default for AutoAway {
+    fn default() -> Self {
+        Self {
+            status: Status::Online,
+            last_input: Instant::now(),
+            last_broadcast: None,
+            pending: false,
+        }
+    }
+}
+
+// Called for every input event
+pub fn handle_activity(app: &mut App) {
+    app.away.last_input = Instant::now();
+    if app.away.status == Status::Away {
+        set_status(Status::Online, app);
+    }
+}
+
+// Called regularly, switches to away once we were inactive long enough and sends held back
+// changes
+pub fn check(app: &mut App) {
+    if let Some(minutes) = app.config.auto_away_minutes {
+        let timeout = Duration::from_secs(u64::from(minutes) * 60);
+        if app.away.status == Status::Online && app.away.last_input.elapsed() >= timeout {
+            set_status(Status::Away, app);
+            return;
+        }
+    }
+    if app.away.pending {
+        broadcast(app);
+    }
+}
+
+fn set_status(status: Status, app: &mut App) {
+    app.away.status = status;
+    let msg = match status {
+        Status::Online => Msg::LogNowOnline,
+        Status::Away => Msg::LogNowAway,
+    };
+    app.connection
+        .push_log(LogLevel::Info, LogCategory::General, tr(msg));
+    broadcast(app);
+}
+
+// Sends our nick and status again, regularly
+pub fn announce(app: &mut App) {
+    broadcast(app);
+}
+
+// Sends our status to every connected peer we sent presence to before
+fn broadcast(app: &mut App) {
+    let rate_limited = app
+        .away
+        .last_broadcast
+        .map_or(false, |last| last.elapsed() < MIN_BROADCAST_INTERVAL);
+    if rate_limited {
+        app.away.pending = true;
+        return;
+    }
+    app.away.pending = false;
+    app.away.last_broadcast = Some(Instant::now());
+
+    let nick = Some(app.ui.nick_input.as_str().to_string()).filter(|nick| !nick.is_empty());
+    let peer_ids = app
+        .connection
+        .presence_sent
+        .iter()
+        .filter(|peer_id| app.connection.swarm.is_connected(peer_id))
+        .cloned()
+        .collect::<Vec<PeerId>>();
+    for peer_id in peer_ids {
+        app.send_payload_logged(
+            &ConversationId::Direct(peer_id),
+            Payload::Presence {
+                nick: nick.clone(),
+                status: app.away.status,
+                card: app.config.status_card.clone(),
+            },
+        );
+    }
+}
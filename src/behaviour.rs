@@ -0,0 +1,59 @@
+use libp2p::gossipsub::{Gossipsub, GossipsubEvent};
+use libp2p::identify::{Identify, IdentifyEvent};
+use libp2p::ping;
+use libp2p::request_response::{RequestResponse, RequestResponseEvent};
+use libp2p::NetworkBehaviour;
+
+use crate::direct::{DirectCodec, DirectResponse};
+use crate::protocol::Envelope;
+use crate::sync::{HistoryCodec, HistoryRequest, HistoryResponse};
+
+// The network behaviour of the swarm, combining all protocols p2pchat speaks
+#[derive(NetworkBehaviour)]
+#[behaviour(out_event = "BehaviourEvent")]
+pub struct Behaviour {
+    pub gossipsub: Gossipsub,
+    pub identify: Identify,
+    pub ping: ping::Behaviour,
+    pub direct: RequestResponse<DirectCodec>,
+    pub history: RequestResponse<HistoryCodec>,
+}
+
+#[derive(Debug)]
+pub enum BehaviourEvent {
+    Gossipsub(GossipsubEvent),
+    Identify(IdentifyEvent),
+    Ping(ping::Event),
+    Direct(RequestResponseEvent<Envelope, DirectResponse>),
+    History(RequestResponseEvent<HistoryRequest, HistoryResponse>),
+}
+
+impl From<GossipsubEvent> for BehaviourEvent {
+    fn from(event: GossipsubEvent) -> Self {
+        Self::Gossipsub(event)
+    }
+}
+
+impl From<IdentifyEvent> for BehaviourEvent {
+    fn from(event: IdentifyEvent) -> Self {
+        Self::Identify(event)
+    }
+}
+
+impl From<ping::Event> for BehaviourEvent {
+    fn from(event: ping::Event) -> Self {
+        Self::Ping(event)
+    }
+}
+
+impl From<RequestResponseEvent<Envelope, DirectResponse>> for BehaviourEvent {
+    fn from(event: RequestResponseEvent<Envelope, DirectResponse>) -> Self {
+        Self::Direct(event)
+    }
+}
+
+impl From<RequestResponseEvent<HistoryRequest, HistoryResponse>> for BehaviourEvent {
+    fn from(event: RequestResponseEvent<HistoryRequest, HistoryResponse>) -> Self {
+        Self::History(event)
+    }
+}
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use unicode_width::UnicodeWidthStr;
+
+use crate::app::App;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::hittest::HitTarget;
+use crate::i18n::{tr, trf, Msg};
+use crate::validation;
+
+// What /snapshot captures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pane {
+    Screen,
+    History,
+    Log,
+}
+
+impl Pane {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "screen" => Some(Self::Screen),
+            "history" => Some(Self::History),
+            "log" => Some(Self::Log),
+            _ => None,
+        }
+    }
+
+    fn target(self) -> Option<HitTarget> {
+        match self {
+            Self::Screen => None,
+            Self::History => Some(HitTarget::ChatHistory),
+            Self::Log => Some(HitTarget::ConnectionLog),
+        }
+    }
+}
+
+// The text of the terminal as it was drawn, sent with a chat message and viewed in a popup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminalSnapshot {
+    pub width: u16,
+    pub height: u16,
+    pub text: String,
+}
+
+impl TerminalSnapshot {
+    // Rows with their trailing blanks removed, a cell after a wide character belongs to it
+    fn from_buffer(buffer: &Buffer, area: Rect) -> Self {
+        let mut lines = Vec::with_capacity(area.height as usize);
+        for y in area.top()..area.bottom() {
+            let mut line = String::new();
+            let mut skip = 0;
+            for x in area.left()..area.right() {
+                if skip > 0 {
+                    skip -= 1;
+                    continue;
+                }
+                let symbol = &buffer.get(x, y).symbol;
+                skip = symbol.width().saturating_sub(1);
+                line.push_str(symbol);
+            }
+            lines.push(line.trim_end().to_string());
+        }
+        while lines.last().map_or(false, |line| line.is_empty()) {
+            lines.pop();
+        }
+        Self {
+            width: area.width,
+            height: area.height,
+            text: lines.join("\n"),
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.text.lines().map(String::from).collect()
+    }
+}
+
+// Captures the pane once the next frame is drawn, so it shows what the user sees
+pub fn request(app: &mut App, pane: Pane) {
+    app.ui.capture_requested = Some(pane);
+}
+
+// Called after drawing a frame, sends the requested capture to the current conversation
+pub fn take(app: &mut App, buffer: &Buffer, screen: Rect) {
+    let pane = match app.ui.capture_requested.take() {
+        Some(pane) => pane,
+        None => return,
+    };
+    let area = match pane.target() {
+        None => Some(screen),
+        Some(target) => app.ui.hit_areas.area(&target),
+    };
+    let area = match area {
+        Some(area) => area,
+        None => {
+            app.connection.push_log(
+                LogLevel::Warn,
+                LogCategory::Messages,
+                tr(Msg::LogSnapshotPaneHidden),
+            );
+            return;
+        }
+    };
+
+    let snapshot = TerminalSnapshot::from_buffer(buffer, area);
+    if snapshot.text.len() > validation::MAX_SNAPSHOT_LEN {
+        app.connection.push_log(
+            LogLevel::Warn,
+            LogCategory::Messages,
+            &trf(
+                Msg::LogSnapshotTooLarge,
+                &[&snapshot.text.len(), &validation::MAX_SNAPSHOT_LEN],
+            ),
+        );
+        return;
+    }
+    let conversation_id = app.ui.current_conversation.clone();
+    app.send_terminal_snapshot(conversation_id, snapshot);
+}
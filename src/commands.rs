@@ -0,0 +1,1021 @@
+use std::collections::BTreeSet;
+use std::mem;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Context};
+use chrono::{DateTime, Local};
+use libp2p::PeerId;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::app::{App, MessageKind};
+use crate::audit::{self, AuditAction};
+use crate::away;
+use crate::capture;
+use crate::connection::ConnectionCommand;
+use crate::conversation::ConversationId;
+use crate::crdt::Pin;
+use crate::eventlog::{self, LogCategory, LogLevel};
+use crate::i18n::{tr, trf, Msg};
+use crate::input::InputTask;
+use crate::invite::{self, Invite};
+use crate::missed;
+use crate::modal::{Modal, ModalAction};
+use crate::nickcert;
+use crate::patch;
+use crate::profile;
+use crate::protocol::{Payload, StatusCard};
+use crate::schedule;
+use crate::session;
+use crate::status;
+use crate::topickeys;
+use crate::utils;
+use crate::validation;
+use crate::verify;
+use crate::voice;
+
+// A slash command the chat input understands
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub args: &'static str,
+    pub help: &'static str,
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "dm",
+        args: "<peer id>",
+        help: "open a direct message conversation with a peer",
+    },
+    CommandSpec {
+        name: "join",
+        args: "<topic>",
+        help: "subscribe to a topic and open its conversation",
+    },
+    CommandSpec {
+        name: "invite",
+        args: "",
+        help: "copy an invite to the current topic, with its key and peers to reach it through",
+    },
+    CommandSpec {
+        name: "join-invite",
+        args: "<invite>",
+        help: "join the topic of an invite, it can also be pasted into the address input",
+    },
+    CommandSpec {
+        name: "leave",
+        args: "[topic]",
+        help: "unsubscribe from a topic, the current one by default",
+    },
+    CommandSpec {
+        name: "topic",
+        args: "[<description> | set <muted|hide_join_leave|highlight|bell|disappear> <value> | color <color|none> | icon <emoji|none> | announcers <peer>...|none | key rotate]",
+        help: "set the description of the current topic, or show or change its settings",
+    },
+    CommandSpec {
+        name: "poll",
+        args: "\"<question>\" <option> <option>...",
+        help: "ask the current conversation a question, vote with the number keys on it",
+    },
+    CommandSpec {
+        name: "voice",
+        args: "",
+        help: "record a voice memo for the current conversation with `voice_memos.record`, it is sent when the recording stops",
+    },
+    CommandSpec {
+        name: "snapshot",
+        args: "[screen|history|log]",
+        help: "send the terminal as text to the current conversation, or only one of its panes",
+    },
+    CommandSpec {
+        name: "patch",
+        args: "<range>",
+        help: "send the commits of a range in the git repository of the working directory, e.g. HEAD~2",
+    },
+    CommandSpec {
+        name: "clip",
+        args: "[set <topic> | off]",
+        help: "send the clipboard to the clipboard topic, press c on a clip to copy it, or set that encrypted topic",
+    },
+    CommandSpec {
+        name: "missed",
+        args: "",
+        help: "show what arrived in our topics while we were offline",
+    },
+    CommandSpec {
+        name: "pin",
+        args: "",
+        help: "pin the selected or the newest message above the history of the current topic",
+    },
+    CommandSpec {
+        name: "unpin",
+        args: "",
+        help: "remove the pinned message of the current topic",
+    },
+    CommandSpec {
+        name: "purge",
+        args: "[all]",
+        help: "delete the local history of the current conversation, or of all of them",
+    },
+    CommandSpec {
+        name: "system",
+        args: "<on|off>",
+        help: "show or hide connection events in the chat history",
+    },
+    CommandSpec {
+        name: "bell",
+        args: "<never|mentions|direct|all>",
+        help: "choose when the terminal bell rings, topics can override it with /topic set bell",
+    },
+    CommandSpec {
+        name: "nick",
+        args: "<name> | unbind <name>",
+        help: "set the nickname sent with your messages, or forget which peer a nick is bound to",
+    },
+    CommandSpec {
+        name: "presence",
+        args: "<emoji> [text] | clear",
+        help: "show what you are up to next to your nick, e.g. /presence 🎧 in a call",
+    },
+    CommandSpec {
+        name: "schedule",
+        args: "[<delay> <text>|cancel <id>]",
+        help: "send a message after a delay like 10m or 1h30m, without arguments list the queue",
+    },
+    CommandSpec {
+        name: "in",
+        args: "<delay> <text>",
+        help: "same as /schedule <delay> <text>",
+    },
+    CommandSpec {
+        name: "at",
+        args: "<HH:MM> <text>",
+        help: "send a message the next time the clock shows HH:MM",
+    },
+    CommandSpec {
+        name: "stats",
+        args: "",
+        help: "show the messages, bytes and peers of this session",
+    },
+    CommandSpec {
+        name: "whois",
+        args: "<nick>",
+        help: "show the peer ids and addresses of the peers using a nick",
+    },
+    CommandSpec {
+        name: "key",
+        args: "<export|import> <file>",
+        help: "export the identity to a passphrase protected file, or import one on the next start",
+    },
+    CommandSpec {
+        name: "identity",
+        args: "<new|load <file>>",
+        help: "switch to a new identity, or one exported with /key, without restarting",
+    },
+    CommandSpec {
+        name: "verbose",
+        args: "<on|off>",
+        help: "log every swarm event, not only connections, subscriptions and errors",
+    },
+    CommandSpec {
+        name: "exportlog",
+        args: "<file>",
+        help: "write the connection log to a file, e.g. for a bug report",
+    },
+    CommandSpec {
+        name: "profiles",
+        args: "",
+        help: "list the profiles, start with --profile <name> to use or create one",
+    },
+    CommandSpec {
+        name: "verify",
+        args: "<nick|peer id>",
+        help: "compare a short authentication string with a peer over another channel",
+    },
+    CommandSpec {
+        name: "mute",
+        args: "<nick|peer id>",
+        help: "hide the messages of a peer without disconnecting, kept across restarts",
+    },
+    CommandSpec {
+        name: "unmute",
+        args: "<nick|peer id>",
+        help: "show the messages of a muted peer again",
+    },
+    CommandSpec {
+        name: "note",
+        args: "<nick|peer id> [text]",
+        help: "attach a local note to a peer, shown in the member pane and /whois, none removes it",
+    },
+    CommandSpec {
+        name: "tag",
+        args: "<nick|peer id> <tag|-tag>...",
+        help: "add tags to a peer, or remove them with a leading -",
+    },
+    CommandSpec {
+        name: "unblock",
+        args: "<peer id>",
+        help: "show messages from a blocked peer again",
+    },
+    CommandSpec {
+        name: "quit",
+        args: "",
+        help: "quit p2pchat",
+    },
+];
+
+pub fn is_command(input: &str) -> bool {
+    input.starts_with('/')
+}
+
+pub fn find(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|command| command.name == name)
+}
+
+// The commands matching what was typed so far, for the hint above the input. Once arguments are
+// being typed only the command itself is left
+pub fn matching(input: &str) -> Vec<&'static CommandSpec> {
+    let typed = match input.strip_prefix('/') {
+        Some(typed) => typed,
+        None => return vec![],
+    };
+    match typed.split_once(char::is_whitespace) {
+        Some((name, _)) => find(name).into_iter().collect(),
+        None => COMMANDS
+            .iter()
+            .filter(|command| command.name.starts_with(typed))
+            .collect(),
+    }
+}
+
+fn usage(name: &str) -> anyhow::Error {
+    match find(name) {
+        Some(command) => anyhow!("usage: /{} {}", command.name, command.args),
+        None => anyhow!("unknown command /{}", name),
+    }
+}
+
+// A peer given by its peer id or a nick as shown in the history
+fn resolve_peer(name: &str, app: &App) -> Result<PeerId, anyhow::Error> {
+    if let Ok(peer_id) = name.parse::<PeerId>() {
+        return Ok(peer_id);
+    }
+    match app.nicks.lookup(name).as_slice() {
+        [peer_id] => Ok(*peer_id),
+        [] => bail!("nobody uses the nick {}", name),
+        _ => bail!("several peers use the nick {}, see /whois {}", name, name),
+    }
+}
+
+// Splits at whitespace except within double quotes, e.g. `"Which day?" Monday "Next week"`
+fn split_quoted(text: &str) -> Result<Vec<String>, anyhow::Error> {
+    let mut parts = vec![];
+    let mut part = String::new();
+    let mut in_part = false;
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_part = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_part {
+                    parts.push(mem::take(&mut part));
+                    in_part = false;
+                }
+            }
+            c => {
+                part.push(c);
+                in_part = true;
+            }
+        }
+    }
+    if quoted {
+        bail!("missing closing quote in `{}`", text);
+    }
+    if in_part {
+        parts.push(part);
+    }
+    Ok(parts)
+}
+
+// on / off
+fn parse_switch(value: &str) -> Result<bool, anyhow::Error> {
+    match value {
+        "on" | "true" | "yes" => Ok(true),
+        "off" | "false" | "no" => Ok(false),
+        _ => bail!("expected on or off, got `{}`", value),
+    }
+}
+
+// Starts a new key epoch for a topic, encrypting it if it wasn't yet, and sends the key to the
+// verified peers subscribed to it. Everyone else can't read messages sent from now on
+fn rotate_topic_key(topic: &str, app: &mut App) -> Result<(), anyhow::Error> {
+    // Peers only take keys from the issuer of a topic, which is us once we hand out the first
+    let local_peer_id = app.connection.swarm.local_peer_id().to_base58();
+    let settings = app.config.topic_settings_mut(topic);
+    if let Some(issuer) = settings
+        .key_issuer
+        .as_ref()
+        .filter(|issuer| **issuer != local_peer_id)
+    {
+        anyhow::bail!("only {} can rotate the key of #{}", issuer, topic);
+    }
+    settings.key_issuer = Some(local_peer_id);
+    let epoch = match settings.keys.keys().next_back() {
+        Some(epoch) => epoch
+            .checked_add(1)
+            .context("the key of this topic can't be rotated anymore, every epoch is used")?,
+        None => 0,
+    };
+    let key = topickeys::generate();
+    settings.keys.insert(epoch, key.clone());
+
+    let topic_hash = app.connection.topics.hash(topic);
+    let members = app
+        .connection
+        .swarm
+        .behaviour()
+        .gossipsub
+        .all_peers()
+        .filter(|(_, topics)| topics.contains(&&topic_hash))
+        .map(|(peer_id, _)| *peer_id)
+        .filter(|peer_id| app.config.is_verified(peer_id))
+        .collect::<Vec<PeerId>>();
+    for peer_id in members.iter() {
+        app.send_payload_logged(
+            &ConversationId::Direct(*peer_id),
+            Payload::TopicKey {
+                topic: topic.to_string(),
+                epoch,
+                key: key.clone(),
+            },
+        );
+    }
+
+    app.connection.push_log(
+        LogLevel::Info,
+        LogCategory::Security,
+        &trf(Msg::LogTopicKeyRotated, &[&topic, &epoch, &members.len()]),
+    );
+    Ok(())
+}
+
+// Queues a message for the current conversation, sent if p2pchat is still running by then
+fn schedule_message(due: DateTime<Local>, text: String, app: &mut App) {
+    let conversation = app.ui.current_conversation.clone();
+    let id = app.schedule.add(conversation.clone(), text, due);
+    app.connection.push_log(
+        LogLevel::Info,
+        LogCategory::Messages,
+        &trf(
+            Msg::LogScheduled,
+            &[&id, &conversation, &due.format("%Y-%m-%d %H:%M:%S")],
+        ),
+    );
+}
+
+pub fn execute(input: &str, app: &mut App) -> Result<InputTask, anyhow::Error> {
+    let mut parts = input.trim_start_matches('/').split_whitespace();
+    let name = parts.next().unwrap_or_default();
+    let args = parts.collect::<Vec<&str>>();
+
+    match name {
+        "dm" => {
+            let peer_id = args
+                .first()
+                .ok_or_else(|| usage(name))?
+                .parse::<PeerId>()
+                .context("parsing peer id failed")?;
+            app.open_conversation(ConversationId::Direct(peer_id));
+        }
+        "join" => {
+            let topic = args.first().ok_or_else(|| usage(name))?;
+            app.connection
+                .outbox
+                .push(ConnectionCommand::Subscribe(topic.to_string()));
+            app.open_conversation(ConversationId::Topic(topic.to_string()));
+        }
+        "invite" => {
+            let topic = match &app.ui.current_conversation {
+                ConversationId::Topic(topic) => topic.clone(),
+                ConversationId::Direct(_) => bail!("/{} only works in topics", name),
+            };
+            let invite = Invite::for_topic(&topic, app).encode()?;
+            utils::copy_to_clipboard(invite.clone())?;
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::General,
+                &trf(Msg::LogInviteCopied, &[&topic]),
+            );
+        }
+        "join-invite" => {
+            let invite = args.first().ok_or_else(|| usage(name))?;
+            invite::accept(invite, app)?;
+        }
+        "leave" => {
+            let topic = match (args.first(), &app.ui.current_conversation) {
+                (Some(topic), _) => topic.to_string(),
+                (None, ConversationId::Topic(topic)) => topic.clone(),
+                (None, ConversationId::Direct(_)) => return Err(usage(name)),
+            };
+            app.connection
+                .outbox
+                .push(ConnectionCommand::Unsubscribe(topic.clone()));
+
+            let conversation_id = ConversationId::Topic(topic);
+            if let Some(conversation) = app.conversations.remove(&conversation_id) {
+                voice::remove(&conversation.history, app);
+            }
+            if app.ui.current_conversation == conversation_id {
+                let next = app
+                    .sorted_conversation_ids()
+                    .into_iter()
+                    .next()
+                    .unwrap_or_else(|| ConversationId::Topic(app.config.default_topic.clone()));
+                app.open_conversation(next);
+            }
+        }
+        "topic" => {
+            let topic = match &app.ui.current_conversation {
+                ConversationId::Topic(topic) => topic.clone(),
+                ConversationId::Direct(_) => bail!("/topic only works in topics"),
+            };
+
+            match args.as_slice() {
+                [] => {}
+                ["set", "muted", value] => {
+                    app.config.topic_settings_mut(&topic).muted = parse_switch(value)?;
+                }
+                ["set", "hide_join_leave", value] => {
+                    app.config.topic_settings_mut(&topic).hide_join_leave = parse_switch(value)?;
+                }
+                ["set", "bell", "default"] => {
+                    app.config.topic_settings_mut(&topic).bell = None;
+                }
+                ["set", "bell", value] => {
+                    app.config.topic_settings_mut(&topic).bell = Some(value.parse()?);
+                }
+                ["set", "disappear", "off"] => {
+                    app.config.topic_settings_mut(&topic).disappear_after = None;
+                }
+                ["set", "disappear", value] => {
+                    let seconds = schedule::parse_delay(value)?.num_seconds();
+                    app.config.topic_settings_mut(&topic).disappear_after =
+                        Some(u64::try_from(seconds)?);
+                }
+                ["key", "rotate"] => {
+                    rotate_topic_key(&topic, app)?;
+                }
+                ["color", "none"] => {
+                    app.config.topic_settings_mut(&topic).color = None;
+                }
+                ["color", value] => {
+                    app.config.topic_settings_mut(&topic).color = Some(value.parse()?);
+                }
+                ["icon", "none"] => {
+                    app.config.topic_settings_mut(&topic).icon = None;
+                }
+                ["icon", icon] => {
+                    // A single emoji or character, anything wider breaks the sidebar
+                    if icon.graphemes(true).count() != 1 {
+                        bail!("the icon must be a single character, got `{}`", icon);
+                    }
+                    app.config.topic_settings_mut(&topic).icon = Some(icon.to_string());
+                }
+                ["announcers", "none"] => {
+                    app.config.topic_settings_mut(&topic).announcers.clear();
+                }
+                ["announcers", peers @ ..] if !peers.is_empty() => {
+                    let announcers = peers
+                        .iter()
+                        .map(|peer| resolve_peer(peer, app).map(|peer_id| peer_id.to_base58()))
+                        .collect::<Result<BTreeSet<String>, anyhow::Error>>()?;
+                    app.config.topic_settings_mut(&topic).announcers = announcers;
+                }
+                ["set", "highlight", words @ ..] => {
+                    app.config.topic_settings_mut(&topic).highlight_words =
+                        words.iter().map(|word| word.to_string()).collect();
+                }
+                [first, ..] if !["set", "key", "color", "icon", "announcers"].contains(first) => {
+                    app.set_topic_description(&topic, args.join(" "));
+                    return Ok(InputTask::Continue);
+                }
+                _ => return Err(usage(name)),
+            }
+            if !args.is_empty() {
+                app.config.save()?;
+            }
+
+            let settings = app.config.topic_settings(&topic);
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::General,
+                &trf(
+                    Msg::TopicSettingsShow,
+                    &[
+                        &topic,
+                        &settings.muted,
+                        &settings.hide_join_leave,
+                        &settings.highlight_words.join(", "),
+                        &format!("{:?}", settings.bell.unwrap_or(app.config.bell)).to_lowercase(),
+                        &settings.color.map_or(String::from("none"), |color| {
+                            format!("{:?}", color).to_lowercase()
+                        }),
+                        &settings.icon.as_deref().unwrap_or("none"),
+                        &settings
+                            .announcers
+                            .iter()
+                            .cloned()
+                            .collect::<Vec<String>>()
+                            .join(", "),
+                        &settings
+                            .disappear_after
+                            .map_or(String::from("off"), schedule::format_delay),
+                    ],
+                ),
+            );
+        }
+        "poll" => {
+            // The question and options may contain spaces, so they are split again
+            let rest = &input.trim_start_matches('/').trim_start()[name.len()..];
+            let parts = split_quoted(rest)?;
+            let (question, options) = match parts.split_first() {
+                Some((question, options)) if options.len() >= 2 => (question, options),
+                _ => return Err(usage(name)),
+            };
+            if options.len() > validation::MAX_POLL_OPTIONS {
+                bail!(
+                    "a poll has at most {} options",
+                    validation::MAX_POLL_OPTIONS
+                );
+            }
+            app.send_poll(question.clone(), options.to_vec());
+        }
+        "voice" => voice::record(app)?,
+        "snapshot" => {
+            let pane = match args.first() {
+                Some(pane) => capture::Pane::parse(pane).ok_or_else(|| usage(name))?,
+                None => capture::Pane::Screen,
+            };
+            capture::request(app, pane);
+        }
+        "clip" => match args.as_slice() {
+            [] => {
+                let topic = app
+                    .config
+                    .clipboard_topic
+                    .clone()
+                    .context("set the clipboard topic with /clip set <topic> first")?;
+                // Clips are only for a trusted group
+                if app.config.topic_settings(&topic).keys.is_empty() {
+                    bail!(
+                        "the clipboard topic `{}` isn't encrypted, add a key with /topic key rotate",
+                        topic
+                    );
+                }
+                let text = utils::paste_from_clipboard()?;
+                if text.trim().is_empty() {
+                    bail!("the clipboard is empty");
+                }
+                if text.len() > validation::MAX_TEXT_LEN {
+                    bail!(
+                        "the clipboard takes {} bytes, at most {} can be sent",
+                        text.len(),
+                        validation::MAX_TEXT_LEN
+                    );
+                }
+                app.send_clip(topic.clone(), text);
+                app.connection.push_log(
+                    LogLevel::Info,
+                    LogCategory::General,
+                    &trf(Msg::LogClipSent, &[&topic]),
+                );
+            }
+            ["set", topic] => {
+                app.config.clipboard_topic = Some(topic.to_string());
+                app.config.save()?;
+                if !app.connection.topics.is_subscribed(topic) {
+                    app.connection
+                        .outbox
+                        .push(ConnectionCommand::Subscribe(topic.to_string()));
+                }
+                app.connection.push_log(
+                    LogLevel::Info,
+                    LogCategory::General,
+                    &trf(Msg::LogClipboardTopicSet, &[topic]),
+                );
+            }
+            ["off"] => {
+                app.config.clipboard_topic = None;
+                app.config.save()?;
+            }
+            _ => return Err(usage(name)),
+        },
+        "missed" => {
+            if app.missed.is_empty() {
+                bail!("nothing arrived while we were offline");
+            }
+            missed::show(app);
+        }
+        "patch" => {
+            let range = args.first().ok_or_else(|| usage(name))?;
+            patch::send(app, range)?;
+        }
+        "pin" | "unpin" => {
+            let topic = match &app.ui.current_conversation {
+                ConversationId::Topic(topic) => topic.clone(),
+                ConversationId::Direct(_) => bail!("/{} only works in topics", name),
+            };
+            let pin = if name == "pin" {
+                let conversation = app.current_conversation().context("nothing to pin")?;
+                let message = app
+                    .ui
+                    .chat_history_selected
+                    .and_then(|selected| conversation.history.get(selected))
+                    .or_else(|| {
+                        conversation
+                            .history
+                            .iter()
+                            .rev()
+                            .find(|message| message.kind == MessageKind::Chat)
+                    })
+                    .filter(|message| message.kind == MessageKind::Chat)
+                    .context("nothing to pin")?;
+                Some(Pin {
+                    message_id: message.id,
+                    author: app.nicks.author(message),
+                    text: message.text.clone(),
+                })
+            } else {
+                None
+            };
+            app.set_topic_pin(&topic, pin);
+        }
+        "system" => {
+            let value = args.first().ok_or_else(|| usage(name))?;
+            app.config.system_messages = parse_switch(value)?;
+            app.config.save()?;
+        }
+        "bell" => {
+            let value = args.first().ok_or_else(|| usage(name))?;
+            app.config.bell = value.parse()?;
+            app.config.save()?;
+        }
+        "nick" => match args.as_slice() {
+            [] | ["unbind"] => return Err(usage(name)),
+            ["unbind", nick @ ..] => nickcert::unbind(&nick.join(" "), app)?,
+            _ => {
+                let nick = args.join(" ");
+                app.ui.nick_input.set(nick.clone());
+                app.connection.push_log(
+                    LogLevel::Info,
+                    LogCategory::General,
+                    &trf(Msg::NickChanged, &[&nick]),
+                );
+                nickcert::announce(app);
+            }
+        },
+        "presence" => {
+            let card = match args.as_slice() {
+                [] => return Err(usage(name)),
+                ["clear"] => None,
+                [emoji, text @ ..] => {
+                    if emoji.graphemes(true).count() != 1 {
+                        bail!("the status must start with a single emoji, got `{}`", emoji);
+                    }
+                    let card = StatusCard {
+                        emoji: emoji.to_string(),
+                        text: text.join(" "),
+                    };
+                    if !validation::is_valid_card(&card) {
+                        bail!(
+                            "the status text is longer than {} bytes",
+                            validation::MAX_CARD_TEXT_LEN
+                        );
+                    }
+                    Some(card)
+                }
+            };
+            let text = card
+                .as_ref()
+                .map_or(tr(Msg::LogStatusCardCleared).to_string(), |card| {
+                    trf(Msg::LogStatusCardSet, &[card])
+                });
+            app.config.status_card = card;
+            app.config.save()?;
+            app.connection
+                .push_log(LogLevel::Info, LogCategory::General, &text);
+            away::announce(app);
+        }
+        "stats" => {
+            for entry in status::stats_entries(app) {
+                app.connection.push_log(
+                    LogLevel::Info,
+                    LogCategory::General,
+                    &format!("{}: {}", entry.label, entry.value),
+                );
+            }
+        }
+        "whois" => {
+            let name = args.first().ok_or_else(|| usage(name))?;
+            let peer_ids = app.nicks.lookup(name);
+            if peer_ids.is_empty() {
+                bail!("nobody uses the nick {}", name);
+            }
+            for peer_id in peer_ids {
+                let addrs = app
+                    .connection
+                    .peer_addrs
+                    .get(&peer_id)
+                    .map(|addrs| {
+                        addrs
+                            .iter()
+                            .map(|addr| addr.to_string())
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    })
+                    .unwrap_or_default();
+                let shown_as = app.nicks.name_of(&peer_id).unwrap_or_default();
+                app.connection.push_log(
+                    LogLevel::Info,
+                    LogCategory::General,
+                    &trf(Msg::WhoisShow, &[&shown_as, &peer_id, &addrs]),
+                );
+                let quality = app
+                    .connection
+                    .quality
+                    .get(&peer_id)
+                    .cloned()
+                    .unwrap_or_default();
+                let rtt = quality
+                    .rtt
+                    .map_or(String::from("-"), |rtt| format!("{} ms", rtt.as_millis()));
+                app.connection.push_log(
+                    LogLevel::Info,
+                    LogCategory::General,
+                    &trf(
+                        Msg::WhoisQuality,
+                        &[
+                            &quality.score(),
+                            &rtt,
+                            &quality.dial_failures,
+                            &quality.delivery_failures,
+                        ],
+                    ),
+                );
+                if let Some(note) = app.roster.get(&peer_id) {
+                    app.connection.push_log(
+                        LogLevel::Info,
+                        LogCategory::General,
+                        &trf(Msg::WhoisNote, &[&note.tag_list(), &note.note]),
+                    );
+                }
+            }
+        }
+        "key" => {
+            let (action, title) = match args.as_slice() {
+                ["export", path] => (
+                    ModalAction::ExportKey(PathBuf::from(path)),
+                    tr(Msg::KeyExportTitle),
+                ),
+                ["import", path] => (
+                    ModalAction::ImportKey(PathBuf::from(path)),
+                    tr(Msg::KeyImportTitle),
+                ),
+                _ => return Err(usage(name)),
+            };
+            app.ui
+                .modals
+                .push(Modal::secret(title, tr(Msg::KeyPassphrase), action));
+        }
+        "identity" => match args.as_slice() {
+            // The old identity is gone unless it was exported
+            ["new"] => app.ui.modals.push(Modal::confirm(
+                tr(Msg::IdentityNewTitle),
+                &trf(
+                    Msg::IdentityNewConfirm,
+                    &[app.connection.swarm.local_peer_id()],
+                ),
+                ModalAction::NewIdentity,
+            )),
+            ["load", path] => app.ui.modals.push(Modal::secret(
+                tr(Msg::IdentityLoadTitle),
+                tr(Msg::KeyPassphrase),
+                ModalAction::LoadIdentity(PathBuf::from(path)),
+            )),
+            _ => return Err(usage(name)),
+        },
+        "verbose" => {
+            let value = args.first().ok_or_else(|| usage(name))?;
+            app.config.verbose_log = parse_switch(value)?;
+            app.connection.verbose = app.config.verbose_log;
+            app.config.save()?;
+        }
+        "exportlog" => {
+            let path = PathBuf::from(args.first().ok_or_else(|| usage(name))?);
+            eventlog::export(&app.connection.log, &path)?;
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::General,
+                &trf(Msg::LogExported, &[&path.display()]),
+            );
+        }
+        "profiles" => {
+            let current = profile::current();
+            let names = std::iter::once(None)
+                .chain(profile::list()?.into_iter().map(Some))
+                .map(|name| {
+                    let shown = name
+                        .clone()
+                        .unwrap_or_else(|| tr(Msg::ProfileDefault).to_string());
+                    if name == current {
+                        format!("*{}", shown)
+                    } else {
+                        shown
+                    }
+                })
+                .collect::<Vec<String>>();
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::General,
+                &trf(Msg::ProfilesShow, &[&names.join(", ")]),
+            );
+        }
+        "verify" => {
+            let peer_id = resolve_peer(args.first().ok_or_else(|| usage(name))?, app)?;
+            let remote = app.connection.peer_keys.get(&peer_id).with_context(|| {
+                format!("the public key of {} is unknown, connect first", peer_id)
+            })?;
+            let sas = verify::sas(&app.connection.id_keys.public(), remote);
+
+            app.ui.modals.push(Modal::confirm(
+                tr(Msg::VerifyTitle),
+                &trf(Msg::VerifyMessage, &[&peer_id, &sas.join("  ")]),
+                ModalAction::Verify(peer_id),
+            ));
+        }
+        "unblock" => {
+            let peer_id = args
+                .first()
+                .ok_or_else(|| usage(name))?
+                .parse::<PeerId>()
+                .context("parsing peer id failed")?;
+            if !app.moderation.unblock(&peer_id) {
+                bail!("peer {} is not blocked", peer_id);
+            }
+            audit::record(
+                app,
+                AuditAction::Unblocked,
+                Some(peer_id),
+                &trf(Msg::AuditReasonCommand, &[&name]),
+            );
+        }
+        "purge" => {
+            let ids = match args.as_slice() {
+                [] => vec![app.ui.current_conversation.clone()],
+                ["all"] => app.conversations.keys().cloned().collect(),
+                _ => return Err(usage(name)),
+            };
+            let mut purged = vec![];
+            for id in ids.iter() {
+                if let Some(conversation) = app.conversations.get_mut(id) {
+                    purged.append(&mut conversation.history);
+                    // The pages never read are removed with the rest of the stored history
+                    conversation.stored_pages = 0;
+                }
+            }
+            let count = purged.len();
+            voice::remove(&purged, app);
+            app.ui.chat_history_selected = None;
+            app.ui.chat_history_anchor = None;
+            // Right away, not with the next snapshot
+            session::save(app);
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::Messages,
+                &trf(Msg::LogPurged, &[&count, &ids.len()]),
+            );
+        }
+        "mute" | "unmute" => {
+            let peer_id = resolve_peer(args.first().ok_or_else(|| usage(name))?, app)?;
+            let (msg, action) = if name == "mute" {
+                app.moderation.mute(&peer_id);
+                (Msg::LogPeerMuted, AuditAction::Muted)
+            } else {
+                if !app.moderation.unmute(&peer_id) {
+                    bail!("peer {} is not muted", peer_id);
+                }
+                (Msg::LogPeerUnmuted, AuditAction::Unmuted)
+            };
+            app.moderation.save()?;
+            audit::record(
+                app,
+                action,
+                Some(peer_id),
+                &trf(Msg::AuditReasonCommand, &[&name]),
+            );
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::Messages,
+                &trf(msg, &[&peer_id]),
+            );
+        }
+        "note" => {
+            let peer_id = resolve_peer(args.first().ok_or_else(|| usage(name))?, app)?;
+            let note = args[1..].join(" ");
+            app.roster.set_note(&peer_id, &note);
+            app.roster.save()?;
+            let msg = if note.is_empty() {
+                Msg::LogPeerNoteRemoved
+            } else {
+                Msg::LogPeerNoteSet
+            };
+            app.connection
+                .push_log(LogLevel::Info, LogCategory::General, &trf(msg, &[&peer_id]));
+        }
+        "tag" => {
+            let (peer, tags) = match args.as_slice() {
+                [peer, tags @ ..] if !tags.is_empty() => (peer, tags),
+                _ => return Err(usage(name)),
+            };
+            let peer_id = resolve_peer(peer, app)?;
+            for tag in tags {
+                app.roster.tag(&peer_id, tag)?;
+            }
+            app.roster.save()?;
+            let tag_list = app
+                .roster
+                .get(&peer_id)
+                .map(|note| note.tag_list())
+                .unwrap_or_default();
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::General,
+                &trf(Msg::LogPeerTagged, &[&peer_id, &tag_list]),
+            );
+        }
+        "schedule" | "in" => match args.as_slice() {
+            [] if name == "schedule" => {
+                if app.schedule.pending().is_empty() {
+                    app.connection.push_log(
+                        LogLevel::Info,
+                        LogCategory::Messages,
+                        tr(Msg::LogScheduleEmpty),
+                    );
+                }
+                let lines = app
+                    .schedule
+                    .pending()
+                    .iter()
+                    .map(|scheduled| {
+                        trf(
+                            Msg::ScheduledShow,
+                            &[
+                                &scheduled.id,
+                                &scheduled.due.format("%Y-%m-%d %H:%M:%S"),
+                                &scheduled.conversation,
+                                &scheduled.text,
+                            ],
+                        )
+                    })
+                    .collect::<Vec<String>>();
+                for line in lines {
+                    app.connection
+                        .push_log(LogLevel::Info, LogCategory::Messages, &line);
+                }
+            }
+            ["cancel", id] if name == "schedule" => {
+                let id = id.parse::<u32>().context("parsing id failed")?;
+                let scheduled = app
+                    .schedule
+                    .cancel(id)
+                    .ok_or_else(|| anyhow!("no scheduled message with id {}", id))?;
+                app.connection.push_log(
+                    LogLevel::Info,
+                    LogCategory::Messages,
+                    &trf(Msg::LogScheduleCancelled, &[&scheduled.id]),
+                );
+            }
+            [delay, text @ ..] if !text.is_empty() => {
+                let due = Local::now()
+                    .checked_add_signed(schedule::parse_delay(delay)?)
+                    .context("delay too long")?;
+                schedule_message(due, text.join(" "), app);
+            }
+            _ => return Err(usage(name)),
+        },
+        "at" => match args.as_slice() {
+            [time, text @ ..] if !text.is_empty() => {
+                let due = schedule::parse_time(time, Local::now())?;
+                schedule_message(due, text.join(" "), app);
+            }
+            _ => return Err(usage(name)),
+        },
+        // Quits after what was queued before, e.g. a message sent just now
+        "quit" => app.connection.outbox.push(ConnectionCommand::Shutdown),
+        _ => bail!("unknown command /{}", name),
+    }
+
+    Ok(InputTask::Continue)
+}
@@ -0,0 +1,731 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use anyhow::Context;
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+
+use crate::conversation::ConversationId;
+use crate::filters::ContentFilter;
+use crate::i18n::Locale;
+use crate::profile;
+use crate::protocol::StatusCard;
+use crate::secrets;
+use crate::topics::DEFAULT_TOPIC;
+use crate::transforms::Transforms;
+use crate::writer;
+
+// The topic keys last read from or written to the secrets, `None` until then. Saving the config
+// only touches the keyring when they changed
+static STORED_TOPIC_KEYS: Mutex<Option<Option<String>>> = Mutex::new(None);
+
+// When to ring the terminal bell for incoming messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Bell {
+    Never,
+    // Messages in topics mentioning our nick or a highlight word
+    Mentions,
+    // Mentions and direct messages
+    Direct,
+    // Every message that counts as unread
+    All,
+}
+
+impl Default for Bell {
+    fn default() -> Self {
+        Self::Never
+    }
+}
+
+impl FromStr for Bell {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" => Ok(Self::Never),
+            "mentions" => Ok(Self::Mentions),
+            "direct" => Ok(Self::Direct),
+            "all" => Ok(Self::All),
+            _ => anyhow::bail!("expected never, mentions, direct or all, got `{}`", s),
+        }
+    }
+}
+
+// Which messages a transform changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransformDirection {
+    // Ours, before they are sent
+    Outgoing,
+    // Other peers', before they are shown
+    Incoming,
+    Both,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransformKind {
+    // Pipes the text through an external program, e.g. a translator or
+    // `iconv -t ascii//TRANSLIT` for transliteration, and uses what it prints
+    Command {
+        program: String,
+        args: Vec<String>,
+    },
+    // Replaces the matches of a regex, `$1` and the like refer to its groups
+    Replace {
+        pattern: String,
+        replacement: String,
+    },
+    // Replaces these words with asterisks, ignoring case
+    Mask {
+        words: Vec<String>,
+    },
+}
+
+// A step of the transform pipeline, applied in the order of the config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransformRule {
+    pub direction: TransformDirection,
+    #[serde(flatten)]
+    pub kind: TransformKind,
+}
+
+// The color a topic is labeled with, set with `/topic color`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelColor {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+impl FromStr for LabelColor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "red" => Ok(Self::Red),
+            "green" => Ok(Self::Green),
+            "yellow" => Ok(Self::Yellow),
+            "blue" => Ok(Self::Blue),
+            "magenta" => Ok(Self::Magenta),
+            "cyan" => Ok(Self::Cyan),
+            "white" => Ok(Self::White),
+            _ => anyhow::bail!(
+                "expected red, green, yellow, blue, magenta, cyan or white, got `{}`",
+                s
+            ),
+        }
+    }
+}
+
+// What happens to incoming messages matching a filter rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterAction {
+    // Drop the message
+    Hide,
+    // Show the matching text replaced, the message itself is kept as it was
+    Redact,
+    // Highlight the message like a mention
+    Highlight,
+}
+
+// A regex matched against the text of incoming messages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilterRule {
+    pub pattern: String,
+    pub action: FilterAction,
+}
+
+// Settings for a single topic, changed with `/topic set`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TopicSettings {
+    // Count new messages, but don't highlight the topic
+    pub muted: bool,
+    // Don't show peers joining and leaving in the history
+    pub hide_join_leave: bool,
+    // Messages containing one of these are highlighted like mentions of our nick
+    pub highlight_words: Vec<String>,
+    // Overrides the global bell setting for this topic
+    pub bell: Option<Bell>,
+    // The base64 keys of an encrypted topic by epoch, messages are sent with the latest one.
+    // Kept with the secrets, only read from the config file to move them there
+    #[serde(skip_serializing)]
+    pub keys: BTreeMap<u32, String>,
+    // The peer id of the only peer whose keys are taken for the topic, the one that sent or
+    // generated its first key
+    pub key_issuer: Option<String>,
+    // Overrides the global retention for this topic
+    pub retention: Option<Retention>,
+    // Shown with the topic's name to tell topics apart at a glance
+    pub color: Option<LabelColor>,
+    pub icon: Option<String>,
+    // Peer ids that may post to the topic, which makes it an announcement topic when set.
+    // Messages from everyone else are dropped
+    pub announcers: BTreeSet<String>,
+    // Seconds after which the messages we send to the topic disappear for everyone
+    pub disappear_after: Option<u64>,
+}
+
+// How long messages are kept in the history. Older ones are dropped, nothing is kept if both
+// are set and either limit is reached
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Retention {
+    pub days: Option<u32>,
+    // Per conversation
+    pub messages: Option<usize>,
+}
+
+impl Retention {
+    // Messages received before are dropped. `None` if no day limit is set, or if it reaches back
+    // further than a date can
+    pub fn cutoff(&self) -> Option<chrono::DateTime<chrono::Local>> {
+        let days = chrono::Duration::try_days(self.days?.into())?;
+        chrono::Local::now().checked_sub_signed(days)
+    }
+}
+
+// The sizes of the panes of the chat page, changed with Ctrl and the arrow keys and F4
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayoutSettings {
+    // Columns of the conversation sidebar
+    pub sidebar_width: u16,
+    pub sidebar_collapsed: bool,
+    // Rows of the chat input including its border, more than 3 wrap long messages
+    pub input_height: u16,
+}
+
+impl LayoutSettings {
+    const SIDEBAR_WIDTH: (u16, u16) = (12, 60);
+    const INPUT_HEIGHT: (u16, u16) = (3, 12);
+
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        let (min, max) = Self::SIDEBAR_WIDTH;
+        anyhow::ensure!(
+            (min..=max).contains(&self.sidebar_width),
+            "layout.sidebar_width ({}) must be between {} and {}",
+            self.sidebar_width,
+            min,
+            max
+        );
+        let (min, max) = Self::INPUT_HEIGHT;
+        anyhow::ensure!(
+            (min..=max).contains(&self.input_height),
+            "layout.input_height ({}) must be between {} and {}",
+            self.input_height,
+            min,
+            max
+        );
+        Ok(())
+    }
+
+    pub fn resize_sidebar(&mut self, delta: i16) {
+        let (min, max) = Self::SIDEBAR_WIDTH;
+        self.sidebar_collapsed = false;
+        self.sidebar_width =
+            (i32::from(self.sidebar_width) + i32::from(delta)).clamp(min.into(), max.into()) as u16;
+    }
+
+    pub fn resize_input(&mut self, delta: i16) {
+        let (min, max) = Self::INPUT_HEIGHT;
+        self.input_height =
+            (i32::from(self.input_height) + i32::from(delta)).clamp(min.into(), max.into()) as u16;
+    }
+}
+
+impl Default for LayoutSettings {
+    fn default() -> Self {
+        Self {
+            sidebar_width: 24,
+            sidebar_collapsed: false,
+            input_height: 3,
+        }
+    }
+}
+
+// An external program, `{file}` in its arguments is replaced with the path of a file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalCommand {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+// How voice memos are recorded and played, both with external programs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceMemoSettings {
+    // Has to stop on its own, e.g. `arecord` with `-d 10 -f S16_LE {file}`
+    pub record: Option<ExternalCommand>,
+    // E.g. `mpv` with `--no-video {file}`
+    pub play: Option<ExternalCommand>,
+    // The extension of recorded files, which also tells the player of peers what they are
+    pub format: String,
+}
+
+impl Default for VoiceMemoSettings {
+    fn default() -> Self {
+        Self {
+            record: None,
+            play: None,
+            format: String::from("wav"),
+        }
+    }
+}
+
+// How strictly gossipsub checks the author and signature of incoming messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GossipValidation {
+    // Messages must be signed by their author
+    Strict,
+    // Signatures are checked when present
+    Permissive,
+    // Messages are published without author and only unsigned messages are accepted
+    Anonymous,
+    // Nothing is checked
+    None,
+}
+
+// Tuning of the gossipsub protocol. Peers with very different values may not get along well
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GossipsubSettings {
+    pub heartbeat_interval_ms: u64,
+    pub validation_mode: GossipValidation,
+    // The number of heartbeats messages are cached for
+    pub history_length: usize,
+    // The number of those heartbeats we gossip about
+    pub history_gossip: usize,
+    // The fraction of peers outside the mesh we gossip to
+    pub gossip_factor: f64,
+    // The largest message in bytes we send or accept
+    pub max_transmit_size: usize,
+    // Publish our own messages to all peers of a topic, not only to the mesh
+    pub flood_publish: bool,
+    // Score peers by their behaviour, see `scoring.rs`. Below the thresholds peers aren't
+    // gossiped with anymore, don't get our messages and are ignored altogether
+    pub peer_scoring: bool,
+    pub gossip_threshold: f64,
+    pub publish_threshold: f64,
+    pub graylist_threshold: f64,
+}
+
+impl Default for GossipsubSettings {
+    fn default() -> Self {
+        Self {
+            // Long to not clutter the connection log
+            heartbeat_interval_ms: 10_000,
+            validation_mode: GossipValidation::Strict,
+            history_length: 5,
+            history_gossip: 3,
+            gossip_factor: 0.25,
+            max_transmit_size: 65536,
+            flood_publish: true,
+            peer_scoring: true,
+            gossip_threshold: -10.0,
+            publish_threshold: -50.0,
+            graylist_threshold: -80.0,
+        }
+    }
+}
+
+impl GossipsubSettings {
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        anyhow::ensure!(
+            self.heartbeat_interval_ms > 0,
+            "gossipsub.heartbeat_interval_ms must be greater than 0"
+        );
+        anyhow::ensure!(
+            self.history_length > 0,
+            "gossipsub.history_length must be greater than 0"
+        );
+        anyhow::ensure!(
+            self.history_gossip <= self.history_length,
+            "gossipsub.history_gossip ({}) must not be greater than gossipsub.history_length ({})",
+            self.history_gossip,
+            self.history_length
+        );
+        anyhow::ensure!(
+            (0.0..=1.0).contains(&self.gossip_factor),
+            "gossipsub.gossip_factor ({}) must be between 0.0 and 1.0",
+            self.gossip_factor
+        );
+        anyhow::ensure!(
+            self.max_transmit_size >= 100,
+            "gossipsub.max_transmit_size ({}) must be at least 100 bytes",
+            self.max_transmit_size
+        );
+        anyhow::ensure!(
+            self.graylist_threshold <= self.publish_threshold
+                && self.publish_threshold <= self.gossip_threshold
+                && self.gossip_threshold <= 0.0,
+            "gossipsub thresholds must be graylist_threshold <= publish_threshold <= gossip_threshold <= 0"
+        );
+
+        Ok(())
+    }
+}
+
+// When files written in the background are flushed to the disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsyncPolicy {
+    // Leave it to the operating system, a power loss can lose the last writes
+    Never,
+    // Every written file, before it replaces the old one
+    Always,
+}
+
+// What a leader key shortcut does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderAction {
+    // Show or hide the members of the current topic
+    Peers,
+    Chat,
+    Connection,
+    Status,
+    SplitView,
+    AllView,
+    SwitchConversation,
+    NextConversation,
+    PreviousConversation,
+}
+
+// Shortcuts typed as the leader key followed by a second key, so they work in terminals that
+// swallow Ctrl and Alt combinations. Only outside of input fields
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LeaderSettings {
+    // Leader shortcuts are disabled if not set
+    pub key: Option<char>,
+    // By their second key. `1` to `9` always open the conversation at that place in the sidebar
+    pub bindings: BTreeMap<char, LeaderAction>,
+}
+
+impl Default for LeaderSettings {
+    fn default() -> Self {
+        Self {
+            key: Some(' '),
+            bindings: BTreeMap::from([
+                ('p', LeaderAction::Peers),
+                ('m', LeaderAction::Chat),
+                ('c', LeaderAction::Connection),
+                ('s', LeaderAction::Status),
+                ('v', LeaderAction::SplitView),
+                ('a', LeaderAction::AllView),
+                ('o', LeaderAction::SwitchConversation),
+                ('n', LeaderAction::NextConversation),
+                ('b', LeaderAction::PreviousConversation),
+            ]),
+        }
+    }
+}
+
+impl LeaderSettings {
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        if let Some(key) = self.bindings.keys().find(|key| ('1'..='9').contains(key)) {
+            anyhow::bail!(
+                "leader.bindings can't use `{}`, 1 to 9 open conversations",
+                key
+            );
+        }
+        Ok(())
+    }
+}
+
+// How the config, roster, drafts and session files are written
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiskSettings {
+    // Writes to the same file within this window are combined into one
+    pub batch_ms: u64,
+    pub fsync: FsyncPolicy,
+}
+
+impl Default for DiskSettings {
+    fn default() -> Self {
+        Self {
+            batch_ms: 200,
+            fsync: FsyncPolicy::Always,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // The nick sent with our messages at startup
+    pub nick: Option<String>,
+    // What we are up to, sent with our presence. Set with /presence
+    pub status_card: Option<StatusCard>,
+    // Keep our keypair in the config directory so our peer id stays the same across restarts
+    pub persistent_identity: bool,
+    // The topic joined on startup
+    pub default_topic: String,
+    // Peers dialed on startup
+    pub bootstrap_peers: Vec<Multiaddr>,
+    // The TCP port to listen on. 0 lets the OS pick a free port
+    pub listen_port: u16,
+    // Rejoin the topics and redial the peers of the last session on startup
+    pub resume_session: bool,
+    // Switch our status to away after this many minutes without input, never if not set
+    pub auto_away_minutes: Option<u32>,
+    // Keep the chat messages in the session file, so the history survives restarts
+    pub store_history: bool,
+    // Encrypt the session file, with its history and roster, with a passphrase asked for on
+    // startup
+    pub encrypt_session: bool,
+    // Keep the session passphrase in the OS keyring, so it isn't asked for on startup
+    pub remember_session_passphrase: bool,
+    // Share the addresses of peers we dialed on the peer exchange topic, and dial the ones
+    // other peers share
+    pub peer_exchange: bool,
+    // Also listen on IPv6, next to IPv4
+    pub ipv6: bool,
+    // Don't listen at all and only dial out, so nobody can open a connection to us. Also enabled
+    // by `--outbound-only`
+    pub outbound_only: bool,
+    // Try to forward the listen port on the local router via UPnP, falling back to NAT-PMP
+    pub port_mapping: bool,
+    // The NAT-PMP gateway. If not set, the `.1` address of the local subnet is assumed
+    pub nat_pmp_gateway: Option<Ipv4Addr>,
+    // The TCP port to accept WebSocket connections on, for example from browser clients.
+    // WebSocket listening is disabled if not set
+    pub websocket_port: Option<u16>,
+    // DER encoded certificate and private key. When both are set, secure WebSockets are used
+    pub websocket_tls_cert: Option<PathBuf>,
+    pub websocket_tls_key: Option<PathBuf>,
+    // Route all outbound dials through this SOCKS5 proxy, e.g. Tor at 127.0.0.1:9050
+    pub socks5_proxy: Option<SocketAddr>,
+    // Our onion service address (`/onion3/<address>:<port>`), with Tor forwarding it to the
+    // listen port. When set, we only listen on localhost and share this address instead
+    pub onion_address: Option<Multiaddr>,
+    // Ask before quitting on Ctrl+C. Pressing Ctrl+C twice or `/quit` always quits
+    pub confirm_quit: bool,
+    // Start with the chat and connection pages side by side on wide terminals, toggled with F3
+    pub split_view: bool,
+    pub layout: LayoutSettings,
+    // Allow opening links from messages in the default browser
+    pub open_links: bool,
+    // Show links to images and other files as cards below the message
+    pub link_previews: bool,
+    pub voice_memos: VoiceMemoSettings,
+    // The encrypted topic /clip sends the clipboard to, shared by machines of a trusted group
+    pub clipboard_topic: Option<String>,
+    // Show connection events like failed dials and reconnects in the chat history
+    pub system_messages: bool,
+    // When to ring the terminal bell, topics can override it
+    pub bell: Bell,
+    // The language of the interface
+    pub locale: Locale,
+    // Line oriented output without a drawn UI, for screen readers. Also enabled by `--plain`
+    pub plain: bool,
+    // Only send the Sha256 hash of topic names over the network instead of the names themselves.
+    // Peers only see each other's messages if they agree on this
+    pub hashed_topics: bool,
+    // Log every swarm event instead of only connections, subscriptions and errors
+    pub verbose_log: bool,
+    // Show a notice in the header when peers run a newer version of p2pchat
+    pub update_check: bool,
+    // How many messages to ask for from a peer already in a topic we joined, 0 to not ask
+    pub history_sync_limit: usize,
+    // How long messages are kept, topics can override it
+    pub retention: Retention,
+    // Rules applied to incoming messages in order, the first hiding rule wins
+    pub filters: Vec<FilterRule>,
+    // Change the text of messages on their way out or in, in order
+    pub transforms: Vec<TransformRule>,
+    // Settings per topic name
+    pub topics: BTreeMap<String, TopicSettings>,
+    // Peer ids whose short authentication string was compared with /verify
+    pub verified_peers: BTreeSet<String>,
+    pub gossipsub: GossipsubSettings,
+    pub disk: DiskSettings,
+    pub leader: LeaderSettings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            nick: None,
+            status_card: None,
+            persistent_identity: false,
+            default_topic: DEFAULT_TOPIC.to_string(),
+            bootstrap_peers: vec![],
+            listen_port: 0,
+            ipv6: true,
+            peer_exchange: false,
+            resume_session: true,
+            auto_away_minutes: Some(10),
+            store_history: false,
+            encrypt_session: false,
+            remember_session_passphrase: false,
+            outbound_only: false,
+            port_mapping: false,
+            nat_pmp_gateway: None,
+            websocket_port: None,
+            websocket_tls_cert: None,
+            websocket_tls_key: None,
+            socks5_proxy: None,
+            onion_address: None,
+            confirm_quit: true,
+            split_view: false,
+            layout: LayoutSettings::default(),
+            open_links: true,
+            link_previews: true,
+            voice_memos: VoiceMemoSettings::default(),
+            clipboard_topic: None,
+            system_messages: true,
+            bell: Bell::default(),
+            locale: Locale::default(),
+            plain: false,
+            hashed_topics: false,
+            verbose_log: false,
+            update_check: false,
+            history_sync_limit: 50,
+            retention: Retention::default(),
+            filters: vec![],
+            transforms: vec![],
+            topics: BTreeMap::new(),
+            verified_peers: BTreeSet::new(),
+            gossipsub: GossipsubSettings::default(),
+            disk: DiskSettings::default(),
+            leader: LeaderSettings::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        profile::dir().map(|dir| dir.join("config.json"))
+    }
+
+    // Whether a config file was written before, i.e. this isn't the first start
+    pub fn exists() -> bool {
+        Self::path().map_or(false, |path| path.exists())
+    }
+
+    // Loads the config file, or the default config if none exists yet
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let path = match Self::path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Self::default()),
+        };
+
+        let config_str = fs::read_to_string(&path)
+            .with_context(|| format!("reading config file {:?} failed", path))?;
+        let mut config: Self = serde_json::from_str(&config_str)
+            .with_context(|| format!("parsing config file {:?} failed", path))?;
+        config
+            .validate()
+            .with_context(|| format!("invalid config file {:?}", path))?;
+
+        let legacy_keys = config
+            .topics
+            .values()
+            .any(|settings| !settings.keys.is_empty());
+        let stored_keys = secrets::get(secrets::TOPIC_KEYS)?;
+        if let Some(topic_keys) = stored_keys.as_ref() {
+            let topic_keys: BTreeMap<String, BTreeMap<u32, String>> =
+                serde_json::from_str(topic_keys).context("parsing stored topic keys failed")?;
+            for (topic, keys) in topic_keys {
+                config.topic_settings_mut(&topic).keys.extend(keys);
+            }
+        }
+        *STORED_TOPIC_KEYS.lock().unwrap() = Some(stored_keys);
+        if legacy_keys {
+            config.save()?;
+        }
+
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        ContentFilter::new(&self.filters)?;
+        Transforms::new(&self.transforms)?;
+        self.layout.validate()?;
+        self.leader.validate()?;
+        self.gossipsub.validate()
+    }
+
+    pub fn topic_settings(&self, topic: &str) -> TopicSettings {
+        self.topics.get(topic).cloned().unwrap_or_default()
+    }
+
+    pub fn topic_settings_mut(&mut self, topic: &str) -> &mut TopicSettings {
+        self.topics.entry(topic.to_string()).or_default()
+    }
+
+    pub fn retention(&self, id: &ConversationId) -> Retention {
+        match id {
+            ConversationId::Topic(topic) => self
+                .topic_settings(topic)
+                .retention
+                .unwrap_or(self.retention),
+            ConversationId::Direct(_) => self.retention,
+        }
+    }
+
+    // Whether a peer may post to a topic. Announcement topics only take messages of their
+    // announcers, and none without a source
+    pub fn may_publish(&self, topic: &str, peer_id: Option<&PeerId>) -> bool {
+        match self.topics.get(topic) {
+            Some(settings) if !settings.announcers.is_empty() => peer_id.map_or(false, |peer_id| {
+                settings.announcers.contains(&peer_id.to_base58())
+            }),
+            _ => true,
+        }
+    }
+
+    pub fn is_verified(&self, peer_id: &PeerId) -> bool {
+        self.verified_peers.contains(&peer_id.to_base58())
+    }
+
+    pub fn websocket_tls_enabled(&self) -> bool {
+        self.websocket_tls_cert.is_some() && self.websocket_tls_key.is_some()
+    }
+
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let path = Self::path().context("no config directory available")?;
+        writer::write(&path, serde_json::to_string_pretty(self)?, false)
+            .with_context(|| format!("writing config file {:?} failed", path))?;
+
+        let topic_keys = self
+            .topics
+            .iter()
+            .filter(|(_, settings)| !settings.keys.is_empty())
+            .map(|(topic, settings)| (topic, &settings.keys))
+            .collect::<BTreeMap<&String, &BTreeMap<u32, String>>>();
+        let topic_keys = if topic_keys.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&topic_keys)?)
+        };
+        let mut stored_keys = STORED_TOPIC_KEYS.lock().unwrap();
+        if stored_keys.as_ref() == Some(&topic_keys) {
+            return Ok(());
+        }
+        match topic_keys.as_ref() {
+            Some(topic_keys) => secrets::set(secrets::TOPIC_KEYS, topic_keys)?,
+            None => secrets::delete(secrets::TOPIC_KEYS)?,
+        }
+        *stored_keys = Some(topic_keys);
+
+        Ok(())
+    }
+}
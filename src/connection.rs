@@ -1,144 +1,1673 @@
 use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{self, Debug};
 use std::hash::{Hash, Hasher};
-use std::time::Duration;
+use std::io;
+use std::iter;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use anyhow::Context;
-use libp2p::core::upgrade;
-use libp2p::gossipsub::error::GossipsubHandlerError;
+use libp2p::bandwidth::BandwidthSinks;
+use libp2p::core::ConnectedPoint;
 use libp2p::gossipsub::{
-    Gossipsub, GossipsubEvent, GossipsubMessage, IdentTopic, MessageAuthenticity, MessageId,
+    GossipsubConfig, GossipsubEvent, GossipsubMessage, MessageAuthenticity, MessageId, TopicHash,
     ValidationMode,
 };
-use libp2p::identity::Keypair;
-use libp2p::swarm::{SwarmBuilder, SwarmEvent};
-use libp2p::{gossipsub, mplex, noise, tcp, Multiaddr, PeerId, Swarm, Transport};
+use libp2p::identify::{Identify, IdentifyConfig, IdentifyEvent};
+use libp2p::identity::{Keypair, PublicKey};
+use libp2p::multiaddr::Protocol;
+use libp2p::ping;
+use libp2p::request_response::{
+    ProtocolSupport, RequestId, RequestResponse, RequestResponseConfig, RequestResponseEvent,
+    RequestResponseMessage,
+};
+use libp2p::swarm::{AddressScore, SwarmBuilder, SwarmEvent};
+use libp2p::{gossipsub, Multiaddr, PeerId, Swarm};
+use uuid::Uuid;
 
-use crate::app::{App, ChatMessage};
+use crate::app::{App, ChatMessage, Delivery, MessageKind};
+use crate::audit::{self, AuditAction};
+use crate::behaviour::{Behaviour, BehaviourEvent};
+use crate::config::{Config, GossipValidation, GossipsubSettings};
+use crate::conversation::ConversationId;
+use crate::direct::{DirectCodec, DirectProtocol, DirectResponse};
+use crate::dmkeys::{self, DmCrypto};
+use crate::eventlog::{LogCategory, LogEntry, LogLevel};
+use crate::events::{AppEvent, EventQueue};
+use crate::i18n::{tr, trf, Msg};
+use crate::identity;
+use crate::input::InputTask;
+use crate::nat::{self, PortMapper, PortMappingEvent};
+use crate::outbox::{self, Outbox};
+use crate::peerexchange;
+use crate::peerlist;
+use crate::protocol::{self, Capabilities, Decoded, Envelope, Payload, PeerProtocol};
+use crate::quality::PeerQualities;
+use crate::recentaddrs;
+use crate::scoring;
+use crate::sync::{
+    HistoryCodec, HistoryProtocol, HistoryRequest, HistoryResponse, MAX_SYNC_MESSAGES,
+};
+use crate::topickeys;
+use crate::topics::TopicManager;
+use crate::transport;
+use crate::utils;
+use crate::validation::{self, Validator, Verdict, MAX_NICK_LEN};
 
 pub enum Transmission {
     Message { message: ChatMessage },
 }
 
+// Something to do with the swarm, queued in the `Outbox` and run by the event loop
+pub enum ConnectionCommand {
+    Dial(Multiaddr),
+    // `id` finds the chat message waiting for it in `pending_publishes`
+    Publish {
+        id: u64,
+        topic: String,
+        data: Vec<u8>,
+    },
+    // A request to a peer, `id` finds the chat message waiting for it in `pending_sends`
+    Send {
+        id: u64,
+        peer_id: PeerId,
+        envelope: Envelope,
+    },
+    // Publishes where we reached peers on the peer exchange topic
+    SharePeers(Vec<Multiaddr>),
+    Subscribe(String),
+    Unsubscribe(String),
+    Disconnect(PeerId),
+    // Dials a peer at the best address we know for it
+    Redial(PeerId),
+    // Measures the round trip to a peer right away, instead of waiting for the next ping
+    Ping(PeerId),
+    // Quits once everything queued before went out
+    Shutdown,
+}
+
+impl fmt::Display for ConnectionCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Dial(addr) => write!(f, "dial {}", addr),
+            Self::Publish { id, topic, data } => {
+                write!(f, "publish #{} to {}, {} bytes", id, topic, data.len())
+            }
+            Self::Send { id, peer_id, .. } => write!(f, "send #{} to {}", id, peer_id),
+            Self::SharePeers(addrs) => write!(f, "share {} peer addresses", addrs.len()),
+            Self::Subscribe(topic) => write!(f, "subscribe to {}", topic),
+            Self::Unsubscribe(topic) => write!(f, "unsubscribe from {}", topic),
+            Self::Disconnect(peer_id) => write!(f, "disconnect {}", peer_id),
+            Self::Redial(peer_id) => write!(f, "re-dial {}", peer_id),
+            Self::Ping(peer_id) => write!(f, "ping {}", peer_id),
+            Self::Shutdown => write!(f, "shutdown"),
+        }
+    }
+}
+
 pub struct Connection {
-    pub swarm: Swarm<Gossipsub>,
-    pub log: Vec<String>,
-    pub current_topic: IdentTopic,
+    pub swarm: Swarm<Behaviour>,
+    // Counts the bytes of the swarm's connections
+    bandwidth: Arc<BandwidthSinks>,
+    // What swarms replaced before counted
+    bytes_before: (u64, u64),
+    // Our identity, the keypair of the local peer id
+    pub id_keys: Keypair,
+    pub log: Vec<LogEntry>,
+    // Also log raw swarm events and received data
+    pub verbose: bool,
+    pub topics: TopicManager,
+    pub external_addrs: Vec<Multiaddr>,
+    pub port_mapper: PortMapper,
+    // The protocol versions and capabilities of peers, learned from identify and envelopes
+    pub peer_protocols: HashMap<PeerId, PeerProtocol>,
+    // The addresses peers listen on, learned from identify
+    pub peer_addrs: HashMap<PeerId, Vec<Multiaddr>>,
+    // The public keys peers told us about with identify
+    pub peer_keys: HashMap<PeerId, PublicKey>,
+    // Peers we were connected to before, to tell reconnects apart
+    pub known_peers: HashSet<PeerId>,
+    // Our direct messages waiting for the peer to acknowledge them
+    pub pending_deliveries: HashMap<RequestId, (ConversationId, Uuid)>,
+    // Our pings waiting to be acknowledged, with when they were sent
+    pub pending_pings: HashMap<RequestId, Instant>,
+    // Messages to topics waiting to be published and direct messages waiting to be sent, and the
+    // chat messages among them
+    pub outbox: Outbox,
+    pub pending_publishes: HashMap<u64, (ConversationId, Uuid)>,
+    pub pending_sends: HashMap<u64, (ConversationId, Uuid)>,
+    // The topics we asked another peer for the history of, once per topic
+    pub history_requested: HashSet<String>,
+    // The peers a history request failed with, they aren't asked for that topic again
+    pub history_failed: HashMap<String, HashSet<PeerId>>,
+    pub pending_syncs: HashMap<RequestId, String>,
+    // The peers we told our nick
+    pub presence_sent: HashSet<PeerId>,
+    // Our key of this run and the sessions with peers that sent theirs
+    pub dm_crypto: DmCrypto,
+    // The address we dialed each peer we are connected to at, shared with peer exchange
+    pub dialed_addrs: HashMap<PeerId, Multiaddr>,
+    // The peers connected to us over the local network, the only ones whose shared local
+    // addresses are dialed
+    pub lan_peers: HashSet<PeerId>,
+    // How many shared addresses of each peer were dialed since when, see `peerexchange`
+    pub exchange_dials: HashMap<PeerId, (Instant, usize)>,
+    // When each peer was last flagged in a conversation for a message we couldn't read
+    pub undecryptable_notices: HashMap<(ConversationId, Option<PeerId>), Instant>,
+    // Listeners that stopped or never started because of an error, with their addresses
+    pub failed_listeners: Vec<(Vec<Multiaddr>, String)>,
+    pub validator: Validator,
+    // Round trip times, dial failures and deliveries of the peers of this session
+    pub quality: PeerQualities,
+    // The peers whose gossipsub score fell below the graylist threshold, at the last check
+    pub graylisted: HashSet<PeerId>,
+    // What happened for the rest of the app, dispatched by the event loop
+    pub events: EventQueue,
 }
 
 impl Connection {
-    pub async fn new() -> Result<Self, anyhow::Error> {
-        // Create a Gossipsub topic
-        let current_topic = IdentTopic::new("test-net");
+    pub async fn new(config: &Config) -> Result<Self, anyhow::Error> {
+        let topics = TopicManager::with_default_topic(&config.default_topic, config.hashed_topics);
+        let id_keys = identity::load_or_generate(config)?;
 
-        let connection = Self {
-            swarm: Self::generate_swarm(&current_topic)?,
+        let (swarm, bandwidth, failed_ipv6) = Self::generate_swarm(&id_keys, &topics, config)?;
+        let mut connection = Self {
+            swarm,
+            bandwidth,
+            bytes_before: (0, 0),
+            id_keys,
             log: vec![],
-            current_topic,
+            verbose: config.verbose_log,
+            topics,
+            external_addrs: config.onion_address.iter().cloned().collect(),
+            port_mapper: PortMapper::new(config.port_mapping, config.nat_pmp_gateway),
+            peer_protocols: HashMap::new(),
+            peer_addrs: HashMap::new(),
+            peer_keys: HashMap::new(),
+            known_peers: HashSet::new(),
+            pending_deliveries: HashMap::new(),
+            pending_pings: HashMap::new(),
+            outbox: Outbox::default(),
+            pending_publishes: HashMap::new(),
+            pending_sends: HashMap::new(),
+            history_requested: HashSet::new(),
+            history_failed: HashMap::new(),
+            pending_syncs: HashMap::new(),
+            presence_sent: HashSet::new(),
+            dm_crypto: DmCrypto::default(),
+            dialed_addrs: HashMap::new(),
+            lan_peers: HashSet::new(),
+            exchange_dials: HashMap::new(),
+            undecryptable_notices: HashMap::new(),
+            failed_listeners: vec![],
+            validator: Validator::default(),
+            quality: PeerQualities::default(),
+            graylisted: HashSet::new(),
+            events: EventQueue::default(),
         };
+        connection.record_failed_listeners(failed_ipv6);
+        connection.dial_bootstrap_peers(config);
 
         Ok(connection)
     }
 
-    pub fn push_log_entry(&mut self, message: &str) {
-        self.log.push(format!("{}", message));
+    // Bytes received and sent since the start, including swarms replaced since
+    pub fn bytes_transferred(&self) -> (u64, u64) {
+        (
+            self.bytes_before.0 + self.bandwidth.total_inbound(),
+            self.bytes_before.1 + self.bandwidth.total_outbound(),
+        )
     }
 
-    pub fn generate_swarm(topic: &IdentTopic) -> Result<Swarm<Gossipsub>, anyhow::Error> {
-        let id_keys = Keypair::generate_ed25519();
-        let peer_id = PeerId::from(id_keys.public());
+    pub fn push_log(&mut self, level: LogLevel, category: LogCategory, message: &str) {
+        if level == LogLevel::Debug && !self.verbose {
+            return;
+        }
+        self.log.push(LogEntry::new(level, category, message));
+    }
 
-        // Create a keypair for authenticated encryption of the transport.
-        let noise_keys = noise::Keypair::<noise::X25519Spec>::new()
-            .into_authentic(&id_keys)
-            .context("Signing libp2p-noise static DH keypair failed.")?;
+    // Like `push_log`, but only builds the message if it is kept. For the paths every message
+    // goes through, where most entries are debug ones
+    pub fn push_log_with(
+        &mut self,
+        level: LogLevel,
+        category: LogCategory,
+        message: impl FnOnce() -> String,
+    ) {
+        if level == LogLevel::Debug && !self.verbose {
+            return;
+        }
+        self.log.push(LogEntry::new(level, category, message()));
+    }
+
+    // Also returns the IPv6 addresses that couldn't be listened on, with the error
+    pub fn generate_swarm(
+        id_keys: &Keypair,
+        topics: &TopicManager,
+        config: &Config,
+    ) -> Result<
+        (
+            Swarm<Behaviour>,
+            Arc<BandwidthSinks>,
+            Vec<(Multiaddr, String)>,
+        ),
+        anyhow::Error,
+    > {
+        let peer_id = PeerId::from(id_keys.public());
 
-        // Create a tokio-based TCP transport use noise for authenticated
-        // encryption and Mplex for multiplexing of substreams on a TCP stream.
-        let transport = tcp::TokioTcpConfig::new()
-            .nodelay(true)
-            .upgrade(upgrade::Version::V1)
-            .authenticate(noise::NoiseConfig::xx(noise_keys).into_authenticated())
-            .multiplex(mplex::MplexConfig::new())
-            .boxed();
+        let (transport, bandwidth) = transport::build_transport(id_keys, config)?;
 
         // Create a Swarm to manage peers and events
         let mut swarm = {
-            // To content-address message, we can take the hash of message and use it as an ID.
-            let message_id_fn = |message: &GossipsubMessage| {
-                let mut s = DefaultHasher::new();
-                message.data.hash(&mut s);
-                MessageId::from(s.finish().to_string())
-            };
+            // Identify messages by their author and sequence number, so the same text sent twice
+            // or by two peers isn't dropped as a duplicate. Anonymous messages have neither and
+            // are content-addressed instead
+            let message_id_fn =
+                |message: &GossipsubMessage| match (message.source, message.sequence_number) {
+                    (Some(source), Some(sequence_number)) => {
+                        MessageId::from(format!("{}{}", source.to_base58(), sequence_number))
+                    }
+                    _ => {
+                        let mut s = DefaultHasher::new();
+                        message.data.hash(&mut s);
+                        MessageId::from(s.finish().to_string())
+                    }
+                };
 
-            // Set a custom gossipsub
-            let gossipsub_config = gossipsub::GossipsubConfigBuilder::default()
-                .heartbeat_interval(Duration::from_secs(10)) // This is set to aid debugging by not cluttering the log space
-                .validation_mode(ValidationMode::Strict) // This sets the kind of message validation. The default is Strict (enforce message signing)
-                .message_id_fn(message_id_fn) // content-address messages. No two messages of the
-                // same content will be propagated.
-                .build()
-                .expect("Valid config");
+            let gossipsub_config = gossipsub_config(&config.gossipsub, message_id_fn)?;
             // build a gossipsub network behaviour
-            let mut gossipsub: gossipsub::Gossipsub =
-                gossipsub::Gossipsub::new(MessageAuthenticity::Signed(id_keys), gossipsub_config)
-                    .expect("Correct configuration");
+            let mut gossipsub: gossipsub::Gossipsub = gossipsub::Gossipsub::new(
+                message_authenticity(config.gossipsub.validation_mode, id_keys),
+                gossipsub_config,
+            )
+            .map_err(|e| anyhow::anyhow!("creating gossipsub failed with Err `{}`", e))?;
+            if config.gossipsub.peer_scoring {
+                gossipsub
+                    .with_peer_score(scoring::params(), scoring::thresholds(&config.gossipsub))
+                    .map_err(|e| {
+                        anyhow::anyhow!("enabling peer scoring failed with Err `{}`", e)
+                    })?;
+            }
+
+            // subscribes to our topics
+            topics.resubscribe(&mut gossipsub)?;
+            if config.peer_exchange {
+                let _ = gossipsub.set_topic_params(peerexchange::topic(), scoring::topic_params());
+                gossipsub.subscribe(&peerexchange::topic())?;
+            }
+
+            // announce our protocol version and capabilities to every connected peer
+            let identify = Identify::new(
+                IdentifyConfig::new(
+                    protocol::IDENTIFY_PROTOCOL_VERSION.to_string(),
+                    id_keys.public(),
+                )
+                .with_agent_version(protocol::agent_version()),
+            );
 
-            // subscribes to our topic
-            gossipsub.subscribe(topic).unwrap();
+            // measures the round trip time to every connected peer. A few missed pings in a row
+            // close the connection, one can get lost on a busy link
+            let ping = ping::Behaviour::new(
+                ping::Config::new().with_max_failures(NonZeroU32::new(3).expect("3 is not zero")),
+            );
+
+            // direct messages between two peers
+            let direct = RequestResponse::new(
+                DirectCodec(),
+                iter::once((DirectProtocol(), ProtocolSupport::Full)),
+                RequestResponseConfig::default(),
+            );
+
+            // the recent messages of a topic, for peers joining it
+            let history = RequestResponse::new(
+                HistoryCodec(),
+                iter::once((HistoryProtocol(), ProtocolSupport::Full)),
+                RequestResponseConfig::default(),
+            );
 
             // build the swarm
-            SwarmBuilder::new(transport, gossipsub, peer_id)
-                .executor(Box::new(|fut| {
-                    tokio::spawn(fut);
-                }))
-                .build()
+            SwarmBuilder::new(
+                transport,
+                Behaviour {
+                    gossipsub,
+                    identify,
+                    ping,
+                    direct,
+                    history,
+                },
+                peer_id,
+            )
+            .executor(Box::new(|fut| {
+                tokio::spawn(fut);
+            }))
+            .build()
         };
-        // Listen on all interfaces and whatever port the OS assigns
-        swarm.listen_on("/ip4/0.0.0.0/tcp/0".parse()?)?;
+        // Listen on all interfaces and the configured ports, or whatever port the OS assigns if
+        // a port is 0. Hosts with IPv6 turned off can't listen on it, IPv4 is enough to carry on
+        let mut failed_ipv6 = vec![];
+        for addr in transport::listen_addrs(config)? {
+            if let Err(e) = swarm.listen_on(addr.clone()) {
+                if addr
+                    .iter()
+                    .any(|protocol| matches!(protocol, Protocol::Ip6(_)))
+                {
+                    failed_ipv6.push((addr, with_hint(e.to_string(), &e)));
+                    continue;
+                }
+                return Err(match error_hint(&e) {
+                    Some(hint) => anyhow::anyhow!(
+                        "listening on {} failed with Err `{}`, {}",
+                        addr,
+                        e,
+                        tr(hint)
+                    ),
+                    None => anyhow::anyhow!("listening on {} failed with Err `{}`", addr, e),
+                });
+            }
+        }
 
-        Ok(swarm)
+        Ok((swarm, bandwidth, failed_ipv6))
+    }
+
+    fn record_failed_listeners(&mut self, failed: Vec<(Multiaddr, String)>) {
+        for (addr, error) in failed {
+            self.push_log(
+                LogLevel::Warn,
+                LogCategory::Network,
+                &trf(Msg::LogIpv6ListenFailed, &[&addr, &error]),
+            );
+            self.failed_listeners.push((vec![addr], error));
+        }
+    }
+
+    // Replaces the swarm with a new one with a fresh identity, starting a new log
+    pub fn regenerate_swarm(&mut self, config: &Config) {
+        self.log.clear();
+        if let Err(e) = self.replace_identity(Keypair::generate_ed25519(), config) {
+            self.push_log(
+                LogLevel::Error,
+                LogCategory::Network,
+                &trf(Msg::LogRegenerateFailed, &[&e]),
+            );
+        }
+    }
+
+    // Rebuilds the swarm around another keypair. The new swarm subscribes to the same topics,
+    // and our nick is announced again as peers identify us. If building it fails, the old swarm
+    // is kept
+    pub fn replace_identity(
+        &mut self,
+        id_keys: Keypair,
+        config: &Config,
+    ) -> Result<(), anyhow::Error> {
+        let handle = tokio::runtime::Handle::current();
+        let _guard = handle.enter();
+
+        let (swarm, bandwidth, failed_ipv6) = Self::generate_swarm(&id_keys, &self.topics, config)?;
+        self.bytes_before = self.bytes_transferred();
+        self.swarm = swarm;
+        self.bandwidth = bandwidth;
+        self.external_addrs = config.onion_address.iter().cloned().collect();
+        self.port_mapper.reset();
+        // The old swarm took their requests with it
+        for (_, (conversation_id, message_id)) in self.pending_deliveries.drain() {
+            self.events.emit(AppEvent::DeliveryChanged {
+                conversation_id,
+                message_id,
+                delivery: Delivery::Failed,
+            });
+        }
+        self.pending_pings.clear();
+        self.history_requested.clear();
+        self.history_failed.clear();
+        self.pending_syncs.clear();
+        self.presence_sent.clear();
+        // Keys are signed with the identity, peers have to start over with a new one
+        self.dm_crypto = DmCrypto::default();
+        self.dialed_addrs.clear();
+        self.lan_peers.clear();
+        self.exchange_dials.clear();
+        self.undecryptable_notices.clear();
+        self.failed_listeners.clear();
+        self.record_failed_listeners(failed_ipv6);
+        if config.persistent_identity {
+            if let Err(e) = identity::save(&id_keys) {
+                self.push_log(
+                    LogLevel::Error,
+                    LogCategory::Security,
+                    &trf(Msg::LogSaveIdentityFailed, &[&e]),
+                );
+            }
+        }
+        self.id_keys = id_keys;
+        self.push_log(
+            LogLevel::Info,
+            LogCategory::Network,
+            &trf(Msg::LogResubscribed, &[&self.topics.topics().count()]),
+        );
+        self.dial_bootstrap_peers(config);
+
+        Ok(())
+    }
+
+    pub fn dial_bootstrap_peers(&mut self, config: &Config) {
+        for addr in config.bootstrap_peers.iter() {
+            if let Err(e) = self.dial(addr.clone()) {
+                self.push_log(
+                    LogLevel::Error,
+                    LogCategory::Network,
+                    &trf(Msg::LogDialFailed, &[addr, &e]),
+                );
+            }
+        }
     }
 
     pub fn dial(&mut self, addr: Multiaddr) -> Result<(), anyhow::Error> {
-        self.push_log_entry(format!("dialing: {}", addr).as_str());
+        self.push_log(
+            LogLevel::Info,
+            LogCategory::Network,
+            &trf(Msg::LogDialing, &[&addr]),
+        );
 
         self.swarm.dial(addr)?;
         Ok(())
     }
+
+    // Records what a peer speaks, warning once when it becomes incompatible
+    pub fn update_peer_protocol(&mut self, peer_id: PeerId, peer_protocol: PeerProtocol) {
+        let was_compatible = self
+            .peer_protocols
+            .get(&peer_id)
+            .map_or(true, |prev| prev.is_compatible());
+
+        if was_compatible && !peer_protocol.is_compatible() {
+            self.push_log(
+                LogLevel::Warn,
+                LogCategory::Network,
+                &trf(
+                    Msg::LogIncompatiblePeer,
+                    &[
+                        &peer_id,
+                        &peer_protocol.version,
+                        &protocol::PROTOCOL_VERSION,
+                    ],
+                ),
+            );
+        }
+        self.peer_protocols.insert(peer_id, peer_protocol);
+    }
+
+    // Records the version and capabilities a peer announced in an envelope
+    pub fn update_peer_envelope_protocol(
+        &mut self,
+        peer_id: PeerId,
+        version: u32,
+        capabilities: Option<Capabilities>,
+    ) {
+        let mut peer_protocol =
+            self.peer_protocols
+                .get(&peer_id)
+                .cloned()
+                .unwrap_or(PeerProtocol {
+                    app_version: None,
+                    version,
+                    capabilities: Capabilities::NONE,
+                });
+        peer_protocol.version = version;
+        if let Some(capabilities) = capabilities {
+            peer_protocol.capabilities = capabilities;
+        }
+
+        self.update_peer_protocol(peer_id, peer_protocol);
+    }
+
+    pub fn peer_is_incompatible(&self, peer_id: &PeerId) -> bool {
+        self.peer_protocols
+            .get(peer_id)
+            .map_or(false, |peer_protocol| !peer_protocol.is_compatible())
+    }
+
+    // Messages the queue holds and requests peers didn't answer yet. Gossipsub doesn't tell how
+    // much it still has to write, but the requests of direct messages wait for their peer
+    pub fn backlog(&self) -> usize {
+        self.outbox.waiting() + self.pending_deliveries.len()
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.backlog() >= outbox::BUSY
+    }
+
+    // The peers subscribed to a topic, who all get what we publish to it with flood publishing
+    pub fn topic_peers(&self, topic: &str) -> Vec<PeerId> {
+        let topic_hash = self.topics.hash(topic);
+        self.swarm
+            .behaviour()
+            .gossipsub
+            .all_peers()
+            .filter(|(_, topics)| topics.contains(&&topic_hash))
+            .map(|(peer_id, _)| *peer_id)
+            .collect()
+    }
+
+    // The peers in our gossipsub mesh for a topic, who forward our messages to everyone else
+    pub fn mesh_peers(&self, topic: &str) -> Vec<PeerId> {
+        self.swarm
+            .behaviour()
+            .gossipsub
+            .mesh_peers(&self.topics.hash(topic))
+            .copied()
+            .collect()
+    }
+
+    // Addresses other peers can dial to reach us, including our peer id
+    pub fn shareable_addrs(&self) -> Vec<Multiaddr> {
+        let local_peer_id = *self.swarm.local_peer_id();
+
+        self.external_addrs
+            .iter()
+            .map(|addr| addr.clone().with(Protocol::P2p(local_peer_id.into())))
+            .collect()
+    }
+}
+
+fn gossipsub_config(
+    settings: &GossipsubSettings,
+    message_id_fn: impl Fn(&GossipsubMessage) -> MessageId + Send + Sync + 'static,
+) -> Result<GossipsubConfig, anyhow::Error> {
+    let validation_mode = match settings.validation_mode {
+        GossipValidation::Strict => ValidationMode::Strict,
+        GossipValidation::Permissive => ValidationMode::Permissive,
+        GossipValidation::Anonymous => ValidationMode::Anonymous,
+        GossipValidation::None => ValidationMode::None,
+    };
+
+    gossipsub::GossipsubConfigBuilder::default()
+        .heartbeat_interval(Duration::from_millis(settings.heartbeat_interval_ms))
+        .validation_mode(validation_mode)
+        .history_length(settings.history_length)
+        .history_gossip(settings.history_gossip)
+        .gossip_factor(settings.gossip_factor)
+        .max_transmit_size(settings.max_transmit_size)
+        .flood_publish(settings.flood_publish)
+        // messages are only forwarded after `validation::Validator` accepted them
+        .validate_messages()
+        .message_id_fn(message_id_fn)
+        .build()
+        .map_err(|e| anyhow::anyhow!("invalid gossipsub config: {}", e))
 }
 
-pub fn handle_connection_event(
-    connection_event: SwarmEvent<GossipsubEvent, GossipsubHandlerError>,
+// Anonymous validation only accepts messages without author, so we can't sign ours
+fn message_authenticity(validation: GossipValidation, id_keys: &Keypair) -> MessageAuthenticity {
+    match validation {
+        GossipValidation::Anonymous => MessageAuthenticity::Anonymous,
+        _ => MessageAuthenticity::Signed(id_keys.clone()),
+    }
+}
+
+pub fn handle_connection_event<E: Debug>(
+    connection_event: SwarmEvent<BehaviourEvent, E>,
     app: &mut App,
 ) -> Result<(), anyhow::Error> {
     match connection_event {
         SwarmEvent::NewListenAddr { address, .. } => {
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::Network,
+                &trf(Msg::LogListening, &[&address]),
+            );
+            app.connection.port_mapper.map_listen_addr(&address);
+        }
+        SwarmEvent::ConnectionEstablished {
+            peer_id,
+            endpoint,
+            num_established,
+            ..
+        } => {
+            // Blocked peers may still dial us, or be dialed from an old session
+            if app.moderation.is_blocked(&peer_id) {
+                app.connection
+                    .outbox
+                    .push(ConnectionCommand::Disconnect(peer_id));
+                audit::record(
+                    app,
+                    AuditAction::ConnectionRejected,
+                    Some(peer_id),
+                    tr(Msg::AuditReasonPeerBlocked),
+                );
+                return Ok(());
+            }
+            if peerexchange::is_lan(endpoint.get_remote_address()) {
+                app.connection.lan_peers.insert(peer_id);
+            }
+            // Addresses we dialed successfully are worth sharing with other peers
+            if let ConnectedPoint::Dialer { address, .. } = endpoint {
+                app.connection.quality.record_dial(peer_id, true);
+                recentaddrs::dialed(app, address.clone(), peer_id);
+                app.connection.dialed_addrs.insert(peer_id, address);
+            }
+            if num_established.get() == 1 {
+                let reconnected = !app.connection.known_peers.insert(peer_id);
+                app.connection.events.emit(AppEvent::PeerConnected {
+                    peer_id,
+                    reconnected,
+                });
+            }
+        }
+        SwarmEvent::ConnectionClosed {
+            peer_id,
+            num_established: 0,
+            ..
+        } => {
+            app.connection.dialed_addrs.remove(&peer_id);
+            app.connection.lan_peers.remove(&peer_id);
+            if app.moderation.is_blocked(&peer_id) {
+                return Ok(());
+            }
+            app.connection
+                .events
+                .emit(AppEvent::PeerDisconnected { peer_id });
+        }
+        SwarmEvent::ExpiredListenAddr { address, .. } => {
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::Network,
+                &trf(Msg::LogListenAddrExpired, &[&address]),
+            );
+        }
+        SwarmEvent::ListenerError { error, .. } => {
+            let text = with_hint(trf(Msg::LogListenerError, &[&error]), &error);
+            app.connection
+                .push_log(LogLevel::Error, LogCategory::Network, &text);
+        }
+        SwarmEvent::ListenerClosed {
+            addresses, reason, ..
+        } => {
+            let addrs = addresses
+                .iter()
+                .map(|addr| addr.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+            match reason {
+                Ok(()) => app.connection.push_log(
+                    LogLevel::Info,
+                    LogCategory::Network,
+                    &trf(Msg::LogListenerClosed, &[&addrs]),
+                ),
+                Err(e) => {
+                    let text = with_hint(trf(Msg::LogListenerFailed, &[&addrs, &e]), &e);
+                    app.connection
+                        .push_log(LogLevel::Error, LogCategory::Network, &text);
+                    app.connection
+                        .failed_listeners
+                        .push((addresses, e.to_string()));
+                }
+            }
+            // Without a listener peers can only reach us over connections we open
+            if app.connection.swarm.listeners().next().is_none() {
+                app.connection.events.emit(AppEvent::Notice {
+                    text: tr(Msg::SystemNotListening).to_string(),
+                });
+            }
+        }
+        SwarmEvent::IncomingConnectionError {
+            send_back_addr,
+            error,
+            ..
+        } => {
+            let text = with_hint(
+                trf(Msg::LogIncomingConnectionFailed, &[&send_back_addr, &error]),
+                &error,
+            );
             app.connection
-                .push_log_entry(format!("Listening on {:?}", address).as_str());
+                .push_log(LogLevel::Warn, LogCategory::Network, &text);
+        }
+        SwarmEvent::OutgoingConnectionError { peer_id, error } => {
+            if let Some(peer_id) = peer_id {
+                app.connection.quality.record_dial(peer_id, false);
+            }
+            app.connection.events.emit(AppEvent::DialFailed {
+                peer_id,
+                error: with_hint(error.to_string(), &error),
+            });
         }
-        SwarmEvent::Behaviour(GossipsubEvent::Message {
+        SwarmEvent::Behaviour(BehaviourEvent::Gossipsub(GossipsubEvent::Message {
             propagation_source: peer_id,
             message_id: id,
             message,
-        }) => {
-            app.connection.log.push(format!(
-                "Got message: {} with id: {} from peer: {:?}",
-                String::from_utf8_lossy(&message.data),
-                id,
-                peer_id
-            ));
-            let mut chat_message: ChatMessage =
-                serde_json::from_str(&String::from_utf8_lossy(&message.data).to_string())?;
-
-            chat_message.source_peer_id = message.source;
-            app.history.push(chat_message);
+        })) => {
+            app.connection
+                .push_log_with(LogLevel::Debug, LogCategory::Messages, || {
+                    trf(
+                        Msg::LogGotMessage,
+                        &[&String::from_utf8_lossy(&message.data), &id, &peer_id],
+                    )
+                });
+
+            // Only messages we accept are forwarded to other peers
+            let may_publish = app
+                .connection
+                .topics
+                .name_of(&message.topic)
+                .map_or(true, |topic| {
+                    app.config.may_publish(&topic, message.source.as_ref())
+                });
+            let verdict = app.connection.validator.validate(
+                message.source,
+                &message.data,
+                may_publish,
+                &app.moderation,
+                app.clock,
+            );
+            if let Err(e) = app
+                .connection
+                .swarm
+                .behaviour_mut()
+                .gossipsub
+                .report_message_validation_result(&id, &peer_id, verdict.acceptance())
+            {
+                app.connection.push_log(
+                    LogLevel::Error,
+                    LogCategory::Messages,
+                    &trf(Msg::LogReportValidationFailed, &[&id, &e]),
+                );
+            }
+            let decoded = match verdict {
+                Verdict::Accept(decoded) => decoded,
+                Verdict::Reject(reason) | Verdict::Ignore(reason) => {
+                    let text = trf(Msg::LogDroppedMessage, &[&id, &peer_id, &tr(reason)]);
+                    app.connection
+                        .push_log(LogLevel::Warn, LogCategory::Messages, &text);
+                    audit::record(app, AuditAction::Dropped, message.source, &text);
+                    return Ok(());
+                }
+            };
+
+            let envelope = match decoded {
+                Decoded::Envelope(envelope) => envelope,
+                Decoded::Unsupported { version } => {
+                    if let Some(source) = message.source {
+                        app.connection
+                            .update_peer_envelope_protocol(source, version, None);
+                    }
+                    app.connection.push_log(
+                        LogLevel::Warn,
+                        LogCategory::Messages,
+                        &trf(
+                            Msg::LogSkippedMessage,
+                            &[&format!("{:?}", message.source), &version],
+                        ),
+                    );
+                    return Ok(());
+                }
+            };
+
+            if let Some(source) = message.source {
+                app.connection.update_peer_envelope_protocol(
+                    source,
+                    envelope.version,
+                    Some(envelope.capabilities),
+                );
+            }
+
+            if peerexchange::is_topic(&message.topic) {
+                if let Payload::Peers { addrs } = envelope.payload {
+                    peerexchange::handle_peers(message.source, addrs, app);
+                }
+                return Ok(());
+            }
+            let conversation_id = match app.connection.topics.name_of(&message.topic) {
+                Some(topic) => ConversationId::Topic(topic),
+                None => return Ok(()),
+            };
+            handle_payload(&conversation_id, message.source, envelope.payload, app);
+        }
+        SwarmEvent::Behaviour(BehaviourEvent::Direct(RequestResponseEvent::Message {
+            peer,
+            message,
+        })) => match message {
+            RequestResponseMessage::Request {
+                request, channel, ..
+            } => {
+                // Only messages we could read are acknowledged
+                let response = handle_direct_envelope(peer, request, app);
+                if app
+                    .connection
+                    .swarm
+                    .behaviour_mut()
+                    .direct
+                    .send_response(channel, response)
+                    .is_err()
+                {
+                    app.connection.push_log(
+                        LogLevel::Error,
+                        LogCategory::Network,
+                        &trf(Msg::LogAckFailed, &[&peer]),
+                    );
+                }
+            }
+            RequestResponseMessage::Response {
+                request_id,
+                response: DirectResponse::Undecryptable,
+            } => {
+                app.stats.record_response(request_id, false);
+                app.connection.pending_pings.remove(&request_id);
+                // The peer gets our key again and the message fails, so it is sealed anew when
+                // it is retried
+                dmkeys::announce(app, peer);
+                if let Some((conversation_id, message_id)) =
+                    app.connection.pending_deliveries.remove(&request_id)
+                {
+                    app.connection.events.emit(AppEvent::DeliveryChanged {
+                        conversation_id,
+                        message_id,
+                        delivery: Delivery::Failed,
+                    });
+                }
+            }
+            RequestResponseMessage::Response { request_id, .. } => {
+                app.stats.record_response(request_id, true);
+                app.connection.quality.record_delivery(peer, true);
+                if let Some(sent) = app.connection.pending_pings.remove(&request_id) {
+                    let rtt = sent.elapsed();
+                    app.connection.quality.record_rtt(peer, rtt);
+                    app.connection.push_log(
+                        LogLevel::Info,
+                        LogCategory::Network,
+                        &trf(Msg::LogPinged, &[&peer, &rtt.as_millis()]),
+                    );
+                }
+                if let Some((conversation_id, message_id)) =
+                    app.connection.pending_deliveries.remove(&request_id)
+                {
+                    app.connection.events.emit(AppEvent::DeliveryChanged {
+                        conversation_id,
+                        message_id,
+                        delivery: Delivery::Sent,
+                    });
+                    app.connection.events.emit(AppEvent::ReceiptConfirmed {
+                        message_id,
+                        peer_id: peer,
+                    });
+                }
+            }
+        },
+        SwarmEvent::Behaviour(BehaviourEvent::Direct(RequestResponseEvent::OutboundFailure {
+            peer,
+            request_id,
+            error,
+        })) => {
+            app.connection.push_log(
+                LogLevel::Error,
+                LogCategory::Network,
+                &trf(Msg::LogDirectSendFailed, &[&peer, &error]),
+            );
+            app.stats.record_response(request_id, false);
+            app.connection.quality.record_delivery(peer, false);
+            app.connection.pending_pings.remove(&request_id);
+            if let Some((conversation_id, message_id)) =
+                app.connection.pending_deliveries.remove(&request_id)
+            {
+                app.connection.events.emit(AppEvent::DeliveryChanged {
+                    conversation_id,
+                    message_id,
+                    delivery: Delivery::Failed,
+                });
+            }
+        }
+        SwarmEvent::Behaviour(BehaviourEvent::History(RequestResponseEvent::Message {
+            peer,
+            message,
+        })) => match message {
+            RequestResponseMessage::Request {
+                request, channel, ..
+            } => {
+                let response = history_response(peer, &request, app);
+                if app
+                    .connection
+                    .swarm
+                    .behaviour_mut()
+                    .history
+                    .send_response(channel, response)
+                    .is_err()
+                {
+                    app.connection.push_log(
+                        LogLevel::Error,
+                        LogCategory::Messages,
+                        &trf(Msg::LogHistoryResponseFailed, &[&peer]),
+                    );
+                }
+            }
+            RequestResponseMessage::Response {
+                request_id,
+                response,
+            } => {
+                if let Some(topic) = app.connection.pending_syncs.remove(&request_id) {
+                    app.connection.quality.record_delivery(peer, true);
+                    handle_history_response(peer, topic, response, app);
+                }
+            }
+        },
+        SwarmEvent::Behaviour(BehaviourEvent::History(RequestResponseEvent::OutboundFailure {
+            peer,
+            request_id,
+            error,
+        })) => {
+            if let Some(topic) = app.connection.pending_syncs.remove(&request_id) {
+                app.connection.push_log(
+                    LogLevel::Error,
+                    LogCategory::Messages,
+                    &trf(Msg::LogHistorySyncFailed, &[&topic, &peer, &error]),
+                );
+                app.connection.quality.record_delivery(peer, false);
+                // Ask the next best peer instead, each one only once
+                app.connection
+                    .history_failed
+                    .entry(topic.clone())
+                    .or_default()
+                    .insert(peer);
+                app.connection.history_requested.remove(&topic);
+                request_history(topic, app);
+            }
+        }
+        SwarmEvent::Behaviour(BehaviourEvent::Identify(IdentifyEvent::Received {
+            peer_id,
+            info,
+        })) => {
+            if let Some(peer_protocol) = protocol::parse_agent_version(&info.agent_version) {
+                if let Some(version) = peer_protocol.app_version.clone() {
+                    app.connection
+                        .events
+                        .emit(AppEvent::VersionAdvertised { peer_id, version });
+                }
+                let supports_presence = peer_protocol.supports(Capabilities::PRESENCE);
+                let supports_dm_encryption = peer_protocol.supports(Capabilities::DM_ENCRYPTION);
+                app.connection.update_peer_protocol(peer_id, peer_protocol);
+                if supports_dm_encryption {
+                    dmkeys::announce(app, peer_id);
+                }
+                if supports_presence && app.connection.presence_sent.insert(peer_id) {
+                    let nick = Some(app.ui.nick_input.as_str().to_string())
+                        .filter(|nick| !nick.is_empty());
+                    app.send_payload_logged(
+                        &ConversationId::Direct(peer_id),
+                        Payload::Presence {
+                            nick,
+                            status: app.away.status,
+                            card: app.config.status_card.clone(),
+                        },
+                    );
+                }
+            }
+            for addr in info.listen_addrs.iter() {
+                app.recent_addrs.record_seen(addr.clone(), peer_id);
+            }
+            app.connection.peer_addrs.insert(peer_id, info.listen_addrs);
+            // Identify can't be trusted to tell the truth about anyone but the peer itself
+            if info.public_key.to_peer_id() == peer_id {
+                app.connection.peer_keys.insert(peer_id, info.public_key);
+            }
+        }
+        SwarmEvent::Behaviour(BehaviourEvent::Ping(ping::Event {
+            peer,
+            result: Ok(ping::Success::Ping { rtt }),
+        })) => {
+            app.connection.quality.record_rtt(peer, rtt);
+        }
+        SwarmEvent::Behaviour(BehaviourEvent::Gossipsub(
+            event @ (GossipsubEvent::Subscribed { .. } | GossipsubEvent::Unsubscribed { .. }),
+        )) => {
+            handle_membership_event(event, app);
         }
         SwarmEvent::Behaviour(event) => {
             app.connection
-                .push_log_entry(format!("{:?}", event).as_str());
+                .push_log_with(classify_event(&event), LogCategory::Network, || {
+                    format!("{:?}", event)
+                });
         }
         _ => {}
     }
 
     Ok(())
 }
+
+// How interesting a behaviour event without its own handling is. Only failures are shown
+// unless the log is verbose
+fn classify_event(event: &BehaviourEvent) -> LogLevel {
+    match event {
+        BehaviourEvent::Identify(IdentifyEvent::Error { .. })
+        | BehaviourEvent::Direct(RequestResponseEvent::InboundFailure { .. })
+        | BehaviourEvent::History(RequestResponseEvent::InboundFailure { .. }) => LogLevel::Warn,
+        _ => LogLevel::Debug,
+    }
+}
+
+// What the user can do about a transport error, judged by the underlying io error
+fn error_hint(error: &(dyn Error + 'static)) -> Option<Msg> {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        if let Some(io_error) = error.downcast_ref::<io::Error>() {
+            return match io_error.kind() {
+                io::ErrorKind::AddrInUse => Some(Msg::HintAddrInUse),
+                io::ErrorKind::AddrNotAvailable => Some(Msg::HintAddrNotAvailable),
+                io::ErrorKind::PermissionDenied => Some(Msg::HintPermissionDenied),
+                io::ErrorKind::ConnectionRefused => Some(Msg::HintConnectionRefused),
+                io::ErrorKind::TimedOut => Some(Msg::HintTimedOut),
+                _ => None,
+            };
+        }
+        source = error.source();
+    }
+    None
+}
+
+fn with_hint(text: String, error: &(dyn Error + 'static)) -> String {
+    match error_hint(error) {
+        Some(hint) => format!("{}, {}", text, tr(hint)),
+        None => text,
+    }
+}
+
+// Returns what to respond, `Undecryptable` if the envelope was sealed with a key we don't have so
+// the sender seals it again
+fn handle_direct_envelope(peer_id: PeerId, envelope: Envelope, app: &mut App) -> DirectResponse {
+    app.connection.update_peer_envelope_protocol(
+        peer_id,
+        envelope.version,
+        Some(envelope.capabilities),
+    );
+    if app.moderation.is_blocked(&peer_id) {
+        return DirectResponse::Ack;
+    }
+
+    let conversation_id = ConversationId::Direct(peer_id);
+    let payload = match envelope.payload {
+        Payload::DirectSealed {
+            counter,
+            nonce,
+            ciphertext,
+        } => {
+            let opened = match app.connection.dm_crypto.session_mut(&peer_id) {
+                Some(session) => session.open(counter, &nonce, &ciphertext),
+                None => Err(anyhow::anyhow!("its key didn't arrive")),
+            };
+            match opened {
+                // Keys and sealed payloads are only valid unsealed
+                Ok(Payload::Sealed { .. } | Payload::DirectSealed { .. } | Payload::DmKey(_)) => {
+                    return DirectResponse::Ack
+                }
+                Ok(payload) => payload,
+                // Messages we can't read are flagged in the history instead of silently dropped,
+                // and the peer gets our current key
+                Err(e) => {
+                    let text = trf(
+                        Msg::SystemUndecryptable,
+                        &[&utils::short_peer_id(&peer_id), &e],
+                    );
+                    flag_unreadable(&conversation_id, Some(peer_id), text, app);
+                    dmkeys::announce(app, peer_id);
+                    return DirectResponse::Undecryptable;
+                }
+            }
+        }
+        payload => payload,
+    };
+    handle_payload(&conversation_id, Some(peer_id), payload, app);
+    DirectResponse::Ack
+}
+
+// Peers joining one of our topics are asked for its history
+fn handle_membership_event(event: GossipsubEvent, app: &mut App) {
+    let (peer_id, topic, joined) = match event {
+        GossipsubEvent::Subscribed { peer_id, topic } => (peer_id, topic, true),
+        GossipsubEvent::Unsubscribed { peer_id, topic } => (peer_id, topic, false),
+        _ => return,
+    };
+    let topic = match app.connection.topics.name_of(&topic) {
+        Some(topic) => topic,
+        None => return,
+    };
+
+    if joined {
+        app.connection.events.emit(AppEvent::TopicSubscribed {
+            peer_id,
+            topic: topic.clone(),
+        });
+        if !app.moderation.is_blocked(&peer_id) {
+            request_history(topic, app);
+        }
+    } else {
+        app.connection
+            .events
+            .emit(AppEvent::TopicUnsubscribed { peer_id, topic });
+    }
+}
+
+// The connected peer in a topic with the best quality that didn't fail to send its history before,
+// to ask for it
+fn sync_provider(topic: &str, app: &App) -> Option<PeerId> {
+    let topic_hash = app.connection.topics.hash(topic);
+    let failed = app.connection.history_failed.get(topic);
+    let peer_ids = app
+        .connection
+        .swarm
+        .behaviour()
+        .gossipsub
+        .all_peers()
+        .filter(|(_, topics)| topics.contains(&&topic_hash))
+        .map(|(peer_id, _)| *peer_id)
+        .filter(|peer_id| failed.map_or(true, |failed| !failed.contains(peer_id)))
+        .filter(|peer_id| app.connection.swarm.is_connected(peer_id))
+        .filter(|peer_id| !app.moderation.is_blocked(peer_id))
+        .collect::<Vec<PeerId>>();
+    app.connection.quality.best(peer_ids)
+}
+
+// Asks the best peer in a topic for the messages we don't have yet, once the first peer shows up
+fn request_history(topic: String, app: &mut App) {
+    // Encrypted topics aren't handed out, see `history_response()`
+    if app.config.history_sync_limit == 0
+        || !app.config.topic_settings(&topic).keys.is_empty()
+        || app.connection.history_requested.contains(&topic)
+    {
+        return;
+    }
+    let peer_id = match sync_provider(&topic, app) {
+        Some(peer_id) => peer_id,
+        None => return,
+    };
+    app.connection.history_requested.insert(topic.clone());
+
+    let conversation_id = ConversationId::Topic(topic.clone());
+    let since = app
+        .conversations
+        .get(&conversation_id)
+        .and_then(|conversation| {
+            conversation
+                .history
+                .iter()
+                .rev()
+                .find(|message| message.kind == MessageKind::Chat)
+        })
+        .map(|message| message.received_at.timestamp_millis());
+    let request = HistoryRequest {
+        topic: app.connection.topics.hash(&topic).into_string(),
+        limit: app.config.history_sync_limit,
+        since,
+    };
+
+    let request_id = app
+        .connection
+        .swarm
+        .behaviour_mut()
+        .history
+        .send_request(&peer_id, request);
+    app.connection.pending_syncs.insert(request_id, topic);
+}
+
+// The recent messages of one of our topics. Blocked peers and topics we don't know or which
+// are encrypted get nothing
+fn history_response(peer_id: PeerId, request: &HistoryRequest, app: &App) -> HistoryResponse {
+    if app.moderation.is_blocked(&peer_id) {
+        return HistoryResponse::default();
+    }
+    let topic = match app
+        .connection
+        .topics
+        .name_of(&TopicHash::from_raw(request.topic.clone()))
+    {
+        Some(topic) => topic,
+        None => return HistoryResponse::default(),
+    };
+    if !app.config.topic_settings(&topic).keys.is_empty() {
+        return HistoryResponse::default();
+    }
+
+    let mut response = app
+        .conversations
+        .get(&ConversationId::Topic(topic.clone()))
+        .map(|conversation| HistoryResponse::from_conversation(conversation, request))
+        .unwrap_or_default();
+    response.state = app.topic_states.get(&topic).cloned().unwrap_or_default();
+    response
+}
+
+fn handle_history_response(
+    peer_id: PeerId,
+    topic: String,
+    response: HistoryResponse,
+    app: &mut App,
+) {
+    let mut messages = response
+        .messages
+        .into_iter()
+        .take(MAX_SYNC_MESSAGES)
+        .filter_map(|synced| synced.into_chat_message())
+        .filter(|message| {
+            message
+                .source_peer_id
+                .map_or(false, |source| !app.moderation.is_blocked(&source))
+        })
+        // The gossipsub validator only sees live messages
+        .filter(|message| {
+            app.config
+                .may_publish(&topic, message.source_peer_id.as_ref())
+        })
+        .filter(|message| {
+            validation::validate_payload(&Payload::Chat(message.clone()), app.clock).is_ok()
+        })
+        .map(|mut message| {
+            message.synced = true;
+            message
+        })
+        .collect::<Vec<ChatMessage>>();
+    let conversation_id = ConversationId::Topic(topic.clone());
+    messages.retain(|message| !app.has_seen(&conversation_id, message.id));
+    // Only announcers may change the state of an announcement topic
+    let state = Some(response.state)
+        .filter(|_| app.config.may_publish(&topic, Some(&peer_id)))
+        .filter(|state| {
+            validation::validate_payload(&Payload::TopicState(state.clone()), app.clock).is_ok()
+        });
+
+    app.connection.events.emit(AppEvent::HistorySynced {
+        topic,
+        peer_id,
+        messages,
+        state,
+    });
+}
+
+// What gossipsub puts around the data of a message we publish, which `max_transmit_size` limits
+// too: our peer id with the inlined ed25519 key, the sequence number, the signature, the topic,
+// and the tags and lengths of the message and RPC fields
+pub fn gossipsub_overhead(topic_hash: &TopicHash) -> usize {
+    const FIELDS: usize = 38 + 8 + 64;
+    // At most three bytes of length for each of the seven fields within the limit, and a tag
+    const FRAMING: usize = 7 * 4;
+    FIELDS + FRAMING + topic_hash.as_str().len()
+}
+
+// How often a peer is flagged in a conversation for messages we can't read, so nobody can fill
+// the history with notices by sending garbage
+const UNREADABLE_NOTICE_INTERVAL: Duration = Duration::from_secs(60);
+
+// Messages we can't read are flagged in the history instead of silently dropped, once per sender
+// and interval. Expired entries are dropped so peer ids made up for each message don't pile up
+fn flag_unreadable(
+    conversation_id: &ConversationId,
+    source: Option<PeerId>,
+    text: String,
+    app: &mut App,
+) {
+    let notices = &mut app.connection.undecryptable_notices;
+    notices.retain(|_, last| last.elapsed() < UNREADABLE_NOTICE_INTERVAL);
+    let key = (conversation_id.clone(), source);
+    if notices.contains_key(&key) {
+        return;
+    }
+    notices.insert(key, Instant::now());
+    app.connection.events.emit(AppEvent::MessageReceived {
+        conversation_id: conversation_id.clone(),
+        message: ChatMessage::system(text),
+    });
+}
+
+fn handle_payload(
+    conversation_id: &ConversationId,
+    source: Option<PeerId>,
+    payload: Payload,
+    app: &mut App,
+) {
+    if let Some(source) = source.as_ref() {
+        if app.moderation.is_blocked(source) {
+            return;
+        }
+    }
+
+    match payload {
+        Payload::Chat(mut chat_message) => {
+            if !app.is_new_message(conversation_id, chat_message.id) {
+                return;
+            }
+            if let (Some(source), Some(reply_to)) = (source, chat_message.reply_to) {
+                app.connection.events.emit(AppEvent::ReceiptConfirmed {
+                    message_id: reply_to,
+                    peer_id: source,
+                });
+            }
+            chat_message.source_peer_id = source;
+            app.connection.events.emit(AppEvent::MessageReceived {
+                conversation_id: conversation_id.clone(),
+                message: chat_message,
+            });
+        }
+        // The acknowledgement is the answer
+        Payload::Ping => {}
+        Payload::ReadMarker { up_to } => {
+            // Only meaningful in DMs
+            if let ConversationId::Direct(_) = conversation_id {
+                app.connection.events.emit(AppEvent::ReadMarkerReceived {
+                    conversation_id: conversation_id.clone(),
+                    up_to,
+                });
+            }
+        }
+        Payload::Reaction {
+            message_id,
+            reaction,
+        } => {
+            if let Some(source) = source {
+                app.connection.events.emit(AppEvent::ReactionReceived {
+                    conversation_id: conversation_id.clone(),
+                    message_id,
+                    reaction,
+                    peer_id: source,
+                });
+            }
+        }
+        Payload::Vote { message_id, option } => {
+            if let Some(source) = source {
+                app.connection.events.emit(AppEvent::VoteReceived {
+                    conversation_id: conversation_id.clone(),
+                    message_id,
+                    option,
+                    peer_id: source,
+                });
+            }
+        }
+        Payload::Delete { message_id } => {
+            if let Some(source) = source {
+                app.connection.events.emit(AppEvent::DeleteReceived {
+                    conversation_id: conversation_id.clone(),
+                    message_id,
+                    peer_id: source,
+                });
+            }
+        }
+        Payload::Sealed {
+            epoch,
+            nonce,
+            ciphertext,
+        } => {
+            let topic = match conversation_id {
+                ConversationId::Topic(topic) => topic,
+                ConversationId::Direct(_) => return,
+            };
+            let author = source
+                .map(|source| utils::short_peer_id(&source))
+                .unwrap_or_else(|| tr(Msg::UnknownSource).to_string());
+            // Messages we can't read are flagged in the history instead of silently dropped
+            let key = match app.config.topic_settings(topic).keys.get(&epoch).cloned() {
+                Some(key) => key,
+                None => {
+                    let text = trf(Msg::SystemUnknownEpoch, &[&author, &epoch]);
+                    flag_unreadable(conversation_id, source, text, app);
+                    return;
+                }
+            };
+            let payload = match topickeys::open(&key, &nonce, &ciphertext) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    let text = trf(Msg::SystemUndecryptable, &[&author, &e]);
+                    flag_unreadable(conversation_id, source, text, app);
+                    return;
+                }
+            };
+            if let Payload::Sealed { .. } | Payload::TopicKey { .. } | Payload::Peers { .. } =
+                payload
+            {
+                return;
+            }
+            if let Err(reason) = validation::validate_payload(&payload, app.clock) {
+                app.connection.push_log(
+                    LogLevel::Warn,
+                    LogCategory::Messages,
+                    &trf(
+                        Msg::LogDroppedSealed,
+                        &[&conversation_id, &author, &tr(reason)],
+                    ),
+                );
+                return;
+            }
+            handle_payload(conversation_id, source, payload, app);
+        }
+        Payload::Presence { nick, status, card } => {
+            if let Some(source) = source {
+                app.connection.events.emit(AppEvent::PresenceReceived {
+                    peer_id: source,
+                    nick: nick.filter(|nick| nick.len() <= MAX_NICK_LEN),
+                    status,
+                    card: card.filter(validation::is_valid_card),
+                });
+            }
+        }
+        Payload::TopicState(state) => {
+            let topic = match conversation_id {
+                ConversationId::Topic(topic) => topic,
+                ConversationId::Direct(_) => return,
+            };
+            app.connection.events.emit(AppEvent::TopicStateReceived {
+                topic: topic.clone(),
+                source,
+                state,
+            });
+        }
+        Payload::TopicKey { topic, epoch, key } => {
+            if let Some(source) = source {
+                app.connection.events.emit(AppEvent::TopicKeyReceived {
+                    topic,
+                    epoch,
+                    key,
+                    peer_id: source,
+                });
+            }
+        }
+        // Only read on the peer exchange topic
+        Payload::Peers { .. } => {}
+        Payload::DmKey(dm_key) => {
+            if let (ConversationId::Direct(_), Some(source)) = (conversation_id, source) {
+                dmkeys::receive(dm_key, source, app);
+            }
+        }
+        // Opened by `handle_direct_envelope`, only valid as a whole envelope
+        Payload::DirectSealed { .. } => {}
+        Payload::NickCertificate(certificate) => {
+            if let ConversationId::Topic(_) = conversation_id {
+                app.connection
+                    .events
+                    .emit(AppEvent::NickCertificateReceived {
+                        certificate,
+                        source,
+                    });
+            }
+        }
+    }
+}
+
+// Runs a command taken from the outbox, `Shutdown` asks the event loop to quit
+pub fn handle_connection_command(command: ConnectionCommand, app: &mut App) -> InputTask {
+    app.connection
+        .push_log_with(LogLevel::Debug, LogCategory::Network, || {
+            command.to_string()
+        });
+    match command {
+        ConnectionCommand::Dial(addr) => {
+            if let Err(e) = app.connection.dial(addr.clone()) {
+                let text = trf(Msg::LogDialFailed, &[&addr, &e]);
+                app.connection
+                    .push_log(LogLevel::Error, LogCategory::Network, &text);
+                app.connection.events.emit(AppEvent::Notice { text });
+            }
+        }
+        ConnectionCommand::Publish { id, topic, data } => {
+            app.connection.outbox.taken();
+            let result = app.connection.topics.publish(
+                &mut app.connection.swarm.behaviour_mut().gossipsub,
+                &topic,
+                data,
+            );
+            let delivery = match result {
+                Ok(_) => Delivery::Sent,
+                Err(e) => {
+                    app.connection.push_log(
+                        LogLevel::Error,
+                        LogCategory::Messages,
+                        &trf(Msg::LogSendFailed, &[&ConversationId::Topic(topic), &e]),
+                    );
+                    Delivery::Failed
+                }
+            };
+            if let Some((conversation_id, message_id)) =
+                app.connection.pending_publishes.remove(&id)
+            {
+                app.connection.events.emit(AppEvent::DeliveryChanged {
+                    conversation_id,
+                    message_id,
+                    delivery,
+                });
+            }
+        }
+        ConnectionCommand::Send {
+            id,
+            peer_id,
+            envelope,
+        } => {
+            app.connection.outbox.taken();
+            let request_id = app
+                .connection
+                .swarm
+                .behaviour_mut()
+                .direct
+                .send_request(&peer_id, envelope);
+            if let Some(pending) = app.connection.pending_sends.remove(&id) {
+                app.stats.record_request(request_id);
+                app.connection
+                    .pending_deliveries
+                    .insert(request_id, pending);
+            }
+        }
+        ConnectionCommand::SharePeers(addrs) => peerexchange::publish(app, addrs),
+        ConnectionCommand::Subscribe(topic) => {
+            if let Err(e) = app
+                .connection
+                .topics
+                .subscribe(&mut app.connection.swarm.behaviour_mut().gossipsub, &topic)
+            {
+                app.connection.push_log(
+                    LogLevel::Error,
+                    LogCategory::Network,
+                    &trf(Msg::LogSubscribeFailed, &[&topic, &e]),
+                );
+            }
+        }
+        ConnectionCommand::Unsubscribe(topic) => {
+            if let Err(e) = app
+                .connection
+                .topics
+                .unsubscribe(&mut app.connection.swarm.behaviour_mut().gossipsub, &topic)
+            {
+                app.connection.push_log(
+                    LogLevel::Error,
+                    LogCategory::Network,
+                    &trf(Msg::LogUnsubscribeFailed, &[&topic, &e]),
+                );
+            }
+        }
+        // Fails only if the peer isn't connected anymore
+        ConnectionCommand::Disconnect(peer_id) => {
+            let _ = app.connection.swarm.disconnect_peer_id(peer_id);
+        }
+        ConnectionCommand::Redial(peer_id) => match peerlist::redial_addr(app, &peer_id) {
+            Some(addr) => {
+                if let Err(e) = app.connection.dial(addr.clone()) {
+                    app.connection.push_log(
+                        LogLevel::Error,
+                        LogCategory::Network,
+                        &trf(Msg::LogDialFailed, &[&addr, &e]),
+                    );
+                }
+            }
+            None => app.connection.push_log(
+                LogLevel::Warn,
+                LogCategory::Network,
+                &trf(Msg::LogNoAddrToRedial, &[&peer_id]),
+            ),
+        },
+        ConnectionCommand::Ping(peer_id) => ping(app, peer_id),
+        ConnectionCommand::Shutdown => return InputTask::Quit,
+    }
+    InputTask::Continue
+}
+
+// Peers that don't know pings would fail to read the request, they are pinged with the regular
+// pings only
+fn ping(app: &mut App, peer_id: PeerId) {
+    let supports_pings = app
+        .connection
+        .peer_protocols
+        .get(&peer_id)
+        .map_or(false, |peer_protocol| {
+            peer_protocol.supports(Capabilities::PINGS)
+        });
+    if !supports_pings {
+        app.connection.push_log(
+            LogLevel::Warn,
+            LogCategory::Network,
+            &trf(Msg::LogPingUnsupported, &[&peer_id]),
+        );
+        return;
+    }
+    // Sent right here rather than queued, so the round trip doesn't include the wait in the queue
+    match app.outgoing_envelope(&ConversationId::Direct(peer_id), Payload::Ping) {
+        Ok(envelope) => {
+            let request_id = app
+                .connection
+                .swarm
+                .behaviour_mut()
+                .direct
+                .send_request(&peer_id, envelope);
+            app.connection
+                .pending_pings
+                .insert(request_id, Instant::now());
+        }
+        Err(e) => app.connection.push_log(
+            LogLevel::Error,
+            LogCategory::Network,
+            &trf(Msg::LogPingFailed, &[&peer_id, &e]),
+        ),
+    }
+}
+
+pub fn handle_port_mapping_event(event: PortMappingEvent, app: &mut App) {
+    match event {
+        PortMappingEvent::Mapped {
+            method,
+            listen_addr,
+            local,
+            external,
+        } => {
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::Network,
+                &trf(Msg::LogPortMapped, &[&method, &external, &local]),
+            );
+
+            let external_addr = nat::external_multiaddr(&listen_addr, external);
+            if !app.connection.external_addrs.contains(&external_addr) {
+                app.connection.external_addrs.push(external_addr.clone());
+            }
+            app.connection
+                .swarm
+                .add_external_address(external_addr, AddressScore::Infinite);
+        }
+        PortMappingEvent::Failed { local, error } => {
+            app.connection.push_log(
+                LogLevel::Error,
+                LogCategory::Network,
+                &trf(Msg::LogPortMappingFailed, &[&local, &error]),
+            );
+        }
+    }
+}
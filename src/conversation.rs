@@ -0,0 +1,244 @@
+use std::fmt;
+use std::mem;
+use std::time::Instant;
+
+use chrono::{DateTime, Local};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::app::ChatMessage;
+use crate::config::Retention;
+use crate::textinput::TextInput;
+use crate::utils;
+
+// How many votes for polls we don't have yet are kept per conversation, the oldest go first
+const MAX_EARLY_VOTES: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ConversationId {
+    Topic(String),
+    Direct(PeerId),
+}
+
+impl fmt::Display for ConversationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Topic(topic) => write!(f, "#{}", topic),
+            Self::Direct(peer_id) => write!(f, "@{}", utils::short_peer_id(peer_id)),
+        }
+    }
+}
+
+// Input typed into a conversation but not sent yet
+#[derive(Debug, Clone, Default)]
+pub struct Draft {
+    pub input: TextInput,
+    pub reply_to: Option<Uuid>,
+}
+
+impl Draft {
+    pub fn is_empty(&self) -> bool {
+        self.input.is_empty() && self.reply_to.is_none()
+    }
+}
+
+// Where the history was scrolled to, kept while another conversation is open
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrollPosition {
+    // The selected message, by id since older messages might be dropped meanwhile
+    pub selected: Option<Uuid>,
+    pub offset: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Conversation {
+    pub history: Vec<ChatMessage>,
+    // Messages that arrived while the conversation wasn't viewed
+    pub unread: usize,
+    // Unread messages mentioning us or a highlight word
+    pub mentions: usize,
+    pub last_activity: Instant,
+    // The newest message we told the peer we have read (DMs only)
+    pub read_marker_sent: Option<Uuid>,
+    // The newest message the peer told us it has read (DMs only)
+    pub read_marker_received: Option<Uuid>,
+    // Kept while another conversation is open
+    pub draft: Draft,
+    pub scroll: ScrollPosition,
+    // Pages of the stored history older than the shown messages, read when scrolled to, see
+    // `session::load_older`
+    pub stored_pages: usize,
+    // The pages the stored history had when it was resumed. Those from `stored_pages` on are
+    // shown now and removed from disk when the history is saved
+    pub resumed_pages: usize,
+    // Votes that arrived before their poll, counted once it does
+    pub early_votes: Vec<(Uuid, PeerId, usize)>,
+}
+
+impl Default for Conversation {
+    fn default() -> Self {
+        Self {
+            history: vec![],
+            unread: 0,
+            mentions: 0,
+            last_activity: Instant::now(),
+            read_marker_sent: None,
+            read_marker_received: None,
+            draft: Draft::default(),
+            scroll: ScrollPosition::default(),
+            stored_pages: 0,
+            resumed_pages: 0,
+            early_votes: vec![],
+        }
+    }
+}
+
+impl Conversation {
+    // Inserts a message where it belongs by `ChatMessage::order_key()`, usually at the end
+    pub fn push_message(&mut self, mut message: ChatMessage) {
+        // The message itself replaces a copy from a history sync, keeping the votes it got
+        if let Some(index) = self
+            .history
+            .iter()
+            .position(|other| other.synced && other.id == message.id)
+        {
+            let synced = self.history.remove(index);
+            for (peer_id, option) in synced.votes {
+                message.votes.entry(peer_id).or_insert(option);
+            }
+        }
+        self.take_early_votes(&mut message);
+        let key = message.order_key();
+        let index = self
+            .history
+            .partition_point(|other| other.order_key() <= key);
+        self.history.insert(index, message);
+        self.last_activity = Instant::now();
+    }
+
+    // Adds messages from another peer's history that we don't have yet. Returns how many were
+    // new
+    pub fn merge_messages(&mut self, messages: Vec<ChatMessage>) -> usize {
+        let len = self.history.len();
+        for mut message in messages {
+            if self.message(message.id).is_none() {
+                self.take_early_votes(&mut message);
+                self.history.push(message);
+            }
+        }
+        self.history.sort_by_key(|message| message.order_key());
+        self.history.len() - len
+    }
+
+    // Drops the messages the retention doesn't keep anymore and returns them
+    pub fn apply_retention(&mut self, retention: &Retention) -> Vec<ChatMessage> {
+        let mut dropped = match retention.cutoff() {
+            Some(cutoff) => self.remove_where(|message| message.received_at < cutoff),
+            None => vec![],
+        };
+        if let Some(messages) = retention.messages {
+            let excess = self.history.len().saturating_sub(messages);
+            dropped.extend(self.history.drain(..excess));
+        }
+        dropped
+    }
+
+    // Returns the disappearing messages that were removed
+    pub fn remove_expired(&mut self, now: DateTime<Local>) -> Vec<ChatMessage> {
+        self.remove_where(|message| message.is_expired(now))
+    }
+
+    // Removes the messages that match and returns them
+    pub fn remove_where(&mut self, remove: impl Fn(&ChatMessage) -> bool) -> Vec<ChatMessage> {
+        let (removed, kept): (Vec<ChatMessage>, Vec<ChatMessage>) = mem::take(&mut self.history)
+            .into_iter()
+            .partition(|message| remove(message));
+        self.history = kept;
+        removed
+    }
+
+    pub fn mark_read(&mut self) {
+        self.unread = 0;
+        self.mentions = 0;
+    }
+
+    pub fn last_message_id(&self) -> Option<Uuid> {
+        self.history.last().map(|message| message.id)
+    }
+
+    pub fn message(&self, id: Uuid) -> Option<&ChatMessage> {
+        self.history.iter().find(|message| message.id == id)
+    }
+
+    pub fn message_mut(&mut self, id: Uuid) -> Option<&mut ChatMessage> {
+        self.history.iter_mut().find(|message| message.id == id)
+    }
+
+    pub fn add_reaction(&mut self, message_id: Uuid, reaction: String, peer_id: PeerId) {
+        if let Some(message) = self.message_mut(message_id) {
+            message
+                .reactions
+                .entry(reaction)
+                .or_default()
+                .insert(peer_id);
+        }
+    }
+
+    // Returns whether the message is a poll with that option. Votes for messages we don't have
+    // are kept until they arrive, e.g. from a history sync
+    pub fn add_vote(&mut self, message_id: Uuid, option: usize, peer_id: PeerId) -> bool {
+        match self.message_mut(message_id) {
+            Some(message)
+                if message
+                    .poll
+                    .as_ref()
+                    .map_or(false, |options| option < options.len()) =>
+            {
+                message.votes.insert(peer_id, option);
+                true
+            }
+            Some(_) => false,
+            None => {
+                if self.early_votes.len() >= MAX_EARLY_VOTES {
+                    self.early_votes.remove(0);
+                }
+                self.early_votes.push((message_id, peer_id, option));
+                false
+            }
+        }
+    }
+
+    // Counts the votes that arrived before the poll, later ones replacing earlier ones of a peer
+    fn take_early_votes(&mut self, message: &mut ChatMessage) {
+        let (votes, others) = mem::take(&mut self.early_votes)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(message_id, _, _)| *message_id == message.id);
+        self.early_votes = others;
+        let options = message.poll.as_ref().map_or(0, |options| options.len());
+        for (_, peer_id, option) in votes.into_iter().filter(|(_, _, option)| *option < options) {
+            message.votes.insert(peer_id, option);
+        }
+    }
+
+    // Only the author can remove a message. Returns the removed message
+    pub fn remove_message(&mut self, message_id: Uuid, author: &PeerId) -> Option<ChatMessage> {
+        let index = self.history.iter().position(|message| {
+            message.id == message_id && message.source_peer_id.as_ref() == Some(author)
+        })?;
+        Some(self.history.remove(index))
+    }
+
+    // The index of our own newest message the peer has seen, to render "seen" below it
+    pub fn last_seen_outgoing(&self, local_peer_id: &PeerId) -> Option<usize> {
+        let read_up_to = self.read_marker_received?;
+        let read_index = self
+            .history
+            .iter()
+            .position(|message| message.id == read_up_to)?;
+
+        self.history[..=read_index]
+            .iter()
+            .rposition(|message| message.source_peer_id.as_ref() == Some(local_peer_id))
+    }
+}
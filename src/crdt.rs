@@ -0,0 +1,64 @@
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// A last-writer-wins register. Writes are ordered by Lamport clock and then peer id, so all peers
+// keep the same value no matter in which order they see the writes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LwwRegister<T> {
+    pub value: T,
+    pub clock: u64,
+    pub peer: String,
+}
+
+impl<T: Clone> LwwRegister<T> {
+    pub fn set(&mut self, value: T, clock: u64, peer_id: &PeerId) {
+        self.value = value;
+        self.clock = clock;
+        self.peer = peer_id.to_base58();
+    }
+
+    // Keeps the newer of both writes. Returns whether the value changed
+    pub fn merge(&mut self, other: &Self) -> bool {
+        if (other.clock, &other.peer) > (self.clock, &self.peer) {
+            *self = other.clone();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pin {
+    pub message_id: Uuid,
+    pub author: String,
+    pub text: String,
+}
+
+// What the members of a topic share about it, shown above its history
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TopicState {
+    pub description: LwwRegister<String>,
+    pub pinned: LwwRegister<Option<Pin>>,
+}
+
+impl TopicState {
+    // Returns whether anything changed
+    pub fn merge(&mut self, other: &Self) -> bool {
+        let description_changed = self.description.merge(&other.description);
+        let pinned_changed = self.pinned.merge(&other.pinned);
+        description_changed || pinned_changed
+    }
+
+    // Credits every write to the peer we got the state from, whatever peer it claims
+    pub fn attribute_to(&mut self, peer_id: &PeerId) {
+        self.description.peer = peer_id.to_base58();
+        self.pinned.peer = peer_id.to_base58();
+    }
+
+    // The newest clock of any write
+    pub fn clock(&self) -> u64 {
+        self.description.clock.max(self.pinned.clock)
+    }
+}
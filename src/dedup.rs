@@ -0,0 +1,41 @@
+use std::collections::{HashSet, VecDeque};
+
+use uuid::Uuid;
+
+// How many message ids are remembered per conversation
+const SEEN_CAPACITY: usize = 1024;
+
+// The ids of the recent messages of a conversation. The same message can arrive more than once,
+// over several mesh paths or again with a history sync, even after it was deleted
+#[derive(Debug, Default)]
+pub struct SeenIds {
+    // Least recently seen first
+    order: VecDeque<Uuid>,
+    ids: HashSet<Uuid>,
+}
+
+impl SeenIds {
+    pub fn contains(&self, id: Uuid) -> bool {
+        self.ids.contains(&id)
+    }
+
+    // Records an id, returning whether it is new
+    pub fn insert(&mut self, id: Uuid) -> bool {
+        if self.ids.contains(&id) {
+            if let Some(position) = self.order.iter().position(|seen| *seen == id) {
+                self.order.remove(position);
+            }
+            self.order.push_back(id);
+            return false;
+        }
+
+        if self.order.len() >= SEEN_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        self.order.push_back(id);
+        self.ids.insert(id);
+        true
+    }
+}
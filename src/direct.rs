@@ -0,0 +1,87 @@
+use std::io;
+
+use async_trait::async_trait;
+use futures::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use libp2p::core::upgrade::{read_length_prefixed, write_length_prefixed};
+use libp2p::core::ProtocolName;
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::Envelope;
+
+// Direct messages are sent as envelopes over a request-response protocol, so they only ever
+// travel between the two peers involved
+const MAX_DIRECT_MESSAGE_SIZE: usize = 1_000_000;
+
+#[derive(Debug, Clone)]
+pub struct DirectProtocol();
+
+impl ProtocolName for DirectProtocol {
+    fn protocol_name(&self) -> &[u8] {
+        b"/p2pchat/direct/1.0.0"
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DirectResponse {
+    Ack,
+    // The message was sealed with a key we don't have, e.g. from before we restarted
+    Undecryptable,
+}
+
+#[derive(Clone)]
+pub struct DirectCodec();
+
+#[async_trait]
+impl libp2p::request_response::RequestResponseCodec for DirectCodec {
+    type Protocol = DirectProtocol;
+    type Request = Envelope;
+    type Response = DirectResponse;
+
+    async fn read_request<T>(&mut self, _: &DirectProtocol, io: &mut T) -> io::Result<Envelope>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let data = read_length_prefixed(io, MAX_DIRECT_MESSAGE_SIZE).await?;
+        serde_json::from_slice(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        _: &DirectProtocol,
+        io: &mut T,
+    ) -> io::Result<DirectResponse>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let data = read_length_prefixed(io, MAX_DIRECT_MESSAGE_SIZE).await?;
+        serde_json::from_slice(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _: &DirectProtocol,
+        io: &mut T,
+        request: Envelope,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let data = serde_json::to_vec(&request)?;
+        write_length_prefixed(io, data).await?;
+        io.close().await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        _: &DirectProtocol,
+        io: &mut T,
+        response: DirectResponse,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let data = serde_json::to_vec(&response)?;
+        write_length_prefixed(io, data).await?;
+        io.close().await
+    }
+}
@@ -0,0 +1,483 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use anyhow::{anyhow, Context};
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use libp2p::identity::{Keypair, PublicKey as IdentityKey};
+use libp2p::PeerId;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::app::App;
+use crate::conversation::ConversationId;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::i18n::{trf, Msg};
+use crate::protocol::Payload;
+
+// DMs are encrypted end to end on top of the transport, so relays and whatever carries them
+// can't read them. Every run has its own X25519 key, announced to peers signed with our
+// identity. Both sides derive a root key from their two keys and a chain per direction from it.
+// The chains advance with every message and forget the keys they used, so a leaked key doesn't
+// reveal earlier messages. It is the symmetric half of a double ratchet, new runs take the place
+// of its DH steps
+
+// Keys of messages that arrive out of order are kept for at most this many skipped messages
+const MAX_SKIPPED: u64 = 100;
+
+// Our DM key for this run, signed with our identity so peers know it is ours
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DmKey {
+    // X25519, base64
+    pub key: String,
+    // Protobuf encoded, in base64
+    pub public_key: String,
+    pub signature: String,
+}
+
+fn signed_bytes(key: &str) -> Vec<u8> {
+    format!("p2pchat dm key\n{}", key).into_bytes()
+}
+
+impl DmKey {
+    fn issue(key: &PublicKey, id_keys: &Keypair) -> Result<Self, anyhow::Error> {
+        let key = data_encoding::BASE64.encode(key.as_bytes());
+        let signature = id_keys
+            .sign(&signed_bytes(&key))
+            .context("signing the DM key failed")?;
+        Ok(Self {
+            public_key: data_encoding::BASE64.encode(&id_keys.public().to_protobuf_encoding()),
+            signature: data_encoding::BASE64.encode(&signature),
+            key,
+        })
+    }
+
+    // The peer that signed the key and the key, if its signature is valid
+    fn verify(&self) -> Result<(PeerId, PublicKey), anyhow::Error> {
+        let public_key = data_encoding::BASE64
+            .decode(self.public_key.as_bytes())
+            .context("decoding the public key failed")?;
+        let public_key = IdentityKey::from_protobuf_encoding(&public_key)
+            .context("parsing the public key failed")?;
+        let signature = data_encoding::BASE64
+            .decode(self.signature.as_bytes())
+            .context("decoding the signature failed")?;
+        anyhow::ensure!(
+            public_key.verify(&signed_bytes(&self.key), &signature),
+            "the signature is invalid"
+        );
+        let key = data_encoding::BASE64
+            .decode(self.key.as_bytes())
+            .context("decoding the key failed")?;
+        let key: [u8; 32] = key
+            .try_into()
+            .map_err(|_| anyhow!("DM keys must be 32 bytes long"))?;
+        Ok((public_key.to_peer_id(), PublicKey::from(key)))
+    }
+}
+
+fn hash(parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+fn cipher(key: &[u8; 32]) -> ChaCha20Poly1305 {
+    ChaCha20Poly1305::new(Key::from_slice(key))
+}
+
+// The messages of one direction of a session
+#[derive(Clone)]
+struct Chain {
+    key: [u8; 32],
+    // Of the next message
+    counter: u64,
+}
+
+impl Chain {
+    fn new(root: &[u8; 32], sender: &PeerId) -> Self {
+        Self {
+            key: hash(&[root, b"chain", &sender.to_bytes()]),
+            counter: 0,
+        }
+    }
+
+    // The key of the next message, the chain moves past it
+    fn next(&mut self) -> [u8; 32] {
+        let message_key = hash(&[&self.key, &[1]]);
+        self.key = hash(&[&self.key, &[2]]);
+        self.counter += 1;
+        message_key
+    }
+}
+
+// What encrypts the DMs with one peer
+#[derive(Clone)]
+pub struct Session {
+    their_key: PublicKey,
+    sending: Chain,
+    receiving: Chain,
+    // The keys of messages that haven't arrived yet by their counter
+    skipped: HashMap<u64, [u8; 32]>,
+}
+
+impl Session {
+    fn new(secret: &StaticSecret, their_key: PublicKey, local: &PeerId, remote: &PeerId) -> Self {
+        let shared = secret.diffie_hellman(&their_key);
+        // Both sides hash the peer ids in the same order
+        let (first, second) = if local.to_bytes() < remote.to_bytes() {
+            (local, remote)
+        } else {
+            (remote, local)
+        };
+        let root = hash(&[
+            b"p2pchat dm root",
+            shared.as_bytes(),
+            &first.to_bytes(),
+            &second.to_bytes(),
+        ]);
+        Self {
+            their_key,
+            sending: Chain::new(&root, local),
+            receiving: Chain::new(&root, remote),
+            skipped: HashMap::new(),
+        }
+    }
+
+    pub fn seal(&mut self, payload: &Payload) -> Result<Payload, anyhow::Error> {
+        let counter = self.sending.counter;
+        let key = self.sending.next();
+        let mut nonce = [0u8; 12];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let plaintext = serde_json::to_vec(payload)?;
+        let ciphertext = cipher(&key)
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| anyhow!("encrypting payload failed"))?;
+
+        Ok(Payload::DirectSealed {
+            counter,
+            nonce: data_encoding::BASE64.encode(&nonce),
+            ciphertext: data_encoding::BASE64.encode(&ciphertext),
+        })
+    }
+
+    fn message_key(&mut self, counter: u64) -> Result<[u8; 32], anyhow::Error> {
+        if counter < self.receiving.counter {
+            return self
+                .skipped
+                .remove(&counter)
+                .context("the message was decrypted before or is too old");
+        }
+        anyhow::ensure!(
+            counter - self.receiving.counter <= MAX_SKIPPED,
+            "more than {} messages are missing before it",
+            MAX_SKIPPED
+        );
+        while self.receiving.counter < counter {
+            let skipped = self.receiving.counter;
+            let key = self.receiving.next();
+            self.skipped.insert(skipped, key);
+        }
+        // The oldest ones are probably lost
+        let oldest = self.receiving.counter.saturating_sub(MAX_SKIPPED);
+        self.skipped.retain(|skipped, _| *skipped >= oldest);
+        Ok(self.receiving.next())
+    }
+
+    // Decrypts the payload of a `Payload::DirectSealed`
+    pub fn open(
+        &mut self,
+        counter: u64,
+        nonce: &str,
+        ciphertext: &str,
+    ) -> Result<Payload, anyhow::Error> {
+        let nonce = data_encoding::BASE64.decode(nonce.as_bytes())?;
+        let ciphertext = data_encoding::BASE64.decode(ciphertext.as_bytes())?;
+        anyhow::ensure!(nonce.len() == 12, "invalid nonce");
+
+        // A forged message mustn't move the chain past the keys of real ones
+        let mut next = self.clone();
+        let key = next.message_key(counter)?;
+        let plaintext = cipher(&key)
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| anyhow!("decrypting payload failed"))?;
+        let payload = serde_json::from_slice(&plaintext)?;
+        *self = next;
+        Ok(payload)
+    }
+}
+
+// Our key of this run and the sessions with the peers that sent us theirs
+pub struct DmCrypto {
+    secret: StaticSecret,
+    sessions: HashMap<PeerId, Session>,
+}
+
+impl Default for DmCrypto {
+    fn default() -> Self {
+        let mut secret = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+        Self {
+            secret: StaticSecret::from(secret),
+            sessions: HashMap::new(),
+        }
+    }
+}
+
+// The secret stays out of logs
+impl fmt::Debug for DmCrypto {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DmCrypto")
+            .field("sessions", &self.sessions.keys().collect::<Vec<&PeerId>>())
+            .finish()
+    }
+}
+
+impl DmCrypto {
+    pub fn announcement(&self, id_keys: &Keypair) -> Result<DmKey, anyhow::Error> {
+        DmKey::issue(&PublicKey::from(&self.secret), id_keys)
+    }
+
+    pub fn session(&self, peer_id: &PeerId) -> Option<&Session> {
+        self.sessions.get(peer_id)
+    }
+
+    pub fn session_mut(&mut self, peer_id: &PeerId) -> Option<&mut Session> {
+        self.sessions.get_mut(peer_id)
+    }
+
+    pub fn is_encrypted(&self, peer_id: &PeerId) -> bool {
+        self.sessions.contains_key(peer_id)
+    }
+
+    // Starts a session with the key a peer sent, replacing the one of its earlier run. Returns
+    // whether the key was new
+    fn accept(
+        &mut self,
+        dm_key: &DmKey,
+        source: PeerId,
+        local: &PeerId,
+    ) -> Result<bool, anyhow::Error> {
+        let (issuer, their_key) = dm_key.verify()?;
+        anyhow::ensure!(issuer == source, "the key was signed by {}", issuer);
+        if self.sessions.get(&source).map_or(false, |session| {
+            session.their_key.as_bytes() == their_key.as_bytes()
+        }) {
+            return Ok(false);
+        }
+        let session = Session::new(&self.secret, their_key, local, &source);
+        self.sessions.insert(source, session);
+        Ok(true)
+    }
+}
+
+// Sends our key to a peer that can encrypt DMs, again on every identify so peers that restarted
+// get it too
+pub fn announce(app: &mut App, peer_id: PeerId) {
+    match app
+        .connection
+        .dm_crypto
+        .announcement(&app.connection.id_keys)
+    {
+        Ok(dm_key) => {
+            app.send_payload_logged(&ConversationId::Direct(peer_id), Payload::DmKey(dm_key))
+        }
+        Err(e) => app.connection.push_log(
+            LogLevel::Error,
+            LogCategory::Security,
+            &trf(Msg::LogDmKeyFailed, &[&e]),
+        ),
+    }
+}
+
+pub fn receive(dm_key: DmKey, source: PeerId, app: &mut App) {
+    let local_peer_id = *app.connection.swarm.local_peer_id();
+    match app
+        .connection
+        .dm_crypto
+        .accept(&dm_key, source, &local_peer_id)
+    {
+        Ok(true) => app.connection.push_log(
+            LogLevel::Info,
+            LogCategory::Security,
+            &trf(Msg::LogDmEncrypted, &[&source]),
+        ),
+        Ok(false) => {}
+        Err(e) => app.connection.push_log(
+            LogLevel::Warn,
+            LogCategory::Security,
+            &trf(Msg::LogDmKeyInvalid, &[&source, &e]),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::ChatMessage;
+
+    struct Peer {
+        id_keys: Keypair,
+        crypto: DmCrypto,
+    }
+
+    impl Peer {
+        fn new() -> Self {
+            Self {
+                id_keys: Keypair::generate_ed25519(),
+                crypto: DmCrypto::default(),
+            }
+        }
+
+        fn peer_id(&self) -> PeerId {
+            self.id_keys.public().to_peer_id()
+        }
+
+        fn accept_from(&mut self, other: &Peer) -> Result<bool, anyhow::Error> {
+            let dm_key = other.crypto.announcement(&other.id_keys)?;
+            let local = self.peer_id();
+            self.crypto.accept(&dm_key, other.peer_id(), &local)
+        }
+
+        fn seal_for(&mut self, other: &Peer, text: &str) -> Payload {
+            self.crypto
+                .session_mut(&other.peer_id())
+                .unwrap()
+                .seal(&payload(text))
+                .unwrap()
+        }
+
+        fn open_from(&mut self, other: &Peer, sealed: &Payload) -> Result<String, anyhow::Error> {
+            let (counter, nonce, ciphertext) = match sealed {
+                Payload::DirectSealed {
+                    counter,
+                    nonce,
+                    ciphertext,
+                } => (*counter, nonce, ciphertext),
+                _ => panic!("not sealed"),
+            };
+            let session = self
+                .crypto
+                .session_mut(&other.peer_id())
+                .context("no session")?;
+            match session.open(counter, nonce, ciphertext)? {
+                Payload::Chat(message) => Ok(message.text),
+                _ => panic!("unexpected payload"),
+            }
+        }
+    }
+
+    fn payload(text: &str) -> Payload {
+        Payload::Chat(ChatMessage::new(None, None, text.to_string()))
+    }
+
+    fn pair() -> (Peer, Peer) {
+        let (mut alice, mut bob) = (Peer::new(), Peer::new());
+        assert!(alice.accept_from(&bob).unwrap());
+        assert!(bob.accept_from(&alice).unwrap());
+        (alice, bob)
+    }
+
+    #[test]
+    fn sealed_messages_open_in_both_directions() {
+        let (mut alice, mut bob) = pair();
+        for i in 0..3 {
+            let sealed = alice.seal_for(&bob, &format!("to bob {}", i));
+            assert_eq!(
+                bob.open_from(&alice, &sealed).unwrap(),
+                format!("to bob {}", i)
+            );
+            let sealed = bob.seal_for(&alice, &format!("to alice {}", i));
+            assert_eq!(
+                alice.open_from(&bob, &sealed).unwrap(),
+                format!("to alice {}", i)
+            );
+        }
+    }
+
+    #[test]
+    fn messages_open_out_of_order() {
+        let (mut alice, mut bob) = pair();
+        let sealed = (0..4)
+            .map(|i| alice.seal_for(&bob, &i.to_string()))
+            .collect::<Vec<Payload>>();
+        for i in [2, 0, 3, 1] {
+            assert_eq!(bob.open_from(&alice, &sealed[i]).unwrap(), i.to_string());
+        }
+    }
+
+    #[test]
+    fn too_many_skipped_messages_are_rejected() {
+        let (mut alice, mut bob) = pair();
+        let sealed = (0..MAX_SKIPPED + 2)
+            .map(|i| alice.seal_for(&bob, &i.to_string()))
+            .collect::<Vec<Payload>>();
+        assert!(bob.open_from(&alice, sealed.last().unwrap()).is_err());
+        // The rejected message didn't move the chain
+        assert_eq!(bob.open_from(&alice, &sealed[0]).unwrap(), "0");
+        assert_eq!(
+            bob.open_from(&alice, &sealed[MAX_SKIPPED as usize])
+                .unwrap(),
+            MAX_SKIPPED.to_string()
+        );
+    }
+
+    #[test]
+    fn replayed_messages_are_rejected() {
+        let (mut alice, mut bob) = pair();
+        let first = alice.seal_for(&bob, "first");
+        let second = alice.seal_for(&bob, "second");
+        assert_eq!(bob.open_from(&alice, &second).unwrap(), "second");
+        assert_eq!(bob.open_from(&alice, &first).unwrap(), "first");
+        assert!(bob.open_from(&alice, &first).is_err());
+        assert!(bob.open_from(&alice, &second).is_err());
+    }
+
+    #[test]
+    fn forged_messages_dont_use_up_keys() {
+        let (mut alice, mut bob) = pair();
+        let sealed = alice.seal_for(&bob, "real");
+        let forged = match &sealed {
+            Payload::DirectSealed { counter, nonce, .. } => Payload::DirectSealed {
+                counter: *counter,
+                nonce: nonce.clone(),
+                ciphertext: data_encoding::BASE64.encode(&[0u8; 32]),
+            },
+            _ => unreachable!(),
+        };
+        assert!(bob.open_from(&alice, &forged).is_err());
+        assert_eq!(bob.open_from(&alice, &sealed).unwrap(), "real");
+    }
+
+    #[test]
+    fn a_new_key_replaces_the_session() {
+        let (mut alice, mut bob) = pair();
+        // The same key again keeps the session and where its chains are
+        let sealed = alice.seal_for(&bob, "before");
+        assert!(!bob.accept_from(&alice).unwrap());
+        assert_eq!(bob.open_from(&alice, &sealed).unwrap(), "before");
+
+        // Alice restarts with a new key, what was sealed with the old one can't be read anymore
+        let old = alice.seal_for(&bob, "old");
+        alice.crypto = DmCrypto::default();
+        assert!(bob.accept_from(&alice).unwrap());
+        assert!(alice.accept_from(&bob).unwrap());
+        assert!(bob.open_from(&alice, &old).is_err());
+        let sealed = alice.seal_for(&bob, "after");
+        assert_eq!(bob.open_from(&alice, &sealed).unwrap(), "after");
+    }
+
+    #[test]
+    fn keys_signed_by_someone_else_are_rejected() {
+        let (alice, mallory, mut bob) = (Peer::new(), Peer::new(), Peer::new());
+        let dm_key = mallory.crypto.announcement(&mallory.id_keys).unwrap();
+        let local = bob.peer_id();
+        assert!(bob.crypto.accept(&dm_key, alice.peer_id(), &local).is_err());
+        assert!(!bob.crypto.is_encrypted(&alice.peer_id()));
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Context;
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+
+use crate::app::App;
+use crate::connection::ConnectionCommand;
+use crate::conversation::ConversationId;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::i18n::{trf, Msg};
+use crate::profile;
+use crate::vault::{self, DerivedKey, Sealed};
+use crate::writer;
+
+// How often changed drafts are written, so a crash loses at most this much typing
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+// The unsent input of each conversation, by topic and by peer id. Encrypted like the session
+// when `encrypt_session` is set
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Drafts {
+    pub topics: BTreeMap<String, String>,
+    pub direct: BTreeMap<String, String>,
+}
+
+impl Drafts {
+    pub fn path() -> Option<PathBuf> {
+        profile::dir().map(|dir| dir.join("drafts.json"))
+    }
+
+    pub fn of(app: &App) -> Self {
+        let mut drafts = Self::default();
+        let inputs = app
+            .conversations
+            .iter()
+            .map(|(id, conversation)| (id, conversation.draft.input.as_str()))
+            .chain(std::iter::once((
+                &app.ui.current_conversation,
+                app.ui.chat_input.as_str(),
+            )));
+        for (id, input) in inputs {
+            if input.is_empty() {
+                continue;
+            }
+            match id {
+                ConversationId::Topic(topic) => {
+                    drafts.topics.insert(topic.clone(), input.to_string());
+                }
+                ConversationId::Direct(peer_id) => {
+                    drafts.direct.insert(peer_id.to_base58(), input.to_string());
+                }
+            }
+        }
+        drafts
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.topics.is_empty() && self.direct.is_empty()
+    }
+
+    fn is_sealed() -> bool {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map_or(false, |drafts_str| {
+                serde_json::from_str::<Sealed>(&drafts_str).is_ok()
+            })
+    }
+
+    // Opened with the session key if they were sealed with it, so the passphrase is only derived
+    // again for older files
+    pub fn load(key: Option<&DerivedKey>, passphrase: Option<&str>) -> Result<Self, anyhow::Error> {
+        let path = match Self::path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Self::default()),
+        };
+
+        let drafts_str = fs::read_to_string(&path)
+            .with_context(|| format!("reading drafts file {:?} failed", path))?;
+        match serde_json::from_str::<Sealed>(&drafts_str) {
+            Ok(sealed) => {
+                let plaintext = match key.filter(|key| key.sealed_with(&sealed)) {
+                    Some(key) => key.open(&sealed),
+                    None => {
+                        vault::open(&sealed, passphrase.context("the drafts file is encrypted")?)
+                    }
+                }
+                .with_context(|| format!("opening drafts file {:?} failed", path))?;
+                serde_json::from_slice(&plaintext)
+            }
+            Err(_) => serde_json::from_str(&drafts_str),
+        }
+        .with_context(|| format!("parsing drafts file {:?} failed", path))
+    }
+
+    // Removes the file when there are no drafts left
+    pub fn save(&self, key: Option<&DerivedKey>) -> Result<(), anyhow::Error> {
+        let path = Self::path().context("no config directory available")?;
+        if self.is_empty() {
+            return writer::remove(&path)
+                .with_context(|| format!("removing drafts file {:?} failed", path));
+        }
+
+        let drafts_str = match passphrase {
+            Some(key) => {
+                let sealed = key.seal(&serde_json::to_vec(self)?)?;
+                serde_json::to_string_pretty(&sealed)?
+            }
+            None => serde_json::to_string_pretty(self)?,
+        };
+        writer::write(&path, drafts_str, true)
+            .with_context(|| format!("writing drafts file {:?} failed", path))
+    }
+}
+
+// The passphrase to use for the drafts, `Err` if they are encrypted and it wasn't entered yet
+fn passphrase(app: &App) -> Result<Option<&str>, ()> {
+    let passphrase = app.session_passphrase.as_deref();
+    if passphrase.is_none() && (app.config.encrypt_session || Drafts::is_sealed()) {
+        return Err(());
+    }
+    Ok(passphrase.filter(|_| app.config.encrypt_session))
+}
+
+// The key to encrypt the drafts with, derived from the same passphrase
+fn key(app: &App) -> Result<Option<&DerivedKey>, ()> {
+    let key = app.session_key.as_ref();
+    if key.is_none() && (app.config.encrypt_session || Drafts::is_sealed()) {
+        return Err(());
+    }
+    Ok(key.filter(|_| app.config.encrypt_session))
+}
+
+// Puts the saved drafts back into their conversations, reopening the ones that are gone. Waits
+// for the session to be unlocked if they are encrypted
+pub fn restore(app: &mut App) -> Result<(), anyhow::Error> {
+    let drafts = match passphrase(app) {
+        Ok(passphrase) => Drafts::load(key(app).unwrap_or(None), passphrase)?,
+        Err(()) => return Ok(()),
+    };
+
+    let inputs = drafts
+        .topics
+        .into_iter()
+        .map(|(topic, input)| (ConversationId::Topic(topic), input))
+        .chain(drafts.direct.into_iter().filter_map(|(peer_id, input)| {
+            Some((
+                ConversationId::Direct(peer_id.parse::<PeerId>().ok()?),
+                input,
+            ))
+        }));
+    let mut count = 0;
+    for (id, input) in inputs {
+        if let ConversationId::Topic(topic) = &id {
+            app.connection
+                .outbox
+                .push(ConnectionCommand::Subscribe(topic.clone()));
+        }
+        if id == app.ui.current_conversation {
+            app.ui.chat_input.set(input);
+        } else {
+            app.conversation_mut(&id).draft.input.set(input);
+        }
+        count += 1;
+    }
+
+    if count > 0 {
+        app.connection.push_log(
+            LogLevel::Info,
+            LogCategory::General,
+            &trf(Msg::LogDraftsRestored, &[&count]),
+        );
+    }
+    app.saved_drafts = Drafts::of(app);
+    Ok(())
+}
+
+// Writes the drafts if they changed since the last time
+pub fn save(app: &mut App) {
+    let drafts = Drafts::of(app);
+    if drafts == app.saved_drafts {
+        return;
+    }
+    let result = match key(app) {
+        Ok(key) => drafts.save(key),
+        // Without the passphrase encrypted drafts would be overwritten in the clear
+        Err(()) => return,
+    };
+    match result {
+        Ok(()) => app.saved_drafts = drafts,
+        Err(e) => log::error!("saving the drafts failed with Err `{}`", e),
+    }
+}
@@ -0,0 +1,110 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context;
+
+use chrono::{DateTime, Local};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    // Raw swarm events
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        }
+    }
+}
+
+// What a log entry is about, to tell entries apart at a glance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogCategory {
+    General,
+    // Listeners, dials, connections and port mappings
+    Network,
+    // Sending, receiving and syncing messages
+    Messages,
+    // Identities, keys and verification
+    Security,
+}
+
+impl LogCategory {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::General => "general",
+            Self::Network => "network",
+            Self::Messages => "messages",
+            Self::Security => "security",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub time: DateTime<Local>,
+    pub level: LogLevel,
+    pub category: LogCategory,
+    pub text: String,
+}
+
+impl LogEntry {
+    pub fn new(level: LogLevel, category: LogCategory, text: impl Into<String>) -> Self {
+        Self {
+            time: Local::now(),
+            level,
+            category,
+            text: text.into(),
+        }
+    }
+
+    // The time of the entry, e.g. `14:03:52`, or how long ago it was, e.g. `5m`
+    pub fn format_time(&self, relative: bool) -> String {
+        if !relative {
+            return self.time.format("%H:%M:%S").to_string();
+        }
+
+        let secs = (Local::now() - self.time).num_seconds().max(0);
+        match secs {
+            0..=59 => format!("{}s", secs),
+            60..=3599 => format!("{}m", secs / 60),
+            3600..=86399 => format!("{}h", secs / 3600),
+            _ => format!("{}d", secs / 86400),
+        }
+    }
+}
+
+// One line with everything, for plain output
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} [{}] {}",
+            self.time.format("%Y-%m-%d %H:%M:%S"),
+            self.level.label(),
+            self.category.label(),
+            self.text
+        )
+    }
+}
+
+// Writes the whole log to a file, one entry per line, e.g. to attach it to a bug report
+pub fn export(log: &[LogEntry], path: &Path) -> Result<(), anyhow::Error> {
+    let mut contents = log
+        .iter()
+        .map(|entry| entry.to_string())
+        .collect::<Vec<String>>()
+        .join("\n");
+    contents.push('\n');
+
+    fs::write(path, contents).with_context(|| format!("writing log file {:?} failed", path))
+}
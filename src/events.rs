@@ -0,0 +1,410 @@
+use std::collections::VecDeque;
+
+use libp2p::PeerId;
+use uuid::Uuid;
+
+use crate::app::{App, ChatMessage, Delivery};
+use crate::conversation::ConversationId;
+use crate::crdt::TopicState;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::i18n::{tr, trf, Msg};
+use crate::nickcert::{self, NickCertificate};
+use crate::protocol::{Status, StatusCard};
+use crate::topickeys;
+use crate::update;
+use crate::utils;
+use crate::voice;
+use crate::writer;
+
+// What happened on the network, as far as the rest of the app cares. The connection emits these
+// instead of changing the app state itself, and `dispatch()` hands them to the parts that keep
+// the state: the conversations and their notifications, the roster of peers and the config
+#[derive(Debug, Clone)]
+pub enum AppEvent {
+    // A chat message from a peer, or a system message about a conversation
+    MessageReceived {
+        conversation_id: ConversationId,
+        message: ChatMessage,
+    },
+    PeerConnected {
+        peer_id: PeerId,
+        reconnected: bool,
+    },
+    PeerDisconnected {
+        peer_id: PeerId,
+    },
+    // Connecting to a peer failed, `error` already carries a hint how to fix it if there is one
+    DialFailed {
+        peer_id: Option<PeerId>,
+        error: String,
+    },
+    // A peer joined or left one of our topics
+    TopicSubscribed {
+        peer_id: PeerId,
+        topic: String,
+    },
+    TopicUnsubscribed {
+        peer_id: PeerId,
+        topic: String,
+    },
+    // Something about our own node, shown in the current conversation
+    Notice {
+        text: String,
+    },
+    DeliveryChanged {
+        conversation_id: ConversationId,
+        message_id: Uuid,
+        delivery: Delivery,
+    },
+    // A peer showed it got one of our messages, e.g. by replying to it
+    ReceiptConfirmed {
+        message_id: Uuid,
+        peer_id: PeerId,
+    },
+    PresenceReceived {
+        peer_id: PeerId,
+        nick: Option<String>,
+        status: Status,
+        card: Option<StatusCard>,
+    },
+    ReadMarkerReceived {
+        conversation_id: ConversationId,
+        up_to: Uuid,
+    },
+    ReactionReceived {
+        conversation_id: ConversationId,
+        message_id: Uuid,
+        reaction: String,
+        peer_id: PeerId,
+    },
+    VoteReceived {
+        conversation_id: ConversationId,
+        message_id: Uuid,
+        option: usize,
+        peer_id: PeerId,
+    },
+    DeleteReceived {
+        conversation_id: ConversationId,
+        message_id: Uuid,
+        peer_id: PeerId,
+    },
+    TopicStateReceived {
+        topic: String,
+        source: Option<PeerId>,
+        state: TopicState,
+    },
+    // Not checked yet whether `peer_id` may hand out keys
+    TopicKeyReceived {
+        topic: String,
+        epoch: u32,
+        key: String,
+        peer_id: PeerId,
+    },
+    // Not verified yet
+    NickCertificateReceived {
+        certificate: NickCertificate,
+        source: Option<PeerId>,
+    },
+    // The p2pchat version a peer told us about with identify
+    VersionAdvertised {
+        peer_id: PeerId,
+        version: String,
+    },
+    // The messages of a topic a peer sent us that we didn't have yet, and its state if it was
+    // valid
+    HistorySynced {
+        topic: String,
+        peer_id: PeerId,
+        messages: Vec<ChatMessage>,
+        state: Option<TopicState>,
+    },
+}
+
+// The events waiting to be dispatched, in the order they were emitted
+#[derive(Debug, Default)]
+pub struct EventQueue {
+    events: VecDeque<AppEvent>,
+}
+
+impl EventQueue {
+    pub fn emit(&mut self, event: AppEvent) {
+        self.events.push_back(event);
+    }
+
+    fn next(&mut self) -> Option<AppEvent> {
+        self.events.pop_front()
+    }
+}
+
+// Called by the event loop after each thing it handled, before drawing
+pub fn dispatch(app: &mut App) {
+    // The writer thread can't emit events itself
+    for (path, error) in writer::take_failures() {
+        app.connection.events.emit(AppEvent::Notice {
+            text: trf(Msg::SystemWriteFailed, &[&path.display(), &error]),
+        });
+    }
+    while let Some(event) = app.connection.events.next() {
+        handle_app_event(event, app);
+    }
+}
+
+fn handle_app_event(event: AppEvent, app: &mut App) {
+    match event {
+        AppEvent::MessageReceived {
+            conversation_id,
+            message,
+        } => {
+            if let (Some(source), Some(nick)) = (message.source_peer_id, message.nick.as_ref()) {
+                app.record_nick(source, nick);
+            }
+            app.receive_message(&conversation_id, message);
+        }
+        AppEvent::PeerConnected {
+            peer_id,
+            reconnected,
+        } => {
+            app.stats.peers_seen.insert(peer_id);
+            let msg = if reconnected {
+                Msg::SystemReconnected
+            } else {
+                Msg::SystemConnected
+            };
+            app.connection
+                .push_log(LogLevel::Info, LogCategory::Network, &trf(msg, &[&peer_id]));
+            app.push_system_message(Some(peer_id), trf(msg, &[&utils::short_peer_id(&peer_id)]));
+        }
+        AppEvent::PeerDisconnected { peer_id } => {
+            app.away_peers.remove(&peer_id);
+            app.status_cards.remove(&peer_id);
+            app.connection.push_log(
+                LogLevel::Warn,
+                LogCategory::Network,
+                &trf(Msg::SystemDisconnected, &[&peer_id]),
+            );
+            app.push_system_message(
+                Some(peer_id),
+                trf(Msg::SystemDisconnected, &[&utils::short_peer_id(&peer_id)]),
+            );
+        }
+        AppEvent::DialFailed { peer_id, error } => {
+            let peer = peer_id.map_or(tr(Msg::UnknownPeer).to_string(), |peer_id| {
+                utils::short_peer_id(&peer_id)
+            });
+            let text = trf(Msg::SystemConnectionFailed, &[&peer, &error]);
+            app.connection
+                .push_log(LogLevel::Error, LogCategory::Network, &text);
+            app.push_system_message(peer_id, text);
+        }
+        AppEvent::TopicSubscribed { peer_id, topic } => {
+            handle_membership(peer_id, topic, true, app);
+        }
+        AppEvent::TopicUnsubscribed { peer_id, topic } => {
+            handle_membership(peer_id, topic, false, app);
+        }
+        AppEvent::Notice { text } => app.push_system_message(None, text),
+        AppEvent::DeliveryChanged {
+            conversation_id,
+            message_id,
+            delivery,
+        } => app.set_delivery(&conversation_id, message_id, delivery),
+        AppEvent::ReceiptConfirmed {
+            message_id,
+            peer_id,
+        } => app.confirm_receipt(message_id, peer_id),
+        AppEvent::PresenceReceived {
+            peer_id,
+            nick,
+            status,
+            card,
+        } => {
+            if let Some(nick) = nick {
+                app.record_nick(peer_id, &nick);
+            }
+            match status {
+                Status::Away => app.away_peers.insert(peer_id),
+                Status::Online => app.away_peers.remove(&peer_id),
+            };
+            match card {
+                Some(card) => app.status_cards.insert(peer_id, card),
+                None => app.status_cards.remove(&peer_id),
+            };
+        }
+        AppEvent::ReadMarkerReceived {
+            conversation_id,
+            up_to,
+        } => {
+            app.conversation_mut(&conversation_id).read_marker_received = Some(up_to);
+        }
+        AppEvent::ReactionReceived {
+            conversation_id,
+            message_id,
+            reaction,
+            peer_id,
+        } => {
+            app.conversation_mut(&conversation_id)
+                .add_reaction(message_id, reaction, peer_id);
+            app.confirm_receipt(message_id, peer_id);
+        }
+        AppEvent::VoteReceived {
+            conversation_id,
+            message_id,
+            option,
+            peer_id,
+        } => {
+            if app
+                .conversation_mut(&conversation_id)
+                .add_vote(message_id, option, peer_id)
+            {
+                app.confirm_receipt(message_id, peer_id);
+            }
+        }
+        AppEvent::DeleteReceived {
+            conversation_id,
+            message_id,
+            peer_id,
+        } => {
+            if let Some(message) = app
+                .conversation_mut(&conversation_id)
+                .remove_message(message_id, &peer_id)
+            {
+                voice::remove(&[message], app);
+            }
+        }
+        AppEvent::TopicStateReceived {
+            topic,
+            source,
+            state,
+        } => {
+            // Without a verified source we couldn't tell who changed the state
+            if let Some(source) = source {
+                if app.merge_topic_state(&topic, source, state) {
+                    app.receive_message(
+                        &ConversationId::Topic(topic),
+                        ChatMessage::system(trf(
+                            Msg::SystemTopicStateChanged,
+                            &[&utils::short_peer_id(&source)],
+                        )),
+                    );
+                }
+            }
+        }
+        AppEvent::TopicKeyReceived {
+            topic,
+            epoch,
+            key,
+            peer_id,
+        } => handle_topic_key(topic, epoch, key, peer_id, app),
+        AppEvent::NickCertificateReceived {
+            certificate,
+            source,
+        } => nickcert::receive(certificate, source, app),
+        AppEvent::VersionAdvertised { peer_id, version } => {
+            update::observe(peer_id, &version, app);
+        }
+        AppEvent::HistorySynced {
+            topic,
+            peer_id,
+            mut messages,
+            state,
+        } => {
+            for message in messages.iter_mut() {
+                app.observe_clock(message);
+            }
+            let count = app.receive_synced(&ConversationId::Topic(topic.clone()), messages);
+            // After the messages, so a pin of a synced message can be checked against them
+            if let Some(state) = state {
+                app.merge_topic_state(&topic, peer_id, state);
+            }
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::Messages,
+                &trf(Msg::LogHistorySynced, &[&count, &topic, &peer_id]),
+            );
+        }
+    }
+}
+
+// Shows peers joining and leaving our topics in the history
+fn handle_membership(peer_id: PeerId, topic: String, joined: bool, app: &mut App) {
+    if app.moderation.is_blocked(&peer_id) {
+        return;
+    }
+    let (msg, text) = if joined {
+        (Msg::LogPeerSubscribed, tr(Msg::PeerJoined))
+    } else {
+        (Msg::LogPeerUnsubscribed, tr(Msg::PeerLeft))
+    };
+    app.connection.push_log(
+        LogLevel::Info,
+        LogCategory::Network,
+        &trf(msg, &[&peer_id, &topic]),
+    );
+    let notice = ChatMessage::membership(peer_id, text.to_string());
+    app.receive_message(&ConversationId::Topic(topic), notice);
+}
+
+// Only peers whose identity was compared with /verify may hand out keys, and only for topics we
+// are subscribed to. The key of an epoch never changes once we have it, the history stored with
+// it would be unreadable otherwise
+fn handle_topic_key(topic: String, epoch: u32, key: String, peer_id: PeerId, app: &mut App) {
+    if !app.config.is_verified(&peer_id) {
+        app.connection.push_log(
+            LogLevel::Warn,
+            LogCategory::Security,
+            &trf(Msg::LogTopicKeyIgnored, &[&topic, &peer_id]),
+        );
+        return;
+    }
+    if !app.connection.topics.is_subscribed(&topic) {
+        app.connection.push_log(
+            LogLevel::Warn,
+            LogCategory::Security,
+            &trf(Msg::LogTopicKeyNotSubscribed, &[&topic, &peer_id]),
+        );
+        return;
+    }
+    if let Err(e) = topickeys::validate(&key) {
+        app.connection.push_log(
+            LogLevel::Warn,
+            LogCategory::Security,
+            &trf(Msg::LogTopicKeyInvalid, &[&topic, &peer_id, &e]),
+        );
+        return;
+    }
+    // Verified peers aren't all trusted with a topic, only the one that gave us its first key
+    let settings = app.config.topic_settings_mut(&topic);
+    let issuer = settings
+        .key_issuer
+        .get_or_insert_with(|| peer_id.to_base58())
+        .clone();
+    if issuer != peer_id.to_base58() {
+        app.connection.push_log(
+            LogLevel::Warn,
+            LogCategory::Security,
+            &trf(Msg::LogTopicKeyNotIssuer, &[&topic, &peer_id, &issuer]),
+        );
+        return;
+    }
+    let stored = settings.keys.entry(epoch).or_insert_with(|| key.clone());
+    if *stored != key {
+        app.connection.push_log(
+            LogLevel::Warn,
+            LogCategory::Security,
+            &trf(Msg::LogTopicKeyEpochTaken, &[&epoch, &topic, &peer_id]),
+        );
+        return;
+    }
+    if let Err(e) = app.config.save() {
+        app.connection.push_log(
+            LogLevel::Error,
+            LogCategory::General,
+            &trf(Msg::LogSaveConfigFailed, &[&e]),
+        );
+    }
+    app.connection.push_log(
+        LogLevel::Info,
+        LogCategory::Security,
+        &trf(Msg::LogTopicKeyReceived, &[&topic, &epoch, &peer_id]),
+    );
+}
@@ -0,0 +1,70 @@
+use std::borrow::Cow;
+
+use anyhow::Context;
+use regex::Regex;
+
+use crate::config::{FilterAction, FilterRule};
+
+const REDACTED: &str = "[redacted]";
+
+// The filter rules of the config, compiled once
+#[derive(Debug, Default)]
+pub struct ContentFilter {
+    rules: Vec<(Regex, FilterAction)>,
+}
+
+// What the filter made of a message. Redacting happens when it's drawn, see `redact`
+#[derive(Debug, PartialEq, Eq)]
+pub enum Filtered {
+    // The pattern of the rule that hid it
+    Hidden { pattern: String },
+    Shown { highlight: bool },
+}
+
+impl ContentFilter {
+    pub fn new(rules: &[FilterRule]) -> Result<Self, anyhow::Error> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                Regex::new(&rule.pattern)
+                    .with_context(|| format!("invalid filter pattern `{}`", rule.pattern))
+                    .map(|regex| (regex, rule.action))
+            })
+            .collect::<Result<Vec<(Regex, FilterAction)>, anyhow::Error>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn apply(&self, text: &str) -> Filtered {
+        let mut text = text.to_string();
+        let mut highlight = false;
+
+        for (regex, action) in self.rules.iter() {
+            if !regex.is_match(&text) {
+                continue;
+            }
+            match action {
+                FilterAction::Hide => {
+                    return Filtered::Hidden {
+                        pattern: regex.as_str().to_string(),
+                    }
+                }
+                FilterAction::Redact => text = regex.replace_all(&text, REDACTED).into_owned(),
+                FilterAction::Highlight => highlight = true,
+            }
+        }
+
+        Filtered::Shown { highlight }
+    }
+
+    // The text as shown. The stored text stays as the author sent it, history sync serves it to
+    // other peers
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for (regex, action) in self.rules.iter() {
+            if *action == FilterAction::Redact && regex.is_match(&text) {
+                text = Cow::Owned(regex.replace_all(&text, REDACTED).into_owned());
+            }
+        }
+        text
+    }
+}
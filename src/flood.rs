@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use crate::app::App;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::i18n::{trf, Msg};
+
+// The timer still needs a period while not flooding
+const IDLE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// Load test mode started with `--flood <msgs/sec>`, sends numbered chat messages to the current
+// conversation at a fixed rate so the message pipeline can be measured with /stats
+#[derive(Debug)]
+pub struct Flood {
+    rate: u32,
+    sent: u64,
+}
+
+impl Flood {
+    pub fn new(rate: u32) -> Result<Self, anyhow::Error> {
+        anyhow::ensure!(
+            (1..=1000).contains(&rate),
+            "--flood must be between 1 and 1000 messages per second, got {}",
+            rate
+        );
+        Ok(Self { rate, sent: 0 })
+    }
+}
+
+// How often to send one message, for the event loop's timer
+pub fn interval(flood: Option<&Flood>) -> Duration {
+    flood.map_or(IDLE_INTERVAL, |flood| Duration::from_secs(1) / flood.rate)
+}
+
+pub fn start(app: &mut App) {
+    if let Some(flood) = app.flood.as_ref() {
+        let msg = trf(
+            Msg::LogFloodStarted,
+            &[&flood.rate, &app.ui.current_conversation],
+        );
+        app.connection
+            .push_log(LogLevel::Warn, LogCategory::Messages, &msg);
+    }
+}
+
+pub fn send(app: &mut App) {
+    let sent = match app.flood.as_mut() {
+        Some(flood) => {
+            flood.sent += 1;
+            flood.sent
+        }
+        None => return,
+    };
+    let id = app.ui.current_conversation.clone();
+    let text = format!("flood {} {}", sent, chrono::Local::now().timestamp_millis());
+    app.send_chat_message_to(id, text, None);
+}
@@ -0,0 +1,181 @@
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::app::App;
+use crate::conversation::ConversationId;
+use crate::profile;
+use crate::sync::SyncedMessage;
+use crate::vault::{self, DerivedKey, Sealed};
+use crate::writer;
+
+// How many stored messages of a conversation are shown at once, older ones are read when
+// scrolling past the top so long histories neither slow down drawing nor stay in memory
+pub const PAGE: usize = 200;
+
+// The stored history of a conversation is a directory with the messages that were shown when it
+// was saved in `head.json`, and the older ones in pages of `PAGE` messages, `page-000000.json`
+// being the oldest. Pages are written when a resumed head is longer than a page, and removed once
+// they were read back and the head holding their messages was saved
+fn dir(id: &ConversationId) -> Option<PathBuf> {
+    let name = match id {
+        ConversationId::Topic(topic) => {
+            format!("topic-{}", data_encoding::HEXLOWER.encode(topic.as_bytes()))
+        }
+        ConversationId::Direct(peer_id) => format!("direct-{}", peer_id.to_base58()),
+    };
+    profile::dir().map(|dir| dir.join("history").join(name))
+}
+
+fn head_path(dir: &Path) -> PathBuf {
+    dir.join("head.json")
+}
+
+fn page_path(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("page-{:06}.json", index))
+}
+
+// Sealed files are opened with the session key if they were sealed with it, otherwise with the
+// passphrase, e.g. after the session was encrypted anew
+fn read(
+    path: &Path,
+    key: Option<&DerivedKey>,
+    passphrase: Option<&str>,
+) -> Result<Vec<SyncedMessage>, anyhow::Error> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("reading history file {:?} failed", path))?;
+    let json = match serde_json::from_str::<Sealed>(&contents) {
+        Ok(sealed) => match key.filter(|key| key.sealed_with(&sealed)) {
+            Some(key) => key.open(&sealed)?,
+            None => vault::open(&sealed, passphrase.context("the history is encrypted")?)?,
+        },
+        Err(_) => contents.into_bytes(),
+    };
+    serde_json::from_slice(&json).with_context(|| format!("parsing history file {:?} failed", path))
+}
+
+// Serializing and sealing happen on the writer thread
+fn write(
+    path: &Path,
+    messages: Vec<SyncedMessage>,
+    key: Option<DerivedKey>,
+) -> Result<(), anyhow::Error> {
+    let contents = move || {
+        let json = serde_json::to_vec(&messages)?;
+        match key {
+            Some(key) => Ok(serde_json::to_vec(&key.seal(&json)?)?),
+            None => Ok(json),
+        }
+    };
+    // The history is only for us to read
+    writer::write_with(path, contents, true)
+        .with_context(|| format!("writing history file {:?} failed", path))
+}
+
+// What `resume` found of a conversation's stored history
+#[derive(Debug, Default)]
+pub struct Stored {
+    // At most a page
+    pub newest: Vec<SyncedMessage>,
+    // The pages older than `newest`
+    pub older: usize,
+    // The pages on disk, including the one `newest` was read from if the head was empty
+    pub pages: usize,
+}
+
+// Reads the newest stored messages of a conversation and splits what doesn't fit on a page off
+// into pages. `migrated` are the messages a session file held before the history got its own
+// files, they are older than the head. `key` seals the files if the session is encrypted, and
+// with `reseal` the existing pages are sealed again or stored in the clear to match it
+pub fn resume(
+    id: &ConversationId,
+    migrated: Vec<SyncedMessage>,
+    reseal: bool,
+    key: Option<&DerivedKey>,
+    app: &App,
+) -> Result<Stored, anyhow::Error> {
+    let dir = dir(id).context("no config directory available")?;
+    let (open_key, passphrase) = (app.session_key.as_ref(), app.session_passphrase.as_deref());
+    let mut pages = 0;
+    while page_path(&dir, pages).exists() {
+        pages += 1;
+    }
+    if reseal {
+        for index in 0..pages {
+            let path = page_path(&dir, index);
+            let messages = read(&path, open_key, passphrase)?;
+            write(&path, messages, key.cloned())?;
+        }
+    }
+
+    let head_path = head_path(&dir);
+    let mut newest = migrated;
+    if head_path.exists() {
+        newest.extend(read(&head_path, open_key, passphrase)?);
+    }
+    if newest.len() > PAGE {
+        let older = newest
+            .drain(..newest.len() - PAGE)
+            .collect::<Vec<SyncedMessage>>();
+        for chunk in older.chunks(PAGE) {
+            write(&page_path(&dir, pages), chunk.to_vec(), key.cloned())?;
+            pages += 1;
+        }
+        // The pages have to be on disk before the head stops holding their messages
+        writer::flush();
+        write(&head_path, newest.clone(), key.cloned())?;
+    }
+
+    if newest.is_empty() && pages > 0 {
+        let newest = read(&page_path(&dir, pages - 1), open_key, passphrase)?;
+        return Ok(Stored {
+            newest,
+            older: pages - 1,
+            pages,
+        });
+    }
+    Ok(Stored {
+        newest,
+        older: pages,
+        pages,
+    })
+}
+
+// A page of older messages, `index` 0 being the oldest
+pub fn read_page(
+    id: &ConversationId,
+    index: usize,
+    app: &App,
+) -> Result<Vec<SyncedMessage>, anyhow::Error> {
+    let dir = dir(id).context("no config directory available")?;
+    read(
+        &page_path(&dir, index),
+        app.session_key.as_ref(),
+        app.session_passphrase.as_deref(),
+    )
+}
+
+// Writes the messages of a conversation that are in memory as its head, and removes the pages
+// that were read into memory since it was resumed. No messages remove the head
+pub fn save(
+    id: &ConversationId,
+    messages: Vec<SyncedMessage>,
+    shown_pages: Range<usize>,
+    key: Option<&DerivedKey>,
+) -> Result<(), anyhow::Error> {
+    let dir = dir(id).context("no config directory available")?;
+    let head_path = head_path(&dir);
+    if messages.is_empty() {
+        writer::remove(&head_path)?;
+    } else {
+        write(&head_path, messages, key.cloned())?;
+    }
+    // The writer writes the files of a batch sorted by path, so the head holding their messages
+    // is written before the pages are removed
+    for index in shown_pages {
+        writer::remove(&page_path(&dir, index))?;
+    }
+    Ok(())
+}
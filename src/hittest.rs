@@ -0,0 +1,66 @@
+use tui::layout::Rect;
+
+use crate::conversation::ConversationId;
+use crate::ui::PageFocus;
+use crate::utils;
+
+// Something on screen that reacts to the mouse
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HitTarget {
+    Tab(PageFocus),
+    Conversation(ConversationId),
+    ChatHistory,
+    // Index into the history of the current conversation
+    ChatMessage(usize),
+    ChatInput,
+    ConnectionLog,
+    // Index into the connection log
+    LogEntry(usize),
+    PeerList,
+    // Index into the peer list of the Connection page
+    PeerEntry(usize),
+    RegenerateButton,
+    AddrInput,
+    // Index into the recent addresses shown under the address input
+    RecentAddr(usize),
+    NickInput,
+    // Index into the entries of the Status page
+    StatusEntry(usize),
+    Modal,
+    // Index into the options of the topmost select modal
+    ModalOption(usize),
+}
+
+// The areas registered while drawing the last frame, used to find what was clicked
+#[derive(Debug, Clone, Default)]
+pub struct HitAreas {
+    areas: Vec<(Rect, HitTarget)>,
+}
+
+impl HitAreas {
+    pub fn clear(&mut self) {
+        self.areas.clear();
+    }
+
+    // Areas registered later are drawn above earlier ones and take precedence
+    pub fn register(&mut self, rect: Rect, target: HitTarget) {
+        self.areas.push((rect, target));
+    }
+
+    // Where a target was drawn, the first area if there are several
+    pub fn area(&self, target: &HitTarget) -> Option<Rect> {
+        self.areas
+            .iter()
+            .find(|(_, registered)| registered == target)
+            .map(|(rect, _)| *rect)
+    }
+
+    // Coord: (column, row)
+    pub fn hit_test(&self, coord: (u16, u16)) -> Option<HitTarget> {
+        self.areas
+            .iter()
+            .rev()
+            .find(|(rect, _)| utils::coord_in_rect(coord, *rect))
+            .map(|(_, target)| target.clone())
+    }
+}
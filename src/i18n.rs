@@ -0,0 +1,1070 @@
+use std::fmt::{Display, Write};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Locale {
+    En = 0,
+    De,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self::En
+    }
+}
+
+// Set once from the config at startup
+static LOCALE: AtomicU8 = AtomicU8::new(Locale::En as u8);
+
+pub fn set_locale(locale: Locale) {
+    LOCALE.store(locale as u8, Ordering::Relaxed);
+}
+
+pub fn locale() -> Locale {
+    match LOCALE.load(Ordering::Relaxed) {
+        1 => Locale::De,
+        _ => Locale::En,
+    }
+}
+
+// Every string shown in the interface. Templates contain `{}` placeholders, filled by `trf()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Msg {
+    AppTitle,
+    AppTitleQuitPending,
+    TabChat,
+    TabConnection,
+    TabStatus,
+    HeaderUpdateAvailable,
+    StatusTitle,
+    AuditTitle,
+    StatusPeerId,
+    StatusFingerprint,
+    StatusUptime,
+    StatusVersion,
+    StatusTransports,
+    StatusPeers,
+    StatusListenAddress,
+    StatusListenerFailed,
+    StatusExternalAddress,
+    StatusTopic,
+    StatusPeerScore,
+    StatusPeerGraylisted,
+    StatusMessages,
+    StatusBytes,
+    StatusPeersSeen,
+    StatusLatency,
+    StatusBandwidth,
+    StatusConversationMessages,
+    StatsCounts,
+    LogCopied,
+    LogTranscriptFailed,
+    LogEntry,
+    LogPeerSubscribed,
+    LogPeerUnsubscribed,
+    LogExported,
+    LogCopiedMessages,
+    UnknownSource,
+    IncompatibleVersion,
+    NickImpostor,
+    SyncedUnverified,
+    DeletedMessage,
+    ReplyPrefix,
+    Seen,
+    Sending,
+    SendFailed,
+    HistoryTitle,
+    HistoryTitleOlder,
+    CommandHintTitle,
+    LeaderHintTitle,
+    LeaderConversation,
+    LeaderPeers,
+    LeaderChat,
+    LeaderConnection,
+    LeaderStatus,
+    LeaderSplitView,
+    LeaderAllView,
+    LeaderSwitchConversation,
+    LeaderNextConversation,
+    LeaderPreviousConversation,
+    InputTitle,
+    InputReplyingTitle,
+    ConversationsTitle,
+    ConnectionLogTitle,
+    PeerListTitle,
+    RegenerateButton,
+    ExternalAddressTitle,
+    WaitingForPortMapping,
+    PortMappingDisabled,
+    ListeningDisabled,
+    ConnectToMultiaddrTitle,
+    AddrHintInvalid,
+    AddrHintLeadingSlash,
+    AddrHintHostPort,
+    AddrHintInvalidPeerId,
+    AddrHintMissingPort,
+    AddrHintMissingTransport,
+    AddrHintMissingPeerId,
+    RecentAddrsTitle,
+    NicknameTitle,
+    QuitModalTitle,
+    QuitModalMessage,
+    SwitchConversationTitle,
+    RegenerateModalMessage,
+    MessageMenuTitle,
+    ReactModalTitle,
+    ReactModalMessage,
+    AttachmentImage,
+    AttachmentVideo,
+    AttachmentAudio,
+    AttachmentDocument,
+    AttachmentArchive,
+    ActionCopyText,
+    ActionCopySenderPeerId,
+    ActionReply,
+    ActionReact,
+    ActionOpenLink,
+    ActionDelete,
+    ActionRetry,
+    ActionReceipts,
+    ReceiptsTitle,
+    ReceiptsNone,
+    ActionBlockSender,
+    ActionVote,
+    PollHint,
+    ActionPlayVoiceMemo,
+    VoiceMemoText,
+    VoiceMemoCard,
+    LogVoiceRecording,
+    LogVoicePlaying,
+    LogVoiceMemoFailed,
+    ActionViewSnapshot,
+    SnapshotText,
+    SnapshotCard,
+    SnapshotTitle,
+    LogSnapshotPaneHidden,
+    LogSnapshotTooLarge,
+    ActionViewPatch,
+    ActionApplyPatch,
+    PatchText,
+    PatchCard,
+    PatchMoreFiles,
+    PatchTitle,
+    ApplyPatchModalTitle,
+    ApplyPatchModalMessage,
+    ActionPingPeer,
+    ActionRedialPeer,
+    ActionDisconnectPeer,
+    ActionBanPeer,
+    PingPeerModalMessage,
+    RedialPeerModalMessage,
+    DisconnectPeerModalMessage,
+    BanPeerModalMessage,
+    LogPatchApplied,
+    LogPatchFailed,
+    LogPatchSendFailed,
+    ClipHint,
+    LogClipSent,
+    LogClipCopied,
+    LogClipboardTopicSet,
+    MissedTitle,
+    MissedEntry,
+    DmEncrypted,
+    DmNotEncrypted,
+    LogDmKeyFailed,
+    LogDmEncrypted,
+    LogDmKeyInvalid,
+    LogOpenLinksDisabled,
+    LogBlockedPeer,
+    LogCommandFailed,
+    LogDialing,
+    LogDialFailed,
+    LogNoAddrToRedial,
+    LogPeerNotConnected,
+    LogPinged,
+    LogPingUnsupported,
+    LogPingFailed,
+    LogSubscribeFailed,
+    LogUnsubscribeFailed,
+    LogPeersShared,
+    LogSharePeersFailed,
+    LogExchangeDialsExceeded,
+    LogParseMultiaddrFailed,
+    LogSendFailed,
+    LogRegenerateFailed,
+    LogResubscribed,
+    LogSessionResumed,
+    LogUpdateAvailable,
+    LogInviteCopied,
+    LogPeerMuted,
+    LogMessageFiltered,
+    LogTransformFailed,
+    LogPurged,
+    LogPeerUnmuted,
+    LogPeerNoteSet,
+    LogPeerNoteRemoved,
+    LogPeerTagged,
+    LogScheduled,
+    LogDraftsRestored,
+    LogFloodStarted,
+    LogLoadHistoryFailed,
+    LogLoadOlderFailed,
+    LogRestoreDraftsFailed,
+    LogScheduledSent,
+    LogScheduleCancelled,
+    LogScheduleEmpty,
+    ScheduledShow,
+    HiddenMessages,
+    LogInviteAccepted,
+    LogAcceptInviteFailed,
+    LogResumeSessionFailed,
+    SessionUnlockTitle,
+    SessionPassphrase,
+    SessionNewPassphrase,
+    LogUnlockSessionFailed,
+    LogSessionLocked,
+    LogIncompatiblePeer,
+    LogListening,
+    LogListenAddrExpired,
+    LogListenerError,
+    LogListenerClosed,
+    LogListenerFailed,
+    LogIpv6ListenFailed,
+    LogIncomingConnectionFailed,
+    LogGotMessage,
+    LogSkippedMessage,
+    LogAckFailed,
+    LogDirectSendFailed,
+    LogPortMapped,
+    LogDroppedMessage,
+    LogReportValidationFailed,
+    ValidationBlocked,
+    ValidationMalformed,
+    ValidationClockAhead,
+    ValidationEmpty,
+    ValidationTooLong,
+    ValidationSpam,
+    ValidationNotAnnouncer,
+    LogPortMappingFailed,
+    PlainHelp,
+    OnboardingTitle,
+    OnboardingNick,
+    OnboardingIdentityPersistent,
+    OnboardingIdentityEphemeral,
+    OnboardingTopic,
+    OnboardingBootstrapPeer,
+    LogOnboardingStepFailed,
+    LogOnboardingDone,
+    LogSaveIdentityFailed,
+    PlainConversationChanged,
+    NickChanged,
+    LogStatusCardSet,
+    LogStatusCardCleared,
+    LogNickConflict,
+    WhoisShow,
+    WhoisNote,
+    WhoisQuality,
+    ProfilesShow,
+    LogHistorySynced,
+    LogHistorySyncFailed,
+    LogHistoryResponseFailed,
+    MembersTitle,
+    MemberAway,
+    MemberGraylisted,
+    LogPeerGraylisted,
+    LogPeerRecovered,
+    AllTimelineTitle,
+    InputToTitle,
+    InputReadOnlyTitle,
+    LogReadOnlyTopic,
+    ComposeIndicator,
+    ComposeIndicatorDirect,
+    ComposeIndicatorBusy,
+    LogNowAway,
+    LogNowOnline,
+    NoPeersBanner,
+    SystemTopicStateChanged,
+    TopicPinned,
+    TopicDisappearing,
+    SystemUnknownEpoch,
+    SystemUndecryptable,
+    LogDroppedSealed,
+    LogTopicKeyRotated,
+    LogTopicKeyReceived,
+    LogNickBound,
+    LogNickImpostor,
+    LogNickTooMany,
+    LogNickUnbound,
+    NickConflictTitle,
+    NickConflictConfirm,
+    LogNickCertificateInvalid,
+    LogNickCertificateForwarded,
+    LogAuditFailed,
+    AuditReasonMenu,
+    AuditReasonCommand,
+    AuditReasonFilter,
+    AuditReasonPeerBlocked,
+    AuditReasonPeerList,
+    LogNickCertificateFailed,
+    LogSaveNickBindingsFailed,
+    LogTopicKeyIgnored,
+    LogTopicKeyNotSubscribed,
+    LogTopicKeyInvalid,
+    LogTopicKeyEpochTaken,
+    LogTopicKeyNotIssuer,
+    LogSaveConfigFailed,
+    LogSaveRecentAddrsFailed,
+    VerifyTitle,
+    VerifyMessage,
+    LogPeerVerified,
+    LogPeerNotVerified,
+    KeyExportTitle,
+    KeyImportTitle,
+    KeyPassphrase,
+    PassphraseRepeat,
+    LogPassphrasesDiffer,
+    KeyImportConfirm,
+    LogKeyExported,
+    LogKeyExportFailed,
+    LogKeyImportFailed,
+    LogKeyImported,
+    IdentityLoadTitle,
+    IdentityNewTitle,
+    IdentityNewConfirm,
+    LogIdentityReplaced,
+    ProfileDefault,
+    PeerJoined,
+    UnknownPeer,
+    SystemConnected,
+    SystemReconnected,
+    SystemDisconnected,
+    SystemConnectionFailed,
+    SystemNotListening,
+    SystemWriteFailed,
+    SystemSessionRestored,
+    HintAddrInUse,
+    HintAddrNotAvailable,
+    HintPermissionDenied,
+    HintConnectionRefused,
+    HintTimedOut,
+    PeerLeft,
+    TopicSettingsShow,
+}
+
+pub fn tr(msg: Msg) -> &'static str {
+    match locale() {
+        Locale::En => en(msg),
+        Locale::De => de(msg),
+    }
+}
+
+// Translates a template and fills its `{}` placeholders in order
+pub fn trf(msg: Msg, args: &[&dyn Display]) -> String {
+    let template = tr(msg);
+    let mut args = args.iter();
+    let mut parts = template.split("{}");
+    // Formatting the arguments straight into the result saves a string per argument
+    let mut filled = String::with_capacity(template.len() + 16 * args.len());
+    filled.push_str(parts.next().unwrap_or_default());
+    for part in parts {
+        if let Some(arg) = args.next() {
+            let _ = write!(filled, "{}", arg);
+        }
+        filled.push_str(part);
+    }
+
+    filled
+}
+
+fn en(msg: Msg) -> &'static str {
+    match msg {
+        Msg::AppTitle => " p2pchat ",
+        Msg::AppTitleQuitPending => " p2pchat - press Ctrl+C again to quit ",
+        Msg::TabChat => "Chat",
+        Msg::TabConnection => "Connection",
+        Msg::TabStatus => "Status",
+        Msg::HeaderUpdateAvailable => "p2pchat {} is available",
+        Msg::StatusTitle => "Status - Enter copies the selected value",
+        Msg::AuditTitle => "Moderation audit",
+        Msg::StatusPeerId => "Peer id",
+        Msg::StatusFingerprint => "Key fingerprint",
+        Msg::StatusUptime => "Uptime",
+        Msg::StatusVersion => "Version",
+        Msg::StatusTransports => "Transports",
+        Msg::StatusPeers => "Connected peers",
+        Msg::StatusListenAddress => "Listening on",
+        Msg::StatusListenerFailed => "Listener failed",
+        Msg::StatusExternalAddress => "External address",
+        Msg::StatusTopic => "Topic",
+        Msg::StatusPeerScore => "Peer score",
+        Msg::StatusPeerGraylisted => "Graylisted peer",
+        Msg::StatusMessages => "Messages",
+        Msg::StatusBytes => "Bytes",
+        Msg::StatusPeersSeen => "Peers seen",
+        Msg::StatusLatency => "Average latency",
+        Msg::StatusBandwidth => "Bandwidth",
+        Msg::StatusConversationMessages => "Messages in",
+        Msg::StatsCounts => "{} sent, {} received",
+        Msg::LogCopied => "copied {} to the clipboard",
+        Msg::LogTranscriptFailed => "writing to the transcript failed with Err `{}`",
+        Msg::LogEntry => "the log entry",
+        Msg::LogPeerSubscribed => "{} joined {}",
+        Msg::LogPeerUnsubscribed => "{} left {}",
+        Msg::LogExported => "wrote the connection log to {}",
+        Msg::LogCopiedMessages => "copied {} messages to the clipboard",
+        Msg::UnknownSource => "unknown source",
+        Msg::IncompatibleVersion => "[incompatible version]",
+        Msg::NickImpostor => "[impostor, nick belongs to another key]",
+        Msg::SyncedUnverified => "[synced, unverified]",
+        Msg::DeletedMessage => "deleted message",
+        Msg::ReplyPrefix => "[re {}] ",
+        Msg::Seen => "  seen",
+        Msg::Sending => "  sending ..",
+        Msg::SendFailed => "  failed to send, press r to retry",
+        Msg::HistoryTitle => "History - {}",
+        Msg::HistoryTitleOlder => "History - {} ({} older pages stored, scroll up to load)",
+        Msg::CommandHintTitle => "Commands",
+        Msg::LeaderHintTitle => "Shortcuts - Esc cancels",
+        Msg::LeaderConversation => "Open conversation 1 to 9 of the sidebar",
+        Msg::LeaderPeers => "Show or hide the members",
+        Msg::LeaderChat => "Chat page",
+        Msg::LeaderConnection => "Connection page",
+        Msg::LeaderStatus => "Status page",
+        Msg::LeaderSplitView => "Toggle the split view",
+        Msg::LeaderAllView => "Toggle the view of all conversations",
+        Msg::LeaderSwitchConversation => "Switch conversation",
+        Msg::LeaderNextConversation => "Next conversation",
+        Msg::LeaderPreviousConversation => "Previous conversation",
+        Msg::InputTitle => "Input",
+        Msg::InputReplyingTitle => "Input - replying to {}",
+        Msg::ConversationsTitle => "Conversations",
+        Msg::ConnectionLogTitle => "Connection Log - j/k move, G newest, t relative times, c copy",
+        Msg::PeerListTitle => "Peers ({}) - Enter for actions",
+        Msg::RegenerateButton => "Regenerate Connection",
+        Msg::ExternalAddressTitle => "External Address",
+        Msg::WaitingForPortMapping => "waiting for port mapping ..",
+        Msg::PortMappingDisabled => "port mapping disabled",
+        Msg::ListeningDisabled => "outbound only, not listening for connections",
+        Msg::ConnectToMultiaddrTitle => "Connect to Multiaddress",
+        Msg::AddrHintInvalid => "not a multiaddr, e.g. /ip4/192.168.1.2/tcp/4001",
+        Msg::AddrHintLeadingSlash => "multiaddrs start with /, e.g. /ip4/192.168.1.2/tcp/4001",
+        Msg::AddrHintHostPort => "write host names as /dns/<host>/tcp/<port>",
+        Msg::AddrHintInvalidPeerId => "the peer id after /p2p/ is invalid",
+        Msg::AddrHintMissingPort => "add the port, e.g. /tcp/4001",
+        Msg::AddrHintMissingTransport => "add where the peer listens before /p2p/, e.g. /ip4/192.168.1.2/tcp/4001",
+        Msg::RecentAddrsTitle => "Recent Addresses (↑/↓, Enter dials)",
+        Msg::AddrHintMissingPeerId => "add /p2p/ with the id of {} to dial it as that peer, → completes it",
+        Msg::NicknameTitle => "Nickname",
+        Msg::QuitModalTitle => "Quit",
+        Msg::QuitModalMessage => "Quit p2pchat? (y/n)",
+        Msg::SwitchConversationTitle => "Switch Conversation",
+        Msg::RegenerateModalMessage => {
+            "This creates a new identity and drops all connections. Continue? (y/n)"
+        }
+        Msg::MessageMenuTitle => "Message",
+        Msg::ReactModalTitle => "React",
+        Msg::ReactModalMessage => "Reaction, e.g. an emoji:",
+        Msg::AttachmentImage => "image",
+        Msg::AttachmentVideo => "video",
+        Msg::AttachmentAudio => "audio",
+        Msg::AttachmentDocument => "document",
+        Msg::AttachmentArchive => "archive",
+        Msg::ActionCopyText => "Copy text",
+        Msg::ActionCopySenderPeerId => "Copy sender peer id",
+        Msg::ActionReply => "Reply",
+        Msg::ActionReact => "React",
+        Msg::ActionOpenLink => "Open link",
+        Msg::ActionDelete => "Delete",
+        Msg::ActionRetry => "Retry sending",
+        Msg::ActionReceipts => "Who got this",
+        Msg::ReceiptsTitle => "Received by {} of {}",
+        Msg::ReceiptsNone => "no peer known to have received it",
+        Msg::ActionBlockSender => "Block sender",
+        Msg::ActionVote => "Vote",
+        Msg::PollHint => "select it and press 1-{} to vote",
+        Msg::ActionPlayVoiceMemo => "Play voice memo",
+        Msg::VoiceMemoText => "🎤 Voice memo ({})",
+        Msg::VoiceMemoCard => "🎤 Voice memo {} - select it and press p to play",
+        Msg::LogVoiceRecording => "recording a voice memo with `{}`, stop it to send the memo",
+        Msg::LogVoicePlaying => "playing a voice memo of {}",
+        Msg::LogVoiceMemoFailed => "voice memo failed with Err `{}`",
+        Msg::ActionViewSnapshot => "View snapshot",
+        Msg::SnapshotText => "🖥 Terminal snapshot ({}x{})",
+        Msg::SnapshotCard => "🖥 Terminal snapshot {}x{} - select it and press v to view",
+        Msg::SnapshotTitle => "Terminal of {} ({}x{}) - arrows scroll, Esc closes",
+        Msg::LogSnapshotPaneHidden => "the pane to snapshot isn't shown, switch to it first",
+        Msg::LogSnapshotTooLarge => "the snapshot takes {} bytes, at most {} can be sent",
+        Msg::ActionViewPatch => "View patch",
+        Msg::ActionApplyPatch => "Apply with git am",
+        Msg::PatchText => "📎 Patch {} ({} commits)",
+        Msg::PatchCard => "📎 Patch {} with {} commits - open the message menu to view or apply it",
+        Msg::PatchMoreFiles => "{} and {} more",
+        Msg::PatchTitle => "Patch {} from {} - arrows scroll, Esc closes",
+        Msg::ApplyPatchModalTitle => "Apply patch",
+        Msg::ApplyPatchModalMessage => "Apply {} commits from {} to the git repository in the working directory with `git am`? They change {} (y/n)",
+        Msg::ActionPingPeer => "Ping now (p)",
+        Msg::ActionRedialPeer => "Re-dial (r)",
+        Msg::ActionDisconnectPeer => "Disconnect (d)",
+        Msg::ActionBanPeer => "Ban (b)",
+        Msg::PingPeerModalMessage => "Measure the round trip to {} now? (y/n)",
+        Msg::RedialPeerModalMessage => "Dial {} again at the last address it was reached at? (y/n)",
+        Msg::DisconnectPeerModalMessage => "Close the connections to {}? It may connect again (y/n)",
+        Msg::BanPeerModalMessage => "Block {}, disconnect it and hide its messages? /unblock undoes it (y/n)",
+        Msg::LogPatchApplied => "applied {} commits of {} with git am",
+        Msg::LogPatchFailed => "applying the patch failed with Err `{}`, the repository was left as it was",
+        Msg::LogPatchSendFailed => "sending the patch failed with Err `{}`",
+        Msg::ClipHint => "📋 clip - select it and press c to copy it",
+        Msg::LogClipSent => "sent the clipboard to {}",
+        Msg::LogClipCopied => "copied the clip of {} to the clipboard",
+        Msg::LogClipboardTopicSet => "/clip sends the clipboard to {} now",
+        Msg::MissedTitle => "While you were away",
+        Msg::MissedEntry => "{}: {} new messages since {}",
+        Msg::DmEncrypted => "🔒 end-to-end encrypted",
+        Msg::DmNotEncrypted => "🔓 not end-to-end encrypted, the peer hasn't sent its key yet",
+        Msg::LogDmKeyFailed => "sending our DM key failed with Err `{}`",
+        Msg::LogDmEncrypted => "DMs with {} are end-to-end encrypted now",
+        Msg::LogDmKeyInvalid => "ignored the DM key of {}: {}",
+        Msg::LogOpenLinksDisabled => "opening links is disabled in the config",
+        Msg::LogBlockedPeer => "blocked peer {}",
+        Msg::LogCommandFailed => "{} failed with Err `{}`",
+        Msg::LogDialing => "dialing: {}",
+        Msg::LogDialFailed => "dialing to addr {} failed with Err {}",
+        Msg::LogNoAddrToRedial => "no address known to dial {} again",
+        Msg::LogPeerNotConnected => "{} isn't connected, it can only be dialed again or banned",
+        Msg::LogPinged => "round trip to {}: {}ms",
+        Msg::LogPingUnsupported => "{} doesn't answer pings on request, its round trip is measured with the regular pings",
+        Msg::LogPingFailed => "pinging {} failed with Err `{}`",
+        Msg::LogSubscribeFailed => "subscribing to {} failed with Err {}",
+        Msg::LogUnsubscribeFailed => "unsubscribing from {} failed with Err {}",
+        Msg::LogPeersShared => "shared {} peer addresses",
+        Msg::LogExchangeDialsExceeded => "{} shared more peers than are dialed for now, ignoring the rest",
+        Msg::LogSharePeersFailed => "sharing peer addresses failed with Err `{}`",
+        Msg::LogParseMultiaddrFailed => "parsing input as MultiAddr failed with Err {}",
+        Msg::LogSendFailed => "sending message to {} failed with Err `{}`",
+        Msg::LogRegenerateFailed => "regenerate_swarm() failed with Err {}",
+        Msg::LogResubscribed => "Subscribed to our {} topics again",
+        Msg::LogSessionResumed => "Resumed the last session with {} topics and {} peer addresses",
+        Msg::LogUpdateAvailable => "Peers run p2pchat {}, which is newer than ours ({})",
+        Msg::LogResumeSessionFailed => "resuming the last session failed with Err `{}`",
+        Msg::SessionUnlockTitle => "Unlock session",
+        Msg::SessionPassphrase => "Passphrase of the encrypted session:",
+        Msg::SessionNewPassphrase => "New passphrase to encrypt the session with:",
+        Msg::LogUnlockSessionFailed => "unlocking the session failed with Err `{}`",
+        Msg::LogSessionLocked => "The session stays locked and won't be saved when quitting",
+        Msg::LogInviteCopied => "copied an invite to #{} to the clipboard",
+        Msg::LogPeerMuted => "Muted {}, their messages are hidden",
+        Msg::LogPeerUnmuted => "Unmuted {}",
+        Msg::LogPeerNoteSet => "Saved the note on {}",
+        Msg::LogPeerNoteRemoved => "Removed the note on {}",
+        Msg::LogPeerTagged => "{} is tagged [{}]",
+        Msg::LogDraftsRestored => "Restored {} unsent drafts",
+        Msg::LogFloodStarted => "Flood mode: sending {} messages per second to {}",
+        Msg::LogLoadHistoryFailed => "loading the stored history of {} failed with Err `{}`",
+        Msg::LogLoadOlderFailed => "loading older messages failed with Err `{}`",
+        Msg::LogRestoreDraftsFailed => "restoring the drafts failed with Err `{}`",
+        Msg::LogScheduled => "Scheduled message {} to {} for {}, cancel with /schedule cancel",
+        Msg::LogScheduledSent => "Sent scheduled message {} to {}",
+        Msg::LogScheduleCancelled => "Cancelled scheduled message {}",
+        Msg::LogScheduleEmpty => "No messages are scheduled",
+        Msg::ScheduledShow => "  {} at {} to {}: {}",
+        Msg::LogMessageFiltered => "hid a message in {} from {} matching a filter",
+        Msg::LogTransformFailed => "transforming a message failed with Err `{}`, it is left as it was",
+        Msg::LogPurged => "Deleted {} messages from {} conversations",
+        Msg::HiddenMessages => "{} hidden messages from muted peers",
+        Msg::LogInviteAccepted => "Joined #{} from an invite, dialing {} peers",
+        Msg::LogAcceptInviteFailed => "accepting the invite failed with Err `{}`",
+        Msg::LogIncompatiblePeer => {
+            "Warning: peer {} speaks protocol version {}, but we speak version {}. Some of its messages may not be shown"
+        }
+        Msg::LogListening => "Listening on {}",
+        Msg::LogListenAddrExpired => "Stopped listening on {}",
+        Msg::LogListenerError => "a listener failed with Err `{}`",
+        Msg::LogListenerClosed => "closed the listener on {}",
+        Msg::LogListenerFailed => "the listener on {} stopped with Err `{}`",
+        Msg::LogIpv6ListenFailed => "listening on {} failed with Err `{}`, continuing with IPv4 only",
+        Msg::LogIncomingConnectionFailed => "incoming connection from {} failed with Err `{}`",
+        Msg::LogGotMessage => "Got message: {} with id: {} from peer: {}",
+        Msg::LogSkippedMessage => "Skipped a message from peer {} which uses protocol version {}",
+        Msg::LogAckFailed => "acknowledging direct message from {} failed",
+        Msg::LogDirectSendFailed => "sending direct message to {} failed with Err {}",
+        Msg::LogPortMapped => "{} port mapping: {} -> {}",
+        Msg::LogDroppedMessage => "dropped message {} forwarded by {}: {}",
+        Msg::LogReportValidationFailed => {
+            "report_message_validation_result() for {} failed with Err `{}`"
+        }
+        Msg::ValidationBlocked => "the author is blocked",
+        Msg::ValidationMalformed => "malformed message",
+        Msg::ValidationClockAhead => "its clock is too far ahead of ours",
+        Msg::ValidationEmpty => "empty message",
+        Msg::ValidationTooLong => "message too long",
+        Msg::ValidationSpam => "the author sends too many messages",
+        Msg::ValidationNotAnnouncer => "only announcers may post to the topic",
+        Msg::LogPortMappingFailed => "port mapping for {} failed with Err {}",
+        Msg::PlainHelp => {
+            "Type a message and press Enter to send it. Commands: /join <topic>, /leave [topic], /dm <peer id>, /nick <name>, /quit"
+        }
+        Msg::PlainConversationChanged => "Now chatting in {}",
+        Msg::OnboardingTitle => "Welcome to p2pchat - setup {}/{}",
+        Msg::OnboardingNick => "Pick a nickname others see next to your messages (optional):",
+        Msg::OnboardingIdentityPersistent => "Keep my identity, so my peer id stays the same",
+        Msg::OnboardingIdentityEphemeral => "Use a new identity on every start",
+        Msg::OnboardingTopic => "The topic to join on startup:",
+        Msg::OnboardingBootstrapPeer => {
+            "The multiaddress of a peer to connect to on startup, e.g. a friend's (optional):"
+        }
+        Msg::LogOnboardingStepFailed => "setup step failed with Err `{}`",
+        Msg::LogOnboardingDone => "setup done, the config was written",
+        Msg::LogSaveIdentityFailed => "saving identity failed with Err `{}`",
+        Msg::NickChanged => "nickname set to {}",
+        Msg::LogStatusCardSet => "status set to {}",
+        Msg::LogStatusCardCleared => "status cleared",
+        Msg::LogNickConflict => "peer {} uses the nick {} of peer {} and is shown as {}",
+        Msg::ProfilesShow => "profiles: {}",
+        Msg::LogHistorySynced => "got {} earlier messages of {} from {}",
+        Msg::LogHistorySyncFailed => "asking for the history of {} from {} failed with Err `{}`",
+        Msg::LogHistoryResponseFailed => "sending the history to {} failed",
+        Msg::MembersTitle => "Members ({}) - F2",
+        Msg::MemberAway => "{} (away)",
+        Msg::MemberGraylisted => "{} (graylisted)",
+        Msg::LogPeerGraylisted => "{} was graylisted for misbehaving, its messages are ignored",
+        Msg::LogPeerRecovered => "{} is no longer graylisted",
+        Msg::AllTimelineTitle => "All conversations - F5",
+        Msg::InputToTitle => "Input - {}",
+        Msg::InputReadOnlyTitle => "Read only - only announcers may post to #{}, commands still work",
+        Msg::LogReadOnlyTopic => "#{} is an announcement topic, only its announcers may post to it",
+        Msg::ComposeIndicator => "{} / {} bytes, {} peers",
+        Msg::ComposeIndicatorDirect => "{} bytes, direct",
+        Msg::ComposeIndicatorBusy => "{} / {} bytes, network busy, {} waiting",
+        Msg::LogNowAway => "No input for a while, your status is now away",
+        Msg::LogNowOnline => "You are back, your status is online again",
+        Msg::NoPeersBanner => "No peers - messages won't be delivered",
+        Msg::SystemTopicStateChanged => "{} changed the topic description or pinned message",
+        Msg::TopicPinned => "pinned - {}: {}",
+        Msg::TopicDisappearing => "Messages you send here disappear {} after sending",
+        Msg::SystemUnknownEpoch => "encrypted message from {} with the unknown key epoch {}",
+        Msg::SystemUndecryptable => "encrypted message from {} could not be read: {}",
+        Msg::LogDroppedSealed => "dropped encrypted message in {} from {}: {}",
+        Msg::LogTopicKeyRotated => "rotated the key of {} to epoch {}, sent it to {} verified members",
+        Msg::LogTopicKeyReceived => "received the key of {} for epoch {} from {}",
+        Msg::LogNickBound => "bound the nick {} to {}",
+        Msg::LogNickImpostor => "{} claims the nick {}, which is bound to {}",
+        Msg::LogNickTooMany => "{} has {} nicks bound already, not binding {}",
+        Msg::LogNickUnbound => "the nick {} isn't bound to {} anymore",
+        Msg::NickConflictTitle => "Nick Conflict",
+        Msg::NickConflictConfirm => "{} claims the nick {} with a certificate from {}, but it is bound to {}. Bind it to the new peer instead? Only agree if you know the nick changed hands. (y/n)",
+        Msg::LogNickCertificateInvalid => "ignored the certificate for nick {} from {}, checking it failed with Err `{}`",
+        Msg::LogNickCertificateForwarded => "ignored the certificate for nick {} of {}, it wasn't sent by that peer",
+        Msg::LogAuditFailed => "writing to the audit log failed with Err `{}`",
+        Msg::AuditReasonMenu => "from the message menu",
+        Msg::AuditReasonCommand => "with /{}",
+        Msg::AuditReasonFilter => "message in {} matched the hide rule `{}`",
+        Msg::AuditReasonPeerBlocked => "the peer is blocked",
+        Msg::AuditReasonPeerList => "from the peer list",
+        Msg::LogNickCertificateFailed => "issuing the certificate for our nick failed with Err `{}`",
+        Msg::LogSaveNickBindingsFailed => "saving the nick bindings failed with Err `{}`",
+        Msg::LogTopicKeyIgnored => "ignored a key for {} from the unverified peer {}",
+        Msg::LogTopicKeyNotSubscribed => "ignored a key for {} from {}, we aren't subscribed to it",
+        Msg::LogTopicKeyInvalid => "ignored an invalid key for {} from {}: {}",
+        Msg::LogTopicKeyEpochTaken => "ignored another key for epoch {} of {} from {}",
+        Msg::LogTopicKeyNotIssuer => "ignored a key for {} from {}, only {} hands out its keys",
+        Msg::LogSaveConfigFailed => "saving the config failed with Err `{}`",
+        Msg::LogSaveRecentAddrsFailed => "saving the recent addresses failed with Err `{}`",
+        Msg::VerifyTitle => "Verify Peer",
+        Msg::VerifyMessage => "Ask {} which emoji they see, over a call or in person:\n\n{}\n\nDo they match? (y/n)",
+        Msg::LogPeerVerified => "verified peer {}",
+        Msg::LogPeerNotVerified => "peer {} is not verified",
+        Msg::KeyExportTitle => "Export Identity",
+        Msg::KeyImportTitle => "Import Identity",
+        Msg::KeyPassphrase => "Passphrase for the key file:",
+        Msg::PassphraseRepeat => "The same passphrase again:",
+        Msg::LogPassphrasesDiffer => "The passphrases don't match, try again",
+        Msg::KeyImportConfirm => "Replace our identity {} with {}? Peers see us as another peer after the next start. (y/n)",
+        Msg::LogKeyExported => "exported the identity to {}",
+        Msg::LogKeyExportFailed => "exporting the identity failed with Err `{}`",
+        Msg::LogKeyImportFailed => "importing the identity failed with Err `{}`",
+        Msg::LogKeyImported => "imported the identity {}, restart p2pchat to use it",
+        Msg::IdentityLoadTitle => "Load Identity",
+        Msg::IdentityNewTitle => "New Identity",
+        Msg::IdentityNewConfirm => "Replace our identity {} with a new one? Peers see us as another peer, and the old identity is gone for good unless it was saved with /key export. (y/n)",
+        Msg::LogIdentityReplaced => "Now using the identity {}",
+        Msg::ProfileDefault => "default",
+        Msg::WhoisShow => "{} is peer {}, listening on [{}]",
+        Msg::WhoisNote => "  tagged [{}], note: {}",
+        Msg::WhoisQuality => "  quality {}, round trip {}, {} failed dials, {} failed deliveries",
+        Msg::PeerJoined => "joined",
+        Msg::UnknownPeer => "unknown peer",
+        Msg::SystemConnected => "connected to {}",
+        Msg::SystemReconnected => "reconnected to {}",
+        Msg::SystemDisconnected => "disconnected from {}",
+        Msg::SystemConnectionFailed => "connecting to {} failed with Err {}",
+        Msg::SystemNotListening => {
+            "not listening on any address anymore, peers can't connect to us until the connection is regenerated"
+        }
+        Msg::SystemWriteFailed => "saving {} failed with Err `{}`",
+        Msg::SystemSessionRestored => {
+            "restored the previous session, p2pchat didn't quit properly last time"
+        }
+        Msg::HintAddrInUse => {
+            "the port is already in use, start with --listen <port> or set listen_port in the config (0 picks a free one)"
+        }
+        Msg::HintAddrNotAvailable => "the address doesn't belong to this machine",
+        Msg::HintPermissionDenied => "not permitted, ports below 1024 need extra privileges",
+        Msg::HintConnectionRefused => "nobody is listening there, check the address and that the peer is running",
+        Msg::HintTimedOut => "the peer didn't answer, it might be offline or behind a firewall",
+        Msg::PeerLeft => "left",
+        Msg::TopicSettingsShow => "#{}: muted {}, hide_join_leave {}, highlight [{}], bell {}, color {}, icon {}, announcers [{}], disappear {}",
+    }
+}
+
+fn de(msg: Msg) -> &'static str {
+    match msg {
+        Msg::AppTitle => " p2pchat ",
+        Msg::AppTitleQuitPending => " p2pchat - zum Beenden erneut Strg+C drücken ",
+        Msg::TabChat => "Chat",
+        Msg::TabConnection => "Verbindung",
+        Msg::TabStatus => "Status",
+        Msg::HeaderUpdateAvailable => "p2pchat {} ist verfügbar",
+        Msg::StatusTitle => "Status - Enter kopiert den ausgewählten Wert",
+        Msg::AuditTitle => "Moderationsprotokoll",
+        Msg::StatusPeerId => "Peer-ID",
+        Msg::StatusFingerprint => "Schlüssel-Fingerabdruck",
+        Msg::StatusUptime => "Laufzeit",
+        Msg::StatusVersion => "Version",
+        Msg::StatusTransports => "Transporte",
+        Msg::StatusPeers => "Verbundene Peers",
+        Msg::StatusListenAddress => "Lauscht auf",
+        Msg::StatusListenerFailed => "Listener fehlgeschlagen",
+        Msg::StatusExternalAddress => "Externe Adresse",
+        Msg::StatusTopic => "Thema",
+        Msg::StatusPeerScore => "Peer-Bewertung",
+        Msg::StatusPeerGraylisted => "Ignorierter Peer",
+        Msg::StatusMessages => "Nachrichten",
+        Msg::StatusBytes => "Bytes",
+        Msg::StatusPeersSeen => "Gesehene Peers",
+        Msg::StatusLatency => "Mittlere Latenz",
+        Msg::StatusBandwidth => "Bandbreite",
+        Msg::StatusConversationMessages => "Nachrichten in",
+        Msg::StatsCounts => "{} gesendet, {} empfangen",
+        Msg::LogCopied => "{} in die Zwischenablage kopiert",
+        Msg::LogTranscriptFailed => "Schreiben ins Protokoll fehlgeschlagen mit Fehler `{}`",
+        Msg::LogEntry => "den Protokolleintrag",
+        Msg::LogPeerSubscribed => "{} ist {} beigetreten",
+        Msg::LogPeerUnsubscribed => "{} hat {} verlassen",
+        Msg::LogExported => "Verbindungsprotokoll nach {} geschrieben",
+        Msg::LogCopiedMessages => "{} Nachrichten in die Zwischenablage kopiert",
+        Msg::UnknownSource => "unbekannte Quelle",
+        Msg::IncompatibleVersion => "[inkompatible Version]",
+        Msg::NickImpostor => "[Betrüger, Nick gehört zu einem anderen Schlüssel]",
+        Msg::SyncedUnverified => "[synchronisiert, unbestätigt]",
+        Msg::DeletedMessage => "gelöschte Nachricht",
+        Msg::ReplyPrefix => "[Antwort auf {}] ",
+        Msg::Seen => "  gesehen",
+        Msg::Sending => "  wird gesendet ..",
+        Msg::SendFailed => "  Senden fehlgeschlagen, r zum Wiederholen",
+        Msg::HistoryTitle => "Verlauf - {}",
+        Msg::HistoryTitleOlder => "Verlauf - {} ({} ältere Seiten gespeichert, nach oben scrollen zum Laden)",
+        Msg::CommandHintTitle => "Befehle",
+        Msg::LeaderHintTitle => "Tastenkürzel - Esc bricht ab",
+        Msg::LeaderConversation => "Unterhaltung 1 bis 9 der Seitenleiste öffnen",
+        Msg::LeaderPeers => "Mitglieder ein- oder ausblenden",
+        Msg::LeaderChat => "Chat-Seite",
+        Msg::LeaderConnection => "Verbindungsseite",
+        Msg::LeaderStatus => "Statusseite",
+        Msg::LeaderSplitView => "Geteilte Ansicht umschalten",
+        Msg::LeaderAllView => "Ansicht aller Unterhaltungen umschalten",
+        Msg::LeaderSwitchConversation => "Unterhaltung wechseln",
+        Msg::LeaderNextConversation => "Nächste Unterhaltung",
+        Msg::LeaderPreviousConversation => "Vorherige Unterhaltung",
+        Msg::InputTitle => "Eingabe",
+        Msg::InputReplyingTitle => "Eingabe - Antwort an {}",
+        Msg::ConversationsTitle => "Unterhaltungen",
+        Msg::ConnectionLogTitle => "Verbindungsprotokoll - j/k bewegen, G neuester, t relative Zeiten, c kopieren",
+        Msg::PeerListTitle => "Peers ({}) - Enter für Aktionen",
+        Msg::RegenerateButton => "Verbindung neu erstellen",
+        Msg::ExternalAddressTitle => "Externe Adresse",
+        Msg::WaitingForPortMapping => "warte auf Portweiterleitung ..",
+        Msg::PortMappingDisabled => "Portweiterleitung deaktiviert",
+        Msg::ListeningDisabled => "nur ausgehend, lauscht nicht auf Verbindungen",
+        Msg::ConnectToMultiaddrTitle => "Mit Multiadresse verbinden",
+        Msg::AddrHintInvalid => "keine Multiadresse, z.B. /ip4/192.168.1.2/tcp/4001",
+        Msg::AddrHintLeadingSlash => "Multiadressen beginnen mit /, z.B. /ip4/192.168.1.2/tcp/4001",
+        Msg::AddrHintHostPort => "Hostnamen als /dns/<Host>/tcp/<Port> schreiben",
+        Msg::AddrHintInvalidPeerId => "die Peer-ID nach /p2p/ ist ungültig",
+        Msg::AddrHintMissingPort => "den Port ergänzen, z.B. /tcp/4001",
+        Msg::AddrHintMissingTransport => "vor /p2p/ ergänzen, wo der Peer lauscht, z.B. /ip4/192.168.1.2/tcp/4001",
+        Msg::RecentAddrsTitle => "Letzte Adressen (↑/↓, Enter wählt an)",
+        Msg::AddrHintMissingPeerId => "/p2p/ mit der ID von {} ergänzen, um ihn als diesen Peer anzuwählen, → vervollständigt es",
+        Msg::NicknameTitle => "Spitzname",
+        Msg::QuitModalTitle => "Beenden",
+        Msg::QuitModalMessage => "p2pchat beenden? (y/n)",
+        Msg::SwitchConversationTitle => "Unterhaltung wechseln",
+        Msg::RegenerateModalMessage => {
+            "Das erstellt eine neue Identität und trennt alle Verbindungen. Fortfahren? (y/n)"
+        }
+        Msg::MessageMenuTitle => "Nachricht",
+        Msg::ReactModalTitle => "Reagieren",
+        Msg::ReactModalMessage => "Reaktion, z.B. ein Emoji:",
+        Msg::AttachmentImage => "Bild",
+        Msg::AttachmentVideo => "Video",
+        Msg::AttachmentAudio => "Audio",
+        Msg::AttachmentDocument => "Dokument",
+        Msg::AttachmentArchive => "Archiv",
+        Msg::ActionCopyText => "Text kopieren",
+        Msg::ActionCopySenderPeerId => "Peer-ID des Absenders kopieren",
+        Msg::ActionReply => "Antworten",
+        Msg::ActionReact => "Reagieren",
+        Msg::ActionOpenLink => "Link öffnen",
+        Msg::ActionDelete => "Löschen",
+        Msg::ActionRetry => "Erneut senden",
+        Msg::ActionReceipts => "Wer hat das erhalten",
+        Msg::ReceiptsTitle => "Erhalten von {} von {}",
+        Msg::ReceiptsNone => "kein Peer hat sie bekanntermaßen erhalten",
+        Msg::ActionBlockSender => "Absender blockieren",
+        Msg::ActionVote => "Abstimmen",
+        Msg::PollHint => "auswählen und 1-{} drücken, um abzustimmen",
+        Msg::ActionPlayVoiceMemo => "Sprachnachricht abspielen",
+        Msg::VoiceMemoText => "🎤 Sprachnachricht ({})",
+        Msg::VoiceMemoCard => "🎤 Sprachnachricht {} - auswählen und p drücken zum Abspielen",
+        Msg::LogVoiceRecording => "Nehme eine Sprachnachricht mit `{}` auf, beende die Aufnahme zum Senden",
+        Msg::LogVoicePlaying => "Spiele eine Sprachnachricht von {} ab",
+        Msg::LogVoiceMemoFailed => "Sprachnachricht fehlgeschlagen mit Fehler `{}`",
+        Msg::ActionViewSnapshot => "Schnappschuss ansehen",
+        Msg::SnapshotText => "🖥 Terminal-Schnappschuss ({}x{})",
+        Msg::SnapshotCard => "🖥 Terminal-Schnappschuss {}x{} - auswählen und v drücken zum Ansehen",
+        Msg::SnapshotTitle => "Terminal von {} ({}x{}) - Pfeiltasten scrollen, Esc schließt",
+        Msg::LogSnapshotPaneHidden => "der Bereich für den Schnappschuss wird nicht angezeigt, wechsle zuerst zu ihm",
+        Msg::LogSnapshotTooLarge => "der Schnappschuss ist {} Bytes groß, höchstens {} können gesendet werden",
+        Msg::ActionViewPatch => "Patch ansehen",
+        Msg::ActionApplyPatch => "Mit git am anwenden",
+        Msg::PatchText => "📎 Patch {} ({} Commits)",
+        Msg::PatchCard => "📎 Patch {} mit {} Commits - im Nachrichtenmenü ansehen oder anwenden",
+        Msg::PatchMoreFiles => "{} und {} weitere",
+        Msg::PatchTitle => "Patch {} von {} - Pfeiltasten scrollen, Esc schließt",
+        Msg::ApplyPatchModalTitle => "Patch anwenden",
+        Msg::ApplyPatchModalMessage => "{} Commits von {} mit `git am` auf das Git-Repository im Arbeitsverzeichnis anwenden? Sie ändern {} (y/n)",
+        Msg::ActionPingPeer => "Jetzt anpingen (p)",
+        Msg::ActionRedialPeer => "Erneut verbinden (r)",
+        Msg::ActionDisconnectPeer => "Trennen (d)",
+        Msg::ActionBanPeer => "Sperren (b)",
+        Msg::PingPeerModalMessage => "Die Umlaufzeit zu {} jetzt messen? (y/n)",
+        Msg::RedialPeerModalMessage => "{} erneut unter der zuletzt erreichten Adresse anwählen? (y/n)",
+        Msg::DisconnectPeerModalMessage => "Die Verbindungen zu {} schließen? Er kann sich wieder verbinden (y/n)",
+        Msg::BanPeerModalMessage => "{} blockieren, trennen und seine Nachrichten ausblenden? /unblock macht es rückgängig (y/n)",
+        Msg::LogPatchApplied => "{} Commits von {} mit git am angewendet",
+        Msg::LogPatchFailed => "Anwenden des Patches fehlgeschlagen mit Fehler `{}`, das Repository blieb unverändert",
+        Msg::LogPatchSendFailed => "Senden des Patches fehlgeschlagen mit Fehler `{}`",
+        Msg::ClipHint => "📋 Zwischenablage - auswählen und c drücken zum Kopieren",
+        Msg::LogClipSent => "Zwischenablage an {} gesendet",
+        Msg::LogClipCopied => "Zwischenablage von {} kopiert",
+        Msg::LogClipboardTopicSet => "/clip sendet die Zwischenablage jetzt an {}",
+        Msg::MissedTitle => "Während du weg warst",
+        Msg::MissedEntry => "{}: {} neue Nachrichten seit {}",
+        Msg::DmEncrypted => "🔒 Ende-zu-Ende-verschlüsselt",
+        Msg::DmNotEncrypted => "🔓 nicht Ende-zu-Ende-verschlüsselt, der Peer hat seinen Schlüssel noch nicht gesendet",
+        Msg::LogDmKeyFailed => "Senden unseres DM-Schlüssels fehlgeschlagen mit Fehler `{}`",
+        Msg::LogDmEncrypted => "DMs mit {} sind jetzt Ende-zu-Ende-verschlüsselt",
+        Msg::LogDmKeyInvalid => "DM-Schlüssel von {} ignoriert: {}",
+        Msg::LogOpenLinksDisabled => "das Öffnen von Links ist in der Konfiguration deaktiviert",
+        Msg::LogBlockedPeer => "Peer {} blockiert",
+        Msg::LogCommandFailed => "{} fehlgeschlagen mit Fehler `{}`",
+        Msg::LogDialing => "verbinde: {}",
+        Msg::LogDialFailed => "Verbinden mit Adresse {} fehlgeschlagen mit Fehler {}",
+        Msg::LogNoAddrToRedial => "keine Adresse bekannt, um {} erneut anzuwählen",
+        Msg::LogPeerNotConnected => "{} ist nicht verbunden und kann nur erneut angewählt oder gesperrt werden",
+        Msg::LogPinged => "Umlaufzeit zu {}: {}ms",
+        Msg::LogPingUnsupported => "{} beantwortet keine Pings auf Anfrage, seine Umlaufzeit wird mit den regulären Pings gemessen",
+        Msg::LogPingFailed => "Anpingen von {} fehlgeschlagen mit Fehler `{}`",
+        Msg::LogSubscribeFailed => "Abonnieren von {} fehlgeschlagen mit Fehler {}",
+        Msg::LogUnsubscribeFailed => "Abbestellen von {} fehlgeschlagen mit Fehler {}",
+        Msg::LogPeersShared => "{} Peer-Adressen geteilt",
+        Msg::LogExchangeDialsExceeded => "{} hat mehr Peers geteilt, als vorerst verbunden werden, der Rest wird ignoriert",
+        Msg::LogSharePeersFailed => "Teilen der Peer-Adressen fehlgeschlagen mit Fehler `{}`",
+        Msg::LogParseMultiaddrFailed => {
+            "Eingabe ist keine gültige Multiadresse, Fehler {}"
+        }
+        Msg::LogSendFailed => "Senden der Nachricht an {} fehlgeschlagen mit Fehler `{}`",
+        Msg::LogRegenerateFailed => "Neuerstellen der Verbindung fehlgeschlagen mit Fehler {}",
+        Msg::LogResubscribed => "Unsere {} Topics erneut abonniert",
+        Msg::LogSessionResumed => "Letzte Sitzung mit {} Topics und {} Peer-Adressen fortgesetzt",
+        Msg::LogUpdateAvailable => "Peers verwenden p2pchat {}, neuer als unsere Version ({})",
+        Msg::LogResumeSessionFailed => "Fortsetzen der letzten Sitzung fehlgeschlagen mit Fehler `{}`",
+        Msg::SessionUnlockTitle => "Sitzung entsperren",
+        Msg::SessionPassphrase => "Passphrase der verschlüsselten Sitzung:",
+        Msg::SessionNewPassphrase => "Neue Passphrase zum Verschlüsseln der Sitzung:",
+        Msg::LogUnlockSessionFailed => "Entsperren der Sitzung fehlgeschlagen mit Fehler `{}`",
+        Msg::LogSessionLocked => "Die Sitzung bleibt gesperrt und wird beim Beenden nicht gespeichert",
+        Msg::LogInviteCopied => "Einladung zu #{} in die Zwischenablage kopiert",
+        Msg::LogPeerMuted => "{} stummgeschaltet, seine Nachrichten werden ausgeblendet",
+        Msg::LogPeerUnmuted => "Stummschaltung von {} aufgehoben",
+        Msg::LogPeerNoteSet => "Notiz zu {} gespeichert",
+        Msg::LogPeerNoteRemoved => "Notiz zu {} entfernt",
+        Msg::LogPeerTagged => "{} ist markiert mit [{}]",
+        Msg::LogDraftsRestored => "{} ungesendete Entwürfe wiederhergestellt",
+        Msg::LogFloodStarted => "Flutmodus: sende {} Nachrichten pro Sekunde an {}",
+        Msg::LogLoadHistoryFailed => "Laden des gespeicherten Verlaufs von {} fehlgeschlagen mit Fehler `{}`",
+        Msg::LogLoadOlderFailed => "Laden älterer Nachrichten fehlgeschlagen mit Fehler `{}`",
+        Msg::LogRestoreDraftsFailed => "Wiederherstellen der Entwürfe fehlgeschlagen mit Fehler `{}`",
+        Msg::LogScheduled => "Nachricht {} an {} für {} geplant, abbrechen mit /schedule cancel",
+        Msg::LogScheduledSent => "Geplante Nachricht {} an {} gesendet",
+        Msg::LogScheduleCancelled => "Geplante Nachricht {} abgebrochen",
+        Msg::LogScheduleEmpty => "Keine Nachrichten geplant",
+        Msg::ScheduledShow => "  {} um {} an {}: {}",
+        Msg::LogMessageFiltered => "Nachricht in {} von {} wegen eines Filters ausgeblendet",
+        Msg::LogTransformFailed => "Umwandeln einer Nachricht fehlgeschlagen mit Fehler `{}`, sie bleibt unverändert",
+        Msg::LogPurged => "{} Nachrichten aus {} Unterhaltungen gelöscht",
+        Msg::HiddenMessages => "{} ausgeblendete Nachrichten von stummgeschalteten Peers",
+        Msg::LogInviteAccepted => "#{} über eine Einladung beigetreten, verbinde mit {} Peers",
+        Msg::LogAcceptInviteFailed => "Annehmen der Einladung fehlgeschlagen mit Fehler `{}`",
+        Msg::LogIncompatiblePeer => {
+            "Warnung: Peer {} spricht Protokollversion {}, wir sprechen Version {}. Einige seiner Nachrichten werden eventuell nicht angezeigt"
+        }
+        Msg::LogListening => "Lausche auf {}",
+        Msg::LogListenAddrExpired => "Lausche nicht mehr auf {}",
+        Msg::LogListenerError => "ein Listener ist fehlgeschlagen mit Fehler `{}`",
+        Msg::LogListenerClosed => "Listener auf {} geschlossen",
+        Msg::LogListenerFailed => "der Listener auf {} wurde beendet mit Fehler `{}`",
+        Msg::LogIpv6ListenFailed => "Lauschen auf {} fehlgeschlagen mit Fehler `{}`, es geht nur mit IPv4 weiter",
+        Msg::LogIncomingConnectionFailed => "eingehende Verbindung von {} fehlgeschlagen mit Fehler `{}`",
+        Msg::LogGotMessage => "Nachricht erhalten: {} mit ID: {} von Peer: {}",
+        Msg::LogSkippedMessage => {
+            "Nachricht von Peer {} übersprungen, der Protokollversion {} verwendet"
+        }
+        Msg::LogAckFailed => "Bestätigen der Direktnachricht von {} fehlgeschlagen",
+        Msg::LogDirectSendFailed => "Senden der Direktnachricht an {} fehlgeschlagen mit Fehler {}",
+        Msg::LogPortMapped => "{} Portweiterleitung: {} -> {}",
+        Msg::LogDroppedMessage => "Nachricht {} von {} verworfen: {}",
+        Msg::LogReportValidationFailed => {
+            "Melden des Prüfergebnisses für {} fehlgeschlagen mit Fehler `{}`"
+        }
+        Msg::ValidationBlocked => "der Autor ist blockiert",
+        Msg::ValidationMalformed => "ungültige Nachricht",
+        Msg::ValidationClockAhead => "ihre Uhr ist unserer zu weit voraus",
+        Msg::ValidationEmpty => "leere Nachricht",
+        Msg::ValidationTooLong => "Nachricht zu lang",
+        Msg::ValidationSpam => "der Autor sendet zu viele Nachrichten",
+        Msg::ValidationNotAnnouncer => "nur Ankündiger dürfen im Topic schreiben",
+        Msg::LogPortMappingFailed => "Portweiterleitung für {} fehlgeschlagen mit Fehler {}",
+        Msg::PlainHelp => {
+            "Nachricht eingeben und mit Enter senden. Befehle: /join <Thema>, /leave [Thema], /dm <Peer-ID>, /nick <Name>, /quit"
+        }
+        Msg::PlainConversationChanged => "Jetzt im Chat {}",
+        Msg::OnboardingTitle => "Willkommen bei p2pchat - Einrichtung {}/{}",
+        Msg::OnboardingNick => {
+            "Wähle einen Spitznamen, den andere neben deinen Nachrichten sehen (optional):"
+        }
+        Msg::OnboardingIdentityPersistent => {
+            "Identität behalten, damit meine Peer-ID gleich bleibt"
+        }
+        Msg::OnboardingIdentityEphemeral => "Bei jedem Start eine neue Identität verwenden",
+        Msg::OnboardingTopic => "Das Thema, dem beim Start beigetreten wird:",
+        Msg::OnboardingBootstrapPeer => {
+            "Die Multiadresse eines Peers, mit dem beim Start verbunden wird, z.B. von Freunden (optional):"
+        }
+        Msg::LogOnboardingStepFailed => "Einrichtungsschritt fehlgeschlagen mit Fehler `{}`",
+        Msg::LogOnboardingDone => "Einrichtung abgeschlossen, die Konfiguration wurde geschrieben",
+        Msg::LogSaveIdentityFailed => "Speichern der Identität fehlgeschlagen mit Fehler `{}`",
+        Msg::NickChanged => "Spitzname ist jetzt {}",
+        Msg::LogStatusCardSet => "Status auf {} gesetzt",
+        Msg::LogStatusCardCleared => "Status entfernt",
+        Msg::LogNickConflict => {
+            "Peer {} verwendet den Spitznamen {} von Peer {} und wird als {} angezeigt"
+        }
+        Msg::ProfilesShow => "Profile: {}",
+        Msg::LogHistorySynced => "{} frühere Nachrichten von {} von {} erhalten",
+        Msg::LogHistorySyncFailed => "Anfrage des Verlaufs von {} bei {} fehlgeschlagen mit Fehler `{}`",
+        Msg::LogHistoryResponseFailed => "Senden des Verlaufs an {} fehlgeschlagen",
+        Msg::MembersTitle => "Mitglieder ({}) - F2",
+        Msg::MemberAway => "{} (abwesend)",
+        Msg::MemberGraylisted => "{} (ignoriert)",
+        Msg::LogPeerGraylisted => "{} wird wegen Fehlverhaltens ignoriert, seine Nachrichten werden verworfen",
+        Msg::LogPeerRecovered => "{} wird nicht mehr ignoriert",
+        Msg::AllTimelineTitle => "Alle Unterhaltungen - F5",
+        Msg::InputToTitle => "Eingabe - {}",
+        Msg::InputReadOnlyTitle => "Nur lesen - nur Ankündiger dürfen in #{} schreiben, Befehle funktionieren weiterhin",
+        Msg::LogReadOnlyTopic => "#{} ist ein Ankündigungs-Topic, nur seine Ankündiger dürfen dort schreiben",
+        Msg::ComposeIndicator => "{} / {} Bytes, {} Peers",
+        Msg::ComposeIndicatorDirect => "{} Bytes, direkt",
+        Msg::ComposeIndicatorBusy => "{} / {} Bytes, Netzwerk ausgelastet, {} warten",
+        Msg::LogNowAway => "Eine Weile keine Eingabe, dein Status ist jetzt abwesend",
+        Msg::LogNowOnline => "Du bist zurück, dein Status ist wieder online",
+        Msg::NoPeersBanner => "Keine Peers - Nachrichten werden nicht zugestellt",
+        Msg::SystemTopicStateChanged => "{} hat die Beschreibung oder die angeheftete Nachricht geändert",
+        Msg::TopicPinned => "angeheftet - {}: {}",
+        Msg::TopicDisappearing => "Hier gesendete Nachrichten verschwinden {} nach dem Senden",
+        Msg::SystemUnknownEpoch => "verschlüsselte Nachricht von {} mit der unbekannten Schlüsselepoche {}",
+        Msg::SystemUndecryptable => "verschlüsselte Nachricht von {} konnte nicht gelesen werden: {}",
+        Msg::LogDroppedSealed => "verschlüsselte Nachricht in {} von {} verworfen: {}",
+        Msg::LogTopicKeyRotated => "Schlüssel von {} auf Epoche {} erneuert, an {} verifizierte Mitglieder gesendet",
+        Msg::LogTopicKeyReceived => "Schlüssel von {} für Epoche {} von {} erhalten",
+        Msg::LogNickBound => "Nick {} an {} gebunden",
+        Msg::LogNickImpostor => "{} beansprucht den Nick {}, der an {} gebunden ist",
+        Msg::LogNickTooMany => "an {} sind bereits {} Nicks gebunden, {} wird nicht gebunden",
+        Msg::LogNickUnbound => "der Nick {} ist nicht mehr an {} gebunden",
+        Msg::NickConflictTitle => "Nick-Konflikt",
+        Msg::NickConflictConfirm => "{} beansprucht den Nick {} mit einem Zertifikat vom {}, er ist aber an {} gebunden. Stattdessen an den neuen Peer binden? Nur zustimmen, wenn der Nick sicher den Besitzer gewechselt hat. (y/n)",
+        Msg::LogNickCertificateInvalid => "Zertifikat für Nick {} von {} ignoriert, Prüfung fehlgeschlagen mit Fehler `{}`",
+        Msg::LogNickCertificateForwarded => "Zertifikat für Nick {} von {} ignoriert, es wurde nicht von diesem Peer gesendet",
+        Msg::LogAuditFailed => "Schreiben ins Moderationsprotokoll fehlgeschlagen mit Fehler `{}`",
+        Msg::AuditReasonMenu => "über das Nachrichtenmenü",
+        Msg::AuditReasonCommand => "mit /{}",
+        Msg::AuditReasonFilter => "Nachricht in {} passte auf die Ausblenden-Regel `{}`",
+        Msg::AuditReasonPeerBlocked => "der Peer ist blockiert",
+        Msg::AuditReasonPeerList => "über die Peer-Liste",
+        Msg::LogNickCertificateFailed => "Ausstellen des Zertifikats für unseren Nick fehlgeschlagen mit Fehler `{}`",
+        Msg::LogSaveNickBindingsFailed => "Speichern der Nick-Bindungen fehlgeschlagen mit Fehler `{}`",
+        Msg::LogTopicKeyIgnored => "Schlüssel für {} vom nicht verifizierten Peer {} ignoriert",
+        Msg::LogTopicKeyNotSubscribed => "Schlüssel für {} von {} ignoriert, das Topic ist nicht abonniert",
+        Msg::LogTopicKeyInvalid => "Ungültigen Schlüssel für {} von {} ignoriert: {}",
+        Msg::LogTopicKeyEpochTaken => "Weiteren Schlüssel für Epoche {} von {} von {} ignoriert",
+        Msg::LogTopicKeyNotIssuer => "Schlüssel für {} von {} ignoriert, nur {} vergibt seine Schlüssel",
+        Msg::LogSaveConfigFailed => "Speichern der Konfiguration fehlgeschlagen mit Fehler `{}`",
+        Msg::LogSaveRecentAddrsFailed => "Speichern der letzten Adressen fehlgeschlagen mit Fehler `{}`",
+        Msg::VerifyTitle => "Peer verifizieren",
+        Msg::VerifyMessage => "Frag {} am Telefon oder persönlich, welche Emoji angezeigt werden:\n\n{}\n\nStimmen sie überein? (y/n)",
+        Msg::LogPeerVerified => "Peer {} verifiziert",
+        Msg::LogPeerNotVerified => "Peer {} ist nicht verifiziert",
+        Msg::KeyExportTitle => "Identität exportieren",
+        Msg::KeyImportTitle => "Identität importieren",
+        Msg::KeyPassphrase => "Passphrase für die Schlüsseldatei:",
+        Msg::PassphraseRepeat => "Dieselbe Passphrase noch einmal:",
+        Msg::LogPassphrasesDiffer => "Die Passphrasen stimmen nicht überein, bitte erneut versuchen",
+        Msg::KeyImportConfirm => "Unsere Identität {} durch {} ersetzen? Peers sehen uns nach dem nächsten Start als anderen Peer. (y/n)",
+        Msg::LogKeyExported => "Identität nach {} exportiert",
+        Msg::LogKeyExportFailed => "Exportieren der Identität fehlgeschlagen mit Fehler `{}`",
+        Msg::LogKeyImportFailed => "Importieren der Identität fehlgeschlagen mit Fehler `{}`",
+        Msg::LogKeyImported => "Identität {} importiert, p2pchat zum Verwenden neu starten",
+        Msg::IdentityLoadTitle => "Identität laden",
+        Msg::IdentityNewTitle => "Neue Identität",
+        Msg::IdentityNewConfirm => "Unsere Identität {} durch eine neue ersetzen? Peers sehen uns als anderen Peer, und die alte Identität ist endgültig verloren, wenn sie nicht mit /key export gesichert wurde. (y/n)",
+        Msg::LogIdentityReplaced => "Verwende jetzt die Identität {}",
+        Msg::ProfileDefault => "Standard",
+        Msg::WhoisShow => "{} ist Peer {}, lauscht auf [{}]",
+        Msg::WhoisNote => "  markiert mit [{}], Notiz: {}",
+        Msg::WhoisQuality => "  Qualität {}, Umlaufzeit {}, {} fehlgeschlagene Verbindungen, {} fehlgeschlagene Zustellungen",
+        Msg::PeerJoined => "ist beigetreten",
+        Msg::UnknownPeer => "unbekannter Peer",
+        Msg::SystemConnected => "verbunden mit {}",
+        Msg::SystemReconnected => "wieder verbunden mit {}",
+        Msg::SystemDisconnected => "Verbindung zu {} getrennt",
+        Msg::SystemConnectionFailed => "Verbinden mit {} fehlgeschlagen mit Fehler {}",
+        Msg::SystemNotListening => {
+            "lausche auf keiner Adresse mehr, Peers können sich erst nach dem Neugenerieren der Verbindung verbinden"
+        }
+        Msg::SystemWriteFailed => "Speichern von {} fehlgeschlagen mit Fehler `{}`",
+        Msg::SystemSessionRestored => {
+            "vorherige Sitzung wiederhergestellt, p2pchat wurde letztes Mal nicht richtig beendet"
+        }
+        Msg::HintAddrInUse => {
+            "der Port wird bereits verwendet, starte mit --listen <Port> oder setze listen_port in der Konfiguration (0 wählt einen freien)"
+        }
+        Msg::HintAddrNotAvailable => "die Adresse gehört nicht zu diesem Rechner",
+        Msg::HintPermissionDenied => "nicht erlaubt, Ports unter 1024 brauchen besondere Rechte",
+        Msg::HintConnectionRefused => "dort lauscht niemand, prüfe die Adresse und ob der Peer läuft",
+        Msg::HintTimedOut => "der Peer antwortet nicht, er ist vielleicht offline oder hinter einer Firewall",
+        Msg::PeerLeft => "hat verlassen",
+        Msg::TopicSettingsShow => "#{}: muted {}, hide_join_leave {}, highlight [{}], bell {}, color {}, icon {}, announcers [{}], disappear {}",
+    }
+}
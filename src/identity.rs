@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use libp2p::identity::{Keypair, PublicKey};
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::secrets;
+use crate::vault::{self, Sealed};
+use crate::writer;
+
+// Where the keypair was stored before it moved to the secrets, still read if it exists
+fn legacy_path() -> Option<PathBuf> {
+    Config::path().and_then(|path| path.parent().map(|dir| dir.join("identity.key")))
+}
+
+// The stored identity if we keep one, otherwise a fresh one
+pub fn load_or_generate(config: &Config) -> Result<Keypair, anyhow::Error> {
+    if !config.persistent_identity {
+        return Ok(Keypair::generate_ed25519());
+    }
+
+    if let Some(encoded) = secrets::get(secrets::IDENTITY)? {
+        let bytes = data_encoding::BASE64
+            .decode(encoded.as_bytes())
+            .context("decoding stored identity failed")?;
+        return Keypair::from_protobuf_encoding(&bytes).context("decoding stored identity failed");
+    }
+    match legacy_path() {
+        Some(path) if path.exists() => {
+            let bytes = fs::read(&path)
+                .with_context(|| format!("reading identity file {:?} failed", path))?;
+            let id_keys = Keypair::from_protobuf_encoding(&bytes)
+                .with_context(|| format!("decoding identity file {:?} failed", path))?;
+            save(&id_keys)?;
+            Ok(id_keys)
+        }
+        _ => {
+            let id_keys = Keypair::generate_ed25519();
+            save(&id_keys)?;
+            Ok(id_keys)
+        }
+    }
+}
+
+// Stores the keypair with the other secrets, removing the old identity file
+pub fn save(id_keys: &Keypair) -> Result<(), anyhow::Error> {
+    let bytes = id_keys
+        .to_protobuf_encoding()
+        .context("encoding identity failed")?;
+    secrets::set(secrets::IDENTITY, &data_encoding::BASE64.encode(&bytes))?;
+
+    if let Some(path) = legacy_path().filter(|path| path.exists()) {
+        fs::remove_file(&path)
+            .with_context(|| format!("removing identity file {:?} failed", path))?;
+    }
+    Ok(())
+}
+
+// A short hash of a public key for comparing identities, in groups of four hex digits
+pub fn fingerprint(public_key: &PublicKey) -> String {
+    let hash = Sha256::digest(&public_key.to_protobuf_encoding());
+    let hex = data_encoding::HEXUPPER.encode(&hash[..16]);
+
+    hex.as_bytes()
+        .chunks(4)
+        .map(|group| String::from_utf8_lossy(group).into_owned())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Writes the keypair to a file that can be imported on another machine
+pub fn export(id_keys: &Keypair, path: &Path, passphrase: &str) -> Result<(), anyhow::Error> {
+    let plaintext = id_keys
+        .to_protobuf_encoding()
+        .context("encoding identity failed")?;
+    let key_file = vault::seal(&plaintext, passphrase)?;
+
+    // Written to a file that is only readable by us and renamed over an old one
+    writer::write(path, serde_json::to_string_pretty(&key_file)?, true)
+        .with_context(|| format!("writing key file {:?} failed", path))?;
+    // A backup that isn't on disk yet must not be reported as exported
+    writer::flush();
+    anyhow::ensure!(
+        !writer::is_failing(path),
+        "writing key file {:?} failed",
+        path
+    );
+    Ok(())
+}
+
+// Reads a keypair written by `export()`
+pub fn import(path: &Path, passphrase: &str) -> Result<Keypair, anyhow::Error> {
+    let key_file_str =
+        fs::read_to_string(path).with_context(|| format!("reading key file {:?} failed", path))?;
+    let key_file: Sealed = serde_json::from_str(&key_file_str)
+        .with_context(|| format!("parsing key file {:?} failed", path))?;
+
+    let plaintext = vault::open(&key_file, passphrase)
+        .with_context(|| format!("opening key file {:?} failed", path))?;
+    Keypair::from_protobuf_encoding(&plaintext)
+        .with_context(|| format!("decoding key file {:?} failed", path))
+}
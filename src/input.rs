@@ -1,11 +1,39 @@
-use crossterm::event::{Event, KeyCode, KeyModifiers, MouseEventKind};
-use libp2p::gossipsub::IdentTopic;
-use libp2p::Multiaddr;
+use std::time::Instant;
 
-use crate::app::{App, ChatMessage};
-use crate::connection::Connection;
-use crate::ui::{ConnectionPageFocus, CycleFocus, PageFocus};
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use libp2p::identity::Keypair;
+use uuid::Uuid;
+
+use crate::addrinput;
+use crate::app::{App, Delivery};
+use crate::audit::{self, AuditAction};
+use crate::commands;
+use crate::config::LeaderAction;
+use crate::connection::ConnectionCommand;
+use crate::conversation::ConversationId;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::hittest::HitTarget;
+use crate::i18n::{tr, trf, Msg};
+use crate::invite;
+use crate::kdf;
+use crate::missed;
+use crate::modal::{MessageAction, Modal, ModalAction, ModalKind, ModalResult, PeerAction};
+use crate::nickcert;
+use crate::onboarding;
+use crate::patch;
+use crate::peerlist;
+use crate::recentaddrs;
+use crate::session;
+use crate::status;
+use crate::transport;
+use crate::ui::{ChatPageFocus, ConnectionPageFocus, CycleFocus, PageFocus};
 use crate::utils;
+use crate::voice;
+
+// Rows a page up or down scrolls in views
+const VIEW_PAGE: u16 = 10;
 
 pub enum InputTask {
     Continue,
@@ -13,15 +41,118 @@ pub enum InputTask {
 }
 
 pub fn handle_input_event(event: Event, app: &mut App) -> Result<InputTask, anyhow::Error> {
-    // Cycle through pages with tab
+    if let Event::Key(key_event) = event {
+        if let (KeyCode::Char('c'), KeyModifiers::CONTROL) = (key_event.code, key_event.modifiers) {
+            // Quit on the second Ctrl+C in a row
+            if app.ui.quit_pending() {
+                return Ok(InputTask::Quit);
+            }
+            app.ui.quit_requested = Some(Instant::now());
+
+            let quit_modal_open = app
+                .ui
+                .modals
+                .iter()
+                .any(|modal| matches!(modal.action, ModalAction::Quit));
+            if app.config.confirm_quit && !quit_modal_open {
+                app.ui.modals.push(Modal::confirm(
+                    tr(Msg::QuitModalTitle),
+                    tr(Msg::QuitModalMessage),
+                    ModalAction::Quit,
+                ));
+            }
+            return Ok(InputTask::Continue);
+        }
+    }
+
+    if let Event::Mouse(mouse_event) = event {
+        return handle_mouse_event(mouse_event, app);
+    }
+
+    // The current conversation counts as read again once the terminal is back in front
+    match event {
+        Event::FocusGained => {
+            app.ui.terminal_focused = true;
+            return Ok(InputTask::Continue);
+        }
+        Event::FocusLost => {
+            app.ui.terminal_focused = false;
+            return Ok(InputTask::Continue);
+        }
+        _ => (),
+    }
+
+    // An open modal takes all input
+    if !app.ui.modals.is_empty() {
+        return handle_input_event_modal(event, app);
+    }
+
+    if let Event::Key(key_event) = event {
+        if handle_leader_key(key_event, app) {
+            return Ok(InputTask::Continue);
+        }
+    }
+
+    // Cycle through pages with tab, Esc backs out of input fields
     match event {
         Event::Key(key_event) => match (key_event.code, key_event.modifiers) {
+            // With the split view shown this moves between its panes
             (KeyCode::Tab, KeyModifiers::NONE) => {
                 app.ui.page_focus = app.ui.page_focus.next();
             }
-            (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
-                // request closing the app
-                return Ok(InputTask::Quit);
+            (KeyCode::F(3), KeyModifiers::NONE) => {
+                app.ui.split_view = !app.ui.split_view;
+                return Ok(InputTask::Continue);
+            }
+            (KeyCode::F(5), KeyModifiers::NONE) => {
+                toggle_all_view(app);
+                return Ok(InputTask::Continue);
+            }
+            // Resize the panes of the chat page, the sizes are kept in the config
+            (
+                KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down,
+                KeyModifiers::CONTROL,
+            )
+            | (KeyCode::F(4), KeyModifiers::NONE) => {
+                let layout = &mut app.config.layout;
+                match key_event.code {
+                    KeyCode::Left => layout.resize_sidebar(-2),
+                    KeyCode::Right => layout.resize_sidebar(2),
+                    KeyCode::Up => layout.resize_input(1),
+                    KeyCode::Down => layout.resize_input(-1),
+                    _ => layout.sidebar_collapsed = !layout.sidebar_collapsed,
+                }
+                if let Err(e) = app.config.save() {
+                    app.connection.push_log(
+                        LogLevel::Error,
+                        LogCategory::General,
+                        &trf(Msg::LogSaveConfigFailed, &[&e]),
+                    );
+                }
+                return Ok(InputTask::Continue);
+            }
+            (KeyCode::Esc, _) => {
+                match app.ui.page_focus {
+                    // Cancel a reply before leaving the input
+                    PageFocus::Chat if app.ui.chat_history_anchor.is_some() => {
+                        app.ui.chat_history_anchor = None
+                    }
+                    PageFocus::Chat if app.ui.reply_to.is_some() => app.ui.reply_to = None,
+                    PageFocus::Chat => app.ui.chat_page_focus = ChatPageFocus::History,
+                    // Back to typing before leaving the input
+                    PageFocus::Connection if app.ui.addr_dropdown_selected.is_some() => {
+                        app.ui.addr_dropdown_selected = None
+                    }
+                    PageFocus::Connection => {
+                        app.ui.connection_page_focus = ConnectionPageFocus::ConnectionLog
+                    }
+                    PageFocus::Status => (),
+                }
+                return Ok(InputTask::Continue);
+            }
+            (KeyCode::Char('k'), KeyModifiers::CONTROL) => {
+                open_conversation_switcher(app);
+                return Ok(InputTask::Continue);
             }
             _ => (),
         },
@@ -29,66 +160,859 @@ pub fn handle_input_event(event: Event, app: &mut App) -> Result<InputTask, anyh
     }
 
     match app.ui.page_focus {
-        PageFocus::Chat => handle_input_event_chat_page(event, app)?,
-        PageFocus::Connection => handle_input_event_connection_page(event, app)?,
+        PageFocus::Chat => handle_input_event_chat_page(event, app),
+        PageFocus::Connection => {
+            handle_input_event_connection_page(event, app)?;
+            Ok(InputTask::Continue)
+        }
+        PageFocus::Status => {
+            handle_input_event_status_page(event, app)?;
+            Ok(InputTask::Continue)
+        }
+    }
+}
+
+// Finds what is under the mouse through the areas registered while drawing
+pub fn handle_mouse_event(
+    mouse_event: MouseEvent,
+    app: &mut App,
+) -> Result<InputTask, anyhow::Error> {
+    let target = match app
+        .ui
+        .hit_areas
+        .hit_test((mouse_event.column, mouse_event.row))
+    {
+        Some(target) => target,
+        None => return Ok(InputTask::Continue),
+    };
+    let clicked = mouse_event.kind == MouseEventKind::Down(MouseButton::Left);
+
+    match (mouse_event.kind, target) {
+        (_, HitTarget::ModalOption(i)) if clicked => {
+            if let Some(modal) = app.ui.modals.pop() {
+                return handle_modal_result(modal.action, ModalResult::Selected(i), app);
+            }
+        }
+        // An open modal blocks the page below
+        _ if !app.ui.modals.is_empty() => (),
+        (_, HitTarget::Tab(page)) if clicked => {
+            app.ui.page_focus = page;
+        }
+        (_, HitTarget::Conversation(id)) if clicked => {
+            app.open_conversation(id);
+        }
+        (_, HitTarget::ChatMessage(i)) if clicked => {
+            app.ui.page_focus = PageFocus::Chat;
+            app.ui.chat_page_focus = ChatPageFocus::History;
+            app.ui.chat_history_anchor = None;
+            app.ui.chat_history_selected = Some(i);
+        }
+        (_, HitTarget::ChatHistory) if clicked => {
+            app.ui.page_focus = PageFocus::Chat;
+            app.ui.chat_page_focus = ChatPageFocus::History;
+        }
+        (_, HitTarget::ChatInput) if clicked => {
+            app.ui.page_focus = PageFocus::Chat;
+            app.ui.chat_page_focus = ChatPageFocus::Input;
+        }
+        (_, HitTarget::LogEntry(i)) if clicked => {
+            app.ui.page_focus = PageFocus::Connection;
+            app.ui.connection_page_focus = ConnectionPageFocus::ConnectionLog;
+            app.ui.connection_log_liststate.select(Some(i));
+        }
+        (_, HitTarget::ConnectionLog) if clicked => {
+            app.ui.page_focus = PageFocus::Connection;
+            app.ui.connection_page_focus = ConnectionPageFocus::ConnectionLog;
+        }
+        (_, HitTarget::PeerEntry(i)) if clicked => {
+            app.ui.page_focus = PageFocus::Connection;
+            app.ui.connection_page_focus = ConnectionPageFocus::PeerList;
+            app.ui.peer_list_selected = i;
+        }
+        (_, HitTarget::PeerList) if clicked => {
+            app.ui.page_focus = PageFocus::Connection;
+            app.ui.connection_page_focus = ConnectionPageFocus::PeerList;
+        }
+        (_, HitTarget::RegenerateButton) if clicked => {
+            app.ui.page_focus = PageFocus::Connection;
+            app.ui.connection_page_focus = ConnectionPageFocus::RegenerateSwarm;
+            app.ui.modals.push(regenerate_swarm_modal());
+        }
+        (_, HitTarget::AddrInput) if clicked => {
+            app.ui.page_focus = PageFocus::Connection;
+            app.ui.connection_page_focus = ConnectionPageFocus::AddrInputField;
+        }
+        (_, HitTarget::RecentAddr(i)) if clicked => {
+            if let Some(addr) = recentaddrs::matching(app).into_iter().nth(i) {
+                recentaddrs::dial(app, addr);
+            }
+        }
+        (_, HitTarget::NickInput) if clicked => {
+            app.ui.page_focus = PageFocus::Connection;
+            app.ui.connection_page_focus = ConnectionPageFocus::NickInputField;
+        }
+        (_, HitTarget::StatusEntry(i)) if clicked => {
+            app.ui.status_selected = i;
+        }
+        (MouseEventKind::ScrollDown, HitTarget::ChatHistory | HitTarget::ChatMessage(_)) => {
+            app.chat_history_next();
+        }
+        (MouseEventKind::ScrollUp, HitTarget::ChatHistory | HitTarget::ChatMessage(_)) => {
+            app.chat_history_previous();
+        }
+        (MouseEventKind::ScrollDown, HitTarget::ConnectionLog | HitTarget::LogEntry(_)) => {
+            app.connection_log_next();
+        }
+        (MouseEventKind::ScrollUp, HitTarget::ConnectionLog | HitTarget::LogEntry(_)) => {
+            app.connection_log_previous();
+        }
+        _ => (),
     }
 
     Ok(InputTask::Continue)
 }
 
-pub fn handle_input_event_chat_page(event: Event, app: &mut App) -> Result<(), anyhow::Error> {
+// Opens the action menu for the selected message
+fn open_message_menu(app: &mut App) {
+    let local_peer_id = *app.connection.swarm.local_peer_id();
+    let conversation_id = app.ui.current_conversation.clone();
+    let message = match app.ui.chat_history_selected.and_then(|selected| {
+        app.current_conversation()
+            .and_then(|conversation| conversation.history.get(selected))
+    }) {
+        Some(message) => message,
+        None => return,
+    };
+
+    let mut actions = vec![
+        MessageAction::CopyText,
+        MessageAction::CopySenderPeerId,
+        MessageAction::Reply,
+        MessageAction::React,
+    ];
+    if app.config.open_links && app.ui.markup.first_url(&message.text).is_some() {
+        actions.push(MessageAction::OpenLink);
+    }
+    if message.voice_memo.is_some() {
+        actions.push(MessageAction::PlayVoiceMemo);
+    }
+    if message.snapshot.is_some() {
+        actions.push(MessageAction::ViewSnapshot);
+    }
+    if message.patch.is_some() {
+        actions.push(MessageAction::ViewPatch);
+        actions.push(MessageAction::ApplyPatch);
+    }
+    match message.source_peer_id {
+        Some(source_peer_id) if source_peer_id == local_peer_id => {
+            if message.delivery == Some(Delivery::Failed) {
+                actions.push(MessageAction::Retry);
+            }
+            if app.receipts.contains_key(&message.id) {
+                actions.push(MessageAction::Receipts);
+            }
+            actions.push(MessageAction::Delete);
+        }
+        Some(source_peer_id) => actions.push(MessageAction::BlockSender(source_peer_id)),
+        None => (),
+    }
+
+    let options = actions
+        .iter()
+        .map(|action| action.label().to_string())
+        .collect::<Vec<String>>();
+    let message_id = message.id;
+    app.ui.modals.push(Modal::select(
+        tr(Msg::MessageMenuTitle),
+        options,
+        ModalAction::MessageMenu {
+            conversation_id,
+            message_id,
+            actions,
+        },
+    ));
+}
+
+fn handle_message_action(
+    conversation_id: ConversationId,
+    message_id: Uuid,
+    action: MessageAction,
+    app: &mut App,
+) -> Result<(), anyhow::Error> {
+    let message = match app
+        .conversations
+        .get(&conversation_id)
+        .and_then(|conversation| conversation.message(message_id))
+    {
+        Some(message) => message.clone(),
+        None => return Ok(()),
+    };
+
+    match action {
+        MessageAction::CopyText => {
+            if message.clip {
+                app.connection.push_log(
+                    LogLevel::Info,
+                    LogCategory::General,
+                    &trf(Msg::LogClipCopied, &[&app.nicks.author(&message)]),
+                );
+            }
+            utils::copy_to_clipboard(message.text)?;
+        }
+        MessageAction::CopySenderPeerId => {
+            if let Some(source_peer_id) = message.source_peer_id {
+                utils::copy_to_clipboard(source_peer_id.to_string())?;
+            }
+        }
+        MessageAction::Reply => {
+            app.ui.reply_to = Some(message_id);
+            app.ui.chat_page_focus = ChatPageFocus::Input;
+        }
+        MessageAction::React => {
+            app.ui.modals.push(Modal::prompt(
+                tr(Msg::ReactModalTitle),
+                tr(Msg::ReactModalMessage),
+                ModalAction::React {
+                    conversation_id,
+                    message_id,
+                },
+            ));
+        }
+        MessageAction::OpenLink => {
+            if !app.config.open_links {
+                app.connection.push_log(
+                    LogLevel::Warn,
+                    LogCategory::General,
+                    tr(Msg::LogOpenLinksDisabled),
+                );
+            } else if let Some(url) = app.ui.markup.first_url(&message.text) {
+                utils::open_link(&url);
+            }
+        }
+        MessageAction::Delete => {
+            app.delete_message(&conversation_id, message_id);
+        }
+        MessageAction::Retry => {
+            app.retry_message(&conversation_id, message_id);
+        }
+        MessageAction::Receipts => {
+            if let Some(receipts) = app.receipts.get(&message_id) {
+                let title = trf(
+                    Msg::ReceiptsTitle,
+                    &[&receipts.confirmed.len(), &receipts.sent_to.len()],
+                );
+                let mut lines = receipts.lines(app);
+                if lines.is_empty() {
+                    lines.push(tr(Msg::ReceiptsNone).to_string());
+                }
+                app.ui
+                    .modals
+                    .push(Modal::select(&title, lines, ModalAction::Receipts));
+            }
+        }
+        MessageAction::Vote(option) => {
+            app.vote(&conversation_id, message_id, option);
+        }
+        MessageAction::PlayVoiceMemo => {
+            voice::play(&message, app)?;
+        }
+        MessageAction::ViewSnapshot => {
+            if let Some(snapshot) = message.snapshot.as_ref() {
+                let title = trf(
+                    Msg::SnapshotTitle,
+                    &[
+                        &app.nicks.author(&message),
+                        &snapshot.width,
+                        &snapshot.height,
+                    ],
+                );
+                app.ui
+                    .modals
+                    .push(Modal::view(&title, snapshot.lines(), ModalAction::Snapshot));
+            }
+        }
+        MessageAction::ViewPatch => {
+            if let Some(patch) = message.patch.as_ref() {
+                let title = trf(
+                    Msg::PatchTitle,
+                    &[&patch.range, &app.nicks.author(&message)],
+                );
+                app.ui
+                    .modals
+                    .push(Modal::view(&title, patch.lines(), ModalAction::Snapshot));
+            }
+        }
+        MessageAction::ApplyPatch => {
+            if let Some(patch) = message.patch.as_ref() {
+                // The sender's word on what the patch holds isn't taken
+                app.ui.modals.push(Modal::confirm(
+                    tr(Msg::ApplyPatchModalTitle),
+                    &trf(
+                        Msg::ApplyPatchModalMessage,
+                        &[
+                            &patch.count_commits(),
+                            &app.nicks.author(&message),
+                            &patch.changed_files_summary(),
+                        ],
+                    ),
+                    ModalAction::ApplyPatch {
+                        conversation_id,
+                        message_id,
+                    },
+                ));
+            }
+        }
+        MessageAction::BlockSender(peer_id) => {
+            app.block_peer(peer_id);
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::Messages,
+                &trf(Msg::LogBlockedPeer, &[&peer_id]),
+            );
+            audit::record(
+                app,
+                AuditAction::Blocked,
+                Some(peer_id),
+                tr(Msg::AuditReasonMenu),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// Shortcut for an action of the message menu on the selected message
+fn handle_selected_message_action(
+    action: MessageAction,
+    app: &mut App,
+) -> Result<(), anyhow::Error> {
+    let message_id = app.ui.chat_history_selected.and_then(|selected| {
+        app.current_conversation()
+            .and_then(|conversation| conversation.history.get(selected))
+            .map(|message| message.id)
+    });
+    if let Some(message_id) = message_id {
+        let conversation_id = app.ui.current_conversation.clone();
+        handle_message_action(conversation_id, message_id, action, app)?;
+    }
+
+    Ok(())
+}
+
+fn regenerate_swarm_modal() -> Modal {
+    Modal::confirm(
+        tr(Msg::RegenerateButton),
+        tr(Msg::RegenerateModalMessage),
+        ModalAction::RegenerateSwarm,
+    )
+}
+
+fn toggle_all_view(app: &mut App) {
+    app.ui.all_view = !app.ui.all_view;
+    // The timeline has no selection, typing goes to the current conversation
+    if app.ui.all_view {
+        app.ui.chat_page_focus = ChatPageFocus::Input;
+        app.ui.chat_history_selected = None;
+        app.ui.chat_history_anchor = None;
+    }
+}
+
+// Quickly switch to another conversation
+fn open_conversation_switcher(app: &mut App) {
+    let conversation_ids = app.sorted_conversation_ids();
+    let options = conversation_ids
+        .iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<String>>();
+    app.ui.modals.push(Modal::select(
+        tr(Msg::SwitchConversationTitle),
+        options,
+        ModalAction::SwitchConversation(conversation_ids),
+    ));
+}
+
+// Whether keys go into a text input, where the leader key is typed as is
+fn is_typing(app: &App) -> bool {
+    match app.ui.page_focus {
+        PageFocus::Chat => app.ui.chat_page_focus == ChatPageFocus::Input,
+        PageFocus::Connection => matches!(
+            app.ui.connection_page_focus,
+            ConnectionPageFocus::AddrInputField | ConnectionPageFocus::NickInputField
+        ),
+        PageFocus::Status => false,
+    }
+}
+
+// Returns whether the key was taken as the leader key or the key following it
+fn handle_leader_key(key_event: KeyEvent, app: &mut App) -> bool {
+    let leader = match app.config.leader.key {
+        Some(leader) if !is_typing(app) => leader,
+        _ => {
+            app.ui.leader_pending = false;
+            return false;
+        }
+    };
+    let key = match (key_event.code, key_event.modifiers) {
+        (KeyCode::Char(key), KeyModifiers::NONE | KeyModifiers::SHIFT) => Some(key),
+        _ => None,
+    };
+    if !app.ui.leader_pending {
+        app.ui.leader_pending = key == Some(leader);
+        return app.ui.leader_pending;
+    }
+
+    // Any other key, e.g. Esc, cancels
+    app.ui.leader_pending = false;
+    match key {
+        Some(digit @ '1'..='9') => {
+            let position = usize::from(digit as u8 - b'1');
+            if let Some(id) = app.sorted_conversation_ids().get(position) {
+                app.open_conversation(id.clone());
+                app.ui.page_focus = PageFocus::Chat;
+            }
+        }
+        Some(key) => {
+            if let Some(action) = app.config.leader.bindings.get(&key).copied() {
+                run_leader_action(action, app);
+            }
+        }
+        None => (),
+    }
+    true
+}
+
+fn run_leader_action(action: LeaderAction, app: &mut App) {
+    match action {
+        LeaderAction::Peers => app.ui.show_members = !app.ui.show_members,
+        LeaderAction::Chat => app.ui.page_focus = PageFocus::Chat,
+        LeaderAction::Connection => app.ui.page_focus = PageFocus::Connection,
+        LeaderAction::Status => app.ui.page_focus = PageFocus::Status,
+        LeaderAction::SplitView => app.ui.split_view = !app.ui.split_view,
+        LeaderAction::AllView => toggle_all_view(app),
+        LeaderAction::SwitchConversation => open_conversation_switcher(app),
+        LeaderAction::NextConversation => app.cycle_conversation(true),
+        LeaderAction::PreviousConversation => app.cycle_conversation(false),
+    }
+}
+
+pub fn handle_input_event_modal(event: Event, app: &mut App) -> Result<InputTask, anyhow::Error> {
+    let key_event = match event {
+        Event::Key(key_event) => key_event,
+        _ => return Ok(InputTask::Continue),
+    };
+    let modal = match app.ui.modals.last_mut() {
+        Some(modal) => modal,
+        None => return Ok(InputTask::Continue),
+    };
+
+    let result = match (&mut modal.kind, key_event.code, key_event.modifiers) {
+        (_, KeyCode::Esc, _) => Some(ModalResult::Cancelled),
+        (ModalKind::Confirm, KeyCode::Enter | KeyCode::Char('y'), _) => {
+            Some(ModalResult::Confirmed)
+        }
+        (ModalKind::Confirm, KeyCode::Char('n'), _) => Some(ModalResult::Cancelled),
+        (ModalKind::Prompt { input } | ModalKind::Secret { input }, KeyCode::Enter, _) => {
+            Some(ModalResult::Submitted(input.take()))
+        }
+        (ModalKind::Prompt { input } | ModalKind::Secret { input }, _, _) => {
+            input.handle_key(key_event);
+            None
+        }
+        (ModalKind::Select { selected, .. }, KeyCode::Up, _) => {
+            *selected = selected.saturating_sub(1);
+            None
+        }
+        (ModalKind::Select { options, selected }, KeyCode::Down, _) => {
+            if *selected + 1 < options.len() {
+                *selected += 1;
+            }
+            None
+        }
+        (ModalKind::View { .. }, KeyCode::Enter, _) => Some(ModalResult::Cancelled),
+        (ModalKind::View { lines, scroll }, code, _) => {
+            let (rows, columns) = scroll;
+            let last_row = lines.len().saturating_sub(1).min(u16::MAX as usize) as u16;
+            match code {
+                KeyCode::Up => *rows = rows.saturating_sub(1),
+                KeyCode::Down => *rows = (*rows + 1).min(last_row),
+                KeyCode::PageUp => *rows = rows.saturating_sub(VIEW_PAGE),
+                KeyCode::PageDown => *rows = rows.saturating_add(VIEW_PAGE).min(last_row),
+                KeyCode::Left => *columns = columns.saturating_sub(1),
+                KeyCode::Right => *columns = columns.saturating_add(1),
+                KeyCode::Home => *scroll = (0, 0),
+                _ => (),
+            }
+            None
+        }
+        (ModalKind::Select { options, selected }, KeyCode::Enter, _) => {
+            if *selected < options.len() {
+                Some(ModalResult::Selected(*selected))
+            } else {
+                Some(ModalResult::Cancelled)
+            }
+        }
+        _ => None,
+    };
+
+    if let Some(result) = result {
+        if let Some(modal) = app.ui.modals.pop() {
+            return handle_modal_result(modal.action, result, app);
+        }
+    }
+
+    Ok(InputTask::Continue)
+}
+
+pub fn handle_modal_result(
+    action: ModalAction,
+    result: ModalResult,
+    app: &mut App,
+) -> Result<InputTask, anyhow::Error> {
+    match (action, result) {
+        (ModalAction::Quit, ModalResult::Confirmed) => {
+            app.connection.outbox.push(ConnectionCommand::Shutdown);
+        }
+        (ModalAction::Quit, _) => {
+            app.ui.quit_requested = None;
+        }
+        (ModalAction::RegenerateSwarm, ModalResult::Confirmed) => {
+            app.connection.regenerate_swarm(&app.config);
+        }
+        (
+            ModalAction::MessageMenu {
+                conversation_id,
+                message_id,
+                actions,
+            },
+            ModalResult::Selected(i),
+        ) => {
+            if let Some(action) = actions.get(i) {
+                handle_message_action(conversation_id, message_id, *action, app)?;
+            }
+        }
+        (
+            ModalAction::React {
+                conversation_id,
+                message_id,
+            },
+            ModalResult::Submitted(reaction),
+        ) => {
+            let reaction = reaction.trim();
+            if !reaction.is_empty() {
+                app.react(&conversation_id, message_id, reaction.to_string());
+            }
+        }
+        (
+            ModalAction::ApplyPatch {
+                conversation_id,
+                message_id,
+            },
+            ModalResult::Confirmed,
+        ) => {
+            let patch = app
+                .conversations
+                .get(&conversation_id)
+                .and_then(|conversation| conversation.message(message_id))
+                .and_then(|message| message.patch.clone());
+            if let Some(patch) = patch {
+                patch::apply(&patch, app);
+            }
+        }
+        (ModalAction::Onboarding(step), result) => {
+            onboarding::handle_step_result(step, result, app)?;
+        }
+        // A typo would leave a backup nobody can open
+        (ModalAction::ExportKey(path), ModalResult::Submitted(passphrase)) => {
+            app.ui.modals.push(Modal::secret(
+                tr(Msg::KeyExportTitle),
+                tr(Msg::PassphraseRepeat),
+                ModalAction::RepeatExportKey { path, passphrase },
+            ));
+        }
+        (ModalAction::RepeatExportKey { path, passphrase }, ModalResult::Submitted(repeated)) => {
+            if repeated == passphrase {
+                kdf::export(path, passphrase, app)?;
+            } else {
+                app.connection.push_log(
+                    LogLevel::Warn,
+                    LogCategory::Security,
+                    tr(Msg::LogPassphrasesDiffer),
+                );
+                app.ui.modals.push(Modal::secret(
+                    tr(Msg::KeyExportTitle),
+                    tr(Msg::KeyPassphrase),
+                    ModalAction::ExportKey(path),
+                ));
+            }
+        }
+        // Checks the file can be opened before asking
+        (ModalAction::ImportKey(path), ModalResult::Submitted(passphrase)) => {
+            kdf::import(path, passphrase, false, app)?;
+        }
+        (ModalAction::ConfirmImportKey { path, passphrase }, ModalResult::Confirmed) => {
+            kdf::import(path, passphrase, true, app)?;
+        }
+        (ModalAction::RebindNick { nick, peer_id }, ModalResult::Confirmed) => {
+            nickcert::rebind(&nick, peer_id, app);
+        }
+        (ModalAction::NewIdentity, ModalResult::Confirmed) => {
+            app.connection
+                .replace_identity(Keypair::generate_ed25519(), &app.config)?;
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::Security,
+                &trf(
+                    Msg::LogIdentityReplaced,
+                    &[app.connection.swarm.local_peer_id()],
+                ),
+            );
+        }
+        (ModalAction::LoadIdentity(path), ModalResult::Submitted(passphrase)) => {
+            kdf::load(path, passphrase, app)?;
+        }
+        // Nothing could open the session again after a typo in a new passphrase
+        (ModalAction::UnlockSession, ModalResult::Submitted(passphrase)) if !app.session_locked => {
+            app.ui.modals.push(Modal::secret(
+                tr(Msg::SessionUnlockTitle),
+                tr(Msg::PassphraseRepeat),
+                ModalAction::RepeatSessionPassphrase(passphrase),
+            ));
+        }
+        (ModalAction::RepeatSessionPassphrase(passphrase), ModalResult::Submitted(repeated))
+            if repeated != passphrase =>
+        {
+            app.connection.push_log(
+                LogLevel::Warn,
+                LogCategory::Security,
+                tr(Msg::LogPassphrasesDiffer),
+            );
+            session::start(app)?;
+        }
+        (
+            ModalAction::UnlockSession | ModalAction::RepeatSessionPassphrase(_),
+            ModalResult::Submitted(passphrase),
+        ) => {
+            if let Err(e) = session::unlock(passphrase, false, app) {
+                app.connection.push_log(
+                    LogLevel::Error,
+                    LogCategory::Security,
+                    &trf(Msg::LogUnlockSessionFailed, &[&e]),
+                );
+                // Most likely a typo, so ask again
+                session::start(app)?;
+            }
+        }
+        (ModalAction::UnlockSession | ModalAction::RepeatSessionPassphrase(_), _) => {
+            app.connection.push_log(
+                LogLevel::Warn,
+                LogCategory::Security,
+                tr(Msg::LogSessionLocked),
+            );
+        }
+        (ModalAction::Verify(peer_id), ModalResult::Confirmed) => {
+            app.config.verified_peers.insert(peer_id.to_base58());
+            app.config.save()?;
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::Security,
+                &trf(Msg::LogPeerVerified, &[&peer_id]),
+            );
+        }
+        (ModalAction::Verify(peer_id), _) => {
+            // A peer that doesn't match anymore loses its earlier verification
+            if app.config.verified_peers.remove(&peer_id.to_base58()) {
+                app.config.save()?;
+            }
+            app.connection.push_log(
+                LogLevel::Warn,
+                LogCategory::Security,
+                &trf(Msg::LogPeerNotVerified, &[&peer_id]),
+            );
+        }
+        (ModalAction::PeerMenu { peer_id, actions }, ModalResult::Selected(i)) => {
+            if let Some(action) = actions.get(i) {
+                peerlist::confirm(app, peer_id, *action);
+            }
+        }
+        (ModalAction::PeerAction { peer_id, action }, ModalResult::Confirmed) => {
+            peerlist::run(app, peer_id, action);
+        }
+        (ModalAction::JumpToMissed(jumps), ModalResult::Selected(i)) => {
+            if let Some((topic, message_id)) = jumps.get(i).cloned() {
+                missed::jump(app, topic, message_id);
+            }
+        }
+        (ModalAction::SwitchConversation(conversation_ids), ModalResult::Selected(i)) => {
+            if let Some(id) = conversation_ids.get(i) {
+                app.open_conversation(id.clone());
+                app.ui.page_focus = PageFocus::Chat;
+            }
+        }
+        _ => (),
+    }
+
+    Ok(InputTask::Continue)
+}
+
+pub fn handle_input_event_chat_page(
+    event: Event,
+    app: &mut App,
+) -> Result<InputTask, anyhow::Error> {
+    // The member pane can be toggled from the history and the input
+    if let Event::Key(key_event) = event {
+        if let (KeyCode::F(2), KeyModifiers::NONE) = (key_event.code, key_event.modifiers) {
+            app.ui.show_members = !app.ui.show_members;
+            return Ok(InputTask::Continue);
+        }
+    }
+
+    if app.ui.chat_page_focus == ChatPageFocus::History {
+        match event {
+            Event::Key(key_event) => match (key_event.code, key_event.modifiers) {
+                (KeyCode::Enter, KeyModifiers::NONE) => {
+                    if app.ui.chat_history_selected.is_some() {
+                        open_message_menu(app);
+                    } else {
+                        app.ui.chat_page_focus = ChatPageFocus::Input;
+                    }
+                }
+                (KeyCode::Char('i'), KeyModifiers::NONE) => {
+                    app.ui.chat_page_focus = ChatPageFocus::Input;
+                }
+                (KeyCode::Char('o'), KeyModifiers::NONE) => {
+                    handle_selected_message_action(MessageAction::OpenLink, app)?;
+                }
+                (KeyCode::Char('r'), KeyModifiers::NONE) => {
+                    handle_selected_message_action(MessageAction::Retry, app)?;
+                }
+                (KeyCode::Char('p'), KeyModifiers::NONE) => {
+                    handle_selected_message_action(MessageAction::PlayVoiceMemo, app)?;
+                }
+                (KeyCode::Char('c'), KeyModifiers::NONE) => {
+                    handle_selected_message_action(MessageAction::CopyText, app)?;
+                }
+                (KeyCode::Char('v'), KeyModifiers::NONE) => {
+                    handle_selected_message_action(MessageAction::ViewSnapshot, app)?;
+                }
+                (KeyCode::Char('y'), KeyModifiers::NONE) => {
+                    app.copy_chat_history_selection()?;
+                }
+                (KeyCode::Char(c @ '1'..='9'), KeyModifiers::NONE) => {
+                    let option = c as usize - '1' as usize;
+                    handle_selected_message_action(MessageAction::Vote(option), app)?;
+                }
+                (KeyCode::Up, KeyModifiers::NONE) => {
+                    app.ui.chat_history_anchor = None;
+                    app.chat_history_previous();
+                }
+                (KeyCode::Down, KeyModifiers::NONE) => {
+                    app.ui.chat_history_anchor = None;
+                    app.chat_history_next();
+                }
+                (KeyCode::Up, KeyModifiers::SHIFT) => {
+                    app.chat_history_extend_selection(false);
+                }
+                (KeyCode::Down, KeyModifiers::SHIFT) => {
+                    app.chat_history_extend_selection(true);
+                }
+                (KeyCode::Up, KeyModifiers::ALT) => {
+                    app.cycle_conversation(false);
+                }
+                (KeyCode::Down, KeyModifiers::ALT) => {
+                    app.cycle_conversation(true);
+                }
+                _ => (),
+            },
+            _ => (),
+        }
+        return Ok(InputTask::Continue);
+    }
+
     match event {
         Event::Key(key_event) => match (key_event.code, key_event.modifiers) {
-            (KeyCode::Backspace, KeyModifiers::NONE) => {
-                app.ui.chat_input.pop();
-            }
             (KeyCode::Enter, KeyModifiers::NONE) => {
-                let nick = if &app.ui.nick_input == "" {
-                    None
-                } else {
-                    Some(app.ui.nick_input.clone())
-                };
-                let chat_message = ChatMessage::new(None, nick.clone(), app.ui.chat_input.clone());
-                let chat_message_ser = serde_json::to_string(&chat_message)?;
-                if let Err(e) = app.connection.swarm.behaviour_mut().publish(
-                    app.connection.current_topic.clone(),
-                    chat_message_ser.as_bytes(),
-                ) {
-                    app.connection.push_log_entry(&format!("publish() message failed with Err `{}`", e));
-                };
+                if commands::is_command(app.ui.chat_input.as_str()) {
+                    let command = app.ui.chat_input.take();
+                    match commands::execute(&command, app) {
+                        Ok(task) => return Ok(task),
+                        Err(e) => {
+                            app.connection.push_log(
+                                LogLevel::Error,
+                                LogCategory::General,
+                                &trf(Msg::LogCommandFailed, &[&command, &e]),
+                            );
+                        }
+                    }
+                    return Ok(InputTask::Continue);
+                }
 
-                app.history.push(ChatMessage::new(
-                    Some(*app.connection.swarm.local_peer_id()),
-                    nick,
-                    app.ui.chat_input.clone(),
-                ));
-                app.ui.chat_input.clear();
+                // The text is kept, e.g. to post it somewhere else
+                let current = app.ui.current_conversation.clone();
+                if !app.may_post(&current) {
+                    return Ok(InputTask::Continue);
+                }
+                let text = app.ui.chat_input.take();
+                app.send_chat_message(text);
+            }
+            (KeyCode::Up, KeyModifiers::ALT) => {
+                app.cycle_conversation(false);
+            }
+            (KeyCode::Down, KeyModifiers::ALT) => {
+                app.cycle_conversation(true);
             }
             (KeyCode::Char('u'), KeyModifiers::CONTROL) => {
                 app.ui.chat_input.clear();
             }
-            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
-                app.ui.chat_input.push(c);
+            _ => {
+                app.ui.chat_input.handle_key(key_event);
             }
-            _ => (),
         },
         _ => (),
     };
 
-    Ok(())
+    Ok(InputTask::Continue)
+}
+
+// Down in the address input goes through the dropdown of recent addresses, past the last one it
+// moves on to the next field. Returns whether the key was taken
+fn select_next_recent_addr(app: &mut App) -> bool {
+    if app.ui.connection_page_focus != ConnectionPageFocus::AddrInputField {
+        return false;
+    }
+    let count = recentaddrs::matching(app).len();
+    app.ui.addr_dropdown_selected = match app.ui.addr_dropdown_selected {
+        None if count > 0 => Some(0),
+        Some(selected) if selected + 1 < count => Some(selected + 1),
+        _ => None,
+    };
+    app.ui.addr_dropdown_selected.is_some()
+}
+
+// Up past the first recent address goes back to typing
+fn select_previous_recent_addr(app: &mut App) -> bool {
+    if app.ui.connection_page_focus != ConnectionPageFocus::AddrInputField {
+        return false;
+    }
+    match app.ui.addr_dropdown_selected.take() {
+        Some(selected) => {
+            app.ui.addr_dropdown_selected = selected.checked_sub(1);
+            true
+        }
+        None => false,
+    }
 }
 
 pub fn handle_input_event_connection_page(
     event: Event,
     app: &mut App,
 ) -> Result<(), anyhow::Error> {
-    // Cycle through the different fields
+    // Cycle through the different fields, unless the dropdown of the address input takes the key
     match event {
         Event::Key(key_event) => match (key_event.code, key_event.modifiers) {
-            (KeyCode::Down, KeyModifiers::NONE) => {
+            (KeyCode::Down, KeyModifiers::NONE) if !select_next_recent_addr(app) => {
                 app.ui.connection_page_focus = app.ui.connection_page_focus.next();
             }
-            (KeyCode::Up, KeyModifiers::NONE) => {
+            (KeyCode::Up, KeyModifiers::NONE) if !select_previous_recent_addr(app) => {
                 app.ui.connection_page_focus = app.ui.connection_page_focus.prev();
             }
             _ => (),
@@ -98,44 +1022,72 @@ pub fn handle_input_event_connection_page(
 
     match app.ui.connection_page_focus {
         ConnectionPageFocus::ConnectionLog => match event {
-            Event::Mouse(mouse_event) => {
-                let mouse_coord = (mouse_event.column, mouse_event.row);
-
-                match mouse_event.kind {
-                    MouseEventKind::ScrollDown => {
-                        if let Some(allocation) = app.ui.connection_log_allocation {
-                            if utils::coord_in_rect(mouse_coord, allocation) {
-                                app.connection_log_next();
-                            }
-                        }
-                    }
-                    MouseEventKind::ScrollUp => {
-                        if let Some(allocation) = app.ui.connection_log_allocation {
-                            if utils::coord_in_rect(mouse_coord, allocation) {
-                                app.connection_log_previous();
-                            }
-                        }
+            Event::Key(key_event) => match (key_event.code, key_event.modifiers) {
+                (KeyCode::Char('j'), KeyModifiers::NONE) => app.connection_log_next(),
+                (KeyCode::Char('k'), KeyModifiers::NONE) => app.connection_log_previous(),
+                (KeyCode::End, KeyModifiers::NONE)
+                | (KeyCode::Char('G'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                    app.connection_log_newest()
+                }
+                (KeyCode::Char('t'), KeyModifiers::NONE) => {
+                    app.ui.log_relative_time = !app.ui.log_relative_time
+                }
+                (KeyCode::Char('c') | KeyCode::Char('y'), KeyModifiers::NONE) => {
+                    let entry = app
+                        .ui
+                        .connection_log_liststate
+                        .selected()
+                        .and_then(|selected| app.connection.log.get(selected));
+                    if let Some(entry) = entry {
+                        utils::copy_to_clipboard(entry.to_string())?;
+                        app.connection.push_log(
+                            LogLevel::Info,
+                            LogCategory::General,
+                            &trf(Msg::LogCopied, &[&tr(Msg::LogEntry)]),
+                        );
                     }
-                    _ => (),
                 }
-            }
+                _ => (),
+            },
             _ => (),
         },
+        // Moves like the log, the actions are confirmed before they run
+        ConnectionPageFocus::PeerList => {
+            if let Event::Key(key_event) = event {
+                let action = match (key_event.code, key_event.modifiers) {
+                    (KeyCode::Char('j'), KeyModifiers::NONE) => {
+                        let len = peerlist::entries(app).len();
+                        app.ui.peer_list_selected =
+                            (app.ui.peer_list_selected + 1).min(len.saturating_sub(1));
+                        None
+                    }
+                    (KeyCode::Char('k'), KeyModifiers::NONE) => {
+                        app.ui.peer_list_selected = app.ui.peer_list_selected.saturating_sub(1);
+                        None
+                    }
+                    (KeyCode::Enter, KeyModifiers::NONE) => {
+                        peerlist::open_menu(app);
+                        None
+                    }
+                    (KeyCode::Char('p'), KeyModifiers::NONE) => Some(PeerAction::Ping),
+                    (KeyCode::Char('r'), KeyModifiers::NONE) => Some(PeerAction::Redial),
+                    (KeyCode::Char('d'), KeyModifiers::NONE) => Some(PeerAction::Disconnect),
+                    (KeyCode::Char('b'), KeyModifiers::NONE) => Some(PeerAction::Ban),
+                    _ => None,
+                };
+                let selected = peerlist::entries(app)
+                    .into_iter()
+                    .nth(app.ui.peer_list_selected);
+                if let (Some(action), Some(entry)) = (action, selected) {
+                    peerlist::confirm(app, entry.peer_id, action);
+                }
+            }
+        }
         ConnectionPageFocus::RegenerateSwarm => {
             match event {
                 Event::Key(key_event) => match (key_event.code, key_event.modifiers) {
                     (KeyCode::Enter, KeyModifiers::NONE) => {
-                        app.connection.current_topic = IdentTopic::new("test-net");
-                        let handle = tokio::runtime::Handle::current();
-                        let _guard = handle.enter();
-
-                        app.connection.log.clear();
-                        match Connection::generate_swarm(&app.connection.current_topic) {
-                            Ok(swarm) => app.connection.swarm = swarm,
-                            Err(e) => app.connection.push_log_entry(
-                                format!("regenerate_swarm() failed with Err {}", e).as_str(),
-                            ),
-                        };
+                        app.ui.modals.push(regenerate_swarm_modal());
                     }
                     _ => (),
                 },
@@ -145,52 +1097,86 @@ pub fn handle_input_event_connection_page(
         ConnectionPageFocus::AddrInputField => {
             match event {
                 Event::Key(key_event) => match (key_event.code, key_event.modifiers) {
-                    (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
-                        app.ui.addr_input.push(c);
+                    (KeyCode::Enter, KeyModifiers::NONE)
+                        if app.ui.addr_dropdown_selected.is_some() =>
+                    {
+                        let selected = app.ui.addr_dropdown_selected.unwrap_or_default();
+                        if let Some(addr) = recentaddrs::matching(app).into_iter().nth(selected) {
+                            recentaddrs::dial(app, addr);
+                        }
                     }
-                    (KeyCode::Backspace, KeyModifiers::NONE) => {
-                        app.ui.addr_input.pop();
+                    (KeyCode::Enter, KeyModifiers::NONE)
+                        if invite::is_invite(app.ui.addr_input.as_str()) =>
+                    {
+                        let invite = app.ui.addr_input.as_str().to_string();
+                        if let Err(e) = invite::accept(&invite, app) {
+                            app.connection.push_log(
+                                LogLevel::Error,
+                                LogCategory::General,
+                                &trf(Msg::LogAcceptInviteFailed, &[&e]),
+                            );
+                        }
                     }
                     (KeyCode::Enter, KeyModifiers::NONE) => {
-                        match app.ui.addr_input.parse::<Multiaddr>() {
+                        match transport::parse_addr(app.ui.addr_input.as_str()) {
                             Ok(dialed) => {
-                                app.connection.dial(dialed.clone()).unwrap_or_else(|e| {
-                                    app.connection.push_log_entry(
-                                        format!(
-                                            "dialing to addr {:?} failed with Err {}",
-                                            dialed, e
-                                        )
-                                        .as_str(),
-                                    );
-                                });
+                                app.connection.outbox.push(ConnectionCommand::Dial(dialed));
                             }
+                            // The hint says how to fix it, the parse error rarely does
                             Err(e) => {
-                                app.connection.push_log_entry(
-                                    format!("parsing input as MultiAddr failed with Err {}", e)
-                                        .as_str(),
+                                let reason = addrinput::hint(app).unwrap_or_else(|| e.to_string());
+                                app.connection.push_log(
+                                    LogLevel::Error,
+                                    LogCategory::Network,
+                                    &trf(Msg::LogParseMultiaddrFailed, &[&reason]),
                                 );
                             }
                         }
                     }
-                    _ => (),
+                    // Takes the completion of a known address shown after the cursor
+                    (KeyCode::Right | KeyCode::End, KeyModifiers::NONE)
+                        if addrinput::complete(app) => {}
+                    _ => {
+                        // The matches change with the text
+                        if app.ui.addr_input.handle_key(key_event) {
+                            app.ui.addr_dropdown_selected = None;
+                        }
+                    }
                 },
                 _ => (),
             };
         }
         ConnectionPageFocus::NickInputField => {
-            match event {
-                Event::Key(key_event) => match (key_event.code, key_event.modifiers) {
-                    (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
-                        app.ui.nick_input.push(c);
-                    }
-                    (KeyCode::Backspace, KeyModifiers::NONE) => {
-                        app.ui.nick_input.pop();
-                    }
-                    _ => (),
-                },
-                _ => (),
-            };
+            if let Event::Key(key_event) = event {
+                app.ui.nick_input.handle_key(key_event);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn handle_input_event_status_page(event: Event, app: &mut App) -> Result<(), anyhow::Error> {
+    if let Event::Key(key_event) = event {
+        match (key_event.code, key_event.modifiers) {
+            (KeyCode::Down, KeyModifiers::NONE) => {
+                app.ui.status_selected += 1;
+            }
+            (KeyCode::Up, KeyModifiers::NONE) => {
+                app.ui.status_selected = app.ui.status_selected.saturating_sub(1);
+            }
+            (KeyCode::Enter | KeyCode::Char('c'), KeyModifiers::NONE) => {
+                if let Some(entry) = status::entries(app).into_iter().nth(app.ui.status_selected) {
+                    utils::copy_to_clipboard(entry.value)?;
+                    app.connection.push_log(
+                        LogLevel::Info,
+                        LogCategory::General,
+                        &trf(Msg::LogCopied, &[&entry.label]),
+                    );
+                }
+            }
+            _ => (),
         }
     }
+
     Ok(())
 }
@@ -0,0 +1,117 @@
+use anyhow::Context;
+use libp2p::Multiaddr;
+use serde::{Deserialize, Serialize};
+
+use crate::app::App;
+use crate::connection::ConnectionCommand;
+use crate::conversation::ConversationId;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::i18n::{trf, Msg};
+use crate::topickeys;
+use crate::utils;
+
+// Invites are plain strings that can be pasted anywhere, the encoded JSON after this prefix
+pub const PREFIX: &str = "p2pchat:invite:";
+// How many addresses to put into an invite, ours first
+const MAX_ADDRS: usize = 3;
+
+// Everything needed to join a topic: its name, its current key if it is encrypted, and peers
+// to reach it through
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Invite {
+    pub topic: String,
+    #[serde(default)]
+    pub key: Option<(u32, String)>,
+    #[serde(default)]
+    pub addrs: Vec<Multiaddr>,
+    // The peer that hands out the keys of the topic
+    #[serde(default)]
+    pub key_issuer: Option<String>,
+}
+
+impl Invite {
+    pub fn for_topic(topic: &str, app: &App) -> Self {
+        let settings = app.config.topic_settings(topic);
+        let key = settings.keys.into_iter().next_back();
+        let mut addrs = app.connection.shareable_addrs();
+        addrs.extend(
+            app.connection
+                .dialed_addrs
+                .iter()
+                .map(|(peer_id, addr)| utils::with_peer_id(addr.clone(), *peer_id)),
+        );
+        addrs.truncate(MAX_ADDRS);
+
+        Self {
+            topic: topic.to_string(),
+            key,
+            addrs,
+            key_issuer: settings.key_issuer,
+        }
+    }
+
+    pub fn encode(&self) -> Result<String, anyhow::Error> {
+        let json = serde_json::to_vec(self)?;
+        Ok(format!(
+            "{}{}",
+            PREFIX,
+            data_encoding::BASE64URL_NOPAD.encode(&json)
+        ))
+    }
+
+    pub fn decode(invite: &str) -> Result<Self, anyhow::Error> {
+        let encoded = invite
+            .trim()
+            .strip_prefix(PREFIX)
+            .context("not an invite")?;
+        let json = data_encoding::BASE64URL_NOPAD
+            .decode(encoded.as_bytes())
+            .context("decoding invite failed")?;
+        serde_json::from_slice(&json).context("parsing invite failed")
+    }
+}
+
+pub fn is_invite(input: &str) -> bool {
+    input.trim().starts_with(PREFIX)
+}
+
+// Joins the topic of an invite, stores its key and dials the peers in it
+pub fn accept(invite: &str, app: &mut App) -> Result<(), anyhow::Error> {
+    let invite = Invite::decode(invite)?;
+
+    // Like a key from a peer, the key of an epoch we already have is never replaced
+    if let Some((epoch, key)) = invite.key {
+        topickeys::validate(&key).context("the key of the invite is invalid")?;
+        let settings = app.config.topic_settings_mut(&invite.topic);
+        if settings.key_issuer.is_none() {
+            settings.key_issuer = invite.key_issuer.clone();
+        }
+        let stored = settings.keys.entry(epoch).or_insert_with(|| key.clone());
+        anyhow::ensure!(
+            *stored == key,
+            "the invite has another key for epoch {} of #{} than ours",
+            epoch,
+            invite.topic
+        );
+        app.config.save()?;
+    }
+    app.connection
+        .outbox
+        .push(ConnectionCommand::Subscribe(invite.topic.clone()));
+    for addr in invite.addrs.iter() {
+        app.connection
+            .outbox
+            .push(ConnectionCommand::Dial(addr.clone()));
+    }
+
+    app.connection.push_log(
+        LogLevel::Info,
+        LogCategory::General,
+        &trf(
+            Msg::LogInviteAccepted,
+            &[&invite.topic, &invite.addrs.len()],
+        ),
+    );
+    app.open_conversation(ConversationId::Topic(invite.topic));
+    Ok(())
+}
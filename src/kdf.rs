@@ -0,0 +1,204 @@
+use std::path::PathBuf;
+
+use futures::channel::mpsc;
+use libp2p::identity::Keypair;
+
+use crate::app::App;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::i18n::{tr, trf, Msg};
+use crate::identity;
+use crate::modal::{Modal, ModalAction};
+use crate::session;
+use crate::vault::DerivedKey;
+
+// What deriving a key from a passphrase in the background ended with
+pub enum KdfDone {
+    Exported {
+        path: PathBuf,
+        result: Result<(), anyhow::Error>,
+    },
+    // Read to ask whether to import it, and read again once that was confirmed
+    Imported {
+        path: PathBuf,
+        passphrase: String,
+        confirmed: bool,
+        id_keys: Result<Keypair, anyhow::Error>,
+    },
+    // Read to use it right away
+    Loaded {
+        id_keys: Result<Keypair, anyhow::Error>,
+    },
+    Unlocked {
+        passphrase: String,
+        // Whether the passphrase came from the keyring instead of the prompt
+        stored: bool,
+        key: Result<DerivedKey, anyhow::Error>,
+    },
+}
+
+// Deriving a key takes a while on purpose, so it runs on a blocking thread instead of the event
+// loop, one at a time. What it ended with comes back through `done_rx`
+pub struct Kdf {
+    running: bool,
+    done_tx: mpsc::UnboundedSender<KdfDone>,
+    pub done_rx: mpsc::UnboundedReceiver<KdfDone>,
+}
+
+impl Default for Kdf {
+    fn default() -> Self {
+        let (done_tx, done_rx) = mpsc::unbounded();
+        Self {
+            running: false,
+            done_tx,
+            done_rx,
+        }
+    }
+}
+
+impl Kdf {
+    pub fn spawn(
+        &mut self,
+        run: impl FnOnce() -> KdfDone + Send + 'static,
+    ) -> Result<(), anyhow::Error> {
+        anyhow::ensure!(!self.running, "another passphrase is still being checked");
+        self.running = true;
+        let done_tx = self.done_tx.clone();
+        tokio::task::spawn_blocking(move || {
+            let _ = done_tx.unbounded_send(run());
+        });
+        Ok(())
+    }
+}
+
+// Continues once the key is derived
+pub fn finish(done: KdfDone, app: &mut App) {
+    app.kdf.running = false;
+    match done {
+        KdfDone::Exported { path, result } => match result {
+            Ok(()) => app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::Security,
+                &trf(Msg::LogKeyExported, &[&path.display()]),
+            ),
+            Err(e) => app.connection.push_log(
+                LogLevel::Error,
+                LogCategory::Security,
+                &trf(Msg::LogKeyExportFailed, &[&e]),
+            ),
+        },
+        KdfDone::Imported {
+            path,
+            passphrase,
+            confirmed: false,
+            id_keys: Ok(id_keys),
+        } => {
+            app.ui.modals.push(Modal::confirm(
+                tr(Msg::KeyImportTitle),
+                &trf(
+                    Msg::KeyImportConfirm,
+                    &[
+                        app.connection.swarm.local_peer_id(),
+                        &id_keys.public().to_peer_id(),
+                    ],
+                ),
+                ModalAction::ConfirmImportKey { path, passphrase },
+            ));
+        }
+        KdfDone::Imported {
+            confirmed: true,
+            id_keys: Ok(id_keys),
+            ..
+        } => {
+            if let Err(e) = save_imported(&id_keys, app) {
+                app.connection.push_log(
+                    LogLevel::Error,
+                    LogCategory::Security,
+                    &trf(Msg::LogKeyImportFailed, &[&e]),
+                );
+                return;
+            }
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::Security,
+                &trf(Msg::LogKeyImported, &[&id_keys.public().to_peer_id()]),
+            );
+        }
+        KdfDone::Loaded {
+            id_keys: Ok(id_keys),
+        } => {
+            if let Err(e) = app.connection.replace_identity(id_keys, &app.config) {
+                app.connection.push_log(
+                    LogLevel::Error,
+                    LogCategory::Security,
+                    &trf(Msg::LogKeyImportFailed, &[&e]),
+                );
+                return;
+            }
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::Security,
+                &trf(
+                    Msg::LogIdentityReplaced,
+                    &[app.connection.swarm.local_peer_id()],
+                ),
+            );
+        }
+        KdfDone::Imported {
+            id_keys: Err(e), ..
+        }
+        | KdfDone::Loaded { id_keys: Err(e) } => {
+            app.connection.push_log(
+                LogLevel::Error,
+                LogCategory::Security,
+                &trf(Msg::LogKeyImportFailed, &[&e]),
+            );
+        }
+        KdfDone::Unlocked {
+            passphrase,
+            stored,
+            key,
+        } => session::finish_unlock(passphrase, stored, key, app),
+    }
+}
+
+// Takes effect on the next start, when the stored identity is loaded
+fn save_imported(id_keys: &Keypair, app: &mut App) -> Result<(), anyhow::Error> {
+    identity::save(id_keys)?;
+    app.config.persistent_identity = true;
+    app.config.save()
+}
+
+// Writes the identity to a file that can be imported elsewhere, sealed with the passphrase
+pub fn export(path: PathBuf, passphrase: String, app: &mut App) -> Result<(), anyhow::Error> {
+    let id_keys = app.connection.id_keys.clone();
+    app.kdf.spawn(move || {
+        let result = identity::export(&id_keys, &path, &passphrase);
+        KdfDone::Exported { path, result }
+    })
+}
+
+// Reads an exported identity, asking whether to import it once it is open. The keypair can't be
+// kept in the modal, so it is read again and stored once `confirmed`
+pub fn import(
+    path: PathBuf,
+    passphrase: String,
+    confirmed: bool,
+    app: &mut App,
+) -> Result<(), anyhow::Error> {
+    app.kdf.spawn(move || {
+        let id_keys = identity::import(&path, &passphrase);
+        KdfDone::Imported {
+            path,
+            passphrase,
+            confirmed,
+            id_keys,
+        }
+    })
+}
+
+// Reads an exported identity and uses it for this run
+pub fn load(path: PathBuf, passphrase: String, app: &mut App) -> Result<(), anyhow::Error> {
+    app.kdf.spawn(move || KdfDone::Loaded {
+        id_keys: identity::import(&path, &passphrase),
+    })
+}
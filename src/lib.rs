@@ -0,0 +1,72 @@
+// The modules of the p2pchat binary, also built as a library so benches can use them
+pub mod addrinput;
+pub mod app;
+pub mod audit;
+pub mod away;
+pub mod behaviour;
+pub mod capture;
+pub mod commands;
+pub mod config;
+pub mod connection;
+pub mod conversation;
+pub mod crdt;
+pub mod dedup;
+pub mod direct;
+pub mod dmkeys;
+pub mod drafts;
+pub mod eventlog;
+pub mod events;
+pub mod filters;
+pub mod flood;
+pub mod history;
+pub mod hittest;
+pub mod i18n;
+pub mod identity;
+pub mod input;
+pub mod invite;
+pub mod kdf;
+pub mod markup;
+pub mod members;
+pub mod missed;
+pub mod modal;
+pub mod moderation;
+pub mod nat;
+pub mod nickcert;
+pub mod nicks;
+pub mod notify;
+pub mod onboarding;
+pub mod outbox;
+pub mod patch;
+pub mod peerexchange;
+pub mod peerlist;
+pub mod plain;
+pub mod preview;
+pub mod profile;
+pub mod protocol;
+pub mod quality;
+pub mod receipts;
+pub mod recentaddrs;
+pub mod roster;
+pub mod schedule;
+pub mod scoring;
+pub mod secrets;
+pub mod session;
+pub mod snapshot;
+pub mod stats;
+pub mod status;
+pub mod sync;
+pub mod textinput;
+pub mod tick;
+pub mod topickeys;
+pub mod topics;
+pub mod transcript;
+pub mod transforms;
+pub mod transport;
+pub mod ui;
+pub mod update;
+pub mod utils;
+pub mod validation;
+pub mod vault;
+pub mod verify;
+pub mod voice;
+pub mod writer;
@@ -1,30 +1,86 @@
-pub mod app;
-pub mod connection;
-pub mod input;
-pub mod ui;
-pub mod utils;
-
-use app::App;
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture},
+    event::{DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use std::{error::Error, io};
+use p2pchat::app::App;
+use p2pchat::config::Config;
+use p2pchat::flood::{self, Flood};
+use p2pchat::transcript::Transcript;
+use p2pchat::{i18n, onboarding, plain, profile, writer};
+use std::{error::Error, io, path::Path};
 use tui::{backend::CrosstermBackend, Terminal};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     pretty_env_logger::init();
+    let args = std::env::args().collect::<Vec<String>>();
+    // Everything stored on disk depends on the profile, so it has to be chosen first
+    profile::set(arg_value(&args, "--profile").map(|name| name.to_string()))?;
+    let first_run = !Config::exists();
+    let mut config = Config::load()?;
+    if args.iter().any(|arg| arg == "--outbound-only") {
+        config.outbound_only = true;
+    }
+    if let Some(port) = arg_value(&args, "--listen") {
+        config.listen_port = port.parse()?;
+    }
+    i18n::set_locale(config.locale);
+    writer::start(config.disk);
+
+    let transcript = match arg_value(&args, "--transcript") {
+        Some(path) => Some(Transcript::open(Path::new(path))?),
+        None => None,
+    };
+    let flood = match arg_value(&args, "--flood") {
+        Some(rate) => Some(Flood::new(rate.parse()?)?),
+        None => None,
+    };
+
+    if config.plain || args.iter().any(|arg| arg == "--plain") {
+        let mut chat = App::new(config).await?;
+        chat.transcript = transcript;
+        chat.flood = flood;
+        flood::start(&mut chat);
+        let res = plain::run(chat).await;
+        writer::flush();
+        return Ok(res?);
+    }
+
+    // Give the terminal back before the panic is printed. The snapshot marker stays, so the next
+    // start restores the session
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = disable_raw_mode();
+        let _ = execute!(
+            io::stdout(),
+            LeaveAlternateScreen,
+            DisableMouseCapture,
+            DisableFocusChange
+        );
+        default_hook(info);
+    }));
+
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableFocusChange
+    )?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
     // create app and run it
-    let chat = App::new().await?;
+    let mut chat = App::new(config).await?;
+    chat.transcript = transcript;
+    chat.flood = flood;
+    flood::start(&mut chat);
+    if first_run {
+        onboarding::start(&mut chat);
+    }
     let res = chat.run(&mut terminal).await;
 
     // restore terminal
@@ -33,12 +89,22 @@ async fn main() -> Result<(), Box<dyn Error>> {
         terminal.backend_mut(),
         LeaveAlternateScreen,
         DisableMouseCapture,
+        DisableFocusChange,
     )?;
     terminal.show_cursor()?;
 
+    writer::flush();
     if let Err(err) = res {
         log::error!("{:?}", err)
     }
 
     Ok(())
 }
+
+// The value following an option, e.g. the path in `--transcript <path>`
+fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .map(|value| value.as_str())
+}
@@ -0,0 +1,144 @@
+use std::borrow::Cow;
+
+use regex::Regex;
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+
+// http(s) links, without trailing punctuation
+const URL_PATTERN: &str = r#"https?://[^\s<>"]*[^\s<>".,;:!?)\]]"#;
+
+// Renders the small markdown subset supported in messages:
+// `inline code`, ```fenced blocks```, *bold*, _italic_ and links
+pub struct Markup {
+    inline: Regex,
+    url: Regex,
+}
+
+impl Markup {
+    pub fn new() -> Self {
+        let inline = Regex::new(&format!(
+            r"`(?P<code>[^`]+)`|(?P<url>{})|(?P<pre>^|\s)(?:\*(?P<bold>[^*\s](?:[^*]*[^*\s])?)\*|_(?P<italic>[^_\s](?:[^_]*[^_\s])?)_)",
+            URL_PATTERN
+        ))
+        .expect("markup regex is valid");
+        let url = Regex::new(URL_PATTERN).expect("url regex is valid");
+
+        Self { inline, url }
+    }
+
+    pub fn urls(&self, text: &str) -> Vec<String> {
+        self.url
+            .find_iter(text)
+            .map(|m| m.as_str().to_string())
+            .collect()
+    }
+
+    pub fn first_url(&self, text: &str) -> Option<String> {
+        self.url.find(text).map(|m| m.as_str().to_string())
+    }
+
+    // Splits a message text into styled lines, borrowing from the text
+    pub fn lines<'a>(&self, text: &'a str, style: Style) -> Vec<Spans<'a>> {
+        let mut lines = vec![];
+        // Every odd part is inside a fenced code block
+        for (i, part) in text.split("```").enumerate() {
+            if i % 2 == 1 {
+                lines.extend(code_block_lines(part));
+            } else {
+                for line in part.split('\n') {
+                    if line.is_empty() {
+                        continue;
+                    }
+                    lines.push(Spans::from(self.inline_spans(line, style)));
+                }
+            }
+        }
+        if lines.is_empty() {
+            lines.push(Spans::default());
+        }
+
+        lines
+    }
+
+    // Like `lines`, for a text that doesn't live long enough to borrow from
+    pub fn lines_owned(&self, text: &str, style: Style) -> Vec<Spans<'static>> {
+        self.lines(text, style)
+            .into_iter()
+            .map(|spans| {
+                Spans::from(
+                    spans
+                        .0
+                        .into_iter()
+                        .map(|span| Span::styled(span.content.into_owned(), span.style))
+                        .collect::<Vec<Span>>(),
+                )
+            })
+            .collect()
+    }
+
+    fn inline_spans<'a>(&self, line: &'a str, style: Style) -> Vec<Span<'a>> {
+        let mut spans = vec![];
+        let mut last = 0;
+        for captures in self.inline.captures_iter(line) {
+            let whole = captures.get(0).expect("capture group 0 always exists");
+            let start = captures.name("pre").map_or(whole.start(), |pre| pre.end());
+            if start > last {
+                spans.push(Span::styled(&line[last..start], style));
+            }
+
+            let (content, content_style) = if let Some(code) = captures.name("code") {
+                (code.as_str(), code_style())
+            } else if let Some(url) = captures.name("url") {
+                (url.as_str(), style.add_modifier(Modifier::UNDERLINED))
+            } else if let Some(bold) = captures.name("bold") {
+                (bold.as_str(), style.add_modifier(Modifier::BOLD))
+            } else if let Some(italic) = captures.name("italic") {
+                (italic.as_str(), style.add_modifier(Modifier::ITALIC))
+            } else {
+                (whole.as_str(), style)
+            };
+            spans.push(Span::styled(content, content_style));
+            last = whole.end();
+        }
+        if last < line.len() {
+            spans.push(Span::styled(&line[last..], style));
+        }
+
+        spans
+    }
+}
+
+// Code blocks keep their whitespace and are set off by a bar on the left
+fn code_block_lines(block: &str) -> Vec<Spans<'_>> {
+    let mut block_lines = block.split('\n').collect::<Vec<&str>>();
+    // The first line of a fence is either empty or names the language
+    if block_lines.len() > 1 && !block_lines[0].contains(char::is_whitespace) {
+        block_lines.remove(0);
+    }
+    if block_lines.len() > 1
+        && block_lines
+            .last()
+            .map_or(false, |line| line.trim().is_empty())
+    {
+        block_lines.pop();
+    }
+
+    block_lines
+        .into_iter()
+        .map(|line| {
+            let line = if line.contains('\t') {
+                Cow::Owned(line.replace('\t', "    "))
+            } else {
+                Cow::Borrowed(line)
+            };
+            Spans::from(vec![
+                Span::styled("│ ", Style::default().fg(Color::DarkGray)),
+                Span::styled(line, code_style()),
+            ])
+        })
+        .collect()
+}
+
+fn code_style() -> Style {
+    Style::default().fg(Color::Cyan)
+}
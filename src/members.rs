@@ -0,0 +1,80 @@
+use libp2p::PeerId;
+
+use crate::app::App;
+use crate::protocol::{Status, StatusCard};
+use crate::roster::PeerNote;
+use crate::scoring;
+use crate::utils;
+
+// A peer subscribed to a topic, as shown in the member pane
+pub struct Member {
+    pub peer_id: PeerId,
+    pub name: String,
+    pub verified: bool,
+    pub is_local: bool,
+    pub away: bool,
+    pub card: Option<StatusCard>,
+    // Our note and tags on the peer
+    pub note: Option<PeerNote>,
+    // How good our connection to the peer is, from 0 to 100. Not set for us
+    pub score: Option<u8>,
+    // Its gossipsub score is so low its messages are ignored
+    pub graylisted: bool,
+}
+
+// The peers gossipsub knows to be subscribed to a topic, and us, sorted by connection quality.
+// Gossipsub keeps track of this from the subscriptions peers announce, nicks come from messages
+// and presence payloads
+pub fn topic_members(app: &App, topic: &str) -> Vec<Member> {
+    let local_peer_id = *app.connection.swarm.local_peer_id();
+    let topic_hash = app.connection.topics.hash(topic);
+    let peer_ids = app
+        .connection
+        .swarm
+        .behaviour()
+        .gossipsub
+        .all_peers()
+        .filter(|(_, topics)| topics.contains(&&topic_hash))
+        .map(|(peer_id, _)| *peer_id)
+        .filter(|peer_id| !app.moderation.is_blocked(peer_id));
+
+    let mut members = std::iter::once(local_peer_id)
+        .chain(peer_ids)
+        .map(|peer_id| {
+            let name = if peer_id == local_peer_id && !app.ui.nick_input.is_empty() {
+                app.ui.nick_input.as_str().to_string()
+            } else {
+                app.nicks
+                    .name_of(&peer_id)
+                    .unwrap_or_else(|| utils::short_peer_id(&peer_id))
+            };
+            Member {
+                peer_id,
+                name,
+                verified: app.config.is_verified(&peer_id),
+                is_local: peer_id == local_peer_id,
+                away: if peer_id == local_peer_id {
+                    app.away.status == Status::Away
+                } else {
+                    app.away_peers.contains(&peer_id)
+                },
+                card: if peer_id == local_peer_id {
+                    app.config.status_card.clone()
+                } else {
+                    app.status_cards.get(&peer_id).cloned()
+                },
+                note: app.roster.get(&peer_id).cloned(),
+                score: (peer_id != local_peer_id).then(|| app.connection.quality.score(&peer_id)),
+                graylisted: scoring::is_graylisted(app, &peer_id),
+            }
+        })
+        .collect::<Vec<Member>>();
+    // Us first, then the best connected peers
+    members.sort_by(|a, b| {
+        b.is_local
+            .cmp(&a.is_local)
+            .then(b.score.cmp(&a.score))
+            .then(a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+    members
+}
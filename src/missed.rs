@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use uuid::Uuid;
+
+use crate::app::{App, ChatMessage, MessageKind};
+use crate::conversation::ConversationId;
+use crate::i18n::{tr, trf, Msg};
+use crate::modal::{Modal, ModalAction};
+use crate::ui::{ChatPageFocus, PageFocus};
+
+// The summary waits this long after startup, so the first syncs of all topics make it in
+const SUMMARY_DELAY: Duration = Duration::from_secs(10);
+
+// What arrived in a topic before we started, learned from history syncs
+#[derive(Debug, Clone)]
+struct Missed {
+    count: usize,
+    // The oldest missed message, where reading should continue
+    first: Uuid,
+    first_at: DateTime<Local>,
+}
+
+// The "while you were away" summary, shown once after startup and again with /missed
+#[derive(Debug)]
+pub struct MissedSummary {
+    started: DateTime<Local>,
+    topics: BTreeMap<String, Missed>,
+    shown: bool,
+}
+
+impl Default for MissedSummary {
+    fn default() -> Self {
+        Self {
+            started: Local::now(),
+            topics: BTreeMap::new(),
+            shown: false,
+        }
+    }
+}
+
+impl MissedSummary {
+    pub fn is_empty(&self) -> bool {
+        self.topics.is_empty()
+    }
+}
+
+// Called with the messages of a history sync before they are merged. Those we didn't have and
+// that peers got before we started are the ones we missed
+pub fn record(app: &mut App, topic: &str, messages: &[ChatMessage]) {
+    let started = app.missed.started;
+    let conversation = app
+        .conversations
+        .get(&ConversationId::Topic(topic.to_string()));
+    let missed = messages
+        .iter()
+        .filter(|message| message.kind == MessageKind::Chat && message.received_at < started)
+        .filter(|message| {
+            conversation.map_or(true, |conversation| {
+                conversation.message(message.id).is_none()
+            })
+        })
+        .collect::<Vec<&ChatMessage>>();
+    let oldest = match missed.iter().min_by_key(|message| message.order_key()) {
+        Some(oldest) => oldest,
+        None => return,
+    };
+
+    let entry = app
+        .missed
+        .topics
+        .entry(topic.to_string())
+        .or_insert_with(|| Missed {
+            count: 0,
+            first: oldest.id,
+            first_at: oldest.received_at,
+        });
+    entry.count += missed.len();
+    if oldest.received_at < entry.first_at {
+        entry.first = oldest.id;
+        entry.first_at = oldest.received_at;
+    }
+}
+
+// Called every tick, shows the summary once the first syncs are done
+pub fn check(app: &mut App) {
+    if app.missed.shown
+        || app.missed.is_empty()
+        || app.started.elapsed() < SUMMARY_DELAY
+        || !app.ui.modals.is_empty()
+    {
+        return;
+    }
+    show(app);
+}
+
+// Lists the topics with how much was missed, picking one jumps to its first missed message
+pub fn show(app: &mut App) {
+    app.missed.shown = true;
+    let (options, jumps): (Vec<String>, Vec<(String, Uuid)>) = app
+        .missed
+        .topics
+        .iter()
+        .map(|(topic, missed)| {
+            (
+                trf(
+                    Msg::MissedEntry,
+                    &[
+                        &ConversationId::Topic(topic.clone()),
+                        &missed.count,
+                        &missed.first_at.format("%Y-%m-%d %H:%M"),
+                    ],
+                ),
+                (topic.clone(), missed.first),
+            )
+        })
+        .unzip();
+    app.ui.modals.push(Modal::select(
+        tr(Msg::MissedTitle),
+        options,
+        ModalAction::JumpToMissed(jumps),
+    ));
+}
+
+pub fn jump(app: &mut App, topic: String, message_id: Uuid) {
+    let id = ConversationId::Topic(topic);
+    app.open_conversation(id.clone());
+    app.ui.page_focus = PageFocus::Chat;
+    let position = app.conversations.get(&id).and_then(|conversation| {
+        conversation
+            .history
+            .iter()
+            .position(|message| message.id == message_id)
+    });
+    if let Some(position) = position {
+        app.ui.chat_history_selected = Some(position);
+        app.ui.chat_history_anchor = None;
+        app.ui.chat_page_focus = ChatPageFocus::History;
+    }
+}
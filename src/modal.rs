@@ -0,0 +1,225 @@
+use std::path::PathBuf;
+
+use libp2p::PeerId;
+use uuid::Uuid;
+
+use crate::conversation::ConversationId;
+use crate::i18n::{tr, Msg};
+use crate::onboarding::OnboardingStep;
+use crate::textinput::TextInput;
+
+// What happens once a modal is answered
+#[derive(Debug, Clone)]
+pub enum ModalAction {
+    Quit,
+    RegenerateSwarm,
+    SwitchConversation(Vec<ConversationId>),
+    MessageMenu {
+        conversation_id: ConversationId,
+        message_id: Uuid,
+        actions: Vec<MessageAction>,
+    },
+    React {
+        conversation_id: ConversationId,
+        message_id: Uuid,
+    },
+    Onboarding(OnboardingStep),
+    ExportKey(PathBuf),
+    // The passphrase entered first, exporting only once it was entered the same way again
+    RepeatExportKey {
+        path: PathBuf,
+        passphrase: String,
+    },
+    ImportKey(PathBuf),
+    // Imported again once confirmed, the keypair can't be kept here
+    ConfirmImportKey {
+        path: PathBuf,
+        passphrase: String,
+    },
+    LoadIdentity(PathBuf),
+    NewIdentity,
+    // A nick that is bound to another peer, moved to this one once confirmed
+    RebindNick {
+        nick: String,
+        peer_id: PeerId,
+    },
+    Verify(PeerId),
+    UnlockSession,
+    // A new session passphrase is only used once it was entered the same way again
+    RepeatSessionPassphrase(String),
+    // The topics of the "while you were away" summary with their first missed message
+    JumpToMissed(Vec<(String, Uuid)>),
+    ApplyPatch {
+        conversation_id: ConversationId,
+        message_id: Uuid,
+    },
+    PeerMenu {
+        peer_id: PeerId,
+        actions: Vec<PeerAction>,
+    },
+    PeerAction {
+        peer_id: PeerId,
+        action: PeerAction,
+    },
+    // Only shows information
+    Receipts,
+    Snapshot,
+}
+
+// The entries of the action menu of a selected message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageAction {
+    CopyText,
+    CopySenderPeerId,
+    Reply,
+    React,
+    OpenLink,
+    Delete,
+    Retry,
+    Receipts,
+    BlockSender(PeerId),
+    // The index of a poll option, chosen with the number keys
+    Vote(usize),
+    PlayVoiceMemo,
+    ViewSnapshot,
+    ViewPatch,
+    ApplyPatch,
+}
+
+impl MessageAction {
+    pub fn label(&self) -> &'static str {
+        tr(match self {
+            Self::CopyText => Msg::ActionCopyText,
+            Self::CopySenderPeerId => Msg::ActionCopySenderPeerId,
+            Self::Reply => Msg::ActionReply,
+            Self::React => Msg::ActionReact,
+            Self::OpenLink => Msg::ActionOpenLink,
+            Self::Delete => Msg::ActionDelete,
+            Self::Retry => Msg::ActionRetry,
+            Self::Receipts => Msg::ActionReceipts,
+            Self::BlockSender(_) => Msg::ActionBlockSender,
+            Self::Vote(_) => Msg::ActionVote,
+            Self::PlayVoiceMemo => Msg::ActionPlayVoiceMemo,
+            Self::ViewSnapshot => Msg::ActionViewSnapshot,
+            Self::ViewPatch => Msg::ActionViewPatch,
+            Self::ApplyPatch => Msg::ActionApplyPatch,
+        })
+    }
+}
+
+// The entries of the action menu of a peer on the Connection page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerAction {
+    Ping,
+    Redial,
+    Disconnect,
+    Ban,
+}
+
+impl PeerAction {
+    pub fn label(&self) -> &'static str {
+        tr(match self {
+            Self::Ping => Msg::ActionPingPeer,
+            Self::Redial => Msg::ActionRedialPeer,
+            Self::Disconnect => Msg::ActionDisconnectPeer,
+            Self::Ban => Msg::ActionBanPeer,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ModalKind {
+    // yes / no
+    Confirm,
+    // free text input
+    Prompt {
+        input: TextInput,
+    },
+    // free text input shown as `*`
+    Secret {
+        input: TextInput,
+    },
+    // pick one of the options
+    Select {
+        options: Vec<String>,
+        selected: usize,
+    },
+    // read only text that keeps its layout, scrolled with the arrow keys
+    View {
+        lines: Vec<String>,
+        // (rows, columns)
+        scroll: (u16, u16),
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum ModalResult {
+    Confirmed,
+    Submitted(String),
+    Selected(usize),
+    Cancelled,
+}
+
+// A popup rendered above the current page, which receives all input while it is open
+#[derive(Debug, Clone)]
+pub struct Modal {
+    pub title: String,
+    pub message: String,
+    pub kind: ModalKind,
+    pub action: ModalAction,
+}
+
+impl Modal {
+    pub fn confirm(title: &str, message: &str, action: ModalAction) -> Self {
+        Self {
+            title: title.to_string(),
+            message: message.to_string(),
+            kind: ModalKind::Confirm,
+            action,
+        }
+    }
+
+    pub fn prompt(title: &str, message: &str, action: ModalAction) -> Self {
+        Self {
+            title: title.to_string(),
+            message: message.to_string(),
+            kind: ModalKind::Prompt {
+                input: TextInput::new(),
+            },
+            action,
+        }
+    }
+
+    pub fn secret(title: &str, message: &str, action: ModalAction) -> Self {
+        Self {
+            kind: ModalKind::Secret {
+                input: TextInput::new(),
+            },
+            ..Self::prompt(title, message, action)
+        }
+    }
+
+    pub fn select(title: &str, options: Vec<String>, action: ModalAction) -> Self {
+        Self {
+            title: title.to_string(),
+            message: String::from(""),
+            kind: ModalKind::Select {
+                options,
+                selected: 0,
+            },
+            action,
+        }
+    }
+
+    pub fn view(title: &str, lines: Vec<String>, action: ModalAction) -> Self {
+        Self {
+            title: title.to_string(),
+            message: String::from(""),
+            kind: ModalKind::View {
+                lines,
+                scroll: (0, 0),
+            },
+            action,
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+
+use crate::profile;
+use crate::writer;
+
+// Peers the user doesn't want to hear from. Blocks only last until p2pchat is quit, mutes are
+// saved in the profile directory
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Moderation {
+    #[serde(skip)]
+    blocked: HashSet<PeerId>,
+    // Peers whose messages are hidden while staying connected, by peer id
+    muted: BTreeSet<String>,
+}
+
+impl Moderation {
+    pub fn path() -> Option<PathBuf> {
+        profile::dir().map(|dir| dir.join("moderation.json"))
+    }
+
+    // Loads the saved mutes, or no moderation at all if none were saved yet
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let path = match Self::path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Self::default()),
+        };
+
+        let moderation_str = fs::read_to_string(&path)
+            .with_context(|| format!("reading moderation file {:?} failed", path))?;
+        serde_json::from_str(&moderation_str)
+            .with_context(|| format!("parsing moderation file {:?} failed", path))
+    }
+
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let path = Self::path().context("no config directory available")?;
+        writer::write(&path, serde_json::to_string_pretty(self)?, false)
+            .with_context(|| format!("writing moderation file {:?} failed", path))?;
+
+        Ok(())
+    }
+
+    pub fn block(&mut self, peer_id: PeerId) {
+        self.blocked.insert(peer_id);
+    }
+
+    // Returns whether the peer was blocked
+    pub fn unblock(&mut self, peer_id: &PeerId) -> bool {
+        self.blocked.remove(peer_id)
+    }
+
+    pub fn is_blocked(&self, peer_id: &PeerId) -> bool {
+        self.blocked.contains(peer_id)
+    }
+
+    pub fn mute(&mut self, peer_id: &PeerId) {
+        self.muted.insert(peer_id.to_base58());
+    }
+
+    // Returns whether the peer was muted
+    pub fn unmute(&mut self, peer_id: &PeerId) -> bool {
+        self.muted.remove(&peer_id.to_base58())
+    }
+
+    pub fn is_muted(&self, peer_id: &PeerId) -> bool {
+        self.muted.contains(&peer_id.to_base58())
+    }
+}
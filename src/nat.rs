@@ -0,0 +1,261 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use futures::channel::mpsc;
+use igd::aio::search_gateway;
+use igd::{PortMappingProtocol, SearchOptions};
+use libp2p::multiaddr::Protocol;
+use libp2p::Multiaddr;
+use tokio::net::UdpSocket;
+use tokio::task::JoinHandle;
+use tokio::time;
+
+// Mappings are requested with a limited lease and renewed periodically, so they vanish from the
+// router when the app is gone
+const LEASE_DURATION: Duration = Duration::from_secs(60 * 60);
+const RENEW_INTERVAL: Duration = Duration::from_secs(30 * 60);
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(3);
+const NATPMP_PORT: u16 = 5351;
+const NATPMP_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortMappingMethod {
+    Upnp,
+    NatPmp,
+}
+
+impl fmt::Display for PortMappingMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Upnp => write!(f, "UPnP"),
+            Self::NatPmp => write!(f, "NAT-PMP"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PortMappingEvent {
+    Mapped {
+        method: PortMappingMethod,
+        listen_addr: Multiaddr,
+        local: SocketAddrV4,
+        external: SocketAddrV4,
+    },
+    Failed {
+        local: SocketAddrV4,
+        error: String,
+    },
+}
+
+pub struct PortMapper {
+    pub enabled: bool,
+    pub nat_pmp_gateway: Option<Ipv4Addr>,
+    pub events_tx: mpsc::UnboundedSender<PortMappingEvent>,
+    pub events_rx: mpsc::UnboundedReceiver<PortMappingEvent>,
+    mapped_ports: HashSet<u16>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl PortMapper {
+    pub fn new(enabled: bool, nat_pmp_gateway: Option<Ipv4Addr>) -> Self {
+        let (events_tx, events_rx) = mpsc::unbounded();
+
+        Self {
+            enabled,
+            nat_pmp_gateway,
+            events_tx,
+            events_rx,
+            mapped_ports: HashSet::new(),
+            tasks: vec![],
+        }
+    }
+
+    // Starts mapping the port of a newly reported listen address, if it is a private IPv4 address.
+    // Each port is only mapped once, even though it is reported for every interface.
+    pub fn map_listen_addr(&mut self, addr: &Multiaddr) {
+        if !self.enabled {
+            return;
+        }
+        let local = match private_ipv4_socket(addr) {
+            Some(local) => local,
+            None => return,
+        };
+        if !self.mapped_ports.insert(local.port()) {
+            return;
+        }
+
+        let gateway = self
+            .nat_pmp_gateway
+            .unwrap_or_else(|| guess_gateway(*local.ip()));
+        let events_tx = self.events_tx.clone();
+        let listen_addr = addr.clone();
+
+        self.tasks.push(tokio::spawn(async move {
+            let mut interval = time::interval(RENEW_INTERVAL);
+            let mut reported = None;
+
+            loop {
+                interval.tick().await;
+
+                let event = match map_port(local, gateway).await {
+                    Ok((method, external)) => PortMappingEvent::Mapped {
+                        method,
+                        listen_addr: listen_addr.clone(),
+                        local,
+                        external,
+                    },
+                    Err(e) => PortMappingEvent::Failed {
+                        local,
+                        error: e.to_string(),
+                    },
+                };
+
+                // Only report renewals when something changed
+                let changed = match (&event, &reported) {
+                    (PortMappingEvent::Mapped { external, .. }, Some(prev)) => external != prev,
+                    _ => true,
+                };
+                if let PortMappingEvent::Mapped { external, .. } = &event {
+                    reported = Some(*external);
+                }
+                if changed && events_tx.unbounded_send(event).is_err() {
+                    break;
+                }
+            }
+        }));
+    }
+
+    // Stops all running mappings, for example when the swarm is regenerated
+    pub fn reset(&mut self) {
+        for task in self.tasks.drain(..) {
+            task.abort();
+        }
+        self.mapped_ports.clear();
+    }
+}
+
+impl Drop for PortMapper {
+    fn drop(&mut self) {
+        self.reset();
+    }
+}
+
+fn private_ipv4_socket(addr: &Multiaddr) -> Option<SocketAddrV4> {
+    let mut ip = None;
+    let mut port = None;
+
+    for protocol in addr.iter() {
+        match protocol {
+            Protocol::Ip4(addr_ip) => ip = Some(addr_ip),
+            Protocol::Tcp(addr_port) => port = Some(addr_port),
+            _ => (),
+        }
+    }
+
+    match (ip, port) {
+        (Some(ip), Some(port)) if ip.is_private() && port != 0 => Some(SocketAddrV4::new(ip, port)),
+        _ => None,
+    }
+}
+
+// Home routers almost always sit at the first address of the subnet
+fn guess_gateway(local_ip: Ipv4Addr) -> Ipv4Addr {
+    let octets = local_ip.octets();
+    Ipv4Addr::new(octets[0], octets[1], octets[2], 1)
+}
+
+// Replaces the local address and port of a listen address with the mapped ones, keeping
+// protocols like `/ws` intact
+pub fn external_multiaddr(listen_addr: &Multiaddr, external: SocketAddrV4) -> Multiaddr {
+    listen_addr
+        .iter()
+        .map(|protocol| match protocol {
+            Protocol::Ip4(_) => Protocol::Ip4(*external.ip()),
+            Protocol::Tcp(_) => Protocol::Tcp(external.port()),
+            protocol => protocol,
+        })
+        .collect()
+}
+
+async fn map_port(
+    local: SocketAddrV4,
+    gateway: Ipv4Addr,
+) -> Result<(PortMappingMethod, SocketAddrV4), anyhow::Error> {
+    match map_port_upnp(local).await {
+        Ok(external) => Ok((PortMappingMethod::Upnp, external)),
+        Err(upnp_err) => match map_port_natpmp(local, gateway).await {
+            Ok(external) => Ok((PortMappingMethod::NatPmp, external)),
+            Err(natpmp_err) => Err(anyhow!(
+                "UPnP failed with Err `{}`, NAT-PMP failed with Err `{}`",
+                upnp_err,
+                natpmp_err
+            )),
+        },
+    }
+}
+
+async fn map_port_upnp(local: SocketAddrV4) -> Result<SocketAddrV4, anyhow::Error> {
+    let gateway = search_gateway(SearchOptions {
+        timeout: Some(SEARCH_TIMEOUT),
+        ..Default::default()
+    })
+    .await?;
+
+    let external_ip = gateway.get_external_ip().await?;
+    gateway
+        .add_port(
+            PortMappingProtocol::TCP,
+            local.port(),
+            local,
+            LEASE_DURATION.as_secs() as u32,
+            "p2pchat",
+        )
+        .await?;
+
+    Ok(SocketAddrV4::new(external_ip, local.port()))
+}
+
+// NAT-PMP as specified in RFC 6886
+async fn map_port_natpmp(
+    local: SocketAddrV4,
+    gateway: Ipv4Addr,
+) -> Result<SocketAddrV4, anyhow::Error> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    socket.connect((gateway, NATPMP_PORT)).await?;
+    let mut buf = [0u8; 16];
+
+    // External address request
+    socket.send(&[0, 0]).await?;
+    let len = time::timeout(NATPMP_TIMEOUT, socket.recv(&mut buf)).await??;
+    if len < 12 || buf[1] != 128 {
+        bail!("invalid NAT-PMP external address response");
+    }
+    natpmp_result(&buf)?;
+    let external_ip = Ipv4Addr::new(buf[8], buf[9], buf[10], buf[11]);
+
+    // TCP mapping request
+    let mut request = [0u8; 12];
+    request[1] = 2;
+    request[4..6].copy_from_slice(&local.port().to_be_bytes());
+    request[6..8].copy_from_slice(&local.port().to_be_bytes());
+    request[8..12].copy_from_slice(&(LEASE_DURATION.as_secs() as u32).to_be_bytes());
+    socket.send(&request).await?;
+    let len = time::timeout(NATPMP_TIMEOUT, socket.recv(&mut buf)).await??;
+    if len < 16 || buf[1] != 130 {
+        bail!("invalid NAT-PMP mapping response");
+    }
+    natpmp_result(&buf)?;
+    let external_port = u16::from_be_bytes([buf[10], buf[11]]);
+
+    Ok(SocketAddrV4::new(external_ip, external_port))
+}
+
+fn natpmp_result(response: &[u8]) -> Result<(), anyhow::Error> {
+    match u16::from_be_bytes([response[2], response[3]]) {
+        0 => Ok(()),
+        code => Err(anyhow!("NAT-PMP gateway returned result code {}", code)),
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use chrono::{Local, TimeZone};
+use libp2p::identity::{Keypair, PublicKey};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+
+use crate::app::{App, ChatMessage};
+use crate::conversation::ConversationId;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::i18n::{tr, trf, Msg};
+use crate::modal::{Modal, ModalAction};
+use crate::profile;
+use crate::protocol::Payload;
+use crate::writer;
+
+// A nick bound to the key of the peer using it. Signed with that key, so anyone can check it
+// without asking the peer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NickCertificate {
+    pub nick: String,
+    // Protobuf encoded, in base64
+    pub public_key: String,
+    // Unix time in milliseconds
+    pub issued_at: i64,
+    pub signature: String,
+}
+
+// A peer can bind this many nicks, so nobody can take everyone's nicks ahead of time
+const MAX_NICKS_PER_PEER: usize = 3;
+
+fn signed_bytes(nick: &str, issued_at: i64) -> Vec<u8> {
+    format!("p2pchat nick certificate\n{}\n{}", nick, issued_at).into_bytes()
+}
+
+impl NickCertificate {
+    pub fn issue(nick: &str, id_keys: &Keypair) -> Result<Self, anyhow::Error> {
+        let issued_at = Local::now().timestamp_millis();
+        let signature = id_keys
+            .sign(&signed_bytes(nick, issued_at))
+            .context("signing the nick certificate failed")?;
+        Ok(Self {
+            nick: nick.to_string(),
+            public_key: data_encoding::BASE64.encode(&id_keys.public().to_protobuf_encoding()),
+            issued_at,
+            signature: data_encoding::BASE64.encode(&signature),
+        })
+    }
+
+    // The peer that issued the certificate, if its signature is valid
+    pub fn verify(&self) -> Result<PeerId, anyhow::Error> {
+        let public_key = data_encoding::BASE64
+            .decode(self.public_key.as_bytes())
+            .context("decoding the public key failed")?;
+        let public_key = PublicKey::from_protobuf_encoding(&public_key)
+            .context("parsing the public key failed")?;
+        let signature = data_encoding::BASE64
+            .decode(self.signature.as_bytes())
+            .context("decoding the signature failed")?;
+        anyhow::ensure!(
+            public_key.verify(&signed_bytes(&self.nick, self.issued_at), &signature),
+            "the signature is invalid"
+        );
+        Ok(public_key.to_peer_id())
+    }
+}
+
+// What a certificate meant for the nick it is about
+pub enum Binding {
+    New,
+    Known,
+    // The nick is already bound to this other peer
+    Conflict(PeerId),
+    // The peer has `MAX_NICKS_PER_PEER` nicks already
+    TooMany,
+}
+
+// The peer each nick was first bound to with a certificate. Trusted on first use and saved in the
+// profile directory, so an impostor can't take over a nick in a later session either. Only the
+// user moves a nick to another peer, after being asked
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NickBindings {
+    // Peer ids by nick
+    nicks: BTreeMap<String, String>,
+    // The conflicting claims the user was asked about during this run, each only once
+    #[serde(skip)]
+    asked: HashSet<(String, PeerId)>,
+}
+
+impl NickBindings {
+    pub fn path() -> Option<PathBuf> {
+        profile::dir().map(|dir| dir.join("nicks.json"))
+    }
+
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let path = match Self::path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Self::default()),
+        };
+
+        let bindings_str = fs::read_to_string(&path)
+            .with_context(|| format!("reading nick bindings file {:?} failed", path))?;
+        serde_json::from_str(&bindings_str)
+            .with_context(|| format!("parsing nick bindings file {:?} failed", path))
+    }
+
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let path = Self::path().context("no config directory available")?;
+        writer::write(&path, serde_json::to_string_pretty(self)?, false)
+            .with_context(|| format!("writing nick bindings file {:?} failed", path))
+    }
+
+    pub fn owner(&self, nick: &str) -> Option<PeerId> {
+        self.nicks
+            .get(nick)
+            .and_then(|peer_id| peer_id.parse().ok())
+    }
+
+    fn nick_count(&self, peer_id: &PeerId) -> usize {
+        let peer_id = peer_id.to_base58();
+        self.nicks
+            .values()
+            .filter(|owner| **owner == peer_id)
+            .count()
+    }
+
+    pub fn bind(&mut self, nick: &str, peer_id: PeerId) -> Binding {
+        match self.owner(nick) {
+            Some(owner) if owner == peer_id => Binding::Known,
+            Some(owner) => Binding::Conflict(owner),
+            None if self.nick_count(&peer_id) >= MAX_NICKS_PER_PEER => Binding::TooMany,
+            None => {
+                self.nicks.insert(nick.to_string(), peer_id.to_base58());
+                Binding::New
+            }
+        }
+    }
+
+    // Binds a nick to another peer than the one it was bound to, once the user agreed
+    pub fn rebind(&mut self, nick: &str, peer_id: PeerId) {
+        self.nicks.insert(nick.to_string(), peer_id.to_base58());
+    }
+
+    // Returns the peer the nick was bound to
+    pub fn unbind(&mut self, nick: &str) -> Option<PeerId> {
+        self.nicks
+            .remove(nick)
+            .and_then(|peer_id| peer_id.parse().ok())
+    }
+
+    // Unbinds all nicks of a peer
+    fn release(&mut self, peer_id: &PeerId) {
+        let peer_id = peer_id.to_base58();
+        self.nicks.retain(|_, owner| *owner != peer_id);
+    }
+
+    // Whether a message uses a nick that is bound to another peer than its author
+    pub fn is_impostor(&self, message: &ChatMessage) -> bool {
+        match (message.nick.as_ref(), message.source_peer_id) {
+            (Some(nick), Some(source)) => self.owner(nick).map_or(false, |owner| owner != source),
+            _ => false,
+        }
+    }
+}
+
+// Sends a certificate for our nick to every topic we are in, so its members can bind it to us
+pub fn announce(app: &mut App) {
+    let nick = app.ui.nick_input.as_str().to_string();
+    if nick.is_empty() {
+        return;
+    }
+    let certificate = match NickCertificate::issue(&nick, &app.connection.id_keys) {
+        Ok(certificate) => certificate,
+        Err(e) => {
+            app.connection.push_log(
+                LogLevel::Error,
+                LogCategory::Security,
+                &trf(Msg::LogNickCertificateFailed, &[&e]),
+            );
+            return;
+        }
+    };
+    // We trust ourselves, our earlier nicks make room for the new one
+    let local_peer_id = *app.connection.swarm.local_peer_id();
+    match app.nick_bindings.bind(&nick, local_peer_id) {
+        Binding::New => save(app),
+        Binding::TooMany => {
+            app.nick_bindings.release(&local_peer_id);
+            app.nick_bindings.bind(&nick, local_peer_id);
+            save(app);
+        }
+        Binding::Known | Binding::Conflict(_) => {}
+    }
+
+    let topics = app
+        .connection
+        .topics
+        .topics()
+        .cloned()
+        .collect::<Vec<String>>();
+    for topic in topics {
+        app.send_payload_logged(
+            &ConversationId::Topic(topic),
+            Payload::NickCertificate(certificate.clone()),
+        );
+    }
+}
+
+// Binds the nick of a certificate to the peer that signed it, unless another peer has it already
+pub fn receive(certificate: NickCertificate, source: Option<PeerId>, app: &mut App) {
+    let issuer = match certificate.verify() {
+        // Only the peer itself may send its certificate, forwarded ones could be stale
+        Ok(issuer) if source.map_or(true, |source| source == issuer) => issuer,
+        Ok(issuer) => {
+            app.connection.push_log(
+                LogLevel::Warn,
+                LogCategory::Security,
+                &trf(
+                    Msg::LogNickCertificateForwarded,
+                    &[&certificate.nick, &issuer],
+                ),
+            );
+            return;
+        }
+        Err(e) => {
+            let source = source.map_or(tr(Msg::UnknownSource).to_string(), |source| {
+                source.to_base58()
+            });
+            app.connection.push_log(
+                LogLevel::Warn,
+                LogCategory::Security,
+                &trf(
+                    Msg::LogNickCertificateInvalid,
+                    &[&certificate.nick, &source, &e],
+                ),
+            );
+            return;
+        }
+    };
+
+    match app.nick_bindings.bind(&certificate.nick, issuer) {
+        Binding::Known => {}
+        Binding::New => {
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::Security,
+                &trf(Msg::LogNickBound, &[&certificate.nick, &issuer]),
+            );
+            save(app);
+        }
+        Binding::TooMany => {
+            app.connection.push_log(
+                LogLevel::Warn,
+                LogCategory::Security,
+                &trf(
+                    Msg::LogNickTooMany,
+                    &[&issuer, &MAX_NICKS_PER_PEER, &certificate.nick],
+                ),
+            );
+        }
+        // The first claim isn't necessarily the right one, e.g. when someone signed another
+        // peer's nick before that peer showed up. The user decides
+        Binding::Conflict(owner) => {
+            app.connection.push_log(
+                LogLevel::Warn,
+                LogCategory::Security,
+                &trf(Msg::LogNickImpostor, &[&issuer, &certificate.nick, &owner]),
+            );
+            if !app
+                .nick_bindings
+                .asked
+                .insert((certificate.nick.clone(), issuer))
+            {
+                return;
+            }
+            let issued_at = Local
+                .timestamp_millis_opt(certificate.issued_at)
+                .single()
+                .map_or_else(String::new, |time| {
+                    time.format("%Y-%m-%d %H:%M").to_string()
+                });
+            app.ui.modals.push(Modal::confirm(
+                tr(Msg::NickConflictTitle),
+                &trf(
+                    Msg::NickConflictConfirm,
+                    &[&issuer, &certificate.nick, &issued_at, &owner],
+                ),
+                ModalAction::RebindNick {
+                    nick: certificate.nick,
+                    peer_id: issuer,
+                },
+            ));
+        }
+    }
+}
+
+// Moves a nick to the peer the user picked in a conflict
+pub fn rebind(nick: &str, peer_id: PeerId, app: &mut App) {
+    app.nick_bindings.rebind(nick, peer_id);
+    app.connection.push_log(
+        LogLevel::Info,
+        LogCategory::Security,
+        &trf(Msg::LogNickBound, &[&nick, &peer_id]),
+    );
+    save(app);
+}
+
+// For `/nick unbind`, the next certificate for the nick binds it again
+pub fn unbind(nick: &str, app: &mut App) -> Result<(), anyhow::Error> {
+    let owner = app
+        .nick_bindings
+        .unbind(nick)
+        .with_context(|| format!("the nick {} isn't bound to anyone", nick))?;
+    app.connection.push_log(
+        LogLevel::Info,
+        LogCategory::Security,
+        &trf(Msg::LogNickUnbound, &[&nick, &owner]),
+    );
+    save(app);
+    Ok(())
+}
+
+fn save(app: &mut App) {
+    if let Err(e) = app.nick_bindings.save() {
+        app.connection.push_log(
+            LogLevel::Error,
+            LogCategory::General,
+            &trf(Msg::LogSaveNickBindingsFailed, &[&e]),
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use libp2p::PeerId;
+
+use crate::app::ChatMessage;
+
+// Which peer uses which nick, learned from their messages. Nicks aren't unique: the first peer
+// seen with a nick keeps it, later ones are shown as `nick~2`, `nick~3`, ..
+#[derive(Debug, Default)]
+pub struct NickRegistry {
+    // The current nick of each peer, in the order they were first seen with it
+    claims: Vec<(PeerId, String)>,
+}
+
+impl NickRegistry {
+    // Records the nick a peer uses. Returns the other peers already using it when the peer
+    // just started to
+    pub fn record(&mut self, peer_id: PeerId, nick: &str) -> Vec<PeerId> {
+        if let Some(position) = self
+            .claims
+            .iter()
+            .position(|(claimer, _)| *claimer == peer_id)
+        {
+            if self.claims[position].1 == nick {
+                return vec![];
+            }
+            self.claims.remove(position);
+        }
+
+        let others = self.peers_with(nick);
+        self.claims.push((peer_id, nick.to_string()));
+        others
+    }
+
+    // Every peer with its current nick
+    pub fn claims(&self) -> impl Iterator<Item = &(PeerId, String)> {
+        self.claims.iter()
+    }
+
+    // The peers using a nick, the first claimer first
+    pub fn peers_with(&self, nick: &str) -> Vec<PeerId> {
+        self.claims
+            .iter()
+            .filter(|(_, claimed)| claimed == nick)
+            .map(|(claimer, _)| *claimer)
+            .collect()
+    }
+
+    // The nick as shown for a peer, with a suffix if another peer claimed it first
+    pub fn display(&self, peer_id: &PeerId, nick: &str) -> String {
+        match self
+            .peers_with(nick)
+            .iter()
+            .position(|claimer| claimer == peer_id)
+        {
+            Some(index) if index > 0 => format!("{}~{}", nick, index + 1),
+            _ => nick.to_string(),
+        }
+    }
+
+    // The current nick of a peer as shown by `display()`
+    pub fn name_of(&self, peer_id: &PeerId) -> Option<String> {
+        self.claims
+            .iter()
+            .find(|(claimer, _)| claimer == peer_id)
+            .map(|(_, nick)| self.display(peer_id, nick))
+    }
+
+    // Like `ChatMessage::author()`, but with clashing nicks told apart
+    pub fn author(&self, message: &ChatMessage) -> String {
+        match (message.nick.as_ref(), message.source_peer_id.as_ref()) {
+            (Some(nick), Some(peer_id)) => self.display(peer_id, nick),
+            _ => message.author(),
+        }
+    }
+
+    // The peers a name as shown by `display()` may refer to. `nick~2` is exactly one peer,
+    // a plain nick all peers using it
+    pub fn lookup(&self, name: &str) -> Vec<PeerId> {
+        if let Some((nick, index)) = name.rsplit_once('~') {
+            if let Ok(index) = index.parse::<usize>() {
+                return self
+                    .peers_with(nick)
+                    .get(index.saturating_sub(1))
+                    .into_iter()
+                    .copied()
+                    .collect();
+            }
+        }
+
+        self.peers_with(name)
+    }
+}
@@ -0,0 +1,74 @@
+use std::io::{self, Write};
+
+use crate::app::{ChatMessage, MessageKind};
+use crate::config::{Bell, Config};
+use crate::conversation::ConversationId;
+
+// How much an incoming message asks for the user's attention
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Attention {
+    // Not counted at all, e.g. join/leave notices
+    Ignore,
+    // Counted as unread, but not highlighted, e.g. in muted topics
+    Silent,
+    Unread,
+    // Contains our nick or one of the topic's highlight words
+    Mention,
+}
+
+pub fn attention(
+    config: &Config,
+    id: &ConversationId,
+    message: &ChatMessage,
+    nick: &str,
+) -> Attention {
+    if message.kind != MessageKind::Chat {
+        return Attention::Ignore;
+    }
+
+    match id {
+        ConversationId::Direct(_) => Attention::Mention,
+        ConversationId::Topic(topic) => {
+            let settings = config.topic_settings(topic);
+            if settings.muted {
+                Attention::Silent
+            } else if is_mention(&message.text, nick, &settings.highlight_words) {
+                Attention::Mention
+            } else {
+                Attention::Unread
+            }
+        }
+    }
+}
+
+// Whether a message with this attention rings the bell, per the topic's or the global setting
+pub fn rings_bell(config: &Config, id: &ConversationId, attention: Attention) -> bool {
+    let bell = match id {
+        ConversationId::Topic(topic) => config.topic_settings(topic).bell.unwrap_or(config.bell),
+        ConversationId::Direct(_) => config.bell,
+    };
+
+    match bell {
+        Bell::Never => false,
+        Bell::Mentions => attention == Attention::Mention && matches!(id, ConversationId::Topic(_)),
+        Bell::Direct => attention == Attention::Mention,
+        Bell::All => attention >= Attention::Unread,
+    }
+}
+
+// BEL makes the terminal beep or flash, or highlight the pane it's in
+pub fn ring_bell() {
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(b"\x07");
+    let _ = stdout.flush();
+}
+
+// Whether the text contains our nick or one of the highlight words, ignoring case
+pub fn is_mention(text: &str, nick: &str, highlight_words: &[String]) -> bool {
+    let text = text.to_lowercase();
+
+    (!nick.is_empty() && text.contains(&nick.to_lowercase()))
+        || highlight_words
+            .iter()
+            .any(|word| !word.is_empty() && text.contains(&word.to_lowercase()))
+}
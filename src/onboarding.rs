@@ -0,0 +1,133 @@
+use crate::app::App;
+use crate::connection::ConnectionCommand;
+use crate::conversation::ConversationId;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::i18n::{tr, trf, Msg};
+use crate::identity;
+use crate::modal::{Modal, ModalAction, ModalKind, ModalResult};
+use crate::transport;
+
+// The setup shown on the first start, one modal per step. Cancelling a step keeps its default
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnboardingStep {
+    Nick,
+    Identity,
+    Topic,
+    BootstrapPeer,
+}
+
+const STEPS: &[OnboardingStep] = &[
+    OnboardingStep::Nick,
+    OnboardingStep::Identity,
+    OnboardingStep::Topic,
+    OnboardingStep::BootstrapPeer,
+];
+
+pub fn start(app: &mut App) {
+    app.ui.modals.push(step_modal(STEPS[0], app));
+}
+
+fn step_modal(step: OnboardingStep, app: &App) -> Modal {
+    let number = STEPS.iter().position(|s| *s == step).unwrap_or_default() + 1;
+    let title = trf(Msg::OnboardingTitle, &[&number, &STEPS.len()]);
+    let action = ModalAction::Onboarding(step);
+
+    match step {
+        OnboardingStep::Nick => Modal::prompt(&title, tr(Msg::OnboardingNick), action),
+        OnboardingStep::Identity => Modal::select(
+            &title,
+            vec![
+                tr(Msg::OnboardingIdentityPersistent).to_string(),
+                tr(Msg::OnboardingIdentityEphemeral).to_string(),
+            ],
+            action,
+        ),
+        OnboardingStep::Topic => {
+            let mut modal = Modal::prompt(&title, tr(Msg::OnboardingTopic), action);
+            if let ModalKind::Prompt { input } = &mut modal.kind {
+                input.set(app.config.default_topic.clone());
+            }
+            modal
+        }
+        OnboardingStep::BootstrapPeer => {
+            Modal::prompt(&title, tr(Msg::OnboardingBootstrapPeer), action)
+        }
+    }
+}
+
+// Applies a step and moves on to the next one, writing the config after the last
+pub fn handle_step_result(
+    step: OnboardingStep,
+    result: ModalResult,
+    app: &mut App,
+) -> Result<(), anyhow::Error> {
+    // A failed step shouldn't keep the others from being shown
+    if let Err(e) = apply_step_result(step, result, app) {
+        app.connection.push_log(
+            LogLevel::Error,
+            LogCategory::General,
+            &trf(Msg::LogOnboardingStepFailed, &[&e]),
+        );
+    }
+
+    let next = STEPS.iter().skip_while(|s| **s != step).nth(1).copied();
+    match next {
+        Some(next) => {
+            let modal = step_modal(next, app);
+            app.ui.modals.push(modal);
+        }
+        None => {
+            app.config.save()?;
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::General,
+                tr(Msg::LogOnboardingDone),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn apply_step_result(
+    step: OnboardingStep,
+    result: ModalResult,
+    app: &mut App,
+) -> Result<(), anyhow::Error> {
+    match (step, result) {
+        (OnboardingStep::Nick, ModalResult::Submitted(nick)) => {
+            let nick = nick.trim();
+            if !nick.is_empty() {
+                app.config.nick = Some(nick.to_string());
+                app.ui.nick_input.set(nick.to_string());
+            }
+        }
+        (OnboardingStep::Identity, ModalResult::Selected(i)) => {
+            app.config.persistent_identity = i == 0;
+            if app.config.persistent_identity {
+                identity::save(&app.connection.id_keys)?;
+            }
+        }
+        (OnboardingStep::Topic, ModalResult::Submitted(topic)) => {
+            let topic = topic.trim();
+            if !topic.is_empty() && topic != app.config.default_topic {
+                app.connection
+                    .outbox
+                    .push(ConnectionCommand::Subscribe(topic.to_string()));
+                app.config.default_topic = topic.to_string();
+                app.open_conversation(ConversationId::Topic(topic.to_string()));
+            }
+        }
+        (OnboardingStep::BootstrapPeer, ModalResult::Submitted(addr)) => {
+            let addr = addr.trim();
+            if !addr.is_empty() {
+                let addr = transport::parse_addr(addr)?;
+                app.config.bootstrap_peers.push(addr.clone());
+                app.connection.outbox.push(ConnectionCommand::Dial(addr));
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
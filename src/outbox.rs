@@ -0,0 +1,85 @@
+use futures::channel::mpsc;
+use libp2p::PeerId;
+
+use crate::connection::ConnectionCommand;
+use crate::protocol::Envelope;
+
+// The most messages waiting to be published or sent, more are refused until the queue drained
+const CAPACITY: usize = 256;
+// From this many messages waiting in the queue or for a peer to answer on, the network counts as
+// busy, see `Connection::is_busy`
+pub const BUSY: usize = 16;
+
+// Everything that changes the swarm is queued here as a `ConnectionCommand` instead of calling
+// into it where it is asked for, e.g. in the input handler. The event loop handles the commands
+// in order between network events, so a slow network holds up the queue instead of the UI
+pub struct Outbox {
+    // Shared by publishes and direct sends
+    next_id: u64,
+    // Publish and send commands not handled yet
+    waiting: usize,
+    tx: mpsc::UnboundedSender<ConnectionCommand>,
+    pub rx: mpsc::UnboundedReceiver<ConnectionCommand>,
+}
+
+impl Default for Outbox {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::unbounded();
+        Self {
+            next_id: 0,
+            waiting: 0,
+            tx,
+            rx,
+        }
+    }
+}
+
+impl Outbox {
+    pub fn push(&mut self, command: ConnectionCommand) {
+        if let ConnectionCommand::Publish { .. } | ConnectionCommand::Send { .. } = command {
+            self.waiting += 1;
+        }
+        // We hold the receiver, so the channel can't be closed
+        let _ = self.tx.unbounded_send(command);
+    }
+
+    // Queues a message to a topic, returns the id to match the result of publishing it with
+    pub fn publish(&mut self, topic: &str, data: Vec<u8>) -> Result<u64, anyhow::Error> {
+        self.check_capacity()?;
+        self.next_id += 1;
+        self.push(ConnectionCommand::Publish {
+            id: self.next_id,
+            topic: topic.to_string(),
+            data,
+        });
+        Ok(self.next_id)
+    }
+
+    // Queues a request to a peer, returns the id to match the request sent for it with
+    pub fn send(&mut self, peer_id: PeerId, envelope: Envelope) -> Result<u64, anyhow::Error> {
+        self.check_capacity()?;
+        self.next_id += 1;
+        self.push(ConnectionCommand::Send {
+            id: self.next_id,
+            peer_id,
+            envelope,
+        });
+        Ok(self.next_id)
+    }
+
+    fn check_capacity(&self) -> Result<(), anyhow::Error> {
+        if self.waiting >= CAPACITY {
+            anyhow::bail!("the network is busy, {} messages are waiting", self.waiting);
+        }
+        Ok(())
+    }
+
+    // Called when a publish or send command was taken from the queue
+    pub fn taken(&mut self) {
+        self.waiting = self.waiting.saturating_sub(1);
+    }
+
+    pub fn waiting(&self) -> usize {
+        self.waiting
+    }
+}
@@ -0,0 +1,278 @@
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::Context;
+use futures::channel::mpsc;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::app::App;
+use crate::conversation::ConversationId;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::i18n::{trf, Msg};
+use crate::validation;
+
+// git is killed after this long, e.g. when it waits for an editor
+const GIT_TIMEOUT: Duration = Duration::from_secs(10);
+// The changed files listed when asking whether to apply a patch
+const MAX_LISTED_FILES: usize = 10;
+
+// The line `git format-patch` starts every commit with
+const COMMIT_MARKER: &str = "Mon Sep 17 00:00:00 2001";
+
+// Commits made with `git format-patch`, as one mailbox that `git am` applies
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Patch {
+    pub range: String,
+    pub commits: usize,
+    pub text: String,
+}
+
+impl Patch {
+    // `range` is anything `git format-patch` takes, e.g. `HEAD~2` or `main..feature`
+    async fn create(range: String) -> Result<Self, anyhow::Error> {
+        let output = run_git(&["format-patch", "--stdout", &range], None).await?;
+        let text = String::from_utf8(output)
+            .context("the patch isn't UTF-8, binary changes can't be sent")?;
+        let commits = count_commits(&text);
+        anyhow::ensure!(commits > 0, "`{}` has no commits", range);
+        anyhow::ensure!(
+            text.len() <= validation::MAX_PATCH_LEN,
+            "the patch takes {} bytes, at most {} can be sent",
+            text.len(),
+            validation::MAX_PATCH_LEN
+        );
+        Ok(Self {
+            range,
+            commits,
+            text,
+        })
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.text.lines().map(String::from).collect()
+    }
+
+    // Counted in the mailbox itself, `commits` is what the sender claims
+    pub fn count_commits(&self) -> usize {
+        count_commits(&self.text)
+    }
+
+    // The files the commits change, each once
+    pub fn changed_files(&self) -> Vec<String> {
+        let mut files = Vec::<String>::new();
+        for file in self
+            .text
+            .lines()
+            .filter_map(|line| line.strip_prefix("diff --git a/"))
+            .filter_map(|paths| paths.split(" b/").next())
+        {
+            if !files.iter().any(|known| known == file) {
+                files.push(file.to_string());
+            }
+        }
+        files
+    }
+
+    // E.g. `src/app.rs, src/ui.rs and 3 more`
+    pub fn changed_files_summary(&self) -> String {
+        let files = self.changed_files();
+        let listed = files
+            .iter()
+            .take(MAX_LISTED_FILES)
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(", ");
+        match files.len().saturating_sub(MAX_LISTED_FILES) {
+            0 => listed,
+            more => trf(Msg::PatchMoreFiles, &[&listed, &more]),
+        }
+    }
+}
+
+// Applies the commits to the repository in the working directory. A failed `git am` is aborted,
+// so the repository isn't left in the middle of it
+async fn apply_text(text: String) -> Result<(), anyhow::Error> {
+    if let Err(e) = run_git(&["am"], Some(&text)).await {
+        let _ = run_git(&["am", "--abort"], None).await;
+        return Err(e);
+    }
+    Ok(())
+}
+
+fn count_commits(text: &str) -> usize {
+    text.lines()
+        .filter(|line| line.starts_with("From ") && line.ends_with(COMMIT_MARKER))
+        .count()
+}
+
+// Runs git in the working directory and returns its stdout, its first line of stderr is the error
+// if it fails
+async fn run_git(args: &[&str], stdin: Option<&str>) -> Result<Vec<u8>, anyhow::Error> {
+    let mut child = Command::new("git")
+        .args(args)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Dropping `run` on timeout kills git
+        .kill_on_drop(true)
+        .spawn()
+        .context("starting `git` failed")?;
+    let child_stdin = child.stdin.take();
+
+    // Writing while reading, so git can't block on a full pipe
+    let write = async move {
+        if let (Some(mut child_stdin), Some(text)) = (child_stdin, stdin) {
+            child_stdin.write_all(text.as_bytes()).await?;
+        }
+        Ok::<(), std::io::Error>(())
+    };
+    let run = async { tokio::try_join!(write, child.wait_with_output()) };
+    let ((), output) = tokio::time::timeout(GIT_TIMEOUT, run)
+        .await
+        .map_err(|_| anyhow::anyhow!("`git {}` didn't finish in time and was killed", args[0]))?
+        .with_context(|| format!("running `git {}` failed", args[0]))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!(
+            "`git {}` failed with {}: {}",
+            args[0],
+            output.status,
+            stderr.lines().next().unwrap_or_default()
+        );
+    }
+    Ok(output.stdout)
+}
+
+// What a git run in the background ended with
+#[derive(Debug)]
+pub enum GitDone {
+    Created {
+        conversation_id: ConversationId,
+        patch: Result<Patch, anyhow::Error>,
+    },
+    Applied {
+        commits: usize,
+        range: String,
+        result: Result<(), anyhow::Error>,
+    },
+}
+
+// Runs git off the event loop, one command at a time so two runs can't get in each other's way.
+// What it ended with comes back through `done_rx`
+#[derive(Debug)]
+pub struct Git {
+    running: bool,
+    done_tx: mpsc::UnboundedSender<GitDone>,
+    pub done_rx: mpsc::UnboundedReceiver<GitDone>,
+}
+
+impl Default for Git {
+    fn default() -> Self {
+        let (done_tx, done_rx) = mpsc::unbounded();
+        Self {
+            running: false,
+            done_tx,
+            done_rx,
+        }
+    }
+}
+
+impl Git {
+    fn spawn(
+        &mut self,
+        run: impl std::future::Future<Output = GitDone> + Send + 'static,
+    ) -> Result<(), anyhow::Error> {
+        anyhow::ensure!(!self.running, "git is still running");
+        self.running = true;
+        let done_tx = self.done_tx.clone();
+        tokio::spawn(async move {
+            let _ = done_tx.unbounded_send(run.await);
+        });
+        Ok(())
+    }
+}
+
+// Sends the commits of `range` to the current conversation, once git made the patch
+pub fn send(app: &mut App, range: &str) -> Result<(), anyhow::Error> {
+    // Otherwise it would be an option
+    anyhow::ensure!(
+        !range.is_empty() && !range.starts_with('-'),
+        "invalid range `{}`",
+        range
+    );
+    let conversation_id = app.ui.current_conversation.clone();
+    let range = range.to_string();
+    app.git.spawn(async move {
+        GitDone::Created {
+            conversation_id,
+            patch: Patch::create(range).await,
+        }
+    })
+}
+
+// Applies a received patch, the result is logged once git is done
+pub fn apply(patch: &Patch, app: &mut App) {
+    let (commits, range, text) = (
+        patch.count_commits(),
+        patch.range.clone(),
+        patch.text.clone(),
+    );
+    let spawned = app.git.spawn(async move {
+        GitDone::Applied {
+            commits,
+            range,
+            result: apply_text(text).await,
+        }
+    });
+    if let Err(e) = spawned {
+        app.connection.push_log(
+            LogLevel::Error,
+            LogCategory::General,
+            &trf(Msg::LogPatchFailed, &[&e]),
+        );
+    }
+}
+
+// Continues once git is done
+pub fn finish(done: GitDone, app: &mut App) {
+    app.git.running = false;
+    match done {
+        GitDone::Created {
+            conversation_id,
+            patch: Ok(patch),
+        } => {
+            if let Err(e) = app.send_patch(conversation_id, patch) {
+                app.connection.push_log(
+                    LogLevel::Error,
+                    LogCategory::General,
+                    &trf(Msg::LogPatchSendFailed, &[&e]),
+                );
+            }
+        }
+        GitDone::Created { patch: Err(e), .. } => app.connection.push_log(
+            LogLevel::Error,
+            LogCategory::General,
+            &trf(Msg::LogPatchSendFailed, &[&e]),
+        ),
+        GitDone::Applied {
+            commits,
+            range,
+            result: Ok(()),
+        } => app.connection.push_log(
+            LogLevel::Info,
+            LogCategory::General,
+            &trf(Msg::LogPatchApplied, &[&commits, &range]),
+        ),
+        GitDone::Applied { result: Err(e), .. } => app.connection.push_log(
+            LogLevel::Error,
+            LogCategory::General,
+            &trf(Msg::LogPatchFailed, &[&e]),
+        ),
+    }
+}
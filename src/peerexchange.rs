@@ -0,0 +1,197 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::{Duration, Instant};
+
+use libp2p::gossipsub::{IdentTopic, TopicHash};
+use libp2p::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
+
+use crate::app::App;
+use crate::connection::ConnectionCommand;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::i18n::{trf, Msg};
+use crate::protocol::{Envelope, Payload};
+use crate::utils;
+
+// Peers with `peer_exchange` enabled tell each other on this topic where they reached others,
+// so the mesh stays connected without a DHT
+pub const TOPIC: &str = "p2pchat-peer-exchange";
+pub const INTERVAL: Duration = Duration::from_secs(5 * 60);
+// The most addresses in one message, more are rejected
+pub const MAX_ADDRS: usize = 8;
+// The most shared addresses of one peer dialed per `INTERVAL`, so nobody can have us dial
+// addresses without end
+const MAX_DIALS: usize = 2 * MAX_ADDRS;
+
+pub fn topic() -> IdentTopic {
+    IdentTopic::new(TOPIC)
+}
+
+pub fn is_topic(hash: &TopicHash) -> bool {
+    *hash == topic().hash()
+}
+
+// Publishes addresses known to work: ours and the ones we dialed peers we are still connected to
+pub fn share(app: &mut App) {
+    if !app.config.peer_exchange {
+        return;
+    }
+
+    let mut addrs = app.connection.shareable_addrs();
+    addrs.extend(
+        app.connection
+            .dialed_addrs
+            .iter()
+            .filter(|(peer_id, _)| !app.moderation.is_blocked(peer_id))
+            .map(|(peer_id, addr)| utils::with_peer_id(addr.clone(), *peer_id)),
+    );
+    addrs.truncate(MAX_ADDRS);
+    if addrs.is_empty() {
+        return;
+    }
+    app.connection
+        .outbox
+        .push(ConnectionCommand::SharePeers(addrs));
+}
+
+// Runs the queued `SharePeers` command
+pub fn publish(app: &mut App, addrs: Vec<Multiaddr>) {
+    let count = addrs.len();
+    let result = Envelope::new(Payload::Peers { addrs })
+        .encode()
+        .and_then(|data| {
+            app.connection
+                .swarm
+                .behaviour_mut()
+                .gossipsub
+                .publish(topic(), data)
+                .map_err(anyhow::Error::from)
+        });
+    match result {
+        Ok(_) => app.connection.push_log(
+            LogLevel::Debug,
+            LogCategory::Network,
+            &trf(Msg::LogPeersShared, &[&count]),
+        ),
+        // Usually nobody else is on the topic yet
+        Err(e) => app.connection.push_log(
+            LogLevel::Debug,
+            LogCategory::Network,
+            &trf(Msg::LogSharePeersFailed, &[&e]),
+        ),
+    }
+}
+
+fn is_local_ipv4(ip: Ipv4Addr) -> bool {
+    let [first, second, ..] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        // Carrier-grade NAT, 100.64.0.0/10
+        || (first == 100 && second & 0xc0 == 64)
+}
+
+fn is_local_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // Unique local, fc00::/7, and link local, fe80::/10
+        || first & 0xfe00 == 0xfc00
+        || first & 0xffc0 == 0xfe80
+        || ip.to_ipv4_mapped().map_or(false, is_local_ipv4)
+}
+
+fn is_local_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_local_ipv4(ip),
+        IpAddr::V6(ip) => is_local_ipv6(ip),
+    }
+}
+
+// Whether an address points into a local network or at the host itself instead of the internet
+fn is_local(addr: &Multiaddr) -> bool {
+    addr.iter().any(|protocol| match protocol {
+        Protocol::Ip4(ip) => is_local_ip(IpAddr::V4(ip)),
+        Protocol::Ip6(ip) => is_local_ip(IpAddr::V6(ip)),
+        Protocol::Dns(host) | Protocol::Dns4(host) | Protocol::Dns6(host) => {
+            let host = host.to_lowercase();
+            host == "localhost" || host.ends_with(".localhost") || host.ends_with(".local")
+        }
+        _ => false,
+    })
+}
+
+// Whether a peer connected from this address is on our local network. The host itself doesn't
+// count, Tor hands us connections to our onion service from there
+pub fn is_lan(addr: &Multiaddr) -> bool {
+    is_local(addr)
+        && !addr.iter().any(|protocol| match protocol {
+            Protocol::Ip4(ip) => ip.is_loopback(),
+            Protocol::Ip6(ip) => ip.is_loopback(),
+            _ => false,
+        })
+}
+
+// Takes one dial from the budget of a peer, false if it is used up
+fn take_dial(source: PeerId, app: &mut App) -> bool {
+    let (since, dials) = app
+        .connection
+        .exchange_dials
+        .entry(source)
+        .or_insert((Instant::now(), 0));
+    if since.elapsed() >= INTERVAL {
+        *since = Instant::now();
+        *dials = 0;
+    }
+    if *dials >= MAX_DIALS {
+        return false;
+    }
+    *dials += 1;
+    true
+}
+
+// Dials the shared peers we aren't connected to yet. Local addresses are only dialed if the peer
+// sharing them is on our local network too, otherwise anyone could have us connect to services
+// on it
+pub fn handle_peers(source: Option<PeerId>, addrs: Vec<Multiaddr>, app: &mut App) {
+    if !app.config.peer_exchange {
+        return;
+    }
+    // Without a source nobody could be held to a budget
+    let source = match source {
+        Some(source) => source,
+        None => return,
+    };
+
+    let local_peer_id = *app.connection.swarm.local_peer_id();
+    let from_lan = app.connection.lan_peers.contains(&source);
+    for addr in addrs.into_iter().take(MAX_ADDRS) {
+        let peer_id = match addr.iter().last() {
+            Some(Protocol::P2p(hash)) => match PeerId::from_multihash(hash) {
+                Ok(peer_id) => peer_id,
+                Err(_) => continue,
+            },
+            _ => continue,
+        };
+        if peer_id == local_peer_id
+            || peer_id == source
+            || app.connection.swarm.is_connected(&peer_id)
+            || app.moderation.is_blocked(&peer_id)
+            || (is_local(&addr) && !from_lan)
+        {
+            continue;
+        }
+        if !take_dial(source, app) {
+            app.connection.push_log(
+                LogLevel::Debug,
+                LogCategory::Network,
+                &trf(Msg::LogExchangeDialsExceeded, &[&source]),
+            );
+            return;
+        }
+
+        app.connection.outbox.push(ConnectionCommand::Dial(addr));
+    }
+}
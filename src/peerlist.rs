@@ -0,0 +1,161 @@
+use std::time::Duration;
+
+use libp2p::{Multiaddr, PeerId};
+
+use crate::app::App;
+use crate::audit::{self, AuditAction};
+use crate::connection::ConnectionCommand;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::i18n::{tr, trf, Msg};
+use crate::modal::{Modal, ModalAction, PeerAction};
+use crate::utils;
+
+// A peer on the Connection page, one we are or were connected to during this session
+pub struct PeerEntry {
+    pub peer_id: PeerId,
+    pub name: Option<String>,
+    pub connected: bool,
+    pub rtt: Option<Duration>,
+}
+
+impl PeerEntry {
+    // Its nick if it has one, and a short peer id either way
+    pub fn label(&self) -> String {
+        match self.name.as_ref() {
+            Some(name) => format!("{} ({})", name, utils::short_peer_id(&self.peer_id)),
+            None => utils::short_peer_id(&self.peer_id),
+        }
+    }
+}
+
+// Connected peers first, blocked ones aren't shown
+pub fn entries(app: &App) -> Vec<PeerEntry> {
+    let mut entries = app
+        .connection
+        .known_peers
+        .iter()
+        .filter(|peer_id| !app.moderation.is_blocked(peer_id))
+        .map(|peer_id| PeerEntry {
+            peer_id: *peer_id,
+            name: app.nicks.name_of(peer_id),
+            connected: app.connection.swarm.is_connected(peer_id),
+            rtt: app
+                .connection
+                .quality
+                .get(peer_id)
+                .and_then(|quality| quality.rtt),
+        })
+        .collect::<Vec<PeerEntry>>();
+    entries.sort_by_key(|entry| (!entry.connected, entry.peer_id.to_base58()));
+    entries
+}
+
+// Pings and disconnects only make sense while connected
+pub fn actions(entry: &PeerEntry) -> Vec<PeerAction> {
+    if entry.connected {
+        vec![
+            PeerAction::Ping,
+            PeerAction::Redial,
+            PeerAction::Disconnect,
+            PeerAction::Ban,
+        ]
+    } else {
+        vec![PeerAction::Redial, PeerAction::Ban]
+    }
+}
+
+// The action menu of the selected peer
+pub fn open_menu(app: &mut App) {
+    let entry = match entries(app).into_iter().nth(app.ui.peer_list_selected) {
+        Some(entry) => entry,
+        None => return,
+    };
+    let actions = actions(&entry);
+    let options = actions
+        .iter()
+        .map(|action| action.label().to_string())
+        .collect();
+    app.ui.modals.push(Modal::select(
+        &entry.label(),
+        options,
+        ModalAction::PeerMenu {
+            peer_id: entry.peer_id,
+            actions,
+        },
+    ));
+}
+
+// Asks before doing anything to a peer, taken from the menu or a shortcut on the peer list
+pub fn confirm(app: &mut App, peer_id: PeerId, action: PeerAction) {
+    let entry = match entries(app)
+        .into_iter()
+        .find(|entry| entry.peer_id == peer_id)
+    {
+        Some(entry) => entry,
+        None => return,
+    };
+    // A shortcut for pinging or disconnecting a peer that isn't connected anymore
+    if !actions(&entry).contains(&action) {
+        app.connection.push_log(
+            LogLevel::Warn,
+            LogCategory::Network,
+            &trf(Msg::LogPeerNotConnected, &[&entry.label()]),
+        );
+        return;
+    }
+    let message = match action {
+        PeerAction::Disconnect => Msg::DisconnectPeerModalMessage,
+        PeerAction::Ban => Msg::BanPeerModalMessage,
+        PeerAction::Redial => Msg::RedialPeerModalMessage,
+        PeerAction::Ping => Msg::PingPeerModalMessage,
+    };
+    app.ui.modals.push(Modal::confirm(
+        action.label(),
+        &trf(message, &[&entry.label()]),
+        ModalAction::PeerAction { peer_id, action },
+    ));
+}
+
+pub fn run(app: &mut App, peer_id: PeerId, action: PeerAction) {
+    match action {
+        PeerAction::Disconnect => app
+            .connection
+            .outbox
+            .push(ConnectionCommand::Disconnect(peer_id)),
+        PeerAction::Ban => {
+            app.block_peer(peer_id);
+            app.connection.push_log(
+                LogLevel::Info,
+                LogCategory::Network,
+                &trf(Msg::LogBlockedPeer, &[&peer_id]),
+            );
+            audit::record(
+                app,
+                AuditAction::Blocked,
+                Some(peer_id),
+                tr(Msg::AuditReasonPeerList),
+            );
+        }
+        PeerAction::Redial => app
+            .connection
+            .outbox
+            .push(ConnectionCommand::Redial(peer_id)),
+        PeerAction::Ping => app.connection.outbox.push(ConnectionCommand::Ping(peer_id)),
+    }
+}
+
+// Where to dial a peer again: where we reached it, where it listens, or where we reached it in
+// an earlier session
+pub fn redial_addr(app: &App, peer_id: &PeerId) -> Option<Multiaddr> {
+    app.connection
+        .dialed_addrs
+        .get(peer_id)
+        .or_else(|| {
+            app.connection
+                .peer_addrs
+                .get(peer_id)
+                .and_then(|addrs| addrs.first())
+        })
+        .map(|addr| utils::with_peer_id(addr.clone(), *peer_id))
+        .or_else(|| app.recent_addrs.of_peer(peer_id))
+}
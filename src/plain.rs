@@ -0,0 +1,193 @@
+use std::collections::HashSet;
+
+use futures::StreamExt;
+use tokio::io::{self, AsyncBufReadExt, BufReader};
+use uuid::Uuid;
+
+use crate::app::{App, MessageKind};
+use crate::away;
+use crate::commands;
+use crate::connection;
+use crate::conversation::ConversationId;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::events;
+use crate::flood;
+use crate::i18n::{tr, trf, Msg};
+use crate::input::InputTask;
+use crate::kdf;
+use crate::patch;
+use crate::session;
+use crate::snapshot;
+use crate::tick;
+use crate::voice;
+
+// Prints what happened since the last call, one line per log entry or message
+struct Printer {
+    printed_log: usize,
+    printed_messages: HashSet<Uuid>,
+    conversation: Option<ConversationId>,
+}
+
+impl Printer {
+    fn new() -> Self {
+        Self {
+            printed_log: 0,
+            printed_messages: HashSet::new(),
+            conversation: None,
+        }
+    }
+
+    fn print_new(&mut self, app: &App) {
+        // The log is cleared when the connection is regenerated
+        if app.connection.log.len() < self.printed_log {
+            self.printed_log = 0;
+        }
+        for log_entry in app.connection.log[self.printed_log..].iter() {
+            println!("* {}", log_entry);
+        }
+        self.printed_log = app.connection.log.len();
+
+        if self.conversation.as_ref() != Some(&app.ui.current_conversation) {
+            println!(
+                "{}",
+                trf(
+                    Msg::PlainConversationChanged,
+                    &[&app.ui.current_conversation]
+                )
+            );
+            self.conversation = Some(app.ui.current_conversation.clone());
+        }
+
+        for (id, conversation) in app.conversations.iter() {
+            let hide_join_leave = match id {
+                ConversationId::Topic(topic) => app.config.topic_settings(topic).hide_join_leave,
+                ConversationId::Direct(_) => false,
+            };
+            for message in conversation.history.iter() {
+                if !self.printed_messages.insert(message.id) {
+                    continue;
+                }
+                if hide_join_leave && message.kind == MessageKind::Membership {
+                    continue;
+                }
+                if message.kind == MessageKind::System {
+                    if app.config.system_messages {
+                        println!("{} -- {}", id, message.text);
+                    }
+                    continue;
+                }
+                let reply = message
+                    .reply_to
+                    .and_then(|reply_to| conversation.message(reply_to))
+                    .map(|replied| trf(Msg::ReplyPrefix, &[&app.nicks.author(replied)]))
+                    .unwrap_or_default();
+                let impostor = if app.nick_bindings.is_impostor(message) {
+                    format!(" {}", tr(Msg::NickImpostor))
+                } else {
+                    String::new()
+                };
+                println!(
+                    "{} {}{}: {}{}",
+                    id,
+                    app.nicks.author(message),
+                    impostor,
+                    reply,
+                    message.text
+                );
+                if let Some(memo) = message.voice_memo.as_ref() {
+                    println!(
+                        "  {}",
+                        trf(
+                            Msg::VoiceMemoCard,
+                            &[&voice::format_duration(memo.duration_secs)]
+                        )
+                    );
+                }
+                if let Some(snapshot) = message.snapshot.as_ref() {
+                    println!("{}", snapshot.text);
+                }
+                if let Some(patch) = message.patch.as_ref() {
+                    println!("{}", patch.text);
+                }
+                for (i, option) in message.poll.iter().flatten().enumerate() {
+                    println!("  {}. {}", i + 1, option);
+                }
+            }
+        }
+    }
+}
+
+// Line oriented mode for screen readers and dumb terminals. Reads messages and commands from
+// stdin and prints everything that happens, without drawing a UI
+pub async fn run(mut app: App) -> Result<(), anyhow::Error> {
+    let mut printer = Printer::new();
+    let mut lines = BufReader::new(io::stdin()).lines();
+
+    let mut tick_timer = tokio::time::interval(tick::INTERVAL);
+    let mut flood_timer = tokio::time::interval(flood::interval(app.flood.as_ref()));
+
+    println!("{}", tr(Msg::PlainHelp));
+    printer.print_new(&app);
+
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let line = match line? {
+                    Some(line) => line,
+                    // stdin was closed
+                    None => break,
+                };
+                away::handle_activity(&mut app);
+                let line = line.trim_end().to_string();
+                if commands::is_command(&line) {
+                    match commands::execute(&line, &mut app) {
+                        Ok(InputTask::Quit) => break,
+                        Ok(InputTask::Continue) => (),
+                        Err(e) => {
+                            app.connection
+                                .push_log(LogLevel::Error, LogCategory::General, &trf(Msg::LogCommandFailed, &[&line, &e]));
+                        }
+                    }
+                } else if !line.is_empty() {
+                    app.send_chat_message(line);
+                }
+            },
+            connection_event = app.connection.swarm.select_next_some() => {
+                if let Err(e) = connection::handle_connection_event(connection_event, &mut app) {
+                    log::error!("handle_connection_event() failed with Err `{}`", e);
+                }
+            },
+            port_mapping_event = app.connection.port_mapper.events_rx.select_next_some() => {
+                connection::handle_port_mapping_event(port_mapping_event, &mut app);
+            }
+            command = app.connection.outbox.rx.select_next_some() => {
+                if let InputTask::Quit = connection::handle_connection_command(command, &mut app) {
+                    break;
+                }
+            }
+            _ = tick_timer.tick() => {
+                tick::tick(&mut app);
+            }
+            _ = flood_timer.tick() => {
+                flood::send(&mut app);
+            }
+            transformed = app.transforms.done_rx.select_next_some() => {
+                app.finish_transform(transformed);
+            }
+            git_done = app.git.done_rx.select_next_some() => {
+                patch::finish(git_done, &mut app);
+            }
+            kdf_done = app.kdf.done_rx.select_next_some() => {
+                kdf::finish(kdf_done, &mut app);
+            }
+        }
+
+        events::dispatch(&mut app);
+        app.view_current_conversation();
+        printer.print_new(&app);
+    }
+
+    session::save(&app);
+    snapshot::finish();
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use tui::style::{Modifier, Style};
+use tui::text::{Span, Spans};
+
+use crate::i18n::{tr, Msg};
+use crate::markup::Markup;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachmentKind {
+    Image,
+    Video,
+    Audio,
+    Document,
+    Archive,
+}
+
+impl AttachmentKind {
+    fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "png" | "jpg" | "jpeg" | "gif" | "webp" | "bmp" | "svg" => Some(Self::Image),
+            "mp4" | "webm" | "mkv" | "mov" | "avi" => Some(Self::Video),
+            "mp3" | "ogg" | "opus" | "flac" | "wav" | "m4a" => Some(Self::Audio),
+            "pdf" | "txt" | "md" | "doc" | "docx" | "odt" | "csv" => Some(Self::Document),
+            "zip" | "tar" | "gz" | "xz" | "zst" | "7z" => Some(Self::Archive),
+            _ => None,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        tr(match self {
+            Self::Image => Msg::AttachmentImage,
+            Self::Video => Msg::AttachmentVideo,
+            Self::Audio => Msg::AttachmentAudio,
+            Self::Document => Msg::AttachmentDocument,
+            Self::Archive => Msg::AttachmentArchive,
+        })
+    }
+}
+
+// A shared link that points to a file, shown as a card below the message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    pub kind: AttachmentKind,
+    pub name: String,
+    pub host: String,
+}
+
+// The links in a message text that look like files, judged by their extension
+pub fn attachments(text: &str, markup: &Markup) -> Vec<Attachment> {
+    markup
+        .urls(text)
+        .iter()
+        .filter_map(|url| {
+            let without_scheme = url.split_once("://").map_or(url.as_str(), |(_, rest)| rest);
+            let path = without_scheme.split(|c| c == '?' || c == '#').next()?;
+            let (host, path) = path.split_once('/')?;
+            let name = path.rsplit('/').next().filter(|name| !name.is_empty())?;
+            let kind = AttachmentKind::from_extension(name.rsplit_once('.')?.1)?;
+
+            Some(Attachment {
+                kind,
+                name: name.to_string(),
+                host: host.to_string(),
+            })
+        })
+        .collect()
+}
+
+pub fn card_lines(attachment: &Attachment, style: Style) -> Vec<Spans<'static>> {
+    let card_style = style.add_modifier(Modifier::DIM);
+    vec![
+        Spans::from(vec![
+            Span::styled("  ┌ ", card_style),
+            Span::styled(attachment.name.clone(), style.add_modifier(Modifier::BOLD)),
+            Span::styled(format!(" ({})", attachment.kind.label()), card_style),
+        ]),
+        Spans::from(Span::styled(format!("  └ {}", attachment.host), card_style)),
+    ]
+}
@@ -0,0 +1,66 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// Set once from `--profile <name>` at startup, `None` is the default profile
+static PROFILE: Mutex<Option<String>> = Mutex::new(None);
+// Replaces the config directory of the platform, e.g. to keep tests away from the real profiles
+static BASE_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+pub fn set(name: Option<String>) -> Result<(), anyhow::Error> {
+    if let Some(name) = name.as_ref() {
+        anyhow::ensure!(
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+            "profile names may only contain letters, digits, `-` and `_`, got `{}`",
+            name
+        );
+    }
+    *PROFILE.lock().unwrap() = name;
+    Ok(())
+}
+
+pub fn current() -> Option<String> {
+    PROFILE.lock().unwrap().clone()
+}
+
+pub fn set_base_dir(dir: PathBuf) {
+    *BASE_DIR.lock().unwrap() = Some(dir);
+}
+
+fn base_dir() -> Option<PathBuf> {
+    if let Some(dir) = BASE_DIR.lock().unwrap().clone() {
+        return Some(dir);
+    }
+    dirs::config_dir().map(|config_dir| config_dir.join("p2pchat"))
+}
+
+// Where the config and identity of the current profile are stored. The default profile
+// keeps the top level directory, so existing setups stay as they are
+pub fn dir() -> Option<PathBuf> {
+    match current() {
+        Some(name) => base_dir().map(|dir| dir.join("profiles").join(name)),
+        None => base_dir(),
+    }
+}
+
+// The named profiles created so far, sorted
+pub fn list() -> Result<Vec<String>, anyhow::Error> {
+    let profiles_dir = match base_dir() {
+        Some(dir) => dir.join("profiles"),
+        None => return Ok(vec![]),
+    };
+    if !profiles_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut names = fs::read_dir(&profiles_dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect::<Vec<String>>();
+    names.sort();
+    Ok(names)
+}
@@ -0,0 +1,280 @@
+use std::fmt;
+
+use libp2p::Multiaddr;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::app::ChatMessage;
+use crate::crdt::TopicState;
+use crate::nickcert::NickCertificate;
+
+// The version of the message envelope and the network protocols.
+// Bump when making changes older peers can't understand.
+pub const PROTOCOL_VERSION: u32 = 1;
+// Peers below this version can't be talked to meaningfully
+pub const MIN_COMPATIBLE_VERSION: u32 = 0;
+// Peers sending plain chat messages without an envelope
+pub const LEGACY_VERSION: u32 = 0;
+
+pub const IDENTIFY_PROTOCOL_VERSION: &str = "/p2pchat/1.0.0";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Capabilities(pub u32);
+
+impl Capabilities {
+    pub const NONE: Self = Self(0);
+    pub const CHAT: Self = Self(1 << 0);
+    pub const DIRECT: Self = Self(1 << 1);
+    pub const READ_RECEIPTS: Self = Self(1 << 2);
+    pub const REACTIONS: Self = Self(1 << 3);
+    pub const DELETIONS: Self = Self(1 << 4);
+    pub const ENCRYPTED_TOPICS: Self = Self(1 << 5);
+    pub const PRESENCE: Self = Self(1 << 6);
+    pub const PEER_EXCHANGE: Self = Self(1 << 7);
+    pub const NICK_CERTIFICATES: Self = Self(1 << 8);
+    pub const DISAPPEARING: Self = Self(1 << 9);
+    pub const POLLS: Self = Self(1 << 10);
+    pub const VOICE_MEMOS: Self = Self(1 << 11);
+    pub const SNAPSHOTS: Self = Self(1 << 12);
+    pub const PATCHES: Self = Self(1 << 13);
+    pub const CLIPS: Self = Self(1 << 14);
+    pub const DM_ENCRYPTION: Self = Self(1 << 15);
+    pub const PINGS: Self = Self(1 << 16);
+
+    // Everything this build understands
+    pub const SUPPORTED: Self = Self(
+        Self::CHAT.0
+            | Self::DIRECT.0
+            | Self::READ_RECEIPTS.0
+            | Self::REACTIONS.0
+            | Self::DELETIONS.0
+            | Self::ENCRYPTED_TOPICS.0
+            | Self::PRESENCE.0
+            | Self::PEER_EXCHANGE.0
+            | Self::NICK_CERTIFICATES.0
+            | Self::DISAPPEARING.0
+            | Self::POLLS.0
+            | Self::VOICE_MEMOS.0
+            | Self::SNAPSHOTS.0
+            | Self::PATCHES.0
+            | Self::CLIPS.0
+            | Self::DM_ENCRYPTION.0
+            | Self::PINGS.0,
+    );
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Payload {
+    Chat(ChatMessage),
+    // Everything up to and including this message has been read (DMs only)
+    ReadMarker {
+        up_to: Uuid,
+    },
+    Reaction {
+        message_id: Uuid,
+        reaction: String,
+    },
+    // The author took back one of their messages
+    Delete {
+        message_id: Uuid,
+    },
+    // A vote for an option of a poll, replacing the earlier vote of the sender
+    Vote {
+        message_id: Uuid,
+        option: usize,
+    },
+    // Another payload encrypted with the key of an epoch (encrypted topics only)
+    Sealed {
+        epoch: u32,
+        nonce: String,
+        ciphertext: String,
+    },
+    // Our nick and status, sent to every peer so member lists show it before we write, and
+    // again when the status changes (DMs only)
+    Presence {
+        nick: Option<String>,
+        #[serde(default)]
+        status: Status,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        card: Option<StatusCard>,
+    },
+    // The shared description and pinned message of a topic (topics only)
+    TopicState(TopicState),
+    // The key of an epoch of an encrypted topic (DMs only)
+    TopicKey {
+        topic: String,
+        epoch: u32,
+        key: String,
+    },
+    // Addresses of peers the sender could reach (peer exchange topic only)
+    Peers {
+        addrs: Vec<Multiaddr>,
+    },
+    // Our nick signed with our key (topics only)
+    NickCertificate(NickCertificate),
+    // The key peers encrypt their DMs to us with (DMs only)
+    DmKey(DmKey),
+    // Another payload encrypted end to end, with the key of the `counter`th message of the
+    // sender's chain (DMs only)
+    DirectSealed {
+        counter: u64,
+        nonce: String,
+        ciphertext: String,
+    },
+    // Only acknowledged, so the round trip can be measured on request (DMs only)
+    Ping,
+}
+
+// What a peer is up to, shown next to its nick, e.g. `🎧 in a call`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusCard {
+    pub emoji: String,
+    #[serde(default)]
+    pub text: String,
+}
+
+impl fmt::Display for StatusCard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.text.is_empty() {
+            write!(f, "{}", self.emoji)
+        } else {
+            write!(f, "{} {}", self.emoji, self.text)
+        }
+    }
+}
+
+// Whether a peer is at the keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Online,
+    Away,
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self::Online
+    }
+}
+
+// Everything sent over gossipsub is wrapped in an envelope
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    pub version: u32,
+    pub capabilities: Capabilities,
+    pub payload: Payload,
+}
+
+impl Envelope {
+    pub fn new(payload: Payload) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            capabilities: Capabilities::SUPPORTED,
+            payload,
+        }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, anyhow::Error> {
+        Ok(serde_json::to_vec(self)?)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Decoded {
+    Envelope(Envelope),
+    // A payload we don't understand, sent by a peer with a (probably newer) protocol version
+    Unsupported { version: u32 },
+}
+
+pub fn decode(data: &[u8]) -> Result<Decoded, anyhow::Error> {
+    if let Ok(envelope) = serde_json::from_slice::<Envelope>(data) {
+        return Ok(Decoded::Envelope(envelope));
+    }
+
+    // Peers before the envelope was introduced send plain chat messages
+    if let Ok(chat_message) = serde_json::from_slice::<ChatMessage>(data) {
+        return Ok(Decoded::Envelope(Envelope {
+            version: LEGACY_VERSION,
+            capabilities: Capabilities::CHAT,
+            payload: Payload::Chat(chat_message),
+        }));
+    }
+
+    #[derive(Deserialize)]
+    struct VersionOnly {
+        version: u32,
+    }
+    let VersionOnly { version } = serde_json::from_slice::<VersionOnly>(data)?;
+
+    Ok(Decoded::Unsupported { version })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerProtocol {
+    pub app_version: Option<String>,
+    pub version: u32,
+    pub capabilities: Capabilities,
+}
+
+impl PeerProtocol {
+    pub fn is_compatible(&self) -> bool {
+        (MIN_COMPATIBLE_VERSION..=PROTOCOL_VERSION).contains(&self.version)
+    }
+
+    pub fn supports(&self, capabilities: Capabilities) -> bool {
+        self.capabilities.contains(capabilities)
+    }
+}
+
+// The identify agent version, e.g. `p2pchat/0.1.0 (protocol 1; capabilities 0x1)`
+pub fn agent_version() -> String {
+    format!(
+        "p2pchat/{} (protocol {}; capabilities {})",
+        env!("CARGO_PKG_VERSION"),
+        PROTOCOL_VERSION,
+        Capabilities::SUPPORTED
+    )
+}
+
+// Returns `None` for agents which are not p2pchat. Split by hand, it is parsed on every identify
+pub fn parse_agent_version(agent_version: &str) -> Option<PeerProtocol> {
+    let rest = agent_version.strip_prefix("p2pchat/")?;
+    let (app_version, rest) = rest.split_once(" (protocol ")?;
+    let (version, rest) = rest.split_once("; capabilities 0x")?;
+    let (capabilities, _) = rest.split_once(')')?;
+    let is_number = |digits: &str, radix: u32| {
+        !digits.is_empty()
+            && digits
+                .chars()
+                .all(|c| c.is_digit(radix) && !c.is_ascii_uppercase())
+    };
+    if app_version.is_empty()
+        || app_version.contains(char::is_whitespace)
+        || !is_number(version, 10)
+        || !is_number(capabilities, 16)
+    {
+        return None;
+    }
+
+    Some(PeerProtocol {
+        app_version: Some(app_version.to_string()),
+        version: version.parse().ok()?,
+        capabilities: Capabilities(u32::from_str_radix(capabilities, 16).ok()?),
+    })
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use libp2p::PeerId;
+
+// Round trips up to this are perfect, the score drops to 0 at `BAD_RTT`
+const GOOD_RTT: Duration = Duration::from_millis(50);
+const BAD_RTT: Duration = Duration::from_secs(2);
+
+// What we measured about our connection to a peer during this session
+#[derive(Debug, Clone, Default)]
+pub struct PeerQuality {
+    // Smoothed over the recent pings
+    pub rtt: Option<Duration>,
+    pub dials: u32,
+    pub dial_failures: u32,
+    // Direct messages and history requests it answered, or that failed
+    pub delivered: u32,
+    pub delivery_failures: u32,
+}
+
+impl PeerQuality {
+    // From 0 to 100, from the round trip time, dial failures and message delivery. What wasn't
+    // measured yet counts as average
+    pub fn score(&self) -> u8 {
+        let rtt = self.rtt.map_or(0.5, |rtt| {
+            let rtt = rtt.max(GOOD_RTT).min(BAD_RTT);
+            1.0 - (rtt - GOOD_RTT).as_secs_f64() / (BAD_RTT - GOOD_RTT).as_secs_f64()
+        });
+        let dials = success_rate(self.dials, self.dial_failures);
+        let delivery = success_rate(self.delivered, self.delivery_failures);
+
+        (100.0 * (0.4 * rtt + 0.3 * dials + 0.3 * delivery)).round() as u8
+    }
+}
+
+// Starts at one half and approaches the real rate the more was counted
+fn success_rate(succeeded: u32, failed: u32) -> f64 {
+    f64::from(succeeded + 1) / f64::from(succeeded + failed + 2)
+}
+
+#[derive(Debug, Default)]
+pub struct PeerQualities {
+    peers: HashMap<PeerId, PeerQuality>,
+}
+
+impl PeerQualities {
+    pub fn get(&self, peer_id: &PeerId) -> Option<&PeerQuality> {
+        self.peers.get(peer_id)
+    }
+
+    pub fn score(&self, peer_id: &PeerId) -> u8 {
+        self.peers
+            .get(peer_id)
+            .map_or_else(|| PeerQuality::default().score(), PeerQuality::score)
+    }
+
+    pub fn record_rtt(&mut self, peer_id: PeerId, rtt: Duration) {
+        let quality = self.peers.entry(peer_id).or_default();
+        quality.rtt = Some(match quality.rtt {
+            Some(smoothed) => (smoothed * 3 + rtt) / 4,
+            None => rtt,
+        });
+    }
+
+    pub fn record_dial(&mut self, peer_id: PeerId, connected: bool) {
+        let quality = self.peers.entry(peer_id).or_default();
+        if connected {
+            quality.dials += 1;
+        } else {
+            quality.dial_failures += 1;
+        }
+    }
+
+    pub fn record_delivery(&mut self, peer_id: PeerId, delivered: bool) {
+        let quality = self.peers.entry(peer_id).or_default();
+        if delivered {
+            quality.delivered += 1;
+        } else {
+            quality.delivery_failures += 1;
+        }
+    }
+
+    // The peer with the highest score, the first one on a tie
+    pub fn best(&self, peer_ids: impl IntoIterator<Item = PeerId>) -> Option<PeerId> {
+        peer_ids
+            .into_iter()
+            .fold(None, |best: Option<(PeerId, u8)>, peer_id| {
+                let score = self.score(&peer_id);
+                match best {
+                    Some((_, best_score)) if best_score >= score => best,
+                    _ => Some((peer_id, score)),
+                }
+            })
+            .map(|(peer_id, _)| peer_id)
+    }
+}
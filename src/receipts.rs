@@ -0,0 +1,86 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use libp2p::PeerId;
+use uuid::Uuid;
+
+use crate::app::App;
+use crate::utils;
+
+// How many of our messages receipts are kept for, the oldest are dropped
+const CAPACITY: usize = 1024;
+
+// Which peers one of our messages went to, and which of them showed that they got it
+#[derive(Debug, Default)]
+pub struct Receipts {
+    // The peers gossipsub handed it to when it was published, or the peer of a direct message
+    pub sent_to: HashSet<PeerId>,
+    // Peers who acknowledged it, reacted to it or replied to it
+    pub confirmed: HashSet<PeerId>,
+}
+
+impl Receipts {
+    // One line per peer for the detail popup, confirmed ones first
+    pub fn lines(&self, app: &App) -> Vec<String> {
+        let name = |peer_id: &PeerId| {
+            app.nicks
+                .name_of(peer_id)
+                .unwrap_or_else(|| utils::short_peer_id(peer_id))
+        };
+
+        let mut confirmed = self.confirmed.iter().map(name).collect::<Vec<String>>();
+        confirmed.sort_by_key(|name| name.to_lowercase());
+        let mut unconfirmed = self
+            .sent_to
+            .difference(&self.confirmed)
+            .map(name)
+            .collect::<Vec<String>>();
+        unconfirmed.sort_by_key(|name| name.to_lowercase());
+
+        confirmed
+            .into_iter()
+            .map(|name| format!("✓ {}", name))
+            .chain(unconfirmed.into_iter().map(|name| format!("→ {}", name)))
+            .collect()
+    }
+}
+
+// The receipts of the messages we sent during this run
+#[derive(Debug, Default)]
+pub struct ReceiptLog {
+    // Oldest first
+    order: VecDeque<Uuid>,
+    receipts: HashMap<Uuid, Receipts>,
+}
+
+impl ReceiptLog {
+    pub fn get(&self, message_id: &Uuid) -> Option<&Receipts> {
+        self.receipts.get(message_id)
+    }
+
+    pub fn contains_key(&self, message_id: &Uuid) -> bool {
+        self.receipts.contains_key(message_id)
+    }
+
+    // Adds peers a message went to, also when it is sent again
+    pub fn sent(&mut self, message_id: Uuid, sent_to: impl IntoIterator<Item = PeerId>) {
+        if !self.receipts.contains_key(&message_id) {
+            if self.order.len() >= CAPACITY {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.receipts.remove(&oldest);
+                }
+            }
+            self.order.push_back(message_id);
+        }
+        self.receipts
+            .entry(message_id)
+            .or_default()
+            .sent_to
+            .extend(sent_to);
+    }
+
+    pub fn confirm(&mut self, message_id: Uuid, peer_id: PeerId) {
+        if let Some(receipts) = self.receipts.get_mut(&message_id) {
+            receipts.confirmed.insert(peer_id);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use libp2p::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+
+use crate::app::App;
+use crate::connection::ConnectionCommand;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::i18n::{trf, Msg};
+use crate::profile;
+use crate::utils;
+use crate::writer;
+
+// How many addresses of each kind are kept
+const MAX_DIALED: usize = 10;
+const MAX_SEEN: usize = 10;
+
+// Addresses we dialed and addresses peers told us they listen on, newest first and with the peer
+// id. Offered under the address input, and saved in the profile directory so they are still there
+// after a restart
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecentAddrs {
+    dialed: Vec<Multiaddr>,
+    seen: Vec<Multiaddr>,
+}
+
+fn push_front(addrs: &mut Vec<Multiaddr>, addr: Multiaddr, max: usize) {
+    addrs.retain(|known| *known != addr);
+    addrs.insert(0, addr);
+    addrs.truncate(max);
+}
+
+impl RecentAddrs {
+    pub fn path() -> Option<PathBuf> {
+        profile::dir().map(|dir| dir.join("recent_addrs.json"))
+    }
+
+    // Loads the saved addresses, or none if none were saved yet
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let path = match Self::path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Self::default()),
+        };
+
+        let recent_str = fs::read_to_string(&path)
+            .with_context(|| format!("reading recent addresses file {:?} failed", path))?;
+        serde_json::from_str(&recent_str)
+            .with_context(|| format!("parsing recent addresses file {:?} failed", path))
+    }
+
+    // Addresses tell where we were, so only we can read them
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let path = Self::path().context("no config directory available")?;
+        writer::write(&path, serde_json::to_string_pretty(self)?, true)
+            .with_context(|| format!("writing recent addresses file {:?} failed", path))
+    }
+
+    pub fn record_dialed(&mut self, addr: Multiaddr, peer_id: PeerId) {
+        push_front(
+            &mut self.dialed,
+            utils::with_peer_id(addr, peer_id),
+            MAX_DIALED,
+        );
+    }
+
+    pub fn record_seen(&mut self, addr: Multiaddr, peer_id: PeerId) {
+        push_front(&mut self.seen, utils::with_peer_id(addr, peer_id), MAX_SEEN);
+    }
+
+    // The most recent address of a peer
+    pub fn of_peer(&self, peer_id: &PeerId) -> Option<Multiaddr> {
+        self.all()
+            .into_iter()
+            .find(|addr| match addr.iter().last() {
+                Some(Protocol::P2p(hash)) => PeerId::from_multihash(hash).ok() == Some(*peer_id),
+                _ => false,
+            })
+            .cloned()
+    }
+
+    // The dialed addresses first, they are the ones most likely to work
+    pub fn all(&self) -> Vec<&Multiaddr> {
+        let mut all = self.dialed.iter().collect::<Vec<&Multiaddr>>();
+        all.extend(self.seen.iter().filter(|addr| !self.dialed.contains(addr)));
+        all
+    }
+}
+
+// The recent addresses containing what was typed into the address input, shown in its dropdown
+pub fn matching(app: &App) -> Vec<Multiaddr> {
+    let input = app.ui.addr_input.as_str().trim();
+    app.recent_addrs
+        .all()
+        .into_iter()
+        .filter(|addr| addr.to_string().contains(input))
+        .cloned()
+        .collect()
+}
+
+// Called when a dial succeeded
+pub fn dialed(app: &mut App, addr: Multiaddr, peer_id: PeerId) {
+    app.recent_addrs.record_dialed(addr, peer_id);
+    if let Err(e) = app.recent_addrs.save() {
+        app.connection.push_log(
+            LogLevel::Error,
+            LogCategory::General,
+            &trf(Msg::LogSaveRecentAddrsFailed, &[&e]),
+        );
+    }
+}
+
+// Dials the address picked from the dropdown, it is put into the address input too
+pub fn dial(app: &mut App, addr: Multiaddr) {
+    app.ui.addr_input.set(addr.to_string());
+    app.ui.addr_dropdown_selected = None;
+    app.connection.outbox.push(ConnectionCommand::Dial(addr));
+}
@@ -0,0 +1,110 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+
+use crate::profile;
+use crate::writer;
+
+// What we remember about a peer, only for ourselves
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PeerNote {
+    pub note: String,
+    pub tags: BTreeSet<String>,
+}
+
+impl PeerNote {
+    pub fn is_empty(&self) -> bool {
+        self.note.is_empty() && self.tags.is_empty()
+    }
+
+    // The tags as `#tag #other`
+    pub fn tag_list(&self) -> String {
+        self.tags
+            .iter()
+            .map(|tag| format!("#{}", tag))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+// Local notes and tags on peers, saved in the profile directory so we remember who's who across
+// sessions
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Roster {
+    // By peer id
+    peers: BTreeMap<String, PeerNote>,
+}
+
+impl Roster {
+    pub fn path() -> Option<PathBuf> {
+        profile::dir().map(|dir| dir.join("roster.json"))
+    }
+
+    // Loads the saved notes, or an empty roster if none were saved yet
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let path = match Self::path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Self::default()),
+        };
+
+        let roster_str = fs::read_to_string(&path)
+            .with_context(|| format!("reading roster file {:?} failed", path))?;
+        serde_json::from_str(&roster_str)
+            .with_context(|| format!("parsing roster file {:?} failed", path))
+    }
+
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let path = Self::path().context("no config directory available")?;
+        writer::write(&path, serde_json::to_string_pretty(self)?, false)
+            .with_context(|| format!("writing roster file {:?} failed", path))?;
+
+        Ok(())
+    }
+
+    pub fn get(&self, peer_id: &PeerId) -> Option<&PeerNote> {
+        self.peers.get(&peer_id.to_base58())
+    }
+
+    // An empty note removes it, the tags stay
+    pub fn set_note(&mut self, peer_id: &PeerId, note: &str) {
+        self.entry(peer_id).note = note.trim().to_string();
+        self.remove_empty(peer_id);
+    }
+
+    // Adds `tag`, or removes it when prefixed with `-`
+    pub fn tag(&mut self, peer_id: &PeerId, tag: &str) -> Result<(), anyhow::Error> {
+        let (remove, tag) = match tag.strip_prefix('-') {
+            Some(tag) => (true, tag),
+            None => (false, tag),
+        };
+        let tag = tag.trim_start_matches('#').to_lowercase();
+        if tag.is_empty() || tag.chars().any(char::is_whitespace) {
+            anyhow::bail!("invalid tag `{}`", tag);
+        }
+
+        if remove {
+            self.entry(peer_id).tags.remove(&tag);
+        } else {
+            self.entry(peer_id).tags.insert(tag);
+        }
+        self.remove_empty(peer_id);
+        Ok(())
+    }
+
+    fn entry(&mut self, peer_id: &PeerId) -> &mut PeerNote {
+        self.peers.entry(peer_id.to_base58()).or_default()
+    }
+
+    fn remove_empty(&mut self, peer_id: &PeerId) {
+        let key = peer_id.to_base58();
+        if self.peers.get(&key).map_or(false, PeerNote::is_empty) {
+            self.peers.remove(&key);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use anyhow::Context;
+use chrono::{DateTime, Local, NaiveTime, TimeZone};
+
+use crate::app::App;
+use crate::conversation::ConversationId;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::i18n::{trf, Msg};
+
+// A message queued with /schedule, /in or /at
+#[derive(Debug, Clone)]
+pub struct Scheduled {
+    pub id: u32,
+    pub conversation: ConversationId,
+    pub text: String,
+    pub due: DateTime<Local>,
+}
+
+// Messages waiting to be sent. They are only kept while p2pchat is running
+#[derive(Debug, Default)]
+pub struct Schedule {
+    next_id: u32,
+    pending: Vec<Scheduled>,
+}
+
+impl Schedule {
+    // Returns the id to cancel it with
+    pub fn add(&mut self, conversation: ConversationId, text: String, due: DateTime<Local>) -> u32 {
+        self.next_id += 1;
+        self.pending.push(Scheduled {
+            id: self.next_id,
+            conversation,
+            text,
+            due,
+        });
+        self.pending.sort_by_key(|scheduled| scheduled.due);
+        self.next_id
+    }
+
+    pub fn cancel(&mut self, id: u32) -> Option<Scheduled> {
+        let index = self
+            .pending
+            .iter()
+            .position(|scheduled| scheduled.id == id)?;
+        Some(self.pending.remove(index))
+    }
+
+    // Soonest first
+    pub fn pending(&self) -> &[Scheduled] {
+        &self.pending
+    }
+
+    fn take_due(&mut self, now: DateTime<Local>) -> Vec<Scheduled> {
+        let count = self
+            .pending
+            .iter()
+            .take_while(|scheduled| scheduled.due <= now)
+            .count();
+        self.pending.drain(..count).collect()
+    }
+}
+
+// Longer delays are most likely typos
+const MAX_DELAY_SECS: i64 = 365 * 24 * 60 * 60;
+
+// A delay like `90s`, `10m`, `1h30m` or `2d`, at most a year
+pub fn parse_delay(delay: &str) -> Result<chrono::Duration, anyhow::Error> {
+    let mut seconds: i64 = 0;
+    let mut number = String::new();
+    for c in delay.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => anyhow::bail!("unknown unit `{}` in delay `{}`", c, delay),
+        };
+        let value = number
+            .parse::<i64>()
+            .with_context(|| format!("invalid delay `{}`", delay))?;
+        seconds = seconds
+            .checked_add(value.checked_mul(unit).context("delay too long")?)
+            .context("delay too long")?;
+        number.clear();
+    }
+    anyhow::ensure!(
+        number.is_empty() && seconds > 0,
+        "invalid delay `{}`, expected e.g. 10m or 1h30m",
+        delay
+    );
+    anyhow::ensure!(
+        seconds <= MAX_DELAY_SECS,
+        "delay `{}` is too long, at most {} are possible",
+        delay,
+        format_delay(MAX_DELAY_SECS as u64)
+    );
+    chrono::Duration::try_seconds(seconds).context("delay too long")
+}
+
+// The other way around, e.g. `1h30m` for 5400 seconds
+pub fn format_delay(seconds: u64) -> String {
+    let units = [("d", 24 * 60 * 60), ("h", 60 * 60), ("m", 60), ("s", 1)];
+    let mut rest = seconds;
+    let mut delay = String::new();
+    for (unit, unit_seconds) in units {
+        if rest >= unit_seconds {
+            delay.push_str(&format!("{}{}", rest / unit_seconds, unit));
+            rest %= unit_seconds;
+        }
+    }
+    delay
+}
+
+// The next time the clock shows `HH:MM`, today or tomorrow
+pub fn parse_time(time: &str, now: DateTime<Local>) -> Result<DateTime<Local>, anyhow::Error> {
+    let time = NaiveTime::parse_from_str(time, "%H:%M")
+        .with_context(|| format!("invalid time `{}`, expected HH:MM", time))?;
+    let mut date = now.naive_local().date();
+    if date.and_time(time) <= now.naive_local() {
+        date += chrono::Duration::days(1);
+    }
+    Local
+        .from_local_datetime(&date.and_time(time))
+        .earliest()
+        .context("that time doesn't exist in the local time zone")
+}
+
+// Sends the messages that are due, every tick
+pub fn send_due(app: &mut App) {
+    for scheduled in app.schedule.take_due(Local::now()) {
+        app.send_chat_message_to(scheduled.conversation.clone(), scheduled.text, None);
+        app.connection.push_log(
+            LogLevel::Info,
+            LogCategory::Messages,
+            &trf(
+                Msg::LogScheduledSent,
+                &[&scheduled.id, &scheduled.conversation],
+            ),
+        );
+    }
+}
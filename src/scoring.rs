@@ -0,0 +1,120 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+use libp2p::gossipsub::{PeerScoreParams, PeerScoreThresholds, TopicScoreParams};
+use libp2p::PeerId;
+
+use crate::app::App;
+use crate::config::GossipsubSettings;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::i18n::{trf, Msg};
+
+// How often the scores are checked for peers that were graylisted or recovered
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+// Gossipsub peer scoring, so peers sending invalid messages or misbehaving in the mesh are
+// stopped being gossiped with and finally ignored
+pub fn params() -> PeerScoreParams {
+    PeerScoreParams {
+        // Several peers on one machine are common while trying p2pchat out
+        ip_colocation_factor_whitelist: HashSet::from([
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ]),
+        ..PeerScoreParams::default()
+    }
+}
+
+pub fn thresholds(settings: &GossipsubSettings) -> PeerScoreThresholds {
+    PeerScoreThresholds {
+        gossip_threshold: settings.gossip_threshold,
+        publish_threshold: settings.publish_threshold,
+        graylist_threshold: settings.graylist_threshold,
+        ..PeerScoreThresholds::default()
+    }
+}
+
+// Chat topics are quiet most of the time, so peers aren't punished for not delivering enough
+// messages, only for invalid ones. One invalid message stops gossip, two graylist the peer until
+// the counter decayed after a few minutes
+pub fn topic_params() -> TopicScoreParams {
+    TopicScoreParams {
+        topic_weight: 1.0,
+        time_in_mesh_weight: 0.01,
+        time_in_mesh_quantum: Duration::from_secs(1),
+        mesh_message_deliveries_weight: 0.0,
+        mesh_failure_penalty_weight: 0.0,
+        invalid_message_deliveries_weight: -20.0,
+        invalid_message_deliveries_decay: 0.99,
+        ..TopicScoreParams::default()
+    }
+}
+
+// The gossipsub score of a peer, if scoring is enabled
+pub fn score(app: &App, peer_id: &PeerId) -> Option<f64> {
+    app.connection
+        .swarm
+        .behaviour()
+        .gossipsub
+        .peer_score(peer_id)
+}
+
+pub fn is_graylisted(app: &App, peer_id: &PeerId) -> bool {
+    score(app, peer_id).map_or(false, |score| {
+        score < app.config.gossipsub.graylist_threshold
+    })
+}
+
+// The peers gossipsub knows with their scores, the worst first
+pub fn scores(app: &App) -> Vec<(PeerId, f64)> {
+    let mut scores = app
+        .connection
+        .swarm
+        .behaviour()
+        .gossipsub
+        .all_peers()
+        .filter_map(|(peer_id, _)| Some((*peer_id, score(app, peer_id)?)))
+        .collect::<Vec<(PeerId, f64)>>();
+    scores.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    scores
+}
+
+// Logs the peers that were graylisted since the last check, and the ones that recovered. Peers
+// that disconnected are forgotten
+pub fn check(app: &mut App) {
+    let scores = scores(app);
+    let graylisted = scores
+        .iter()
+        .filter(|(_, score)| *score < app.config.gossipsub.graylist_threshold)
+        .map(|(peer_id, _)| *peer_id)
+        .collect::<HashSet<PeerId>>();
+
+    let newly_graylisted = graylisted
+        .difference(&app.connection.graylisted)
+        .copied()
+        .collect::<Vec<PeerId>>();
+    let recovered = app
+        .connection
+        .graylisted
+        .difference(&graylisted)
+        .filter(|peer_id| scores.iter().any(|(known, _)| known == *peer_id))
+        .copied()
+        .collect::<Vec<PeerId>>();
+    for peer_id in newly_graylisted {
+        app.connection.push_log(
+            LogLevel::Warn,
+            LogCategory::Network,
+            &trf(Msg::LogPeerGraylisted, &[&peer_id]),
+        );
+    }
+    for peer_id in recovered {
+        app.connection.push_log(
+            LogLevel::Info,
+            LogCategory::Network,
+            &trf(Msg::LogPeerRecovered, &[&peer_id]),
+        );
+    }
+    app.connection.graylisted = graylisted;
+}
@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+use crate::profile;
+use crate::writer;
+
+// Secrets live in the OS keyring (secret service, keychain, credential manager) when it is
+// available, otherwise in a file only we can read next to the config
+#[cfg(feature = "keyring")]
+const SERVICE: &str = "p2pchat";
+
+// Our identity keypair, base64 encoded
+pub const IDENTITY: &str = "identity";
+// The keys of all encrypted topics, as JSON
+pub const TOPIC_KEYS: &str = "topic-keys";
+// The passphrase of the encrypted session, never written to the fallback file
+pub const SESSION_PASSPHRASE: &str = "session-passphrase";
+
+fn path() -> Option<PathBuf> {
+    profile::dir().map(|dir| dir.join("secrets.json"))
+}
+
+fn load_file() -> Result<BTreeMap<String, String>, anyhow::Error> {
+    let path = match path() {
+        Some(path) if path.exists() => path,
+        _ => return Ok(BTreeMap::new()),
+    };
+
+    let secrets_str = fs::read_to_string(&path)
+        .with_context(|| format!("reading secrets file {:?} failed", path))?;
+    serde_json::from_str(&secrets_str)
+        .with_context(|| format!("parsing secrets file {:?} failed", path))
+}
+
+fn save_file(secrets: &BTreeMap<String, String>) -> Result<(), anyhow::Error> {
+    let path = path().context("no config directory available")?;
+    if secrets.is_empty() {
+        writer::remove(&path)?;
+    } else {
+        // Written to a file that is only readable by us and renamed over the old one
+        writer::write(&path, serde_json::to_string_pretty(secrets)?, true)
+            .with_context(|| format!("writing secrets file {:?} failed", path))?;
+    }
+    // The next `load_file` has to see it, and a lost identity can't be made again
+    writer::flush();
+
+    Ok(())
+}
+
+// Every profile has its own secrets
+#[cfg(feature = "keyring")]
+fn entry(name: &str) -> keyring::Entry {
+    let account = format!(
+        "{}:{}",
+        profile::current().unwrap_or_else(|| String::from("default")),
+        name
+    );
+    keyring::Entry::new(SERVICE, &account)
+}
+
+// `Err` if the keyring can't be used at all, `Ok(None)` if it has no such secret
+#[cfg(feature = "keyring")]
+fn keyring_get(name: &str) -> Result<Option<String>, anyhow::Error> {
+    match entry(name).get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(anyhow::anyhow!("keyring failed with Err `{}`", e)),
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_get(_name: &str) -> Result<Option<String>, anyhow::Error> {
+    anyhow::bail!("built without keyring support")
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_set(name: &str, secret: &str) -> Result<(), anyhow::Error> {
+    entry(name)
+        .set_password(secret)
+        .map_err(|e| anyhow::anyhow!("keyring failed with Err `{}`", e))
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_set(_name: &str, _secret: &str) -> Result<(), anyhow::Error> {
+    anyhow::bail!("built without keyring support")
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_delete(name: &str) -> Result<(), anyhow::Error> {
+    match entry(name).delete_password() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(anyhow::anyhow!("keyring failed with Err `{}`", e)),
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_delete(_name: &str) -> Result<(), anyhow::Error> {
+    Ok(())
+}
+
+pub fn get(name: &str) -> Result<Option<String>, anyhow::Error> {
+    match keyring_get(name) {
+        Ok(Some(secret)) => Ok(Some(secret)),
+        Ok(None) => Ok(load_file()?.remove(name)),
+        Err(e) => {
+            log::debug!("reading secret `{}` from the keyring failed, {}", name, e);
+            Ok(load_file()?.remove(name))
+        }
+    }
+}
+
+// Stores a secret in the keyring, or in the secrets file if that isn't possible
+pub fn set(name: &str, secret: &str) -> Result<(), anyhow::Error> {
+    let mut secrets = load_file()?;
+    match keyring_set(name, secret) {
+        Ok(()) => {
+            // Secrets from before the keyring was available move over to it
+            if secrets.remove(name).is_some() {
+                save_file(&secrets)?;
+            }
+        }
+        Err(e) => {
+            log::warn!(
+                "storing secret `{}` in the keyring failed, using the secrets file. {}",
+                name,
+                e
+            );
+            secrets.insert(name.to_string(), secret.to_string());
+            save_file(&secrets)?;
+        }
+    }
+    Ok(())
+}
+
+// Only stores a secret if the keyring is available, for ones that must not end up in a file
+pub fn set_in_keyring(name: &str, secret: &str) -> Result<(), anyhow::Error> {
+    keyring_set(name, secret)
+}
+
+pub fn delete(name: &str) -> Result<(), anyhow::Error> {
+    // Without a keyring there is nothing to delete from it
+    if let Err(e) = keyring_delete(name) {
+        log::debug!("deleting secret `{}` from the keyring failed, {}", name, e);
+    }
+    let mut secrets = load_file()?;
+    if secrets.remove(name).is_some() {
+        save_file(&secrets)?;
+    }
+    Ok(())
+}
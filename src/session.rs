@@ -0,0 +1,497 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+
+use crate::app::{App, ChatMessage, MessageKind};
+use crate::connection::ConnectionCommand;
+use crate::conversation::{Conversation, ConversationId, ScrollPosition};
+use crate::drafts;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::history;
+use crate::i18n::{tr, trf, Msg};
+use crate::kdf::KdfDone;
+use crate::modal::{Modal, ModalAction};
+use crate::profile;
+use crate::secrets;
+use crate::sync::SyncedMessage;
+use crate::utils;
+use crate::vault::{DerivedKey, Sealed};
+use crate::writer;
+
+// What we were doing when p2pchat was quit, picked up again on the next start when
+// `resume_session` is set
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub topics: Vec<String>,
+    // Peers we had a direct conversation with, by peer id
+    pub direct: Vec<String>,
+    // The nicks of the peers we knew, by peer id
+    pub nicks: BTreeMap<String, String>,
+    // Addresses we dialed successfully, including the peer id
+    pub addrs: Vec<Multiaddr>,
+    // The chat messages of each conversation, by topic and by peer id. Only read from sessions
+    // saved before the history got its own files, see `history`
+    #[serde(skip_serializing)]
+    pub topic_history: BTreeMap<String, Vec<SyncedMessage>>,
+    #[serde(skip_serializing)]
+    pub direct_history: BTreeMap<String, Vec<SyncedMessage>>,
+    // The conversation that was open, and where the others were scrolled to
+    pub current: Option<StoredConversation>,
+    pub topic_scroll: BTreeMap<String, ScrollPosition>,
+    pub direct_scroll: BTreeMap<String, ScrollPosition>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoredConversation {
+    Topic(String),
+    // By peer id
+    Direct(String),
+}
+
+impl StoredConversation {
+    fn of(id: &ConversationId) -> Self {
+        match id {
+            ConversationId::Topic(topic) => Self::Topic(topic.clone()),
+            ConversationId::Direct(peer_id) => Self::Direct(peer_id.to_base58()),
+        }
+    }
+
+    fn id(&self) -> Option<ConversationId> {
+        match self {
+            Self::Topic(topic) => Some(ConversationId::Topic(topic.clone())),
+            Self::Direct(peer_id) => peer_id.parse().ok().map(ConversationId::Direct),
+        }
+    }
+}
+
+fn stored_messages(conversation: &Conversation) -> Vec<SyncedMessage> {
+    conversation
+        .history
+        .iter()
+        .filter(|message| message.kind == MessageKind::Chat)
+        // Disappearing messages are never written down
+        .filter(|message| message.expires_in.is_none())
+        .filter_map(SyncedMessage::of)
+        .collect()
+}
+
+impl Session {
+    pub fn path() -> Option<PathBuf> {
+        profile::dir().map(|dir| dir.join("session.json"))
+    }
+
+    pub fn of(app: &App) -> Self {
+        let direct = app
+            .conversations
+            .keys()
+            .filter_map(|id| match id {
+                ConversationId::Direct(peer_id) => Some(peer_id.to_base58()),
+                ConversationId::Topic(_) => None,
+            })
+            .collect();
+        let nicks = app
+            .nicks
+            .claims()
+            .map(|(peer_id, nick)| (peer_id.to_base58(), nick.clone()))
+            .collect();
+        let addrs = app
+            .connection
+            .dialed_addrs
+            .iter()
+            .map(|(peer_id, addr)| utils::with_peer_id(addr.clone(), *peer_id))
+            .collect();
+
+        let mut topic_scroll = BTreeMap::new();
+        let mut direct_scroll = BTreeMap::new();
+        for (id, conversation) in app.conversations.iter() {
+            // The open conversation's position is only kept in the UI
+            let scroll = if *id == app.ui.current_conversation {
+                ScrollPosition {
+                    selected: app
+                        .ui
+                        .chat_history_selected
+                        .and_then(|selected| conversation.history.get(selected))
+                        .map(|message| message.id),
+                    offset: app.ui.chat_history_offset,
+                }
+            } else {
+                conversation.scroll
+            };
+            if scroll == ScrollPosition::default() {
+                continue;
+            }
+            match id {
+                ConversationId::Topic(topic) => {
+                    topic_scroll.insert(topic.clone(), scroll);
+                }
+                ConversationId::Direct(peer_id) => {
+                    direct_scroll.insert(peer_id.to_base58(), scroll);
+                }
+            }
+        }
+
+        Self {
+            topics: app.connection.topics.topics().cloned().collect(),
+            direct,
+            nicks,
+            addrs,
+            topic_history: BTreeMap::new(),
+            direct_history: BTreeMap::new(),
+            current: Some(StoredConversation::of(&app.ui.current_conversation)),
+            topic_scroll,
+            direct_scroll,
+        }
+    }
+
+    // The saved session if it is encrypted and needs a passphrase to load
+    fn sealed() -> Option<Sealed> {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|session_str| serde_json::from_str::<Sealed>(&session_str).ok())
+    }
+
+    pub fn is_sealed() -> bool {
+        Self::sealed().is_some()
+    }
+
+    // The session of the last run, if one was saved. An encrypted one needs the key derived from
+    // the passphrase
+    pub fn load(key: Option<&DerivedKey>) -> Result<Option<Self>, anyhow::Error> {
+        let path = match Self::path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(None),
+        };
+
+        let session_str = fs::read_to_string(&path)
+            .with_context(|| format!("reading session file {:?} failed", path))?;
+        // Every field of a plain session is optional, so the sealed form has to be tried first
+        let session = match serde_json::from_str::<Sealed>(&session_str) {
+            Ok(sealed) => {
+                let key = key.context("the session file is encrypted")?;
+                let plaintext = key
+                    .open(&sealed)
+                    .with_context(|| format!("opening session file {:?} failed", path))?;
+                serde_json::from_slice(&plaintext)
+            }
+            Err(_) => serde_json::from_str(&session_str),
+        }
+        .with_context(|| format!("parsing session file {:?} failed", path))?;
+        Ok(Some(session))
+    }
+
+    // Encrypts the session if a key is given. A long history takes a while to serialize and
+    // encrypt, so the writer thread does it
+    pub fn save(self, key: Option<DerivedKey>) -> Result<(), anyhow::Error> {
+        let path = Self::path().context("no config directory available")?;
+
+        let contents = move || {
+            let session_str = match key {
+                Some(key) => {
+                    let sealed = key.seal(&serde_json::to_vec(&self)?)?;
+                    serde_json::to_string_pretty(&sealed)?
+                }
+                None => serde_json::to_string_pretty(&self)?,
+            };
+            Ok(session_str.into_bytes())
+        };
+        // The history is only for us to read
+        writer::write_with(&path, contents, true)
+            .with_context(|| format!("writing session file {:?} failed", path))
+    }
+}
+
+// Resumes the last session, first asking for the passphrase if it is encrypted or is going to be
+pub fn start(app: &mut App) -> Result<(), anyhow::Error> {
+    if app.config.encrypt_session || Session::is_sealed() {
+        let stored = if app.config.remember_session_passphrase {
+            secrets::get(secrets::SESSION_PASSPHRASE)?
+        } else {
+            None
+        };
+        app.session_locked = Session::is_sealed();
+        match stored {
+            // Asks for the passphrase if the stored one doesn't open the session
+            Some(passphrase) => unlock(passphrase, true, app)?,
+            None => prompt(app),
+        }
+        return Ok(());
+    }
+
+    match Session::load(None)? {
+        Some(session) => resume(session, app),
+        None => Ok(()),
+    }
+}
+
+fn prompt(app: &mut App) {
+    let message = if app.session_locked {
+        tr(Msg::SessionPassphrase)
+    } else {
+        tr(Msg::SessionNewPassphrase)
+    };
+    app.ui.modals.push(Modal::secret(
+        tr(Msg::SessionUnlockTitle),
+        message,
+        ModalAction::UnlockSession,
+    ));
+}
+
+// Derives the key of the encrypted session from the passphrase of the unlock prompt, or the
+// `stored` one from the keyring, off the event loop. `finish_unlock` opens the session with it
+pub fn unlock(passphrase: String, stored: bool, app: &mut App) -> Result<(), anyhow::Error> {
+    anyhow::ensure!(!passphrase.is_empty(), "the passphrase must not be empty");
+    app.kdf.spawn(move || {
+        // With the salt of the saved session, so the history files sealed with the same key open
+        // without deriving it again for each
+        let key = match Session::sealed() {
+            Some(sealed) => DerivedKey::for_sealed(&passphrase, &sealed),
+            None => DerivedKey::new(&passphrase),
+        };
+        KdfDone::Unlocked {
+            passphrase,
+            stored,
+            key,
+        }
+    })
+}
+
+// Asks for the passphrase again if it didn't open the session, most likely it was a typo
+pub fn finish_unlock(
+    passphrase: String,
+    stored: bool,
+    key: Result<DerivedKey, anyhow::Error>,
+    app: &mut App,
+) {
+    if let Err(e) = key.and_then(|key| open(passphrase, key, app)) {
+        if stored {
+            log::warn!(
+                "unlocking the session with the stored passphrase failed with Err `{}`",
+                e
+            );
+        } else {
+            app.connection.push_log(
+                LogLevel::Error,
+                LogCategory::Security,
+                &trf(Msg::LogUnlockSessionFailed, &[&e]),
+            );
+        }
+        prompt(app);
+    }
+}
+
+// Opens the encrypted session with the key, the passphrase is kept to encrypt it again when
+// quitting
+fn open(passphrase: String, key: DerivedKey, app: &mut App) -> Result<(), anyhow::Error> {
+    let session = Session::load(Some(&key))?;
+    // Only if asked for, anyone who can use our keyring could read the history otherwise
+    if app.config.remember_session_passphrase {
+        if let Err(e) = secrets::set_in_keyring(secrets::SESSION_PASSPHRASE, &passphrase) {
+            log::debug!("storing the session passphrase failed with Err `{}`", e);
+        }
+    }
+    app.session_key = Some(key);
+    app.session_passphrase = Some(passphrase);
+    app.session_locked = false;
+    if let Some(session) = session {
+        resume(session, app)?;
+    }
+    // They are encrypted with the same passphrase
+    if let Err(e) = drafts::restore(app) {
+        app.connection.push_log(
+            LogLevel::Error,
+            LogCategory::General,
+            &trf(Msg::LogRestoreDraftsFailed, &[&e]),
+        );
+    }
+    Ok(())
+}
+
+// Subscribes to the topics of the last session, reopens its direct conversations with their
+// history and dials the peers it was connected to
+fn resume(mut session: Session, app: &mut App) -> Result<(), anyhow::Error> {
+    for topic in session.topics.iter() {
+        app.connection
+            .outbox
+            .push(ConnectionCommand::Subscribe(topic.clone()));
+        app.conversation_mut(&ConversationId::Topic(topic.clone()));
+    }
+    for peer_id in session.direct.iter() {
+        if let Ok(peer_id) = peer_id.parse::<PeerId>() {
+            app.conversation_mut(&ConversationId::Direct(peer_id));
+        }
+    }
+    for (peer_id, nick) in session.nicks.iter() {
+        if let Ok(peer_id) = peer_id.parse::<PeerId>() {
+            app.record_nick(peer_id, nick);
+        }
+    }
+    // The history files are sealed like the session file, so they follow it when
+    // `encrypt_session` was changed
+    let key = key(app).ok().flatten();
+    let reseal = Session::is_sealed() != key.is_some();
+    let ids = app
+        .conversations
+        .keys()
+        .cloned()
+        .collect::<Vec<ConversationId>>();
+    for id in ids {
+        let migrated = match &id {
+            ConversationId::Topic(topic) => session.topic_history.remove(topic),
+            ConversationId::Direct(peer_id) => session.direct_history.remove(&peer_id.to_base58()),
+        };
+        // Only the newest page, the older ones are read when scrolled to
+        let stored =
+            match history::resume(&id, migrated.unwrap_or_default(), reseal, key.as_ref(), app) {
+                Ok(stored) => stored,
+                Err(e) => {
+                    app.connection.push_log(
+                        LogLevel::Error,
+                        LogCategory::Messages,
+                        &trf(Msg::LogLoadHistoryFailed, &[&id, &e]),
+                    );
+                    continue;
+                }
+            };
+        merge_stored(&id, stored.newest, app);
+        let conversation = app.conversation_mut(&id);
+        conversation.stored_pages = stored.older;
+        conversation.resumed_pages = stored.pages;
+        app.apply_retention(&id);
+    }
+    for addr in session.addrs.iter() {
+        app.connection
+            .outbox
+            .push(ConnectionCommand::Dial(addr.clone()));
+    }
+    restore_view(
+        session.current,
+        session.topic_scroll,
+        session.direct_scroll,
+        app,
+    );
+
+    app.connection.push_log(
+        LogLevel::Info,
+        LogCategory::General,
+        &trf(
+            Msg::LogSessionResumed,
+            &[&session.topics.len(), &session.addrs.len()],
+        ),
+    );
+    Ok(())
+}
+
+// Scrolls the conversations back to where they were and opens the one that was open
+fn restore_view(
+    current: Option<StoredConversation>,
+    topic_scroll: BTreeMap<String, ScrollPosition>,
+    direct_scroll: BTreeMap<String, ScrollPosition>,
+    app: &mut App,
+) {
+    let scrolls = topic_scroll
+        .into_iter()
+        .map(|(topic, scroll)| (StoredConversation::Topic(topic), scroll))
+        .chain(
+            direct_scroll
+                .into_iter()
+                .map(|(peer_id, scroll)| (StoredConversation::Direct(peer_id), scroll)),
+        );
+    for (stored, scroll) in scrolls {
+        let id = match stored.id() {
+            Some(id) if app.conversations.contains_key(&id) => id,
+            _ => continue,
+        };
+        if id == app.ui.current_conversation {
+            let conversation = app.conversation_mut(&id);
+            let selected = scroll.selected.and_then(|selected| {
+                conversation
+                    .history
+                    .iter()
+                    .position(|message| message.id == selected)
+            });
+            app.ui.chat_history_selected = selected;
+            app.ui.chat_history_offset = scroll.offset;
+        } else {
+            app.conversation_mut(&id).scroll = scroll;
+        }
+    }
+    if let Some(id) = current.and_then(|current| current.id()) {
+        if app.conversations.contains_key(&id) {
+            app.open_conversation(id);
+        }
+    }
+}
+
+// Adds stored messages to a conversation. Returns how many were new
+fn merge_stored(id: &ConversationId, messages: Vec<SyncedMessage>, app: &mut App) -> usize {
+    let mut messages = messages
+        .into_iter()
+        .filter_map(|synced| synced.into_chat_message())
+        .collect::<Vec<ChatMessage>>();
+    messages.retain(|message| app.is_new_message(id, message.id));
+    for message in messages.iter_mut() {
+        app.observe_clock(message);
+    }
+    app.conversation_mut(id).merge_messages(messages)
+}
+
+// The key the session file is written with, `Err` if it is encrypted but wasn't unlocked
+fn key(app: &App) -> Result<Option<DerivedKey>, ()> {
+    let key = app.session_key.clone();
+    if key.is_none() && (app.config.encrypt_session || app.session_locked) {
+        return Err(());
+    }
+    Ok(key.filter(|_| app.config.encrypt_session))
+}
+
+// Shows the next page of older stored messages of a conversation, when scrolling past the top of
+// its history. Returns how many were added in front of the shown ones
+pub fn load_older(id: &ConversationId, app: &mut App) -> Result<usize, anyhow::Error> {
+    let index = match app.conversations.get(id) {
+        Some(conversation) if conversation.stored_pages > 0 => conversation.stored_pages - 1,
+        _ => return Ok(0),
+    };
+    let page = history::read_page(id, index, app)?;
+    app.conversation_mut(id).stored_pages = index;
+    let added = merge_stored(id, page, app);
+    Ok(added.saturating_sub(app.apply_retention(id)))
+}
+
+// Saves the session when quitting and for the snapshots taken while running, so the next start
+// can resume it
+pub fn save(app: &App) {
+    if !app.config.resume_session {
+        return;
+    }
+    // Without the passphrase an encrypted session would be overwritten in the clear
+    let key = match key(app) {
+        Ok(key) => key,
+        Err(()) => {
+            log::warn!("not saving the session, it wasn't unlocked");
+            return;
+        }
+    };
+    for (id, conversation) in app.conversations.iter() {
+        // Without `store_history` the stored history is removed, including the pages never read
+        let (messages, read) = if app.config.store_history {
+            (
+                stored_messages(conversation),
+                conversation.stored_pages..conversation.resumed_pages,
+            )
+        } else {
+            (vec![], 0..conversation.resumed_pages)
+        };
+        if let Err(e) = history::save(id, messages, read, key.as_ref()) {
+            log::error!("saving the history of {} failed with Err `{}`", id, e);
+        }
+    }
+    if let Err(e) = Session::of(app).save(key) {
+        log::error!("saving the session failed with Err `{}`", e);
+    }
+}
@@ -0,0 +1,49 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::app::App;
+use crate::profile;
+use crate::session;
+use crate::writer;
+
+// How often the session is saved while running, so a crash loses at most this much
+pub const INTERVAL: Duration = Duration::from_secs(60);
+
+// Exists while p2pchat runs and is removed when quitting properly. Finding it on startup means
+// the last run crashed or was killed
+fn marker_path() -> Option<PathBuf> {
+    profile::dir().map(|dir| dir.join("running"))
+}
+
+// Marks this run as started. Returns whether the last one didn't quit properly, in which case the
+// resumed session is its last snapshot
+pub fn start() -> bool {
+    let path = match marker_path() {
+        Some(path) => path,
+        None => return false,
+    };
+    let crashed = path.exists();
+    if let Err(e) = writer::write(&path, std::process::id().to_string(), false) {
+        log::error!("writing the marker file {:?} failed with Err `{}`", path, e);
+    }
+    crashed
+}
+
+// Saves the topics, peers, history and scroll positions the way quitting does, only when they are
+// resumed on the next start. Drafts are saved on their own every few seconds
+pub fn take(app: &App) {
+    session::save(app);
+}
+
+// Called when quitting properly, after the last snapshot was taken
+pub fn finish() {
+    if let Some(path) = marker_path() {
+        if let Err(e) = writer::remove(&path) {
+            log::error!(
+                "removing the marker file {:?} failed with Err `{}`",
+                path,
+                e
+            );
+        }
+    }
+}
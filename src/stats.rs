@@ -0,0 +1,92 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use libp2p::request_response::RequestId;
+use libp2p::PeerId;
+
+use crate::conversation::ConversationId;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessageCounts {
+    pub sent: u64,
+    pub received: u64,
+}
+
+// What happened since p2pchat was started, for `/stats` and the Status page
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub messages: BTreeMap<ConversationId, MessageCounts>,
+    pub peers_seen: HashSet<PeerId>,
+    // Direct messages waiting for their ack, to measure the round trip
+    requests: HashMap<RequestId, Instant>,
+    latency_total: Duration,
+    latency_samples: u32,
+    // Bytes per second in and out, between the last two samples
+    pub rate: (u64, u64),
+    last_sample: Option<(Instant, (u64, u64))>,
+}
+
+impl Stats {
+    pub fn record_sent(&mut self, id: &ConversationId) {
+        self.messages.entry(id.clone()).or_default().sent += 1;
+    }
+
+    pub fn record_received(&mut self, id: &ConversationId) {
+        self.messages.entry(id.clone()).or_default().received += 1;
+    }
+
+    pub fn record_request(&mut self, request_id: RequestId) {
+        self.requests.insert(request_id, Instant::now());
+    }
+
+    // The ack of a direct message arrived, or `acked` is false if sending it failed
+    pub fn record_response(&mut self, request_id: RequestId, acked: bool) {
+        if let Some(sent) = self.requests.remove(&request_id) {
+            if acked {
+                self.latency_total += sent.elapsed();
+                self.latency_samples += 1;
+            }
+        }
+    }
+
+    // Called regularly with the bytes transferred so far, in and out
+    pub fn sample(&mut self, bytes: (u64, u64)) {
+        let now = Instant::now();
+        if let Some((at, before)) = self.last_sample {
+            let millis = now.duration_since(at).as_millis().max(1) as u64;
+            self.rate = (
+                bytes.0.saturating_sub(before.0) * 1000 / millis,
+                bytes.1.saturating_sub(before.1) * 1000 / millis,
+            );
+        }
+        self.last_sample = Some((now, bytes));
+    }
+
+    pub fn average_latency(&self) -> Option<Duration> {
+        (self.latency_samples > 0).then(|| self.latency_total / self.latency_samples)
+    }
+
+    pub fn totals(&self) -> MessageCounts {
+        self.messages
+            .values()
+            .fold(MessageCounts::default(), |total, counts| MessageCounts {
+                sent: total.sent + counts.sent,
+                received: total.received + counts.received,
+            })
+    }
+}
+
+// Bytes with a binary unit, e.g. `1.5 MiB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
@@ -0,0 +1,140 @@
+use std::time::Duration;
+
+use crate::app::App;
+use crate::i18n::{tr, trf, Msg};
+use crate::identity;
+use crate::protocol;
+use crate::scoring;
+use crate::stats;
+use crate::transport;
+
+// One line of the Status page. Values can be copied one at a time
+pub struct StatusEntry {
+    pub label: &'static str,
+    pub value: String,
+}
+
+impl StatusEntry {
+    fn new(label: Msg, value: String) -> Self {
+        Self {
+            label: tr(label),
+            value,
+        }
+    }
+}
+
+// Everything about our node someone needs to connect to us, one value per entry
+pub fn entries(app: &App) -> Vec<StatusEntry> {
+    let swarm = &app.connection.swarm;
+    let mut entries = vec![
+        StatusEntry::new(Msg::StatusPeerId, swarm.local_peer_id().to_string()),
+        StatusEntry::new(
+            Msg::StatusFingerprint,
+            identity::fingerprint(&app.connection.id_keys.public()),
+        ),
+        StatusEntry::new(Msg::StatusVersion, protocol::agent_version()),
+        StatusEntry::new(Msg::StatusTransports, transport::describe(&app.config)),
+        StatusEntry::new(
+            Msg::StatusPeers,
+            swarm.network_info().num_peers().to_string(),
+        ),
+    ];
+    entries.extend(
+        swarm
+            .listeners()
+            .map(|addr| StatusEntry::new(Msg::StatusListenAddress, addr.to_string())),
+    );
+    entries.extend(
+        app.connection
+            .failed_listeners
+            .iter()
+            .flat_map(|(addrs, error)| {
+                addrs
+                    .iter()
+                    .map(move |addr| format!("{} ({})", addr, error))
+            })
+            .map(|value| StatusEntry::new(Msg::StatusListenerFailed, value)),
+    );
+    entries.extend(
+        app.connection
+            .shareable_addrs()
+            .iter()
+            .map(|addr| StatusEntry::new(Msg::StatusExternalAddress, addr.to_string())),
+    );
+    entries.extend(
+        app.connection
+            .topics
+            .topics()
+            .map(|topic| StatusEntry::new(Msg::StatusTopic, topic.clone())),
+    );
+    // The worst peers first, graylisted ones are ignored by gossipsub
+    entries.extend(scoring::scores(app).into_iter().map(|(peer_id, score)| {
+        let label = if score < app.config.gossipsub.graylist_threshold {
+            Msg::StatusPeerGraylisted
+        } else {
+            Msg::StatusPeerScore
+        };
+        StatusEntry::new(label, format!("{} {:.1}", peer_id, score))
+    }));
+    entries.extend(stats_entries(app));
+
+    entries
+}
+
+// What happened during this session, also shown by `/stats`
+pub fn stats_entries(app: &App) -> Vec<StatusEntry> {
+    let totals = app.stats.totals();
+    let (bytes_in, bytes_out) = app.connection.bytes_transferred();
+    let mut entries = vec![
+        StatusEntry::new(Msg::StatusUptime, format_duration(app.started.elapsed())),
+        StatusEntry::new(
+            Msg::StatusMessages,
+            trf(Msg::StatsCounts, &[&totals.sent, &totals.received]),
+        ),
+        StatusEntry::new(
+            Msg::StatusBytes,
+            trf(
+                Msg::StatsCounts,
+                &[
+                    &stats::format_bytes(bytes_out),
+                    &stats::format_bytes(bytes_in),
+                ],
+            ),
+        ),
+        StatusEntry::new(
+            Msg::StatusBandwidth,
+            trf(
+                Msg::StatsCounts,
+                &[
+                    &format!("{}/s", stats::format_bytes(app.stats.rate.1)),
+                    &format!("{}/s", stats::format_bytes(app.stats.rate.0)),
+                ],
+            ),
+        ),
+        StatusEntry::new(Msg::StatusPeersSeen, app.stats.peers_seen.len().to_string()),
+        StatusEntry::new(
+            Msg::StatusLatency,
+            app.stats
+                .average_latency()
+                .map_or(String::from("-"), |latency| {
+                    format!("{} ms", latency.as_millis())
+                }),
+        ),
+    ];
+    entries.extend(app.stats.messages.iter().map(|(id, counts)| {
+        StatusEntry::new(
+            Msg::StatusConversationMessages,
+            format!(
+                "{} {}",
+                id,
+                trf(Msg::StatsCounts, &[&counts.sent, &counts.received])
+            ),
+        )
+    }));
+    entries
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use async_trait::async_trait;
+use chrono::{Local, TimeZone, Utc};
+use futures::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use libp2p::core::upgrade::{read_length_prefixed, write_length_prefixed};
+use libp2p::core::ProtocolName;
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+
+use crate::app::{ChatMessage, Delivery, MessageKind};
+use crate::conversation::Conversation;
+use crate::crdt::TopicState;
+
+// Peers joining a topic ask one of the peers already in it for the messages they missed, over a
+// request-response protocol next to gossipsub
+const MAX_HISTORY_RESPONSE_SIZE: usize = 4_000_000;
+// The most messages handed out for a single request
+pub const MAX_SYNC_MESSAGES: usize = 200;
+// What the messages of a response may take together, the rest of the limit is left for the topic
+// state. Patches and snapshots take tens of kilobytes each
+const MAX_SYNC_MESSAGES_SIZE: usize = MAX_HISTORY_RESPONSE_SIZE * 3 / 4;
+
+#[derive(Debug, Clone)]
+pub struct HistoryProtocol();
+
+impl ProtocolName for HistoryProtocol {
+    fn protocol_name(&self) -> &[u8] {
+        b"/p2pchat/history/1.0.0"
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryRequest {
+    // The topic hash, so hashed topic names aren't revealed
+    pub topic: String,
+    pub limit: usize,
+    // Only messages after this, in milliseconds since the epoch
+    pub since: Option<i64>,
+}
+
+// A message as stored by the responding peer, with what it knows about the author. Nothing
+// proves the author really sent it, so synced messages are shown as unverified and don't teach
+// us nicks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncedMessage {
+    pub source: String,
+    pub received_at: i64,
+    pub message: ChatMessage,
+    // The option of a poll each peer voted for, by peer id
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub votes: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistoryResponse {
+    pub messages: Vec<SyncedMessage>,
+    #[serde(default)]
+    pub state: TopicState,
+}
+
+impl HistoryResponse {
+    // The newest chat messages of a conversation the request asks for, as many as fit into a
+    // response
+    pub fn from_conversation(conversation: &Conversation, request: &HistoryRequest) -> Self {
+        let mut size = 0usize;
+        let mut messages = conversation
+            .history
+            .iter()
+            .rev()
+            .filter(|message| message.kind == MessageKind::Chat)
+            // Our own messages that didn't get out yet aren't part of the topic's history
+            .filter(|message| {
+                message
+                    .delivery
+                    .map_or(true, |delivery| delivery == Delivery::Sent)
+            })
+            .filter(|message| {
+                request
+                    .since
+                    .map_or(true, |since| message.received_at.timestamp_millis() > since)
+            })
+            .filter_map(SyncedMessage::of)
+            .take(request.limit.min(MAX_SYNC_MESSAGES))
+            .take_while(|message| {
+                let len = serde_json::to_vec(message).map_or(usize::MAX, |data| data.len());
+                size = size.saturating_add(len);
+                size <= MAX_SYNC_MESSAGES_SIZE
+            })
+            .collect::<Vec<SyncedMessage>>();
+        messages.reverse();
+
+        Self {
+            messages,
+            state: TopicState::default(),
+        }
+    }
+}
+
+impl SyncedMessage {
+    // `None` for messages without a source, which can't be told apart from forged ones
+    pub fn of(message: &ChatMessage) -> Option<Self> {
+        Some(Self {
+            source: message.source_peer_id?.to_base58(),
+            received_at: message.received_at.timestamp_millis(),
+            message: message.clone(),
+            votes: message
+                .votes
+                .iter()
+                .map(|(peer_id, option)| (peer_id.to_base58(), *option))
+                .collect(),
+        })
+    }
+
+    // The message as if we had received it ourselves, `None` if it is malformed. Votes of
+    // malformed peer ids or for options the poll doesn't have are dropped
+    pub fn into_chat_message(self) -> Option<ChatMessage> {
+        let mut message = self.message;
+        message.source_peer_id = Some(self.source.parse::<PeerId>().ok()?);
+        let options = message.poll.as_ref().map_or(0, |options| options.len());
+        message.votes = self
+            .votes
+            .into_iter()
+            .filter(|(_, option)| *option < options)
+            .filter_map(|(peer_id, option)| Some((peer_id.parse::<PeerId>().ok()?, option)))
+            .collect();
+        message.received_at = Utc
+            .timestamp_millis_opt(self.received_at)
+            .single()?
+            .with_timezone(&Local);
+        message.kind = MessageKind::Chat;
+        Some(message)
+    }
+}
+
+#[derive(Clone)]
+pub struct HistoryCodec();
+
+#[async_trait]
+impl libp2p::request_response::RequestResponseCodec for HistoryCodec {
+    type Protocol = HistoryProtocol;
+    type Request = HistoryRequest;
+    type Response = HistoryResponse;
+
+    async fn read_request<T>(
+        &mut self,
+        _: &HistoryProtocol,
+        io: &mut T,
+    ) -> io::Result<HistoryRequest>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let data = read_length_prefixed(io, MAX_HISTORY_RESPONSE_SIZE).await?;
+        serde_json::from_slice(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        _: &HistoryProtocol,
+        io: &mut T,
+    ) -> io::Result<HistoryResponse>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let data = read_length_prefixed(io, MAX_HISTORY_RESPONSE_SIZE).await?;
+        serde_json::from_slice(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _: &HistoryProtocol,
+        io: &mut T,
+        request: HistoryRequest,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let data = serde_json::to_vec(&request)?;
+        write_length_prefixed(io, data).await?;
+        io.close().await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        _: &HistoryProtocol,
+        io: &mut T,
+        response: HistoryResponse,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let data = serde_json::to_vec(&response)?;
+        write_length_prefixed(io, data).await?;
+        io.close().await
+    }
+}
@@ -0,0 +1,154 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// A single line text field. The cursor moves and deletes whole grapheme clusters, so emoji,
+// wide CJK characters and combining characters behave like one character
+#[derive(Debug, Clone, Default)]
+pub struct TextInput {
+    text: String,
+    // Byte offset into `text`, always on a grapheme boundary
+    cursor: usize,
+}
+
+impl TextInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn is_cursor_at_end(&self) -> bool {
+        self.cursor == self.text.len()
+    }
+
+    pub fn set(&mut self, text: String) {
+        self.cursor = text.len();
+        self.text = text;
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+    }
+
+    // Clears the field, returning its text
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.text)
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    // Removes the grapheme before the cursor
+    pub fn backspace(&mut self) {
+        if let Some(grapheme) = self.text[..self.cursor].graphemes(true).next_back() {
+            let start = self.cursor - grapheme.len();
+            self.text.replace_range(start..self.cursor, "");
+            self.cursor = start;
+        }
+    }
+
+    // Removes the grapheme after the cursor
+    pub fn delete(&mut self) {
+        if let Some(grapheme) = self.text[self.cursor..].graphemes(true).next() {
+            let end = self.cursor + grapheme.len();
+            self.text.replace_range(self.cursor..end, "");
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        if let Some(grapheme) = self.text[..self.cursor].graphemes(true).next_back() {
+            self.cursor -= grapheme.len();
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        if let Some(grapheme) = self.text[self.cursor..].graphemes(true).next() {
+            self.cursor += grapheme.len();
+        }
+    }
+
+    pub fn move_home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    // The same field with every grapheme replaced by `*`, for showing passphrases
+    pub fn masked(&self) -> Self {
+        let before = self.text[..self.cursor].graphemes(true).count();
+        let after = self.text[self.cursor..].graphemes(true).count();
+        Self {
+            text: "*".repeat(before + after),
+            cursor: before,
+        }
+    }
+
+    // The columns the text before the cursor takes up on screen
+    pub fn cursor_width(&self) -> u16 {
+        self.text[..self.cursor].width() as u16
+    }
+
+    // The text broken into rows at most `width` columns wide, with the column and row of the
+    // cursor. A cursor at the end of a full row goes to the start of the next one
+    pub fn wrapped(&self, width: u16) -> (Vec<&str>, u16, u16) {
+        let width = usize::from(width.max(1));
+        let mut rows = vec![];
+        let mut row_start = 0;
+        let mut row_width = 0;
+        let mut cursor = None;
+        for (i, grapheme) in self.text.grapheme_indices(true) {
+            let grapheme_width = grapheme.width();
+            if row_width > 0 && row_width + grapheme_width > width {
+                rows.push(&self.text[row_start..i]);
+                row_start = i;
+                row_width = 0;
+            }
+            if i == self.cursor {
+                cursor = Some((row_width as u16, rows.len() as u16));
+            }
+            row_width += grapheme_width;
+        }
+        rows.push(&self.text[row_start..]);
+
+        let (x, y) = cursor.unwrap_or(if row_width >= width {
+            (0, rows.len() as u16)
+        } else {
+            (row_width as u16, rows.len() as u16 - 1)
+        });
+        (rows, x, y)
+    }
+
+    // How many columns the text has to be scrolled to keep the cursor inside a field `width`
+    // columns wide
+    pub fn scroll_offset(&self, width: u16) -> u16 {
+        self.cursor_width().saturating_sub(width.saturating_sub(1))
+    }
+
+    // Handles the keys for editing text. Returns whether the key was used
+    pub fn handle_key(&mut self, key_event: KeyEvent) -> bool {
+        match (key_event.code, key_event.modifiers) {
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => self.insert(c),
+            (KeyCode::Backspace, KeyModifiers::NONE) => self.backspace(),
+            (KeyCode::Delete, KeyModifiers::NONE) => self.delete(),
+            (KeyCode::Left, KeyModifiers::NONE) => self.move_left(),
+            (KeyCode::Right, KeyModifiers::NONE) => self.move_right(),
+            (KeyCode::Home, KeyModifiers::NONE) => self.move_home(),
+            (KeyCode::End, KeyModifiers::NONE) => self.move_end(),
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,150 @@
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::app::{self, App, Delivery};
+use crate::away;
+use crate::conversation::ConversationId;
+use crate::drafts;
+use crate::missed;
+use crate::nickcert;
+use crate::peerexchange;
+use crate::schedule;
+use crate::scoring;
+use crate::snapshot;
+use crate::voice;
+
+// The event loop wakes up this often to run periodic work and redraw, so times shown in the UI
+// stay current while nothing happens
+pub const INTERVAL: Duration = Duration::from_secs(1);
+// Failed messages are sent again this often once there are peers to get them
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+// Only messages that failed recently, older ones are probably out of context by now
+const RETRY_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+// Our nick and status are sent again this often, for peers that missed a change
+const PRESENCE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const STATS_INTERVAL: Duration = Duration::from_secs(5);
+
+// Work that runs every `interval`, on the first tick after it is due
+#[derive(Debug)]
+struct Periodic {
+    interval: Duration,
+    last: Instant,
+}
+
+impl Periodic {
+    fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: Instant::now(),
+        }
+    }
+
+    fn due(&mut self, now: Instant) -> bool {
+        if now.duration_since(self.last) < self.interval {
+            return false;
+        }
+        self.last = now;
+        true
+    }
+}
+
+#[derive(Debug)]
+pub struct Ticks {
+    peer_exchange: Periodic,
+    retention: Periodic,
+    away: Periodic,
+    drafts: Periodic,
+    retries: Periodic,
+    presence: Periodic,
+    stats: Periodic,
+    scoring: Periodic,
+    snapshot: Periodic,
+}
+
+impl Default for Ticks {
+    fn default() -> Self {
+        Self {
+            peer_exchange: Periodic::new(peerexchange::INTERVAL),
+            retention: Periodic::new(app::RETENTION_INTERVAL),
+            away: Periodic::new(away::CHECK_INTERVAL),
+            drafts: Periodic::new(drafts::SAVE_INTERVAL),
+            retries: Periodic::new(RETRY_INTERVAL),
+            presence: Periodic::new(PRESENCE_INTERVAL),
+            stats: Periodic::new(STATS_INTERVAL),
+            scoring: Periodic::new(scoring::CHECK_INTERVAL),
+            snapshot: Periodic::new(snapshot::INTERVAL),
+        }
+    }
+}
+
+// Called every `INTERVAL` by the event loop
+pub fn tick(app: &mut App) {
+    let now = Instant::now();
+    schedule::send_due(app);
+    app.remove_expired();
+    voice::check(app);
+    missed::check(app);
+    if app.ticks.away.due(now) {
+        away::check(app);
+    }
+    if app.ticks.stats.due(now) {
+        let bytes = app.connection.bytes_transferred();
+        app.stats.sample(bytes);
+    }
+    if app.ticks.scoring.due(now) {
+        scoring::check(app);
+    }
+    if app.ticks.drafts.due(now) {
+        drafts::save(app);
+    }
+    if app.ticks.snapshot.due(now) {
+        snapshot::take(app);
+    }
+    if app.ticks.retries.due(now) {
+        retry_failed(app);
+    }
+    if app.ticks.retention.due(now) {
+        app.apply_retention_all();
+    }
+    if app.ticks.presence.due(now) {
+        away::announce(app);
+        nickcert::announce(app);
+    }
+    if app.ticks.peer_exchange.due(now) {
+        peerexchange::share(app);
+    }
+}
+
+// Sends our recent failed messages again, in conversations that have someone to get them now
+fn retry_failed(app: &mut App) {
+    let local_peer_id = *app.connection.swarm.local_peer_id();
+    let max_bytes = app.config.gossipsub.max_transmit_size;
+    let cutoff =
+        chrono::Local::now() - chrono::Duration::from_std(RETRY_MAX_AGE).expect("retry age fits");
+    let failed = app
+        .conversations
+        .iter()
+        .filter(|(id, _)| match id {
+            ConversationId::Topic(topic) => !app.connection.mesh_peers(topic).is_empty(),
+            ConversationId::Direct(peer_id) => app.connection.swarm.is_connected(peer_id),
+        })
+        .flat_map(|(id, conversation)| {
+            conversation
+                .history
+                .iter()
+                .filter(|message| {
+                    message.delivery == Some(Delivery::Failed)
+                        && message.source_peer_id == Some(local_peer_id)
+                        && message.received_at >= cutoff
+                })
+                // Too large ones would only fail again
+                .filter(|message| app.outgoing_size(id, &message.text) <= max_bytes)
+                .map(move |message| (id.clone(), message.id))
+        })
+        .collect::<Vec<(ConversationId, Uuid)>>();
+
+    for (id, message_id) in failed {
+        app.retry_message(&id, message_id);
+    }
+}
@@ -0,0 +1,58 @@
+use anyhow::{anyhow, Context};
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::RngCore;
+
+use crate::protocol::Payload;
+
+// Encrypted topics share a symmetric key per epoch. Rotating the key starts a new epoch, the
+// keys of earlier ones are kept so older messages stay readable
+
+// A new random key, base64 encoded like in the config
+pub fn generate() -> String {
+    let mut key = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut key);
+    data_encoding::BASE64.encode(&key)
+}
+
+fn cipher(key: &str) -> Result<ChaCha20Poly1305, anyhow::Error> {
+    let key = data_encoding::BASE64
+        .decode(key.as_bytes())
+        .context("decoding topic key failed")?;
+    anyhow::ensure!(key.len() == 32, "topic keys must be 32 bytes long");
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+// Whether a key decodes to one we can encrypt with
+pub fn validate(key: &str) -> Result<(), anyhow::Error> {
+    cipher(key).map(|_| ())
+}
+
+// Encrypts a payload with the key of an epoch
+pub fn seal(epoch: u32, key: &str, payload: &Payload) -> Result<Payload, anyhow::Error> {
+    let mut nonce = [0u8; 12];
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let plaintext = serde_json::to_vec(payload)?;
+    let ciphertext = cipher(key)?
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|_| anyhow!("encrypting payload failed"))?;
+
+    Ok(Payload::Sealed {
+        epoch,
+        nonce: data_encoding::BASE64.encode(&nonce),
+        ciphertext: data_encoding::BASE64.encode(&ciphertext),
+    })
+}
+
+// Decrypts the payload of a `Payload::Sealed`
+pub fn open(key: &str, nonce: &str, ciphertext: &str) -> Result<Payload, anyhow::Error> {
+    let nonce = data_encoding::BASE64.decode(nonce.as_bytes())?;
+    let ciphertext = data_encoding::BASE64.decode(ciphertext.as_bytes())?;
+    anyhow::ensure!(nonce.len() == 12, "invalid nonce");
+
+    let plaintext = cipher(key)?
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| anyhow!("decrypting payload failed"))?;
+    Ok(serde_json::from_slice(&plaintext)?)
+}
@@ -0,0 +1,116 @@
+use std::collections::BTreeSet;
+
+use libp2p::gossipsub::error::{PublishError, SubscriptionError};
+use libp2p::gossipsub::{Gossipsub, IdentTopic, MessageId, Sha256Topic, TopicHash};
+
+use crate::scoring;
+
+// The topic joined on startup unless the config names another one
+pub const DEFAULT_TOPIC: &str = "test-net";
+
+// Keeps track of the gossipsub topics we are subscribed to. With hashed topics only the Sha256
+// hash of a name goes over the network, so peers need to know the name to join
+pub struct TopicManager {
+    topics: BTreeSet<String>,
+    hashed: bool,
+}
+
+impl TopicManager {
+    pub fn new(hashed: bool) -> Self {
+        Self {
+            topics: BTreeSet::new(),
+            hashed,
+        }
+    }
+
+    pub fn with_default_topic(name: &str, hashed: bool) -> Self {
+        Self {
+            topics: BTreeSet::from([name.to_string()]),
+            hashed,
+        }
+    }
+
+    pub fn hash(&self, name: &str) -> TopicHash {
+        if self.hashed {
+            Sha256Topic::new(name).hash()
+        } else {
+            IdentTopic::new(name).hash()
+        }
+    }
+
+    pub fn topics(&self) -> impl Iterator<Item = &String> {
+        self.topics.iter()
+    }
+
+    pub fn is_subscribed(&self, name: &str) -> bool {
+        self.topics.contains(name)
+    }
+
+    // Returns the topic name for a hash received from the network, if we are subscribed to it
+    pub fn name_of(&self, hash: &TopicHash) -> Option<String> {
+        self.topics
+            .iter()
+            .find(|name| self.hash(name) == *hash)
+            .cloned()
+    }
+
+    pub fn subscribe(
+        &mut self,
+        gossipsub: &mut Gossipsub,
+        name: &str,
+    ) -> Result<bool, SubscriptionError> {
+        let subscribed = self.subscribe_gossipsub(gossipsub, name)?;
+        self.topics.insert(name.to_string());
+        Ok(subscribed)
+    }
+
+    // Subscribes gossipsub to all our topics, e.g. after it was created
+    pub fn resubscribe(&self, gossipsub: &mut Gossipsub) -> Result<(), SubscriptionError> {
+        for name in self.topics.iter() {
+            self.subscribe_gossipsub(gossipsub, name)?;
+        }
+        Ok(())
+    }
+
+    fn subscribe_gossipsub(
+        &self,
+        gossipsub: &mut Gossipsub,
+        name: &str,
+    ) -> Result<bool, SubscriptionError> {
+        // Fails only if peer scoring is disabled
+        if self.hashed {
+            let _ = gossipsub.set_topic_params(Sha256Topic::new(name), scoring::topic_params());
+            gossipsub.subscribe(&Sha256Topic::new(name))
+        } else {
+            let _ = gossipsub.set_topic_params(IdentTopic::new(name), scoring::topic_params());
+            gossipsub.subscribe(&IdentTopic::new(name))
+        }
+    }
+
+    pub fn unsubscribe(
+        &mut self,
+        gossipsub: &mut Gossipsub,
+        name: &str,
+    ) -> Result<bool, PublishError> {
+        let unsubscribed = if self.hashed {
+            gossipsub.unsubscribe(&Sha256Topic::new(name))?
+        } else {
+            gossipsub.unsubscribe(&IdentTopic::new(name))?
+        };
+        self.topics.remove(name);
+        Ok(unsubscribed)
+    }
+
+    pub fn publish(
+        &self,
+        gossipsub: &mut Gossipsub,
+        name: &str,
+        data: Vec<u8>,
+    ) -> Result<MessageId, PublishError> {
+        if self.hashed {
+            gossipsub.publish(Sha256Topic::new(name), data)
+        } else {
+            gossipsub.publish(IdentTopic::new(name), data)
+        }
+    }
+}
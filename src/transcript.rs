@@ -0,0 +1,50 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::app::ChatMessage;
+use crate::conversation::ConversationId;
+
+// A human readable log of every sent and received chat message, appended to as they happen
+pub struct Transcript {
+    file: File,
+}
+
+impl Transcript {
+    pub fn open(path: &Path) -> Result<Self, anyhow::Error> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("opening transcript {:?} failed", path))?;
+
+        Ok(Self { file })
+    }
+
+    pub fn write(
+        &mut self,
+        id: &ConversationId,
+        message: &ChatMessage,
+        author: &str,
+    ) -> Result<(), anyhow::Error> {
+        let peer = message
+            .source_peer_id
+            .map(|peer_id| peer_id.to_string())
+            .unwrap_or_default();
+        // Continuation lines are indented so every entry starts at the beginning of a line
+        let text = message.text.replace('\n', "\n    ");
+
+        writeln!(
+            self.file,
+            "[{}] {} {} ({}): {}",
+            message.received_at.format("%Y-%m-%d %H:%M:%S %:z"),
+            id,
+            author,
+            peer,
+            text
+        )?;
+        Ok(())
+    }
+}
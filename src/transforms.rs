@@ -0,0 +1,223 @@
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use futures::channel::mpsc;
+use regex::{Regex, RegexBuilder};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+
+use crate::app::ChatMessage;
+use crate::config::{TransformDirection, TransformKind, TransformRule};
+use crate::conversation::ConversationId;
+
+// Programs that take longer are killed, the message goes out or is shown unchanged
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+enum Step {
+    Command { program: String, args: Vec<String> },
+    Replace { regex: Regex, replacement: String },
+    Mask(Regex),
+}
+
+impl Step {
+    fn new(kind: &TransformKind) -> Result<Self, anyhow::Error> {
+        match kind {
+            TransformKind::Command { program, args } => Ok(Self::Command {
+                program: program.clone(),
+                args: args.clone(),
+            }),
+            TransformKind::Replace {
+                pattern,
+                replacement,
+            } => Ok(Self::Replace {
+                regex: Regex::new(pattern)
+                    .with_context(|| format!("invalid transform pattern `{}`", pattern))?,
+                replacement: replacement.clone(),
+            }),
+            TransformKind::Mask { words } => {
+                anyhow::ensure!(!words.is_empty(), "a mask transform needs words to mask");
+                let pattern = format!(
+                    r"\b({})\b",
+                    words
+                        .iter()
+                        .map(|word| regex::escape(word))
+                        .collect::<Vec<String>>()
+                        .join("|")
+                );
+                Ok(Self::Mask(
+                    RegexBuilder::new(&pattern)
+                        .case_insensitive(true)
+                        .build()
+                        .context("invalid mask words")?,
+                ))
+            }
+        }
+    }
+
+    async fn apply(&self, text: &str) -> Result<String, anyhow::Error> {
+        match self {
+            Self::Command { program, args } => run_command(program, args, text).await,
+            Self::Replace { regex, replacement } => {
+                Ok(regex.replace_all(text, replacement.as_str()).into_owned())
+            }
+            Self::Mask(regex) => Ok(regex
+                .replace_all(text, |captures: &regex::Captures| {
+                    "*".repeat(captures[0].chars().count())
+                })
+                .into_owned()),
+        }
+    }
+}
+
+// Writes the text to the program's stdin and returns its stdout without the final newline
+async fn run_command(program: &str, args: &[String], text: &str) -> Result<String, anyhow::Error> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("starting `{}` failed", program))?;
+    let mut stdin = child.stdin.take().context("the program has no stdin")?;
+    let mut stdout = child.stdout.take().context("the program has no stdout")?;
+
+    // Writing and reading at the same time, so a program that answers before it read everything
+    // can't block on a full pipe. Closing stdin tells the program the text is complete
+    let write = async move { stdin.write_all(text.as_bytes()).await };
+    let read = async {
+        let mut output = Vec::new();
+        stdout.read_to_end(&mut output).await.map(|_| output)
+    };
+    let run = async {
+        let ((), output) = tokio::try_join!(write, read)?;
+        Ok::<_, std::io::Error>((child.wait().await?, output))
+    };
+    let (status, output) = match tokio::time::timeout(COMMAND_TIMEOUT, run).await {
+        Ok(finished) => finished.with_context(|| format!("running `{}` failed", program))?,
+        Err(_) => {
+            let _ = child.kill().await;
+            anyhow::bail!("`{}` didn't finish in time and was killed", program);
+        }
+    };
+    anyhow::ensure!(status.success(), "`{}` failed with {}", program, status);
+
+    let output = String::from_utf8(output)
+        .with_context(|| format!("`{}` printed invalid UTF-8", program))?;
+    Ok(output.strip_suffix('\n').unwrap_or(&output).to_string())
+}
+
+// A message whose text went through the transforms that run programs, waiting to be sent or
+// shown
+#[derive(Debug)]
+pub struct Transformed {
+    pub direction: TransformDirection,
+    pub conversation_id: ConversationId,
+    pub message: ChatMessage,
+    // If a step failed, the message should be sent or shown as it was
+    pub text: Result<String, anyhow::Error>,
+}
+
+// The transform rules of the config, compiled once and split by direction
+#[derive(Debug)]
+pub struct Transforms {
+    outgoing: Arc<Vec<Step>>,
+    incoming: Arc<Vec<Step>>,
+    done_tx: mpsc::UnboundedSender<Transformed>,
+    pub done_rx: mpsc::UnboundedReceiver<Transformed>,
+}
+
+impl Default for Transforms {
+    fn default() -> Self {
+        let (done_tx, done_rx) = mpsc::unbounded();
+        Self {
+            outgoing: Arc::default(),
+            incoming: Arc::default(),
+            done_tx,
+            done_rx,
+        }
+    }
+}
+
+impl Transforms {
+    pub fn new(rules: &[TransformRule]) -> Result<Self, anyhow::Error> {
+        let (mut outgoing, mut incoming) = (vec![], vec![]);
+        for rule in rules {
+            if rule.direction != TransformDirection::Incoming {
+                outgoing.push(Step::new(&rule.kind)?);
+            }
+            if rule.direction != TransformDirection::Outgoing {
+                incoming.push(Step::new(&rule.kind)?);
+            }
+        }
+        Ok(Self {
+            outgoing: Arc::new(outgoing),
+            incoming: Arc::new(incoming),
+            ..Self::default()
+        })
+    }
+
+    fn steps(&self, direction: TransformDirection) -> &Arc<Vec<Step>> {
+        if direction == TransformDirection::Outgoing {
+            &self.outgoing
+        } else {
+            &self.incoming
+        }
+    }
+
+    // Whether a step of the direction runs a program. Those messages have to go through `spawn`,
+    // the event loop mustn't wait for the program
+    pub fn runs_programs(&self, direction: TransformDirection) -> bool {
+        self.steps(direction)
+            .iter()
+            .any(|step| matches!(step, Step::Command { .. }))
+    }
+
+    // Runs our text through the outgoing steps. If one fails, the error is returned and the
+    // text should be sent as it was. Only for steps that don't run programs
+    pub fn outgoing(&self, text: &str) -> Result<String, anyhow::Error> {
+        apply_now(&self.outgoing, text)
+    }
+
+    pub fn incoming(&self, text: &str) -> Result<String, anyhow::Error> {
+        apply_now(&self.incoming, text)
+    }
+
+    // Runs the message's text through the steps of the direction on a task of its own. The
+    // message comes back through `done_rx` when all steps are done
+    pub fn spawn(
+        &self,
+        direction: TransformDirection,
+        conversation_id: ConversationId,
+        message: ChatMessage,
+    ) {
+        let steps = Arc::clone(self.steps(direction));
+        let done_tx = self.done_tx.clone();
+        tokio::spawn(async move {
+            let text = apply(&steps, &message.text).await;
+            let _ = done_tx.unbounded_send(Transformed {
+                direction,
+                conversation_id,
+                message,
+                text,
+            });
+        });
+    }
+}
+
+async fn apply(steps: &[Step], text: &str) -> Result<String, anyhow::Error> {
+    let mut text = text.to_string();
+    for step in steps {
+        text = step.apply(&text).await?;
+    }
+    Ok(text)
+}
+
+// Steps that don't run programs never wait, so they are done after the first poll
+fn apply_now(steps: &[Step], text: &str) -> Result<String, anyhow::Error> {
+    futures::FutureExt::now_or_never(apply(steps, text))
+        .context("a transform runs a program and has to be spawned")?
+}
@@ -0,0 +1,215 @@
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::Context;
+use data_encoding::BASE32;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use libp2p::bandwidth::{BandwidthLogging, BandwidthSinks};
+use libp2p::core::muxing::StreamMuxerBox;
+use libp2p::core::transport::{Boxed, TransportError};
+use libp2p::core::upgrade;
+use libp2p::identity::Keypair;
+use libp2p::multiaddr::Protocol;
+use libp2p::websocket::{tls, WsConfig};
+use libp2p::{mplex, noise, tcp, Multiaddr, PeerId, Transport};
+use tokio_socks::tcp::Socks5Stream;
+
+use crate::config::Config;
+
+pub type BoxedTransport = Boxed<(PeerId, StreamMuxerBox)>;
+
+// TCP transport which optionally routes outbound dials through a SOCKS5 proxy (e.g. Tor).
+// Listening is never proxied.
+#[derive(Debug, Clone)]
+pub struct ProxyTcpConfig {
+    inner: tcp::TokioTcpConfig,
+    socks5_proxy: Option<SocketAddr>,
+}
+
+impl ProxyTcpConfig {
+    pub fn new(socks5_proxy: Option<SocketAddr>) -> Self {
+        Self {
+            inner: tcp::TokioTcpConfig::new().nodelay(true),
+            socks5_proxy,
+        }
+    }
+}
+
+impl Transport for ProxyTcpConfig {
+    type Output = <tcp::TokioTcpConfig as Transport>::Output;
+    type Error = io::Error;
+    type Listener = <tcp::TokioTcpConfig as Transport>::Listener;
+    type ListenerUpgrade = <tcp::TokioTcpConfig as Transport>::ListenerUpgrade;
+    type Dial = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        self.inner.listen_on(addr)
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let proxy = match self.socks5_proxy {
+            Some(proxy) => proxy,
+            None => return self.inner.dial(addr),
+        };
+        let target = match socks5_target(&addr) {
+            Some(target) => target,
+            None => return Err(TransportError::MultiaddrNotSupported(addr)),
+        };
+
+        Ok(async move {
+            let stream = Socks5Stream::connect(proxy, target)
+                .await
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            Ok(tcp::tokio::TcpStream(stream.into_inner()))
+        }
+        .boxed())
+    }
+
+    fn address_translation(&self, listen: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
+        self.inner.address_translation(listen, observed)
+    }
+}
+
+// The host and port the proxy should connect to. Host names are passed on unresolved, so DNS
+// lookups happen at the proxy and don't leak.
+fn socks5_target(addr: &Multiaddr) -> Option<(String, u16)> {
+    let mut iter = addr.iter();
+
+    let host = match iter.next()? {
+        Protocol::Ip4(ip) => ip.to_string(),
+        Protocol::Ip6(ip) => ip.to_string(),
+        Protocol::Dns(host) | Protocol::Dns4(host) | Protocol::Dns6(host) => host.to_string(),
+        Protocol::Onion3(onion) => {
+            let onion_host = format!("{}.onion", BASE32.encode(onion.hash()).to_lowercase());
+            return Some((onion_host, onion.port()));
+        }
+        _ => return None,
+    };
+    let port = match iter.next()? {
+        Protocol::Tcp(port) => port,
+        _ => return None,
+    };
+
+    Some((host, port))
+}
+
+// The transport, and the counters of the bytes going through its connections
+pub fn build_transport(
+    id_keys: &Keypair,
+    config: &Config,
+) -> Result<(BoxedTransport, Arc<BandwidthSinks>), anyhow::Error> {
+    // Create a keypair for authenticated encryption of the transport.
+    let noise_keys = noise::Keypair::<noise::X25519Spec>::new()
+        .into_authentic(id_keys)
+        .context("Signing libp2p-noise static DH keypair failed.")?;
+
+    // Plain TCP, and WebSocket over TCP so js-libp2p browser clients can reach us
+    let tcp = ProxyTcpConfig::new(config.socks5_proxy);
+    let mut websocket = WsConfig::new(tcp.clone());
+    if let Some(tls_config) = websocket_tls_config(config)? {
+        websocket.set_tls_config(tls_config);
+    }
+
+    // Use noise for authenticated encryption and Mplex for multiplexing of substreams on a
+    // stream.
+    let (transport, bandwidth) = BandwidthLogging::new(tcp.or_transport(websocket));
+    let transport = transport
+        .upgrade(upgrade::Version::V1)
+        .authenticate(noise::NoiseConfig::xx(noise_keys).into_authenticated())
+        .multiplex(mplex::MplexConfig::new())
+        .boxed();
+
+    Ok((transport, bandwidth))
+}
+
+// A short description of the transport stack, e.g. for the Status page
+pub fn describe(config: &Config) -> String {
+    let mut transports = vec![String::from("TCP")];
+    if config.websocket_port.is_some() {
+        transports.push(if config.websocket_tls_enabled() {
+            String::from("WebSocket (TLS)")
+        } else {
+            String::from("WebSocket")
+        });
+    }
+    let mut stack = format!("{} / Noise / Mplex", transports.join(" + "));
+    if let Some(proxy) = config.socks5_proxy {
+        stack.push_str(&format!(", dialing via SOCKS5 {}", proxy));
+    }
+    if config.onion_address.is_some() {
+        stack.push_str(", onion service");
+    }
+    if config.outbound_only {
+        stack.push_str(", outbound only");
+    }
+
+    stack
+}
+
+// The addresses to listen on, depending on which transports and address families are enabled
+pub fn listen_addrs(config: &Config) -> Result<Vec<Multiaddr>, anyhow::Error> {
+    if config.outbound_only {
+        return Ok(vec![]);
+    }
+    // Behind an onion service, Tor forwards inbound connections from localhost, so we don't
+    // expose ourselves on any other interface
+    let listen_ips = if config.onion_address.is_some() {
+        vec!["/ip4/127.0.0.1"]
+    } else if config.ipv6 {
+        vec!["/ip4/0.0.0.0", "/ip6/::"]
+    } else {
+        vec!["/ip4/0.0.0.0"]
+    };
+
+    let mut addrs = vec![];
+    for listen_ip in listen_ips {
+        addrs.push(format!("{}/tcp/{}", listen_ip, config.listen_port).parse()?);
+
+        if let Some(websocket_port) = config.websocket_port {
+            let scheme = if config.websocket_tls_enabled() {
+                "wss"
+            } else {
+                "ws"
+            };
+            addrs.push(format!("{}/tcp/{}/{}", listen_ip, websocket_port, scheme).parse()?);
+        }
+    }
+
+    Ok(addrs)
+}
+
+// Parses an address typed by the user. Besides multiaddrs, plain socket addresses like
+// `192.168.1.2:4001` or `[::1]:4001` are accepted for TCP
+pub fn parse_addr(input: &str) -> Result<Multiaddr, anyhow::Error> {
+    let input = input.trim();
+    if let Ok(socket_addr) = input.parse::<SocketAddr>() {
+        return Ok(Multiaddr::from(socket_addr.ip()).with(Protocol::Tcp(socket_addr.port())));
+    }
+
+    input.parse::<Multiaddr>().with_context(|| {
+        format!(
+            "`{}` is neither a multiaddr nor an ip address with a port",
+            input
+        )
+    })
+}
+
+fn websocket_tls_config(config: &Config) -> Result<Option<tls::Config>, anyhow::Error> {
+    let (cert_path, key_path) = match (&config.websocket_tls_cert, &config.websocket_tls_key) {
+        (Some(cert_path), Some(key_path)) => (cert_path, key_path),
+        _ => return Ok(None),
+    };
+
+    let cert = fs::read(cert_path)
+        .with_context(|| format!("reading websocket TLS certificate {:?} failed", cert_path))?;
+    let key = fs::read(key_path)
+        .with_context(|| format!("reading websocket TLS key {:?} failed", key_path))?;
+
+    let tls_config = tls::Config::new(tls::PrivateKey::new(key), [tls::Certificate::new(cert)])
+        .context("creating websocket TLS config failed")?;
+
+    Ok(Some(tls_config))
+}
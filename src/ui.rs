@@ -1,32 +1,68 @@
+use std::borrow::Cow;
 use std::io::Stdout;
+use std::time::{Duration, Instant};
 
+use libp2p::PeerId;
 use tui::{
     backend::{Backend, CrosstermBackend},
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     symbols,
     text::{Span, Spans, Text},
-    widgets::{Block, BorderType, Borders, List, ListItem, ListState, Paragraph, Tabs, Wrap},
+    widgets::{
+        Block, BorderType, Borders, Clear, List, ListItem, ListState, Paragraph, Tabs, Wrap,
+    },
     Frame, Terminal,
 };
 use unicode_width::UnicodeWidthStr;
+use uuid::Uuid;
 
 pub trait CycleFocus {
     fn next(self) -> Self;
     fn prev(self) -> Self;
 }
 
-use crate::app::{self};
+use crate::addrinput;
+use crate::app::{self, Delivery, MessageKind};
+use crate::capture;
+use crate::commands;
+use crate::config::{LabelColor, LeaderAction, TopicSettings};
+use crate::conversation::ConversationId;
+use crate::eventlog::LogLevel;
+use crate::filters::ContentFilter;
+use crate::hittest::{HitAreas, HitTarget};
+use crate::i18n::{tr, trf, Msg};
+use crate::markup::Markup;
+use crate::members;
+use crate::modal::{Modal, ModalKind};
+use crate::notify;
+use crate::peerlist;
+use crate::preview;
+use crate::recentaddrs;
+use crate::schedule;
+use crate::status;
+use crate::textinput::TextInput;
+use crate::utils;
+use crate::voice;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum PageFocus {
     Chat = 0,
     Connection,
+    Status,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChatPageFocus {
+    // Neutral focus, keys are not typed into the input
+    History = 0,
+    Input,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ConnectionPageFocus {
     ConnectionLog = 0,
+    PeerList,
     RegenerateSwarm,
     AddrInputField,
     NickInputField,
@@ -35,7 +71,8 @@ pub enum ConnectionPageFocus {
 impl CycleFocus for ConnectionPageFocus {
     fn next(self) -> Self {
         match self {
-            Self::ConnectionLog => Self::RegenerateSwarm,
+            Self::ConnectionLog => Self::PeerList,
+            Self::PeerList => Self::RegenerateSwarm,
             Self::RegenerateSwarm => Self::AddrInputField,
             Self::AddrInputField => Self::NickInputField,
             Self::NickInputField => Self::ConnectionLog,
@@ -46,7 +83,8 @@ impl CycleFocus for ConnectionPageFocus {
         match self {
             Self::ConnectionLog => Self::NickInputField,
             Self::NickInputField => Self::AddrInputField,
-            Self::RegenerateSwarm => Self::ConnectionLog,
+            Self::PeerList => Self::ConnectionLog,
+            Self::RegenerateSwarm => Self::PeerList,
             Self::AddrInputField => Self::RegenerateSwarm,
         }
     }
@@ -56,56 +94,134 @@ impl CycleFocus for PageFocus {
     fn next(self) -> Self {
         match self {
             Self::Chat => Self::Connection,
-            Self::Connection => Self::Chat,
+            Self::Connection => Self::Status,
+            Self::Status => Self::Chat,
         }
     }
 
     fn prev(self) -> Self {
         match self {
-            Self::Chat => Self::Connection,
+            Self::Chat => Self::Status,
             Self::Connection => Self::Chat,
+            Self::Status => Self::Connection,
         }
     }
 }
 
+// How long a second Ctrl+C is accepted to quit
+pub const QUIT_INTERVAL: Duration = Duration::from_secs(2);
+// Narrower terminals show the chat and connection pages as tabs even in the split view
+pub const SPLIT_MIN_WIDTH: u16 = 140;
+
 pub struct Ui {
     pub page_focus: PageFocus,
+    pub chat_page_focus: ChatPageFocus,
     pub connection_page_focus: ConnectionPageFocus,
+    pub current_conversation: ConversationId,
+    // Open modals, the last one is on top and has focus
+    pub modals: Vec<Modal>,
+    // When Ctrl+C was last pressed, a second press shortly after quits
+    pub quit_requested: Option<Instant>,
+    // Whether the terminal window has focus. Stays true in terminals that don't report it
+    pub terminal_focused: bool,
 
-    pub chat_input: String,
-    pub addr_input: String,
-    pub nick_input: String,
-    pub connection_log_allocation: Option<Rect>,
+    pub chat_input: TextInput,
+    pub addr_input: TextInput,
+    // The recent address picked in the dropdown under the address input, None while typing
+    pub addr_dropdown_selected: Option<usize>,
+    pub nick_input: TextInput,
+    // The selected message in the current conversation's history
+    pub chat_history_selected: Option<usize>,
+    // Where a range selection started, the range ends at the selected message
+    pub chat_history_anchor: Option<usize>,
+    pub chat_history_offset: usize,
+    // The message the next sent message replies to
+    pub reply_to: Option<Uuid>,
     pub connection_log_liststate: ListState,
+    pub connection_log_offset: usize,
+    // Show how long ago log entries happened instead of the time
+    pub log_relative_time: bool,
+    // The selected entry of the Status page
+    pub status_selected: usize,
+    // The selected peer of the peer list on the Connection page
+    pub peer_list_selected: usize,
+    // Whether the members of the current topic are shown next to the history
+    pub show_members: bool,
+    // The leader key was pressed, the next key picks the shortcut
+    pub leader_pending: bool,
+    // Show the chat and connection pages side by side, toggled with F3
+    pub split_view: bool,
+    // Whether the last frame was wide enough to actually show the split view
+    pub split_shown: bool,
+    // Show the messages of all conversations in one timeline instead of the current one,
+    // toggled with F5
+    pub all_view: bool,
+    pub hit_areas: HitAreas,
+    // Set by /snapshot, the pane is captured after the next frame is drawn
+    pub capture_requested: Option<capture::Pane>,
+    // Compiled once, messages are rendered every frame
+    pub markup: Markup,
 }
 
 impl Ui {
-    pub fn new() -> Self {
+    pub fn new(current_conversation: ConversationId) -> Self {
         let mut connection_log_liststate = ListState::default();
         connection_log_liststate.select(Some(0));
 
         Self {
             page_focus: PageFocus::Chat,
+            chat_page_focus: ChatPageFocus::Input,
             connection_page_focus: ConnectionPageFocus::AddrInputField,
-            chat_input: String::from(""),
-            addr_input: String::from(""),
-            nick_input: String::from(""),
-            connection_log_allocation: None,
+            current_conversation,
+            modals: vec![],
+            quit_requested: None,
+            terminal_focused: true,
+            chat_input: TextInput::new(),
+            addr_input: TextInput::new(),
+            addr_dropdown_selected: None,
+            nick_input: TextInput::new(),
+            chat_history_selected: None,
+            chat_history_anchor: None,
+            chat_history_offset: 0,
+            reply_to: None,
             connection_log_liststate,
+            connection_log_offset: 0,
+            log_relative_time: false,
+            status_selected: 0,
+            peer_list_selected: 0,
+            show_members: false,
+            leader_pending: false,
+            split_view: false,
+            split_shown: false,
+            all_view: false,
+            hit_areas: HitAreas::default(),
+            capture_requested: None,
+            markup: Markup::new(),
         }
     }
+
+    pub fn quit_pending(&self) -> bool {
+        self.quit_requested
+            .map_or(false, |requested| requested.elapsed() < QUIT_INTERVAL)
+    }
 }
 
 pub fn draw_ui(
     app: &mut app::App,
     terminal: &mut Terminal<CrosstermBackend<Stdout>>,
 ) -> Result<(), anyhow::Error> {
-    terminal.draw(|frame| {
+    let frame = terminal.draw(|frame| {
         let size = frame.size();
+        app.ui.hit_areas.clear();
 
         // Surrounding block
+        let app_title = if app.ui.quit_pending() && app.ui.modals.is_empty() {
+            tr(Msg::AppTitleQuitPending)
+        } else {
+            tr(Msg::AppTitle)
+        };
         let app_block = Block::default()
-            .title(" p2pchat ")
+            .title(app_title)
             .title_alignment(Alignment::Center)
             .borders(Borders::ALL)
             .border_type(BorderType::Rounded);
@@ -119,26 +235,221 @@ pub fn draw_ui(
 
         draw_header(frame, chunks[0], app);
 
-        match app.ui.page_focus {
-            PageFocus::Chat => {
-                draw_chat_page(frame, chunks[1], app);
-            }
-            PageFocus::Connection => {
-                draw_connection_page(frame, chunks[1], app);
+        app.ui.split_shown = app.ui.split_view
+            && chunks[1].width >= SPLIT_MIN_WIDTH
+            && app.ui.page_focus != PageFocus::Status;
+        if app.ui.split_shown {
+            let panes = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+                .split(chunks[1]);
+            draw_chat_page(frame, panes[0], app);
+            draw_connection_page(frame, panes[1], app);
+        } else {
+            match app.ui.page_focus {
+                PageFocus::Chat => {
+                    draw_chat_page(frame, chunks[1], app);
+                }
+                PageFocus::Connection => {
+                    draw_connection_page(frame, chunks[1], app);
+                }
+                PageFocus::Status => {
+                    draw_status_page(frame, chunks[1], app);
+                }
             }
         }
+
+        draw_leader_hint(frame, chunks[1], app);
+
+        for modal in app.ui.modals.iter() {
+            draw_modal(frame, size, modal, &mut app.ui.hit_areas);
+        }
     })?;
+    if app.ui.capture_requested.is_some() {
+        capture::take(app, frame.buffer, frame.area);
+    }
     Ok(())
 }
 
+pub fn draw_modal<B: Backend>(
+    frame: &mut Frame<B>,
+    size: Rect,
+    modal: &Modal,
+    hit_areas: &mut HitAreas,
+) {
+    // Views show whole screens, they get as much room as possible
+    let area = match modal.kind {
+        ModalKind::View { .. } => utils::centered_rect(90, 80, size),
+        _ => utils::centered_rect(60, 40, size),
+    };
+    frame.render_widget(Clear, area);
+    hit_areas.register(area, HitTarget::Modal);
+
+    let modal_block = Block::default()
+        .title(Span::styled(
+            modal.title.as_str(),
+            Style::default().add_modifier(Modifier::BOLD),
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded);
+    let inner = modal_block.inner(area);
+    frame.render_widget(modal_block, area);
+
+    match &modal.kind {
+        ModalKind::Confirm => {
+            let message = Paragraph::new(modal.message.as_str()).wrap(Wrap { trim: true });
+            frame.render_widget(message, inner);
+        }
+        ModalKind::Prompt { input } => {
+            let modal_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(1), Constraint::Length(3)].as_ref())
+                .split(inner);
+
+            let message = Paragraph::new(modal.message.as_str()).wrap(Wrap { trim: true });
+            frame.render_widget(message, modal_chunks[0]);
+
+            draw_text_input(frame, modal_chunks[1], input, "", true);
+        }
+        ModalKind::Secret { input } => {
+            let modal_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(1), Constraint::Length(3)].as_ref())
+                .split(inner);
+
+            let message = Paragraph::new(modal.message.as_str()).wrap(Wrap { trim: true });
+            frame.render_widget(message, modal_chunks[0]);
+
+            draw_text_input(frame, modal_chunks[1], &input.masked(), "", true);
+        }
+        ModalKind::Select { options, selected } => {
+            let (start, end) =
+                utils::visible_range(0, Some(*selected), options.len(), inner.height as usize);
+            let option_items = options[start..end]
+                .iter()
+                .map(|option| ListItem::new(option.as_str()))
+                .collect::<Vec<ListItem>>();
+            for (row, i) in (start..end).enumerate() {
+                hit_areas.register(utils::row_rect(inner, row), HitTarget::ModalOption(i));
+            }
+            let option_list = List::new(option_items)
+                .highlight_style(Style::default().bg(Color::DarkGray))
+                .highlight_symbol("> ");
+
+            let mut option_liststate = ListState::default();
+            option_liststate.select(selected.checked_sub(start));
+            frame.render_stateful_widget(option_list, inner, &mut option_liststate);
+        }
+        ModalKind::View { lines, scroll } => {
+            let text = lines
+                .iter()
+                .map(|line| Spans::from(line.as_str()))
+                .collect::<Vec<Spans>>();
+            frame.render_widget(Paragraph::new(text).scroll(*scroll), inner);
+        }
+    }
+}
+
+// A text field with a border, one line that scrolls sideways or wrapped over several if it
+// is taller. When focused, the title is underlined and the terminal cursor is placed in it
+pub fn draw_text_input<B: Backend>(
+    frame: &mut Frame<B>,
+    size: Rect,
+    input: &TextInput,
+    title: &str,
+    focused: bool,
+) {
+    draw_styled_text_input(
+        frame,
+        size,
+        input,
+        title,
+        focused,
+        Style::default().fg(Color::White),
+        None,
+    );
+}
+
+// A text input in `text_style`, with a completion shown grayed out after the text if it fits on
+// one line
+pub fn draw_styled_text_input<B: Backend>(
+    frame: &mut Frame<B>,
+    size: Rect,
+    input: &TextInput,
+    title: &str,
+    focused: bool,
+    text_style: Style,
+    completion: Option<&str>,
+) {
+    let inner_width = size.width.saturating_sub(2);
+    let inner_height = size.height.saturating_sub(2);
+    let (text, (cursor_x, cursor_y), scroll) = if inner_height > 1 {
+        let (rows, x, y) = input.wrapped(inner_width);
+        let scroll = (y + 1).saturating_sub(inner_height);
+        let rows = rows
+            .into_iter()
+            .map(|row| Spans::from(Span::styled(row, text_style)))
+            .collect::<Vec<Spans>>();
+        (Text::from(rows), (x, y - scroll), (scroll, 0))
+    } else {
+        let scroll = input.scroll_offset(inner_width);
+        let mut spans = vec![Span::styled(input.as_str(), text_style)];
+        if let Some(completion) = completion {
+            spans.push(Span::styled(
+                completion,
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        (
+            Text::from(Spans::from(spans)),
+            (input.cursor_width() - scroll, 0),
+            (0, scroll),
+        )
+    };
+    let title_style = if focused {
+        // Inside the border, minus what is scrolled out of view
+        frame.set_cursor(size.x + 1 + cursor_x, size.y + 1 + cursor_y);
+        Style::default().add_modifier(Modifier::UNDERLINED)
+    } else {
+        Style::default()
+    };
+
+    let input_field = Paragraph::new(text)
+        .block(
+            Block::default()
+                .title(Span::styled(title, title_style))
+                .borders(Borders::ALL)
+                .border_type(BorderType::Plain),
+        )
+        .scroll(scroll);
+    frame.render_widget(input_field, size);
+}
+
 pub fn draw_header<B: Backend>(frame: &mut Frame<B>, size: Rect, app: &mut app::App) {
     let selected = app.ui.page_focus as usize;
 
-    let titles = ["Chat", "Connection"]
-        .iter()
-        .cloned()
-        .map(Spans::from)
-        .collect();
+    let pages = [
+        (PageFocus::Chat, tr(Msg::TabChat)),
+        (PageFocus::Connection, tr(Msg::TabConnection)),
+        (PageFocus::Status, tr(Msg::TabStatus)),
+    ];
+    // Tabs are padded by one space and separated by a one column wide divider
+    let mut x = size.x + 1;
+    for (page, title) in pages.iter() {
+        let width = (title.width() as u16).min(size.right().saturating_sub(x));
+        app.ui.hit_areas.register(
+            Rect {
+                x,
+                y: size.y,
+                width,
+                height: 1,
+            },
+            HitTarget::Tab(*page),
+        );
+        x = x.saturating_add(width + 3);
+    }
+
+    let titles = pages.iter().map(|(_, title)| Spans::from(*title)).collect();
     let pages_tabs = Tabs::new(titles)
         .block(Block::default().borders(Borders::NONE))
         .style(Style::default().fg(Color::White))
@@ -151,75 +462,902 @@ pub fn draw_header<B: Backend>(frame: &mut Frame<B>, size: Rect, app: &mut app::
         .select(selected);
 
     frame.render_widget(pages_tabs, size);
+
+    // Right aligned, as long as it doesn't cover the tabs
+    if let Some(version) = app.update.available.as_ref() {
+        let notice = trf(Msg::HeaderUpdateAvailable, &[version]);
+        let width = notice.width() as u16 + 1;
+        if size.right().saturating_sub(width) >= x {
+            let area = Rect {
+                x: size.right() - width,
+                y: size.y,
+                width,
+                height: 1,
+            };
+            let paragraph =
+                Paragraph::new(Span::styled(notice, Style::default().fg(Color::Yellow)));
+            frame.render_widget(paragraph, area);
+        }
+    }
+}
+
+fn label_color(color: LabelColor) -> Color {
+    match color {
+        LabelColor::Red => Color::Red,
+        LabelColor::Green => Color::Green,
+        LabelColor::Yellow => Color::Yellow,
+        LabelColor::Blue => Color::Blue,
+        LabelColor::Magenta => Color::Magenta,
+        LabelColor::Cyan => Color::Cyan,
+        LabelColor::White => Color::White,
+    }
+}
+
+// The name of a conversation with the icon of its topic, and the topic's color if it has one
+pub fn conversation_label(id: &ConversationId, app: &app::App) -> (String, Option<Color>) {
+    match id {
+        ConversationId::Topic(topic) => {
+            let settings = app.config.topic_settings(topic);
+            let label = match settings.icon.as_ref() {
+                Some(icon) => format!("{} {}", icon, id),
+                None => id.to_string(),
+            };
+            (label, settings.color.map(label_color))
+        }
+        // The peer list shows what peers are up to
+        ConversationId::Direct(peer_id) => match app.status_cards.get(peer_id) {
+            Some(card) => (format!("{} {}", id, card.emoji), None),
+            None => (id.to_string(), None),
+        },
+    }
+}
+
+// The lines of a message as shown, with what the filter rules redact
+fn shown_lines<'a>(
+    markup: &Markup,
+    filter: &ContentFilter,
+    text: &'a str,
+    style: Style,
+) -> Vec<Spans<'a>> {
+    match filter.redact(text) {
+        Cow::Borrowed(text) => markup.lines(text, style),
+        Cow::Owned(text) => markup.lines_owned(&text, style),
+    }
+}
+
+fn hidden_messages_item(count: usize) -> ListItem<'static> {
+    ListItem::new(Span::styled(
+        trf(Msg::HiddenMessages, &[&count]),
+        Style::default()
+            .fg(Color::DarkGray)
+            .add_modifier(Modifier::ITALIC),
+    ))
+}
+
+// The options of a poll with how many voted for each, ours marked with `>`
+fn poll_lines(
+    message: &app::ChatMessage,
+    options: &[String],
+    local_peer_id: &PeerId,
+    style: Style,
+) -> Vec<Spans<'static>> {
+    let tally = message.tally();
+    let total = message.votes.len().max(1);
+    let ours = message.votes.get(local_peer_id);
+    let mut lines = options
+        .iter()
+        .zip(tally.iter())
+        .enumerate()
+        .map(|(i, (option, count))| {
+            let marker = if ours == Some(&i) { ">" } else { " " };
+            Spans::from(vec![
+                Span::styled(format!(" {} {}. {} ", marker, i + 1, option), style),
+                Span::styled(
+                    format!("{} {}", "█".repeat(count * 10 / total), count),
+                    style.fg(Color::Cyan),
+                ),
+            ])
+        })
+        .collect::<Vec<Spans>>();
+    if ours.is_none() {
+        lines.push(Spans::from(Span::styled(
+            format!("   {}", trf(Msg::PollHint, &[&options.len()])),
+            style.add_modifier(Modifier::ITALIC),
+        )));
+    }
+    lines
 }
 
 pub fn draw_chat_page<B: Backend>(frame: &mut Frame<B>, size: Rect, app: &mut app::App) {
+    let member_pane_width = match &app.ui.current_conversation {
+        ConversationId::Topic(_) if app.ui.show_members => 24,
+        _ => 0,
+    };
+    let layout = app.config.layout;
+    let sidebar_width = if layout.sidebar_collapsed {
+        0
+    } else {
+        layout.sidebar_width
+    };
+    let sidebar_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .margin(0)
+        .constraints(
+            [
+                Constraint::Length(sidebar_width),
+                Constraint::Min(20),
+                Constraint::Length(member_pane_width),
+            ]
+            .as_ref(),
+        )
+        .split(size);
+
+    if sidebar_width > 0 {
+        draw_conversation_sidebar(frame, sidebar_chunks[0], app);
+    }
+    if member_pane_width > 0 {
+        draw_member_pane(frame, sidebar_chunks[2], app);
+    }
+
+    // Whether anyone receives our messages, and the shared description and pinned message of
+    // a topic
+    let mut topic_header_lines = vec![];
+    if let ConversationId::Topic(topic) = &app.ui.current_conversation {
+        // Shown until a peer joins our mesh again
+        if app.connection.mesh_peers(topic).is_empty() {
+            topic_header_lines.push(Spans::from(Span::styled(
+                tr(Msg::NoPeersBanner),
+                Style::default()
+                    .fg(Color::White)
+                    .bg(Color::Red)
+                    .add_modifier(Modifier::BOLD),
+            )));
+        }
+        if let Some(state) = app.topic_states.get(topic) {
+            if !state.description.value.is_empty() {
+                topic_header_lines.push(Spans::from(Span::styled(
+                    state.description.value.clone(),
+                    Style::default().fg(Color::White),
+                )));
+            }
+            if let Some(pin) = state.pinned.value.as_ref() {
+                let text = app.filter.redact(&pin.text);
+                let text = text.lines().next().unwrap_or_default();
+                topic_header_lines.push(Spans::from(Span::styled(
+                    trf(Msg::TopicPinned, &[&pin.author, &text]),
+                    Style::default().fg(Color::Yellow),
+                )));
+            }
+        }
+        if let Some(seconds) = app.config.topic_settings(topic).disappear_after {
+            topic_header_lines.push(Spans::from(Span::styled(
+                trf(Msg::TopicDisappearing, &[&schedule::format_delay(seconds)]),
+                Style::default().fg(Color::Gray),
+            )));
+        }
+    }
+    // DMs are sent in plain text until the peer's key arrived
+    if let ConversationId::Direct(peer_id) = &app.ui.current_conversation {
+        topic_header_lines.push(if app.connection.dm_crypto.is_encrypted(peer_id) {
+            Spans::from(Span::styled(
+                tr(Msg::DmEncrypted),
+                Style::default().fg(Color::Green),
+            ))
+        } else {
+            Spans::from(Span::styled(
+                tr(Msg::DmNotEncrypted),
+                Style::default().fg(Color::Yellow),
+            ))
+        });
+    }
+
     let chat_page_chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(0)
-        .constraints([Constraint::Min(3), Constraint::Length(3)].as_ref())
-        .split(size);
+        .constraints(
+            [
+                Constraint::Length(topic_header_lines.len() as u16),
+                Constraint::Min(3),
+                Constraint::Length(layout.input_height),
+                Constraint::Length(1),
+            ]
+            .as_ref(),
+        )
+        .split(sidebar_chunks[1]);
+    frame.render_widget(Paragraph::new(topic_header_lines), chat_page_chunks[0]);
+
+    // Chat Input
+    let replying_to = app.ui.reply_to.and_then(|reply_to| {
+        app.current_conversation()
+            .and_then(|conversation| conversation.message(reply_to))
+            .map(|message| app.nicks.author(message))
+    });
+    // Announcement topics we aren't an announcer of only take commands
+    let read_only_topic = match &app.ui.current_conversation {
+        ConversationId::Topic(topic)
+            if !app
+                .config
+                .may_publish(topic, Some(app.connection.swarm.local_peer_id())) =>
+        {
+            Some(topic)
+        }
+        _ => None,
+    };
+    let chat_input_title = match (read_only_topic, replying_to) {
+        (Some(topic), _) => trf(Msg::InputReadOnlyTitle, &[topic]),
+        (None, Some(author)) => trf(Msg::InputReplyingTitle, &[&author]),
+        // The timeline doesn't show where the input goes
+        (None, None) if app.ui.all_view => trf(Msg::InputToTitle, &[&app.ui.current_conversation]),
+        (None, None) => tr(Msg::InputTitle).to_string(),
+    };
+    draw_text_input(
+        frame,
+        chat_page_chunks[2],
+        &app.ui.chat_input,
+        &chat_input_title,
+        app.ui.page_focus == PageFocus::Chat && app.ui.chat_page_focus == ChatPageFocus::Input,
+    );
+    app.ui
+        .hit_areas
+        .register(chat_page_chunks[2], HitTarget::ChatInput);
+
+    draw_compose_indicator(frame, chat_page_chunks[3], app);
+
+    if app.ui.all_view {
+        draw_all_timeline(frame, chat_page_chunks[1], app);
+        draw_command_hint(frame, chat_page_chunks[1], app);
+        return;
+    }
 
     // Chat History
-    let chat_history_items = app
-        .history
-        .iter()
-        .map(|message| {
-            let style = if let Some(source_peer_id) = message.source_peer_id {
-                if source_peer_id == *app.connection.swarm.local_peer_id() {
-                    Style::default().fg(Color::Green)
-                } else {
-                    Style::default().fg(Color::Gray)
+    let local_peer_id = *app.connection.swarm.local_peer_id();
+    let empty_history = vec![];
+    // Not through `current_conversation()`, the items borrow the messages while `app.ui` changes
+    let (history, last_seen_outgoing) = match app.conversations.get(&app.ui.current_conversation) {
+        Some(conversation) => (
+            &conversation.history,
+            conversation.last_seen_outgoing(&local_peer_id),
+        ),
+        None => (&empty_history, None),
+    };
+    let mut chat_history_items = vec![];
+    // The message each list item belongs to, `None` for read receipts
+    let mut chat_history_item_messages = vec![];
+    let mut chat_history_item_heights = vec![];
+    let markup = &app.ui.markup;
+    // Inside the borders
+    let max_message_lines = chat_page_chunks[1].height.saturating_sub(2).max(1) as usize;
+    let topic_settings = match &app.ui.current_conversation {
+        ConversationId::Topic(topic) => app.config.topic_settings(topic),
+        ConversationId::Direct(_) => TopicSettings::default(),
+    };
+    let nick = app.ui.nick_input.as_str();
+    // Consecutive messages of muted peers collapse into one line
+    let mut hidden = 0;
+    for (i, message) in history.iter().enumerate() {
+        let muted = message
+            .source_peer_id
+            .map_or(false, |peer_id| app.moderation.is_muted(&peer_id));
+        if muted && message.kind == MessageKind::Chat {
+            hidden += 1;
+            continue;
+        }
+        if hidden > 0 {
+            chat_history_items.push(hidden_messages_item(hidden));
+            chat_history_item_messages.push(None);
+            chat_history_item_heights.push(1);
+            hidden = 0;
+        }
+        if message.kind == MessageKind::Membership {
+            if topic_settings.hide_join_leave {
+                continue;
+            }
+            let author = app.nicks.author(message);
+            chat_history_items.push(ListItem::new(Span::styled(
+                format!("{} {}", author, message.text),
+                Style::default()
+                    .fg(Color::DarkGray)
+                    .add_modifier(Modifier::ITALIC),
+            )));
+            chat_history_item_messages.push(Some(i));
+            chat_history_item_heights.push(1);
+            continue;
+        }
+        if message.kind == MessageKind::System {
+            if !app.config.system_messages {
+                continue;
+            }
+            chat_history_items.push(ListItem::new(Span::styled(
+                format!("-- {}", message.text),
+                Style::default()
+                    .fg(Color::DarkGray)
+                    .add_modifier(Modifier::ITALIC),
+            )));
+            chat_history_item_messages.push(Some(i));
+            chat_history_item_heights.push(1);
+            continue;
+        }
+
+        let style = if let Some(source_peer_id) = message.source_peer_id {
+            if source_peer_id == local_peer_id {
+                match message.delivery {
+                    Some(Delivery::Sending) => Style::default().fg(Color::DarkGray),
+                    Some(Delivery::Failed) => Style::default().fg(Color::Red),
+                    Some(Delivery::Sent) | None => Style::default().fg(Color::Green),
                 }
+            } else if message.highlighted
+                || notify::is_mention(&message.text, nick, &topic_settings.highlight_words)
+            {
+                Style::default().fg(Color::Yellow)
             } else {
                 Style::default().fg(Color::Gray)
+            }
+        } else {
+            Style::default().fg(Color::Gray)
+        };
+        let mut message_id_string = if let Some(source_peer_id) = message.source_peer_id {
+            utils::short_peer_id(&source_peer_id)
+        } else {
+            tr(Msg::UnknownSource).to_string()
+        };
+        if let Some(nick) = message.nick.as_ref() {
+            let nick = match message.source_peer_id.as_ref() {
+                Some(source_peer_id) => app.nicks.display(source_peer_id, nick),
+                None => nick.clone(),
             };
-            let mut message_id_string = if let Some(source_peer_id) = message.source_peer_id {
-                let source_peer_id = source_peer_id.to_string();
-                format!(
-                    "{}..{}",
-                    &source_peer_id[..4],
-                    &source_peer_id[source_peer_id.chars().count() - 5..]
-                )
-            } else {
-                String::from("unknown source")
-            };
-            if let Some(nick) = message.nick.as_ref() {
-                message_id_string = format!("{} ({})", message_id_string, nick)
-            };
+            message_id_string = format!("{} ({})", message_id_string, nick)
+        };
+        if message.synced {
+            message_id_string = format!("{} {}", message_id_string, tr(Msg::SyncedUnverified))
+        } else if let Some(source_peer_id) = message.source_peer_id {
+            if app.config.is_verified(&source_peer_id) {
+                message_id_string = format!("{} ✓", message_id_string)
+            }
+        }
+        if let Some(source_peer_id) = message.source_peer_id {
+            if app.connection.peer_is_incompatible(&source_peer_id) {
+                message_id_string =
+                    format!("{} {}", message_id_string, tr(Msg::IncompatibleVersion))
+            }
+        }
+        let style = if app.nick_bindings.is_impostor(message) {
+            message_id_string = format!("{} {}", message_id_string, tr(Msg::NickImpostor));
+            style.fg(Color::Red)
+        } else {
+            style
+        };
 
-            ListItem::new(Span::styled(
-                format!("{}: {}", message_id_string, message.text),
-                style,
+        let mut message_spans = vec![Span::styled(format!("{}: ", message_id_string), style)];
+        if let Some(reply_to) = message.reply_to {
+            let replied = history
+                .iter()
+                .find(|replied| replied.id == reply_to)
+                .map_or(tr(Msg::DeletedMessage).to_string(), |replied| {
+                    format!(
+                        "{}: {}",
+                        app.nicks.author(replied),
+                        utils::truncate(&app.filter.redact(&replied.text), 24)
+                    )
+                });
+            message_spans.push(Span::styled(trf(Msg::ReplyPrefix, &[&replied]), style));
+        }
+        // Memos, snapshots and patches show as a card, their text is only for peers that can't
+        // show them
+        let card = if let Some(memo) = message.voice_memo.as_ref() {
+            Some(trf(
+                Msg::VoiceMemoCard,
+                &[&voice::format_duration(memo.duration_secs)],
             ))
+        } else if let Some(snapshot) = message.snapshot.as_ref() {
+            Some(trf(Msg::SnapshotCard, &[&snapshot.width, &snapshot.height]))
+        } else {
+            message
+                .patch
+                .as_ref()
+                .map(|patch| trf(Msg::PatchCard, &[&patch.range, &patch.count_commits()]))
+        };
+        let mut message_lines = match card {
+            Some(card) => vec![Spans::from(Span::styled(card, style.fg(Color::Magenta)))],
+            None => shown_lines(markup, &app.filter, &message.text, style),
+        };
+        if message.clip {
+            message_lines.push(Spans::from(Span::styled(
+                tr(Msg::ClipHint),
+                style.fg(Color::Magenta).add_modifier(Modifier::ITALIC),
+            )));
+        }
+        if let Some(options) = message.poll.as_ref() {
+            message_lines.append(&mut poll_lines(message, options, &local_peer_id, style));
+        }
+        for (reaction, peers) in message.reactions.iter() {
+            if let Some(last_line) = message_lines.last_mut() {
+                last_line.0.push(Span::styled(
+                    format!("  {} {}", reaction, peers.len()),
+                    style,
+                ));
+            }
+        }
+        let delivery_status = match message.delivery {
+            Some(Delivery::Sending) => Some(tr(Msg::Sending)),
+            Some(Delivery::Failed) => Some(tr(Msg::SendFailed)),
+            Some(Delivery::Sent) | None => None,
+        };
+        if let (Some(delivery_status), Some(last_line)) =
+            (delivery_status, message_lines.last_mut())
+        {
+            last_line.0.push(Span::styled(
+                delivery_status,
+                style.add_modifier(Modifier::ITALIC),
+            ));
+        }
+        if app.config.link_previews {
+            for attachment in
+                preview::attachments(&app.filter.redact(&message.text), &app.ui.markup)
+            {
+                message_lines.append(&mut preview::card_lines(&attachment, style));
+            }
+        }
+        // The sender goes in front of the first line
+        message_spans.append(&mut message_lines[0].0);
+        message_lines[0] = Spans::from(message_spans);
+        // Long messages are cut off so they always fit into the history
+        message_lines.truncate(max_message_lines);
+
+        chat_history_item_heights.push(message_lines.len());
+        chat_history_items.push(ListItem::new(Text::from(message_lines)));
+        chat_history_item_messages.push(Some(i));
+        if last_seen_outgoing == Some(i) {
+            chat_history_items.push(ListItem::new(Span::styled(
+                tr(Msg::Seen),
+                Style::default()
+                    .fg(Color::DarkGray)
+                    .add_modifier(Modifier::ITALIC),
+            )));
+            chat_history_item_heights.push(1);
+            chat_history_item_messages.push(None);
+        }
+    }
+    if hidden > 0 {
+        chat_history_items.push(hidden_messages_item(hidden));
+        chat_history_item_messages.push(None);
+        chat_history_item_heights.push(1);
+    }
+
+    // The rest of a range selection is highlighted like the selected message
+    if let (Some((first, last)), Some(_)) =
+        (app.chat_history_selection(), app.ui.chat_history_anchor)
+    {
+        chat_history_items = chat_history_items
+            .into_iter()
+            .zip(chat_history_item_messages.iter())
+            .map(|(item, message)| match message {
+                Some(i) if (first..=last).contains(i) => {
+                    item.style(Style::default().bg(Color::DarkGray))
+                }
+                _ => item,
+            })
+            .collect();
+    }
+
+    let (conversation_label, conversation_color) =
+        conversation_label(&app.ui.current_conversation, app);
+    let chat_history_block = Block::default()
+        .title(Span::styled(
+            match app.current_conversation().map_or(0, |c| c.stored_pages) {
+                0 => trf(Msg::HistoryTitle, &[&conversation_label]),
+                older => trf(Msg::HistoryTitleOlder, &[&conversation_label, &older]),
+            },
+            conversation_color.map_or(Style::default(), |color| Style::default().fg(color)),
+        ))
+        .borders(Borders::ALL);
+    let chat_history_inner = chat_history_block.inner(chat_page_chunks[1]);
+    let selected_item = app.ui.chat_history_selected.and_then(|selected| {
+        chat_history_item_messages
+            .iter()
+            .position(|message| *message == Some(selected))
+    });
+    let (start, end) = utils::visible_range_with_heights(
+        app.ui.chat_history_offset,
+        selected_item,
+        &chat_history_item_heights,
+        chat_history_inner.height as usize,
+    );
+    app.ui.chat_history_offset = start;
+
+    app.ui
+        .hit_areas
+        .register(chat_page_chunks[1], HitTarget::ChatHistory);
+    let mut row = 0;
+    for (message, height) in chat_history_item_messages[start..end]
+        .iter()
+        .zip(chat_history_item_heights[start..end].iter())
+    {
+        if let Some(i) = message {
+            let mut message_rect = utils::row_rect(chat_history_inner, row);
+            message_rect.height = *height as u16;
+            app.ui
+                .hit_areas
+                .register(message_rect, HitTarget::ChatMessage(*i));
+        }
+        row += height;
+    }
+
+    let chat_history_list = List::new(chat_history_items[start..end].to_vec())
+        .block(chat_history_block)
+        .highlight_style(Style::default().bg(Color::DarkGray));
+    let mut chat_history_liststate = ListState::default();
+    chat_history_liststate.select(selected_item.and_then(|item| item.checked_sub(start)));
+    frame.render_stateful_widget(
+        chat_history_list,
+        chat_page_chunks[1],
+        &mut chat_history_liststate,
+    );
+
+    draw_command_hint(frame, chat_page_chunks[1], app);
+}
+
+// The commands matching a `/` typed into the input with their arguments, drawn over the bottom of
+// the history
+pub fn draw_command_hint<B: Backend>(frame: &mut Frame<B>, history_area: Rect, app: &app::App) {
+    if app.ui.page_focus != PageFocus::Chat || app.ui.chat_page_focus != ChatPageFocus::Input {
+        return;
+    }
+    let commands = commands::matching(app.ui.chat_input.as_str());
+    if commands.is_empty() {
+        return;
+    }
+
+    let height = (commands.len() as u16 + 2).min(history_area.height);
+    let area = Rect {
+        x: history_area.x,
+        y: history_area.y + history_area.height - height,
+        width: history_area.width,
+        height,
+    };
+    let command_items = commands
+        .iter()
+        .map(|command| {
+            ListItem::new(Spans::from(vec![
+                Span::styled(
+                    format!("/{} {}", command.name, command.args),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::styled(
+                    format!("  {}", command.help),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]))
         })
         .collect::<Vec<ListItem>>();
+    let command_list = List::new(command_items).block(
+        Block::default()
+            .title(Span::styled(tr(Msg::CommandHintTitle), Style::default()))
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded),
+    );
+    frame.render_widget(Clear, area);
+    frame.render_widget(command_list, area);
+}
+
+fn leader_action_label(action: LeaderAction) -> Msg {
+    match action {
+        LeaderAction::Peers => Msg::LeaderPeers,
+        LeaderAction::Chat => Msg::LeaderChat,
+        LeaderAction::Connection => Msg::LeaderConnection,
+        LeaderAction::Status => Msg::LeaderStatus,
+        LeaderAction::SplitView => Msg::LeaderSplitView,
+        LeaderAction::AllView => Msg::LeaderAllView,
+        LeaderAction::SwitchConversation => Msg::LeaderSwitchConversation,
+        LeaderAction::NextConversation => Msg::LeaderNextConversation,
+        LeaderAction::PreviousConversation => Msg::LeaderPreviousConversation,
+    }
+}
+
+// The shortcuts that can follow the leader key, while waiting for it
+pub fn draw_leader_hint<B: Backend>(frame: &mut Frame<B>, size: Rect, app: &app::App) {
+    if !app.ui.leader_pending {
+        return;
+    }
+
+    let key_style = Style::default().fg(Color::Cyan);
+    let help_style = Style::default().fg(Color::DarkGray);
+    let shortcut_items = std::iter::once(ListItem::new(Spans::from(vec![
+        Span::styled("1-9", key_style),
+        Span::styled(format!("  {}", tr(Msg::LeaderConversation)), help_style),
+    ])))
+    .chain(app.config.leader.bindings.iter().map(|(key, action)| {
+        ListItem::new(Spans::from(vec![
+            Span::styled(format!("{:<3}", key), key_style),
+            Span::styled(
+                format!("  {}", tr(leader_action_label(*action))),
+                help_style,
+            ),
+        ]))
+    }))
+    .collect::<Vec<ListItem>>();
 
-    let chat_history_list = List::new(chat_history_items).block(
+    let height = (shortcut_items.len() as u16 + 2).min(size.height);
+    let area = Rect {
+        x: size.x,
+        y: size.y + size.height - height,
+        width: size.width,
+        height,
+    };
+    let shortcut_list = List::new(shortcut_items).block(
         Block::default()
-            .title(Span::styled("History", Style::default()))
+            .title(Span::styled(tr(Msg::LeaderHintTitle), Style::default()))
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded),
+    );
+    frame.render_widget(Clear, area);
+    frame.render_widget(shortcut_list, area);
+}
+
+// The chat messages of every conversation interleaved by time, newest at the bottom, each
+// prefixed with its conversation
+pub fn draw_all_timeline<B: Backend>(frame: &mut Frame<B>, size: Rect, app: &mut app::App) {
+    let local_peer_id = *app.connection.swarm.local_peer_id();
+    let markup = &app.ui.markup;
+    let nick = app.ui.nick_input.as_str();
+    let inner_height = size.height.saturating_sub(2) as usize;
+
+    let mut messages = app
+        .conversations
+        .iter()
+        .flat_map(|(id, conversation)| {
+            conversation
+                .history
+                .iter()
+                .map(move |message| (id, message))
+        })
+        .filter(|(_, message)| message.kind == MessageKind::Chat)
+        .filter(|(_, message)| {
+            !message
+                .source_peer_id
+                .map_or(false, |peer_id| app.moderation.is_muted(&peer_id))
+        })
+        .collect::<Vec<(&ConversationId, &app::ChatMessage)>>();
+    messages.sort_by_key(|(_, message)| message.received_at);
+
+    // Only as many of the newest messages as fit
+    let mut items = vec![];
+    let mut rows = 0;
+    for (id, message) in messages.into_iter().rev() {
+        let (label, color) = conversation_label(id, app);
+        let highlight_words = match id {
+            ConversationId::Topic(topic) => app.config.topic_settings(topic).highlight_words,
+            ConversationId::Direct(_) => vec![],
+        };
+        let style = if message.source_peer_id == Some(local_peer_id) {
+            Style::default().fg(Color::Green)
+        } else if message.highlighted || notify::is_mention(&message.text, nick, &highlight_words) {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default().fg(Color::Gray)
+        };
+
+        let mut message_lines = shown_lines(markup, &app.filter, &message.text, style);
+        let mut prefix = vec![
+            Span::styled(
+                format!("{} ", label),
+                Style::default().fg(color.unwrap_or(Color::DarkGray)),
+            ),
+            Span::styled(format!("{}: ", app.nicks.author(message)), style),
+        ];
+        prefix.append(&mut message_lines[0].0);
+        message_lines[0] = Spans::from(prefix);
+        message_lines.truncate(inner_height.max(1));
+
+        rows += message_lines.len();
+        if rows > inner_height && !items.is_empty() {
+            break;
+        }
+        items.push(ListItem::new(Text::from(message_lines)));
+    }
+    items.reverse();
+
+    let timeline = List::new(items).block(
+        Block::default()
+            .title(Span::styled(tr(Msg::AllTimelineTitle), Style::default()))
             .borders(Borders::ALL),
     );
-    frame.render_widget(chat_history_list, chat_page_chunks[0]);
+    frame.render_widget(timeline, size);
+}
 
-    // Chat Input
-    let chat_input_text =
-        Text::styled(app.ui.chat_input.clone(), Style::default().fg(Color::White));
-    frame.set_cursor(
-        // Put cursor past the end of the input text
-        chat_page_chunks[1].x + app.ui.chat_input.width() as u16 + 1,
-        // Move one line down, from the border to the input line
-        chat_page_chunks[1].y + 1,
+// The size of the message being typed and how many peers will get it
+pub fn draw_compose_indicator<B: Backend>(frame: &mut Frame<B>, size: Rect, app: &mut app::App) {
+    let id = app.ui.current_conversation.clone();
+    let bytes = app.compose_size();
+    let max_bytes = app.config.gossipsub.max_transmit_size;
+
+    let (text, warning) = match &id {
+        _ if app.connection.is_busy() => (
+            trf(
+                Msg::ComposeIndicatorBusy,
+                &[&bytes, &max_bytes, &app.connection.backlog()],
+            ),
+            true,
+        ),
+        ConversationId::Topic(topic) => {
+            let peers = app.connection.mesh_peers(topic).len();
+            (
+                trf(Msg::ComposeIndicator, &[&bytes, &max_bytes, &peers]),
+                bytes > max_bytes || peers == 0,
+            )
+        }
+        ConversationId::Direct(peer_id) => {
+            let connected = app.connection.swarm.is_connected(peer_id);
+            (trf(Msg::ComposeIndicatorDirect, &[&bytes]), !connected)
+        }
+    };
+    let style = if warning {
+        Style::default().fg(Color::Red)
+    } else {
+        Style::default().fg(Color::DarkGray)
+    };
+    frame.render_widget(
+        Paragraph::new(Span::styled(text, style)).alignment(Alignment::Right),
+        size,
     );
-    let chat_input_paragraph = Paragraph::new(chat_input_text)
-        .block(
-            Block::default()
-                .title(Span::styled("Input", Style::default()))
-                .borders(Borders::ALL),
-        )
-        .alignment(Alignment::Left)
-        .wrap(Wrap { trim: true });
-    frame.render_widget(chat_input_paragraph, chat_page_chunks[1]);
+}
+
+pub fn draw_conversation_sidebar<B: Backend>(frame: &mut Frame<B>, size: Rect, app: &mut app::App) {
+    let conversation_ids = app.sorted_conversation_ids();
+    let selected = conversation_ids
+        .iter()
+        .position(|id| *id == app.ui.current_conversation);
+
+    let conversation_block = Block::default()
+        .title(Span::styled(tr(Msg::ConversationsTitle), Style::default()))
+        .borders(Borders::ALL);
+    let conversation_inner = conversation_block.inner(size);
+    let (start, end) = utils::visible_range(
+        0,
+        selected,
+        conversation_ids.len(),
+        conversation_inner.height as usize,
+    );
+    for (row, id) in conversation_ids[start..end].iter().enumerate() {
+        app.ui.hit_areas.register(
+            utils::row_rect(conversation_inner, row),
+            HitTarget::Conversation(id.clone()),
+        );
+    }
+
+    let conversation_items = conversation_ids[start..end]
+        .iter()
+        .map(|id| {
+            let unread = app.conversations.get(id).map_or(0, |c| c.unread);
+            // Conversations with unsent input are marked with a pencil
+            let has_draft = app
+                .conversations
+                .get(id)
+                .map_or(false, |c| !c.draft.is_empty());
+            let (mut label, color) = conversation_label(id, app);
+            if let ConversationId::Direct(peer_id) = id {
+                if app.config.is_verified(peer_id) {
+                    label = format!("{} ✓", label);
+                }
+            }
+            if has_draft {
+                label = format!("{} ✎", label);
+            }
+            let mentions = app.conversations.get(id).map_or(0, |c| c.mentions);
+            let muted = match id {
+                ConversationId::Topic(topic) => app.config.topic_settings(topic).muted,
+                ConversationId::Direct(_) => false,
+            };
+            if mentions > 0 {
+                ListItem::new(Span::styled(
+                    format!("{} ({}!)", label, unread),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ))
+            } else if unread > 0 && muted {
+                ListItem::new(Span::styled(
+                    format!("{} ({})", label, unread),
+                    Style::default().fg(Color::DarkGray),
+                ))
+            } else if unread > 0 {
+                ListItem::new(Span::styled(
+                    format!("{} ({})", label, unread),
+                    Style::default()
+                        .fg(Color::White)
+                        .add_modifier(Modifier::BOLD),
+                ))
+            } else {
+                ListItem::new(Span::styled(
+                    label,
+                    Style::default().fg(color.unwrap_or(Color::Gray)),
+                ))
+            }
+        })
+        .collect::<Vec<ListItem>>();
+
+    let conversation_list = List::new(conversation_items)
+        .block(conversation_block)
+        .highlight_style(Style::default().bg(Color::DarkGray))
+        .highlight_symbol("> ");
+
+    let mut conversation_liststate = ListState::default();
+    conversation_liststate.select(selected.and_then(|selected| selected.checked_sub(start)));
+    frame.render_stateful_widget(conversation_list, size, &mut conversation_liststate);
+}
+
+pub fn draw_member_pane<B: Backend>(frame: &mut Frame<B>, size: Rect, app: &mut app::App) {
+    let members = match &app.ui.current_conversation {
+        ConversationId::Topic(topic) => members::topic_members(app, topic),
+        ConversationId::Direct(_) => vec![],
+    };
+
+    let member_items = members
+        .iter()
+        .map(|member| {
+            let mut label = member.name.clone();
+            if member.verified {
+                label = format!("{} ✓", label);
+            }
+            if member.away {
+                label = trf(Msg::MemberAway, &[&label]);
+            }
+            if member.graylisted {
+                label = trf(Msg::MemberGraylisted, &[&label]);
+            }
+            let style = if member.is_local {
+                Style::default().fg(Color::Green)
+            } else if member.graylisted {
+                Style::default().fg(Color::Red)
+            } else if member.away {
+                Style::default().fg(Color::DarkGray)
+            } else {
+                Style::default().fg(Color::Gray)
+            };
+            let mut spans = vec![Span::styled(label, style)];
+            if let Some(card) = member.card.as_ref() {
+                spans.push(Span::styled(
+                    format!(" {}", card),
+                    Style::default().fg(Color::White),
+                ));
+            }
+            if let Some(score) = member.score {
+                let score_color = match score {
+                    70..=100 => Color::Green,
+                    40..=69 => Color::Yellow,
+                    _ => Color::Red,
+                };
+                spans.push(Span::styled(
+                    format!(" {}", score),
+                    Style::default().fg(score_color),
+                ));
+            }
+            if let Some(note) = member.note.as_ref() {
+                if !note.tags.is_empty() {
+                    spans.push(Span::styled(
+                        format!(" {}", note.tag_list()),
+                        Style::default().fg(Color::Cyan),
+                    ));
+                }
+                if !note.note.is_empty() {
+                    spans.push(Span::styled(
+                        format!(" {}", note.note),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+            }
+            ListItem::new(Spans::from(spans))
+        })
+        .collect::<Vec<ListItem>>();
+
+    let member_list = List::new(member_items).block(
+        Block::default()
+            .title(Span::styled(
+                trf(Msg::MembersTitle, &[&members.len()]),
+                Style::default(),
+            ))
+            .borders(Borders::ALL),
+    );
+    frame.render_widget(member_list, size);
 }
 
 pub fn draw_connection_page<B: Backend>(frame: &mut Frame<B>, size: Rect, app: &mut app::App) {
@@ -232,45 +1370,89 @@ pub fn draw_connection_page<B: Backend>(frame: &mut Frame<B>, size: Rect, app: &
                 Constraint::Length(3),
                 Constraint::Length(3),
                 Constraint::Length(3),
+                Constraint::Length(3),
             ]
             .as_ref(),
         )
         .split(size);
 
-    // Connection Log
-    // Regenerate Swarm Button
+    // Connection Log, with the peers of this session next to it
+    let log_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(20), Constraint::Length(PEER_LIST_WIDTH)].as_ref())
+        .split(connection_page_chunks[0]);
+    draw_peer_list(frame, log_chunks[1], app);
+
     let connection_log_style = if app.ui.connection_page_focus == ConnectionPageFocus::ConnectionLog
     {
         Style::default().add_modifier(Modifier::UNDERLINED)
     } else {
         Style::default()
     };
-    let connection_log_items = app
-        .connection
-        .log
+    let connection_log_block = Block::default()
+        .title(Span::styled(
+            tr(Msg::ConnectionLogTitle),
+            connection_log_style,
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Plain);
+    let connection_log_inner = connection_log_block.inner(log_chunks[0]);
+    let selected = app.ui.connection_log_liststate.selected();
+    let (start, end) = utils::visible_range(
+        app.ui.connection_log_offset,
+        selected,
+        app.connection.log.len(),
+        connection_log_inner.height as usize,
+    );
+    app.ui.connection_log_offset = start;
+
+    app.ui
+        .hit_areas
+        .register(log_chunks[0], HitTarget::ConnectionLog);
+    for (row, i) in (start..end).enumerate() {
+        app.ui.hit_areas.register(
+            utils::row_rect(connection_log_inner, row),
+            HitTarget::LogEntry(i),
+        );
+    }
+
+    let connection_log_items = app.connection.log[start..end]
         .iter()
-        .map(|log_entry| ListItem::new(Text::styled(log_entry, Style::default().fg(Color::Gray))))
+        .map(|log_entry| {
+            let level_style = match log_entry.level {
+                LogLevel::Debug => Style::default().fg(Color::DarkGray),
+                LogLevel::Info => Style::default().fg(Color::Gray),
+                LogLevel::Warn => Style::default().fg(Color::Yellow),
+                LogLevel::Error => Style::default().fg(Color::Red),
+            };
+            ListItem::new(Spans::from(vec![
+                Span::styled(
+                    format!("{:>8} ", log_entry.format_time(app.ui.log_relative_time)),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::styled(
+                    format!("{:<8} ", log_entry.category.label()),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::styled(log_entry.text.as_str(), level_style),
+            ]))
+        })
         .collect::<Vec<ListItem>>();
 
     let connection_log_list = List::new(connection_log_items)
-        .block(
-            Block::default()
-                .title(Span::styled("Connection Log", connection_log_style))
-                .borders(Borders::ALL)
-                .border_type(BorderType::Plain),
-        )
+        .block(connection_log_block)
         .highlight_style(
             Style::default()
                 .bg(Color::DarkGray)
                 .add_modifier(Modifier::BOLD),
         )
         .highlight_symbol(">> ");
-    app.ui.connection_log_allocation = Some(connection_page_chunks[0]);
-
+    let mut connection_log_liststate = ListState::default();
+    connection_log_liststate.select(selected.and_then(|selected| selected.checked_sub(start)));
     frame.render_stateful_widget(
         connection_log_list,
-        connection_page_chunks[0],
-        &mut app.ui.connection_log_liststate,
+        log_chunks[0],
+        &mut connection_log_liststate,
     );
 
     // Regenerate Swarm Button
@@ -284,55 +1466,330 @@ pub fn draw_connection_page<B: Backend>(frame: &mut Frame<B>, size: Rect, app: &
         };
     let regenerate_button = Block::default()
         .title(Span::styled(
-            "Regenerate Connection",
+            tr(Msg::RegenerateButton),
             regenerate_button_style,
         ))
         .borders(Borders::NONE);
     frame.render_widget(regenerate_button, connection_page_chunks[1]);
+    app.ui
+        .hit_areas
+        .register(connection_page_chunks[1], HitTarget::RegenerateButton);
 
-    // Address Input Field
-    let addr_input_span = Span::styled(app.ui.addr_input.as_str(), Style::default());
-    let addr_input_field_style =
-        if app.ui.connection_page_focus == ConnectionPageFocus::AddrInputField {
-            // Chat Input paragraph
-            frame.set_cursor(
-                // Put cursor past the end of the input text
-                connection_page_chunks[2].x + app.ui.addr_input.width() as u16 + 1,
-                // Move one line down, from the border to the input line
-                connection_page_chunks[2].y + 1,
-            );
-            Style::default().add_modifier(Modifier::UNDERLINED)
-        } else {
-            Style::default()
-        };
-    let addr_input_field = Paragraph::new(addr_input_span).block(
+    // External Address
+    let shareable_addrs = app.connection.shareable_addrs();
+    let external_addr_text = if app.config.outbound_only {
+        Text::styled(tr(Msg::ListeningDisabled), Style::default().fg(Color::Gray))
+    } else if !shareable_addrs.is_empty() {
+        Text::styled(
+            shareable_addrs
+                .iter()
+                .map(|addr| addr.to_string())
+                .collect::<Vec<String>>()
+                .join(" "),
+            Style::default().fg(Color::White),
+        )
+    } else if app.connection.port_mapper.enabled {
+        Text::styled(
+            tr(Msg::WaitingForPortMapping),
+            Style::default().fg(Color::Gray),
+        )
+    } else {
+        Text::styled(
+            tr(Msg::PortMappingDisabled),
+            Style::default().fg(Color::Gray),
+        )
+    };
+    let external_addr_field = Paragraph::new(external_addr_text).block(
         Block::default()
-            .title(Span::styled("Connect to Multiaddress", addr_input_field_style))
+            .title(Span::styled(
+                tr(Msg::ExternalAddressTitle),
+                Style::default(),
+            ))
             .borders(Borders::ALL)
             .border_type(BorderType::Plain),
     );
-    frame.render_widget(addr_input_field, connection_page_chunks[2]);
+    frame.render_widget(external_addr_field, connection_page_chunks[2]);
+
+    // Address Input Field, red with a hint how to fix it while the address is invalid
+    let addr_input_focused = app.ui.page_focus == PageFocus::Connection
+        && app.ui.connection_page_focus == ConnectionPageFocus::AddrInputField;
+    let (addr_input_title, addr_input_style) = match addrinput::hint(app) {
+        Some(hint) => (
+            format!("{} - {}", tr(Msg::ConnectToMultiaddrTitle), hint),
+            Style::default().fg(Color::Red),
+        ),
+        None => (
+            tr(Msg::ConnectToMultiaddrTitle).to_string(),
+            Style::default().fg(Color::White),
+        ),
+    };
+    let addr_completion = addrinput::completion(app).filter(|_| addr_input_focused);
+    draw_styled_text_input(
+        frame,
+        connection_page_chunks[3],
+        &app.ui.addr_input,
+        &addr_input_title,
+        addr_input_focused,
+        addr_input_style,
+        addr_completion.as_deref(),
+    );
+    app.ui
+        .hit_areas
+        .register(connection_page_chunks[3], HitTarget::AddrInput);
 
     // Nickname Input Field
-    let nick_input_span = Span::styled(app.ui.nick_input.as_str(), Style::default());
-    let nick_input_field_style =
-        if app.ui.connection_page_focus == ConnectionPageFocus::NickInputField {
-            // Chat Input paragraph
-            frame.set_cursor(
-                // Put cursor past the end of the input text
-                connection_page_chunks[3].x + app.ui.nick_input.width() as u16 + 1,
-                // Move one line down, from the border to the input line
-                connection_page_chunks[3].y + 1,
-            );
-            Style::default().add_modifier(Modifier::UNDERLINED)
-        } else {
+    draw_text_input(
+        frame,
+        connection_page_chunks[4],
+        &app.ui.nick_input,
+        tr(Msg::NicknameTitle),
+        app.ui.page_focus == PageFocus::Connection
+            && app.ui.connection_page_focus == ConnectionPageFocus::NickInputField,
+    );
+    app.ui
+        .hit_areas
+        .register(connection_page_chunks[4], HitTarget::NickInput);
+
+    if addr_input_focused {
+        draw_addr_dropdown(frame, size, connection_page_chunks[3], app);
+    }
+}
+
+const PEER_LIST_WIDTH: u16 = 36;
+
+// The peers we are or were connected to, the selected one's actions open with Enter
+fn draw_peer_list<B: Backend>(frame: &mut Frame<B>, size: Rect, app: &mut app::App) {
+    let entries = peerlist::entries(app);
+    app.ui.peer_list_selected = app
+        .ui
+        .peer_list_selected
+        .min(entries.len().saturating_sub(1));
+    let focused = app.ui.connection_page_focus == ConnectionPageFocus::PeerList;
+
+    let peer_list_style = if focused {
+        Style::default().add_modifier(Modifier::UNDERLINED)
+    } else {
+        Style::default()
+    };
+    let peer_list_block = Block::default()
+        .title(Span::styled(
+            trf(Msg::PeerListTitle, &[&entries.len()]),
+            peer_list_style,
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Plain);
+    let peer_list_inner = peer_list_block.inner(size);
+    let selected = Some(app.ui.peer_list_selected).filter(|_| !entries.is_empty());
+    let (start, end) =
+        utils::visible_range(0, selected, entries.len(), peer_list_inner.height as usize);
+
+    app.ui.hit_areas.register(size, HitTarget::PeerList);
+    for (row, i) in (start..end).enumerate() {
+        app.ui.hit_areas.register(
+            utils::row_rect(peer_list_inner, row),
+            HitTarget::PeerEntry(i),
+        );
+    }
+
+    let peer_items = entries[start..end]
+        .iter()
+        .map(|entry| {
+            let (marker, style) = if entry.connected {
+                ("●", Style::default().fg(Color::Green))
+            } else {
+                ("○", Style::default().fg(Color::DarkGray))
+            };
+            let mut spans = vec![
+                Span::styled(format!("{} ", marker), style),
+                Span::styled(entry.label(), Style::default().fg(Color::White)),
+            ];
+            if let Some(rtt) = entry.rtt.filter(|_| entry.connected) {
+                spans.push(Span::styled(
+                    format!(" {}ms", rtt.as_millis()),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            ListItem::new(Spans::from(spans))
+        })
+        .collect::<Vec<ListItem>>();
+    let peer_list = List::new(peer_items)
+        .block(peer_list_block)
+        .highlight_style(
             Style::default()
-        };
-    let nick_input_field = Paragraph::new(nick_input_span).block(
-        Block::default()
-            .title(Span::styled("Nickname", nick_input_field_style))
-            .borders(Borders::ALL)
-            .border_type(BorderType::Plain),
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        );
+    let mut peer_liststate = ListState::default();
+    peer_liststate.select(
+        selected
+            .filter(|_| focused)
+            .and_then(|selected| selected.checked_sub(start)),
+    );
+    frame.render_stateful_widget(peer_list, size, &mut peer_liststate);
+}
+
+// At most this many recent addresses are shown at once under the address input
+const ADDR_DROPDOWN_ROWS: u16 = 6;
+
+// The recent addresses matching the address input, under it if there is room and above otherwise
+fn draw_addr_dropdown<B: Backend>(
+    frame: &mut Frame<B>,
+    size: Rect,
+    input_area: Rect,
+    app: &mut app::App,
+) {
+    let addrs = recentaddrs::matching(app);
+    app.ui.addr_dropdown_selected = app
+        .ui
+        .addr_dropdown_selected
+        .filter(|selected| *selected < addrs.len());
+    if addrs.is_empty() {
+        return;
+    }
+
+    let height = (addrs.len() as u16).min(ADDR_DROPDOWN_ROWS) + 2;
+    let y = if size.bottom().saturating_sub(input_area.bottom()) >= height {
+        input_area.bottom()
+    } else {
+        input_area.top().saturating_sub(height).max(size.top())
+    };
+    let area = Rect::new(input_area.x, y, input_area.width, height.min(size.height));
+    let block = Block::default()
+        .title(Span::styled(tr(Msg::RecentAddrsTitle), Style::default()))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Plain);
+    let inner = block.inner(area);
+    let (start, end) = utils::visible_range(
+        0,
+        app.ui.addr_dropdown_selected,
+        addrs.len(),
+        inner.height as usize,
+    );
+
+    let items = addrs[start..end]
+        .iter()
+        .map(|addr| {
+            ListItem::new(Span::styled(
+                addr.to_string(),
+                Style::default().fg(Color::White),
+            ))
+        })
+        .collect::<Vec<ListItem>>();
+    let list = List::new(items).block(block).highlight_style(
+        Style::default()
+            .bg(Color::DarkGray)
+            .add_modifier(Modifier::BOLD),
+    );
+    let mut liststate = ListState::default();
+    liststate.select(
+        app.ui
+            .addr_dropdown_selected
+            .and_then(|selected| selected.checked_sub(start)),
+    );
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(list, area, &mut liststate);
+
+    for (row, i) in (start..end).enumerate() {
+        app.ui
+            .hit_areas
+            .register(utils::row_rect(inner, row), HitTarget::RecentAddr(i));
+    }
+}
+
+pub fn draw_status_page<B: Backend>(frame: &mut Frame<B>, size: Rect, app: &mut app::App) {
+    // The audit log gets the lower part once the moderation layer did something
+    let size = if app.audit.is_empty() {
+        size
+    } else {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(6), Constraint::Percentage(40)].as_ref())
+            .split(size);
+        draw_audit_log(frame, chunks[1], app);
+        chunks[0]
+    };
+
+    let entries = status::entries(app);
+    app.ui.status_selected = app.ui.status_selected.min(entries.len().saturating_sub(1));
+
+    let status_block = Block::default()
+        .title(Span::styled(tr(Msg::StatusTitle), Style::default()))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Plain);
+    let status_inner = status_block.inner(size);
+    let (start, end) = utils::visible_range(
+        0,
+        Some(app.ui.status_selected),
+        entries.len(),
+        status_inner.height as usize,
     );
-    frame.render_widget(nick_input_field, connection_page_chunks[3]);
+    for (row, i) in (start..end).enumerate() {
+        app.ui.hit_areas.register(
+            utils::row_rect(status_inner, row),
+            HitTarget::StatusEntry(i),
+        );
+    }
+
+    let label_width = entries
+        .iter()
+        .map(|entry| entry.label.width())
+        .max()
+        .unwrap_or_default();
+    let status_items = entries[start..end]
+        .iter()
+        .map(|entry| {
+            ListItem::new(Spans::from(vec![
+                Span::styled(
+                    format!("{:width$}  ", entry.label, width = label_width),
+                    Style::default().fg(Color::Gray),
+                ),
+                Span::styled(entry.value.clone(), Style::default().fg(Color::White)),
+            ]))
+        })
+        .collect::<Vec<ListItem>>();
+
+    let status_list = List::new(status_items)
+        .block(status_block)
+        .highlight_style(Style::default().bg(Color::DarkGray))
+        .highlight_symbol("> ");
+    let mut status_liststate = ListState::default();
+    status_liststate.select(app.ui.status_selected.checked_sub(start));
+    frame.render_stateful_widget(status_list, size, &mut status_liststate);
+}
+
+// The newest entries of the audit log, newest last
+fn draw_audit_log<B: Backend>(frame: &mut Frame<B>, size: Rect, app: &app::App) {
+    let audit_block = Block::default()
+        .title(Span::styled(tr(Msg::AuditTitle), Style::default()))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Plain);
+    let height = audit_block.inner(size).height as usize;
+    let skip = app.audit.entries().len().saturating_sub(height);
+    let audit_items = app
+        .audit
+        .entries()
+        .skip(skip)
+        .map(|entry| {
+            ListItem::new(Spans::from(vec![
+                Span::styled(
+                    format!("{} ", entry.time.format("%H:%M:%S")),
+                    Style::default().fg(Color::Gray),
+                ),
+                Span::styled(
+                    format!("{:<19} ", entry.action.label()),
+                    Style::default().fg(Color::Yellow),
+                ),
+                Span::styled(
+                    entry
+                        .peer_id
+                        .map(|peer_id| format!("{} ", utils::short_peer_id(&peer_id)))
+                        .unwrap_or_default(),
+                    Style::default().fg(Color::White),
+                ),
+                Span::styled(entry.reason.clone(), Style::default().fg(Color::Gray)),
+            ]))
+        })
+        .collect::<Vec<ListItem>>();
+
+    frame.render_widget(List::new(audit_items).block(audit_block), size);
 }
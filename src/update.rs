@@ -0,0 +1,72 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+
+use libp2p::PeerId;
+
+use crate::app::App;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::i18n::{trf, Msg};
+
+// A newer version is only announced once this many peers run it, so a single peer can't make us
+// nag about a version that doesn't exist
+const MIN_PEERS: usize = 2;
+
+// The p2pchat versions peers told us about with identify, when `update_check` is set
+#[derive(Debug, Default)]
+pub struct UpdateCheck {
+    newer: BTreeMap<String, HashSet<PeerId>>,
+    // The newest version enough peers run, shown in the header
+    pub available: Option<String>,
+}
+
+// The numeric parts of a version like `0.2.1`. Pre-releases and anything else are ignored
+fn parse(version: &str) -> Option<Vec<u64>> {
+    version
+        .split('.')
+        .map(|part| part.parse::<u64>().ok())
+        .collect()
+}
+
+fn compare(a: &[u64], b: &[u64]) -> Ordering {
+    let len = a.len().max(b.len());
+    let part = |version: &[u64], i: usize| version.get(i).copied().unwrap_or(0);
+    (0..len)
+        .map(|i| part(a, i).cmp(&part(b, i)))
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+pub fn is_newer(version: &str, than: &str) -> bool {
+    match (parse(version), parse(than)) {
+        (Some(version), Some(than)) => compare(&version, &than) == Ordering::Greater,
+        _ => false,
+    }
+}
+
+// Notes the version a peer runs, and announces it once enough peers run it and it is newer than
+// ours and the one announced before
+pub fn observe(peer_id: PeerId, version: &str, app: &mut App) {
+    if !app.config.update_check || !is_newer(version, env!("CARGO_PKG_VERSION")) {
+        return;
+    }
+    let peers = app.update.newer.entry(version.to_string()).or_default();
+    peers.insert(peer_id);
+    if peers.len() < MIN_PEERS {
+        return;
+    }
+    if let Some(available) = app.update.available.as_ref() {
+        if !is_newer(version, available) {
+            return;
+        }
+    }
+
+    app.update.available = Some(version.to_string());
+    app.connection.push_log(
+        LogLevel::Info,
+        LogCategory::General,
+        &trf(
+            Msg::LogUpdateAvailable,
+            &[&version, &env!("CARGO_PKG_VERSION")],
+        ),
+    );
+}
@@ -1,10 +1,142 @@
-use tui::layout::Rect;
-
+use libp2p::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
+use tui::layout::{Constraint, Direction, Layout, Rect};
 
 // Coord: (column, row)
 pub fn coord_in_rect(coord: (u16, u16), rect: Rect) -> bool {
-    let x_range = rect.x..=rect.width;
-    let y_range = rect.y..=rect.height;
+    let x_range = rect.x..rect.x + rect.width;
+    let y_range = rect.y..rect.y + rect.height;
 
     x_range.contains(&coord.0) && y_range.contains(&coord.1)
-}
\ No newline at end of file
+}
+
+// The range of items a list of single line items shows in `height` rows, scrolled as little as
+// possible from `offset` to keep the selected item in view
+pub fn visible_range(
+    offset: usize,
+    selected: Option<usize>,
+    len: usize,
+    height: usize,
+) -> (usize, usize) {
+    let mut start = offset.min(len.saturating_sub(1));
+    if let Some(selected) = selected {
+        if selected >= start + height {
+            start = selected + 1 - height;
+        }
+        if selected < start {
+            start = selected;
+        }
+    }
+
+    (start, (start + height).min(len))
+}
+
+// Like `visible_range`, for items spanning `item_heights` rows each. The selected item is kept in
+// view as long as it fits
+pub fn visible_range_with_heights(
+    offset: usize,
+    selected: Option<usize>,
+    item_heights: &[usize],
+    height: usize,
+) -> (usize, usize) {
+    let len = item_heights.len();
+    let mut start = offset.min(len.saturating_sub(1));
+    if let Some(selected) = selected {
+        if selected < start {
+            start = selected;
+        }
+        while start < selected && item_heights[start..=selected].iter().sum::<usize>() > height {
+            start += 1;
+        }
+    }
+
+    let mut end = start;
+    let mut used = 0;
+    while end < len && used + item_heights[end] <= height {
+        used += item_heights[end];
+        end += 1;
+    }
+
+    (start, end)
+}
+
+// The rect of a single row inside an area
+pub fn row_rect(area: Rect, row: usize) -> Rect {
+    Rect {
+        x: area.x,
+        y: area.y + row as u16,
+        width: area.width,
+        height: 1,
+    }
+}
+
+pub fn copy_to_clipboard(text: String) -> Result<(), anyhow::Error> {
+    let mut clipboard = arboard::Clipboard::new()?;
+    clipboard.set_text(text)?;
+
+    Ok(())
+}
+
+pub fn paste_from_clipboard() -> Result<String, anyhow::Error> {
+    let mut clipboard = arboard::Clipboard::new()?;
+    Ok(clipboard.get_text()?)
+}
+
+// Opens a link with the default browser, without blocking
+pub fn open_link(url: &str) {
+    open::that_in_background(url);
+}
+
+// Shortens text to at most `max_chars` characters, marking that it was cut off
+pub fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        text.to_string()
+    } else {
+        format!("{}..", text.chars().take(max_chars).collect::<String>())
+    }
+}
+
+// Abbreviates a peer id to its first four and last five characters
+pub fn short_peer_id(peer_id: &PeerId) -> String {
+    let peer_id = peer_id.to_string();
+    format!(
+        "{}..{}",
+        &peer_id[..4],
+        &peer_id[peer_id.chars().count() - 5..]
+    )
+}
+
+// The address with the peer id appended, so it can be dialed as that peer
+pub fn with_peer_id(addr: Multiaddr, peer_id: PeerId) -> Multiaddr {
+    match addr.iter().last() {
+        Some(Protocol::P2p(_)) => addr,
+        _ => addr.with(Protocol::P2p(peer_id.into())),
+    }
+}
+
+// A rect centered in `rect`, taking up the given percentages of its size
+pub fn centered_rect(percent_x: u16, percent_y: u16, rect: Rect) -> Rect {
+    let vertical_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_y) / 2),
+                Constraint::Percentage(percent_y),
+                Constraint::Percentage((100 - percent_y) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(rect);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_x) / 2),
+                Constraint::Percentage(percent_x),
+                Constraint::Percentage((100 - percent_x) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(vertical_chunks[1])[1]
+}
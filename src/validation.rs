@@ -0,0 +1,252 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use libp2p::gossipsub::MessageAcceptance;
+use libp2p::PeerId;
+
+use crate::i18n::Msg;
+use crate::moderation::Moderation;
+use crate::peerexchange;
+use crate::protocol::{self, Decoded, Payload, StatusCard};
+use crate::voice;
+
+// The longest chat message text in bytes
+pub const MAX_TEXT_LEN: usize = 8 * 1024;
+pub const MAX_NICK_LEN: usize = 64;
+pub const MAX_REACTION_LEN: usize = 32;
+// One per number key
+pub const MAX_POLL_OPTIONS: usize = 9;
+// Of the base64 encoded clips of voice memos
+pub const MAX_VOICE_MEMO_LEN: usize = 1024 * 1024;
+// Of the ciphertext of sealed payloads, a chat message with a voice memo in base64 once more
+const MAX_SEALED_LEN: usize = MAX_VOICE_MEMO_LEN * 4 / 3 + 2 * MAX_TEXT_LEN;
+// Of the text of terminal snapshots, a full screen with box drawing characters fits
+pub const MAX_SNAPSHOT_LEN: usize = 32 * 1024;
+// Of the mailboxes of patches, a topic message has to fit into the gossipsub `max_transmit_size`
+pub const MAX_PATCH_LEN: usize = 48 * 1024;
+// Of status cards, emoji can take several code points
+const MAX_CARD_EMOJI_LEN: usize = 32;
+pub const MAX_CARD_TEXT_LEN: usize = 64;
+// Of the base64 encoded keys and signatures in nick certificates, RSA ones are the longest
+const MAX_KEY_LEN: usize = 2048;
+// How far the clock of a message may be ahead of ours. Far more than any topic sends, but a peer
+// can't push our clock to the end of its range
+const MAX_CLOCK_LEAD: u64 = 1 << 32;
+// More messages than this from a single peer within the window are dropped as spam
+const SPAM_LIMIT: usize = 20;
+const SPAM_WINDOW: Duration = Duration::from_secs(10);
+
+// The outcome of validating a gossipsub message, with the reason when it isn't accepted
+pub enum Verdict {
+    Accept(Decoded),
+    // The message is invalid and the peer who forwarded it is penalized
+    Reject(Msg),
+    // The message is dropped without penalizing anyone
+    Ignore(Msg),
+}
+
+impl Verdict {
+    pub fn acceptance(&self) -> MessageAcceptance {
+        match self {
+            Self::Accept(_) => MessageAcceptance::Accept,
+            Self::Reject(_) => MessageAcceptance::Reject,
+            Self::Ignore(_) => MessageAcceptance::Ignore,
+        }
+    }
+}
+
+// Checks incoming topic messages before they are shown and forwarded to other peers
+#[derive(Debug, Default)]
+pub struct Validator {
+    // When the recent messages of each peer arrived
+    recent: HashMap<PeerId, VecDeque<Instant>>,
+}
+
+impl Validator {
+    // `may_publish` is whether the source may post to the topic, see `Config::may_publish()`
+    pub fn validate(
+        &mut self,
+        source: Option<PeerId>,
+        data: &[u8],
+        may_publish: bool,
+        moderation: &Moderation,
+        clock: u64,
+    ) -> Verdict {
+        if let Some(source) = source.as_ref() {
+            if moderation.is_blocked(source) {
+                return Verdict::Ignore(Msg::ValidationBlocked);
+            }
+        }
+
+        let decoded = match protocol::decode(data) {
+            Ok(decoded) => decoded,
+            Err(_) => return Verdict::Reject(Msg::ValidationMalformed),
+        };
+        // Payloads of newer protocol versions can't be checked, but other peers might
+        // understand them
+        if let Decoded::Envelope(envelope) = &decoded {
+            if let Err(reason) = validate_payload(&envelope.payload, clock) {
+                return Verdict::Reject(reason);
+            }
+            // Peers that don't know the topic is an announcement topic forward these, so nobody
+            // is penalized
+            if !may_publish && is_post(&envelope.payload) {
+                return Verdict::Ignore(Msg::ValidationNotAnnouncer);
+            }
+        }
+
+        if let Some(source) = source {
+            if self.is_flooding(source) {
+                return Verdict::Ignore(Msg::ValidationSpam);
+            }
+        }
+
+        Verdict::Accept(decoded)
+    }
+
+    // Records a message from the peer and returns whether it sent too many recently
+    fn is_flooding(&mut self, peer_id: PeerId) -> bool {
+        let now = Instant::now();
+        let recent = self.recent.entry(peer_id).or_default();
+        while recent.front().map_or(false, |received| {
+            now.duration_since(*received) > SPAM_WINDOW
+        }) {
+            recent.pop_front();
+        }
+        recent.push_back(now);
+
+        recent.len() > SPAM_LIMIT
+    }
+}
+
+// Whether a payload changes what a topic shows. Announcement topics only take these from their
+// announcers, peer exchange and nick certificates still come from everyone
+fn is_post(payload: &Payload) -> bool {
+    matches!(
+        payload,
+        Payload::Chat(_)
+            | Payload::Reaction { .. }
+            | Payload::Delete { .. }
+            | Payload::Vote { .. }
+            | Payload::TopicState(_)
+            | Payload::Sealed { .. }
+    )
+}
+
+// `clock` is ours, see `App::clock`
+pub fn validate_payload(payload: &Payload, clock: u64) -> Result<(), Msg> {
+    match payload {
+        Payload::Chat(chat_message) => {
+            if !is_valid_clock(chat_message.clock, clock) {
+                return Err(Msg::ValidationClockAhead);
+            }
+            if chat_message.text.trim().is_empty() {
+                return Err(Msg::ValidationEmpty);
+            }
+            if chat_message.text.len() > MAX_TEXT_LEN
+                || chat_message
+                    .nick
+                    .as_ref()
+                    .map_or(false, |nick| nick.len() > MAX_NICK_LEN)
+            {
+                return Err(Msg::ValidationTooLong);
+            }
+            if let Some(options) = chat_message.poll.as_ref() {
+                if options.len() < 2 || options.iter().any(|option| option.trim().is_empty()) {
+                    return Err(Msg::ValidationEmpty);
+                }
+                if options.len() > MAX_POLL_OPTIONS
+                    || options.iter().any(|option| option.len() > MAX_TEXT_LEN)
+                {
+                    return Err(Msg::ValidationTooLong);
+                }
+            }
+            if let Some(memo) = chat_message.voice_memo.as_ref() {
+                if !voice::is_valid_format(&memo.format) {
+                    return Err(Msg::ValidationMalformed);
+                }
+                if memo.data.len() > MAX_VOICE_MEMO_LEN {
+                    return Err(Msg::ValidationTooLong);
+                }
+            }
+            if let Some(snapshot) = chat_message.snapshot.as_ref() {
+                if snapshot.text.len() > MAX_SNAPSHOT_LEN {
+                    return Err(Msg::ValidationTooLong);
+                }
+            }
+            if let Some(patch) = chat_message.patch.as_ref() {
+                if patch.text.trim().is_empty() {
+                    return Err(Msg::ValidationEmpty);
+                }
+                if patch.text.len() > MAX_PATCH_LEN || patch.range.len() > MAX_NICK_LEN {
+                    return Err(Msg::ValidationTooLong);
+                }
+            }
+        }
+        // Read markers, topic keys, presence, DM encryption and pings are only sent directly
+        Payload::ReadMarker { .. }
+        | Payload::TopicKey { .. }
+        | Payload::Presence { .. }
+        | Payload::DmKey(_)
+        | Payload::DirectSealed { .. }
+        | Payload::Ping => return Err(Msg::ValidationMalformed),
+        Payload::Reaction { reaction, .. } => {
+            if reaction.is_empty() {
+                return Err(Msg::ValidationEmpty);
+            }
+            if reaction.len() > MAX_REACTION_LEN {
+                return Err(Msg::ValidationTooLong);
+            }
+        }
+        // Votes for options a poll doesn't have are ignored
+        Payload::Delete { .. } | Payload::Vote { .. } => {}
+        Payload::NickCertificate(certificate) => {
+            if certificate.nick.is_empty() {
+                return Err(Msg::ValidationEmpty);
+            }
+            if certificate.nick.len() > MAX_NICK_LEN
+                || certificate.public_key.len() > MAX_KEY_LEN
+                || certificate.signature.len() > MAX_KEY_LEN
+            {
+                return Err(Msg::ValidationTooLong);
+            }
+        }
+        Payload::Peers { addrs } => {
+            if addrs.len() > peerexchange::MAX_ADDRS {
+                return Err(Msg::ValidationTooLong);
+            }
+        }
+        Payload::TopicState(state) => {
+            if !is_valid_clock(state.clock(), clock) {
+                return Err(Msg::ValidationClockAhead);
+            }
+            let pin_len = state
+                .pinned
+                .value
+                .as_ref()
+                .map_or(0, |pin| pin.text.len() + pin.author.len());
+            if state.description.value.len() > MAX_TEXT_LEN || pin_len > MAX_TEXT_LEN {
+                return Err(Msg::ValidationTooLong);
+            }
+        }
+        // Checked once decrypted
+        Payload::Sealed { ciphertext, .. } => {
+            if ciphertext.len() > MAX_SEALED_LEN {
+                return Err(Msg::ValidationTooLong);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn is_valid_clock(clock: u64, ours: u64) -> bool {
+    clock <= ours.saturating_add(MAX_CLOCK_LEAD)
+}
+
+// Cards that don't fit next to a nick are dropped
+pub fn is_valid_card(card: &StatusCard) -> bool {
+    !card.emoji.is_empty()
+        && card.emoji.len() <= MAX_CARD_EMOJI_LEN
+        && card.text.len() <= MAX_CARD_TEXT_LEN
+}
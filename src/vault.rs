@@ -0,0 +1,159 @@
+use anyhow::anyhow;
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+// The format of sealed data. Files from before there was one are version 0, their key was derived
+// with `KdfParams::LEGACY`
+const VERSION: u32 = 1;
+// Higher costs in a file would take minutes and gigabytes to open, nobody we wrote it for
+const MAX_LOG_N: u8 = 20;
+
+// Data encrypted with a key derived from a passphrase, as stored in files
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Sealed {
+    #[serde(default)]
+    version: u32,
+    #[serde(default = "KdfParams::legacy")]
+    kdf: KdfParams,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+// The scrypt parameters a key was derived with, stored so they can be raised for new files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct KdfParams {
+    log_n: u8,
+    r: u32,
+    p: u32,
+}
+
+impl KdfParams {
+    // What `scrypt::Params::recommended()` was when sealed files didn't store their parameters
+    const LEGACY: Self = Self {
+        log_n: 15,
+        r: 8,
+        p: 1,
+    };
+    // For everything sealed now
+    const CURRENT: Self = Self::LEGACY;
+
+    fn legacy() -> Self {
+        Self::LEGACY
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], kdf: KdfParams) -> Result<[u8; 32], anyhow::Error> {
+    anyhow::ensure!(
+        kdf.log_n <= MAX_LOG_N,
+        "the scrypt cost {} is too high",
+        kdf.log_n
+    );
+    let params = scrypt::Params::new(kdf.log_n, kdf.r, kdf.p)
+        .map_err(|e| anyhow!("invalid scrypt parameters: {}", e))?;
+    let mut key = [0u8; 32];
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, &mut key)
+        .map_err(|e| anyhow!("deriving key failed with Err `{}`", e))?;
+    Ok(key)
+}
+
+// Data of a newer version could be sealed in a way we don't know
+fn check_version(sealed: &Sealed) -> Result<(), anyhow::Error> {
+    anyhow::ensure!(
+        sealed.version <= VERSION,
+        "sealed with the newer format {}, update p2pchat to open it",
+        sealed.version
+    );
+    Ok(())
+}
+
+// A key derived from a passphrase, with its salt. Deriving takes a while on purpose, so files
+// that are written again and again are sealed with one kept in memory
+#[derive(Clone)]
+pub struct DerivedKey {
+    salt: [u8; 16],
+    kdf: KdfParams,
+    key: [u8; 32],
+}
+
+impl DerivedKey {
+    // With a new random salt
+    pub fn new(passphrase: &str) -> Result<Self, anyhow::Error> {
+        anyhow::ensure!(!passphrase.is_empty(), "the passphrase must not be empty");
+
+        let mut salt = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let kdf = KdfParams::CURRENT;
+        let key = derive_key(passphrase, &salt, kdf)?;
+        Ok(Self { salt, kdf, key })
+    }
+
+    // With the salt of sealed data, so the key opens it and everything sealed with it afterwards
+    // can be opened without deriving again
+    pub fn for_sealed(passphrase: &str, sealed: &Sealed) -> Result<Self, anyhow::Error> {
+        check_version(sealed)?;
+        let salt = <[u8; 16]>::try_from(data_encoding::BASE64.decode(sealed.salt.as_bytes())?)
+            .map_err(|_| anyhow!("invalid salt"))?;
+        let key = derive_key(passphrase, &salt, sealed.kdf)?;
+        Ok(Self {
+            salt,
+            kdf: sealed.kdf,
+            key,
+        })
+    }
+
+    // Whether the data was sealed with a key of the same salt and parameters, and so `open` can
+    // open it
+    pub fn sealed_with(&self, sealed: &Sealed) -> bool {
+        sealed.version <= VERSION
+            && sealed.kdf == self.kdf
+            && sealed.salt == data_encoding::BASE64.encode(&self.salt)
+    }
+
+    pub fn open(&self, sealed: &Sealed) -> Result<Vec<u8>, anyhow::Error> {
+        anyhow::ensure!(self.sealed_with(sealed), "sealed with another key");
+        decrypt(&self.key, sealed)
+    }
+
+    // Each call takes a new nonce, the salt stays the same
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Sealed, anyhow::Error> {
+        let mut nonce = [0u8; 12];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&self.key))
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| anyhow!("encrypting failed"))?;
+
+        Ok(Sealed {
+            version: VERSION,
+            kdf: self.kdf,
+            salt: data_encoding::BASE64.encode(&self.salt),
+            nonce: data_encoding::BASE64.encode(&nonce),
+            ciphertext: data_encoding::BASE64.encode(&ciphertext),
+        })
+    }
+}
+
+pub fn seal(plaintext: &[u8], passphrase: &str) -> Result<Sealed, anyhow::Error> {
+    DerivedKey::new(passphrase)?.seal(plaintext)
+}
+
+pub fn open(sealed: &Sealed, passphrase: &str) -> Result<Vec<u8>, anyhow::Error> {
+    check_version(sealed)?;
+    let salt = data_encoding::BASE64.decode(sealed.salt.as_bytes())?;
+    let key = derive_key(passphrase, &salt, sealed.kdf)?;
+    decrypt(&key, sealed)
+}
+
+fn decrypt(key: &[u8; 32], sealed: &Sealed) -> Result<Vec<u8>, anyhow::Error> {
+    let nonce = data_encoding::BASE64.decode(sealed.nonce.as_bytes())?;
+    let ciphertext = data_encoding::BASE64.decode(sealed.ciphertext.as_bytes())?;
+    anyhow::ensure!(nonce.len() == 12, "invalid nonce");
+
+    ChaCha20Poly1305::new(Key::from_slice(key))
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        // Also what a wrong passphrase looks like
+        .map_err(|_| anyhow!("decrypting failed, wrong passphrase?"))
+}
@@ -0,0 +1,87 @@
+use libp2p::identity::PublicKey;
+use sha2::{Digest, Sha256};
+
+// The emoji of a short authentication string, 6 bits each
+const EMOJI: [&str; 64] = [
+    "🐶 dog",
+    "🐱 cat",
+    "🦁 lion",
+    "🐎 horse",
+    "🦄 unicorn",
+    "🐷 pig",
+    "🐘 elephant",
+    "🐰 rabbit",
+    "🐼 panda",
+    "🐓 rooster",
+    "🐧 penguin",
+    "🐢 turtle",
+    "🐟 fish",
+    "🐙 octopus",
+    "🦋 butterfly",
+    "🌷 flower",
+    "🌳 tree",
+    "🌵 cactus",
+    "🍄 mushroom",
+    "🌏 globe",
+    "🌙 moon",
+    "☁ cloud",
+    "🔥 fire",
+    "🍌 banana",
+    "🍎 apple",
+    "🍓 strawberry",
+    "🌽 corn",
+    "🍕 pizza",
+    "🎂 cake",
+    "❤ heart",
+    "😀 smiley",
+    "🤖 robot",
+    "🎩 hat",
+    "👓 glasses",
+    "🔧 spanner",
+    "🎅 santa",
+    "👍 thumbs up",
+    "☂ umbrella",
+    "⌛ hourglass",
+    "⏰ clock",
+    "🎁 gift",
+    "💡 light bulb",
+    "📕 book",
+    "✏ pencil",
+    "📎 paperclip",
+    "✂ scissors",
+    "🔒 lock",
+    "🔑 key",
+    "🔨 hammer",
+    "☎ telephone",
+    "🏁 flag",
+    "🚂 train",
+    "🚲 bicycle",
+    "✈ aeroplane",
+    "🚀 rocket",
+    "🏆 trophy",
+    "⚽ ball",
+    "🎸 guitar",
+    "🎺 trumpet",
+    "🔔 bell",
+    "⚓ anchor",
+    "🎧 headphones",
+    "📁 folder",
+    "📌 pin",
+];
+const SAS_LEN: usize = 7;
+
+// A short authentication string for two identities. Both peers get the same one, so reading it
+// to each other over another channel shows nobody is in between
+pub fn sas(local: &PublicKey, remote: &PublicKey) -> Vec<&'static str> {
+    let mut keys = [local.to_protobuf_encoding(), remote.to_protobuf_encoding()];
+    keys.sort();
+    let hash = Sha256::digest(&keys.concat());
+
+    // The first 42 bits of the hash, 6 per emoji
+    let bits = hash[..6]
+        .iter()
+        .fold(0u64, |bits, byte| (bits << 8) | *byte as u64);
+    (0..SAS_LEN)
+        .map(|i| EMOJI[((bits >> (42 - 6 * i)) & 0x3f) as usize])
+        .collect()
+}
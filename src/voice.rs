@@ -0,0 +1,322 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Instant, SystemTime};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::app::{App, ChatMessage};
+use crate::config::ExternalCommand;
+use crate::conversation::ConversationId;
+use crate::eventlog::{LogCategory, LogLevel};
+use crate::i18n::{trf, Msg};
+use crate::profile;
+use crate::stats;
+use crate::writer;
+
+// Larger clips aren't sent. In topics the gossipsub `max_transmit_size` limits them further
+const MAX_MEMO_BYTES: usize = 512 * 1024;
+// All stored clips together, the oldest are removed to make room for new ones
+const MAX_STORED_BYTES: u64 = 64 * 1024 * 1024;
+const MAX_FORMAT_LEN: usize = 8;
+
+// A recorded audio clip sent with a chat message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoiceMemo {
+    // The file extension, e.g. `ogg`
+    pub format: String,
+    pub duration_secs: u32,
+    // The clip in base64, only while it is on the wire. Clips are kept in the profile directory
+    // and the history only refers to them
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub data: String,
+}
+
+// The recording that is running, only one at a time
+#[derive(Debug, Default)]
+pub struct Recorder {
+    recording: Option<Recording>,
+}
+
+#[derive(Debug)]
+struct Recording {
+    conversation_id: ConversationId,
+    // The memo is sent with this id, which also names its file
+    message_id: Uuid,
+    format: String,
+    path: PathBuf,
+    started: Instant,
+    done: Receiver<io::Result<ExitStatus>>,
+}
+
+// The format ends up in a file name, so it can't be anything else than letters and digits
+pub fn is_valid_format(format: &str) -> bool {
+    !format.is_empty()
+        && format.len() <= MAX_FORMAT_LEN
+        && format.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn path(message_id: Uuid, format: &str) -> Option<PathBuf> {
+    if !is_valid_format(format) {
+        return None;
+    }
+    profile::dir().map(|dir| dir.join("voice").join(format!("{}.{}", message_id, format)))
+}
+
+// E.g. `0:42`
+pub fn format_duration(secs: u32) -> String {
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+fn command(external: &ExternalCommand, path: &Path) -> Command {
+    let file = path.to_string_lossy();
+    let mut command = Command::new(&external.program);
+    command
+        .args(external.args.iter().map(|arg| arg.replace("{file}", &file)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    command
+}
+
+// Starts recording a memo for the current conversation. It is sent once the program stops
+pub fn record(app: &mut App) -> Result<(), anyhow::Error> {
+    anyhow::ensure!(
+        app.recorder.recording.is_none(),
+        "a voice memo is being recorded already"
+    );
+    let settings = app.config.voice_memos.clone();
+    let record = settings
+        .record
+        .context("set `voice_memos.record` in the config to record voice memos")?;
+    anyhow::ensure!(
+        is_valid_format(&settings.format),
+        "invalid voice memo format `{}`",
+        settings.format
+    );
+    let message_id = Uuid::new_v4();
+    let path = path(message_id, &settings.format).context("no config directory available")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("creating directory {:?} failed", parent))?;
+    }
+
+    let mut child = command(&record, &path)
+        .spawn()
+        .with_context(|| format!("starting `{}` failed", record.program))?;
+    // Waiting happens on another thread, the tick checks whether it finished
+    let (done_tx, done) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = done_tx.send(child.wait());
+    });
+    app.recorder.recording = Some(Recording {
+        conversation_id: app.ui.current_conversation.clone(),
+        message_id,
+        format: settings.format,
+        path,
+        started: Instant::now(),
+        done,
+    });
+    app.connection.push_log(
+        LogLevel::Info,
+        LogCategory::Messages,
+        &trf(Msg::LogVoiceRecording, &[&record.program]),
+    );
+    Ok(())
+}
+
+// Called every tick, sends the memo once the recording program stopped
+pub fn check(app: &mut App) {
+    let result = match app
+        .recorder
+        .recording
+        .as_ref()
+        .map(|recording| recording.done.try_recv())
+    {
+        None | Some(Err(TryRecvError::Empty)) => return,
+        Some(Ok(result)) => result,
+        Some(Err(TryRecvError::Disconnected)) => Err(io::Error::new(
+            io::ErrorKind::Other,
+            "the recording thread stopped",
+        )),
+    };
+    let recording = match app.recorder.recording.take() {
+        Some(recording) => recording,
+        None => return,
+    };
+    if let Err(e) = send(recording, result, app) {
+        app.connection.push_log(
+            LogLevel::Error,
+            LogCategory::Messages,
+            &trf(Msg::LogVoiceMemoFailed, &[&e]),
+        );
+    }
+}
+
+fn send(
+    recording: Recording,
+    result: io::Result<ExitStatus>,
+    app: &mut App,
+) -> Result<(), anyhow::Error> {
+    let status = result.context("waiting for the recording program failed")?;
+    anyhow::ensure!(
+        status.success(),
+        "the recording program failed with {}",
+        status
+    );
+    let len = fs::metadata(&recording.path)
+        .with_context(|| format!("reading the recording {:?} failed", recording.path))?
+        .len() as usize;
+    anyhow::ensure!(len > 0, "the recording is empty");
+    // base64 takes a third more
+    let limit = match recording.conversation_id {
+        ConversationId::Topic(_) => {
+            MAX_MEMO_BYTES.min(app.config.gossipsub.max_transmit_size * 3 / 4)
+        }
+        ConversationId::Direct(_) => MAX_MEMO_BYTES,
+    };
+    anyhow::ensure!(
+        len <= limit,
+        "the recording takes {}, at most {} can be sent",
+        stats::format_bytes(len as u64),
+        stats::format_bytes(limit as u64)
+    );
+
+    let memo = VoiceMemo {
+        format: recording.format,
+        duration_secs: recording.started.elapsed().as_secs() as u32,
+        data: String::new(),
+    };
+    app.send_voice_memo(recording.conversation_id, recording.message_id, memo);
+    Ok(())
+}
+
+// Adds the clip of one of our memos before sending it
+pub fn attach(message: &mut ChatMessage) -> Result<(), anyhow::Error> {
+    let memo = match message.voice_memo.as_mut() {
+        Some(memo) if memo.data.is_empty() => memo,
+        _ => return Ok(()),
+    };
+    let path = path(message.id, &memo.format).context("no config directory available")?;
+    let data = fs::read(&path).with_context(|| format!("reading the clip {:?} failed", path))?;
+    memo.data = data_encoding::BASE64.encode(&data);
+    Ok(())
+}
+
+// Saves the clip of a received memo, so the history only refers to it. A clip that is stored
+// already is only replaced with `replace`
+pub fn store(message: &mut ChatMessage, replace: bool) -> Result<(), anyhow::Error> {
+    let memo = match message.voice_memo.as_mut() {
+        Some(memo) if !memo.data.is_empty() => memo,
+        _ => return Ok(()),
+    };
+    let data = std::mem::take(&mut memo.data);
+    let data = data_encoding::BASE64
+        .decode(data.as_bytes())
+        .context("decoding the clip failed")?;
+    let path = path(message.id, &memo.format).with_context(|| {
+        format!(
+            "no config directory available or invalid format `{}`",
+            memo.format
+        )
+    })?;
+    if path.exists() && !replace {
+        return Ok(());
+    }
+    if let Some(dir) = path.parent() {
+        make_room(dir, data.len() as u64)?;
+    }
+    writer::write(&path, data, true).with_context(|| format!("writing the clip {:?} failed", path))
+}
+
+// Drops the clip of a received memo without storing it
+pub fn discard(message: &mut ChatMessage) {
+    if let Some(memo) = message.voice_memo.as_mut() {
+        memo.data.clear();
+    }
+}
+
+// Removes the oldest clips until `len` more bytes fit into `MAX_STORED_BYTES`
+fn make_room(dir: &Path, len: u64) -> Result<(), anyhow::Error> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        // Nothing stored yet
+        Err(_) => return Ok(()),
+    };
+    let mut clips = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some((metadata.modified().ok()?, metadata.len(), entry.path()))
+        })
+        .collect::<Vec<(SystemTime, u64, PathBuf)>>();
+    clips.sort();
+    let mut total = len + clips.iter().map(|(_, len, _)| len).sum::<u64>();
+    for (_, len, path) in clips {
+        if total <= MAX_STORED_BYTES {
+            break;
+        }
+        writer::remove(&path)?;
+        total -= len;
+    }
+    Ok(())
+}
+
+// Removes the clips of memos whose messages are gone
+pub fn remove(messages: &[ChatMessage], app: &mut App) {
+    for message in messages {
+        let path = match message
+            .voice_memo
+            .as_ref()
+            .and_then(|memo| path(message.id, &memo.format))
+        {
+            Some(path) => path,
+            None => continue,
+        };
+        if let Err(e) = writer::remove(&path) {
+            app.connection.push_log(
+                LogLevel::Error,
+                LogCategory::Messages,
+                &trf(Msg::LogVoiceMemoFailed, &[&e]),
+            );
+        }
+    }
+}
+
+// Plays the clip of a memo with the configured player, without waiting for it
+pub fn play(message: &ChatMessage, app: &mut App) -> Result<(), anyhow::Error> {
+    let memo = match message.voice_memo.as_ref() {
+        Some(memo) => memo,
+        None => return Ok(()),
+    };
+    let play = app
+        .config
+        .voice_memos
+        .play
+        .clone()
+        .context("set `voice_memos.play` in the config to play voice memos")?;
+    let path = path(message.id, &memo.format)
+        .filter(|path| path.exists())
+        .context("the clip of this voice memo isn't available")?;
+
+    let mut child = command(&play, &path)
+        .spawn()
+        .with_context(|| format!("starting `{}` failed", play.program))?;
+    thread::spawn(move || {
+        let _ = child.wait();
+    });
+    app.connection.push_log(
+        LogLevel::Info,
+        LogCategory::Messages,
+        &trf(
+            Msg::LogVoicePlaying,
+            &[&format_duration(memo.duration_secs)],
+        ),
+    );
+    Ok(())
+}
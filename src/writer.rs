@@ -0,0 +1,217 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+
+use crate::config::{DiskSettings, FsyncPolicy};
+
+// Set by `start`, until then files are written right away
+static QUEUE: Mutex<Option<Sender<Request>>> = Mutex::new(None);
+// Nobody waits for queued writes, so their errors are collected for the event loop to show
+static FAILURES: Mutex<Failures> = Mutex::new(Failures {
+    failing: BTreeSet::new(),
+    unreported: Vec::new(),
+});
+
+struct Failures {
+    // Reported once until writing the file works again
+    failing: BTreeSet<PathBuf>,
+    unreported: Vec<(PathBuf, String)>,
+}
+
+enum Contents {
+    Ready(Vec<u8>),
+    // Made on the writer thread, for files that take a while to serialize or encrypt. Skipped if
+    // a newer write of the same file replaced it
+    Deferred(Box<dyn FnOnce() -> Result<Vec<u8>, anyhow::Error> + Send>),
+}
+
+struct PendingWrite {
+    contents: Contents,
+    // Only readable by us, for files with history or secrets
+    private: bool,
+}
+
+enum Request {
+    // `None` removes the file
+    Write(PathBuf, Option<PendingWrite>),
+    // Answered once everything queued before it is on disk
+    Flush(Sender<()>),
+}
+
+// Writes files on a background thread, so saving never blocks the event loop. Writes to the same
+// file within `batch_ms` are combined, only the last one hits the disk
+pub fn start(settings: DiskSettings) {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || run(rx, settings));
+    *QUEUE.lock().unwrap() = Some(tx);
+}
+
+// Queues the new contents of a file, or writes it right away before `start`. Errors of queued
+// writes are only logged
+pub fn write(
+    path: &Path,
+    contents: impl Into<Vec<u8>>,
+    private: bool,
+) -> Result<(), anyhow::Error> {
+    queue_write(
+        path,
+        PendingWrite {
+            contents: Contents::Ready(contents.into()),
+            private,
+        },
+    )
+}
+
+// Like `write`, but the contents are made on the writer thread
+pub fn write_with(
+    path: &Path,
+    contents: impl FnOnce() -> Result<Vec<u8>, anyhow::Error> + Send + 'static,
+    private: bool,
+) -> Result<(), anyhow::Error> {
+    queue_write(
+        path,
+        PendingWrite {
+            contents: Contents::Deferred(Box::new(contents)),
+            private,
+        },
+    )
+}
+
+fn queue_write(path: &Path, write: PendingWrite) -> Result<(), anyhow::Error> {
+    if let Some(queue) = QUEUE.lock().unwrap().as_ref() {
+        return queue
+            .send(Request::Write(path.to_path_buf(), Some(write)))
+            .map_err(|_| anyhow::anyhow!("the writer thread stopped"));
+    }
+    write_file(path, write, FsyncPolicy::Always)
+}
+
+// Queues removing a file, after the writes queued before
+pub fn remove(path: &Path) -> Result<(), anyhow::Error> {
+    if let Some(queue) = QUEUE.lock().unwrap().as_ref() {
+        return queue
+            .send(Request::Write(path.to_path_buf(), None))
+            .map_err(|_| anyhow::anyhow!("the writer thread stopped"));
+    }
+    remove_file(path)
+}
+
+// The files whose queued writes failed since the last call, with the error
+pub fn take_failures() -> Vec<(PathBuf, String)> {
+    std::mem::take(&mut FAILURES.lock().unwrap().unreported)
+}
+
+// Whether the last queued write of a file failed, for callers that `flush` and have to know
+pub fn is_failing(path: &Path) -> bool {
+    FAILURES.lock().unwrap().failing.contains(path)
+}
+
+fn record_result(path: PathBuf, result: Result<(), anyhow::Error>) {
+    let mut failures = FAILURES.lock().unwrap();
+    match result {
+        Ok(()) => {
+            failures.failing.remove(&path);
+        }
+        Err(e) => {
+            log::error!("writing {:?} failed with Err `{}`", path, e);
+            if failures.failing.insert(path.clone()) {
+                failures.unreported.push((path, format!("{:#}", e)));
+            }
+        }
+    }
+}
+
+// Waits until everything queued so far is written, e.g. before quitting
+pub fn flush() {
+    let (tx, rx) = mpsc::channel();
+    let sent = QUEUE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map_or(false, |queue| queue.send(Request::Flush(tx)).is_ok());
+    if sent {
+        let _ = rx.recv();
+    }
+}
+
+fn run(rx: Receiver<Request>, settings: DiskSettings) {
+    let batch = Duration::from_millis(settings.batch_ms);
+    while let Ok(request) = rx.recv() {
+        let mut pending = BTreeMap::new();
+        let mut flushes = vec![];
+        let mut request = Some(request);
+        let deadline = Instant::now() + batch;
+        while let Some(next) = request.take() {
+            match next {
+                Request::Write(path, write) => {
+                    pending.insert(path, write);
+                }
+                Request::Flush(done) => {
+                    flushes.push(done);
+                    break;
+                }
+            }
+            request = rx
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .ok();
+        }
+
+        for (path, write) in pending {
+            let result = match write {
+                Some(write) => write_file(&path, write, settings.fsync),
+                None => remove_file(&path),
+            };
+            record_result(path, result);
+        }
+        for done in flushes {
+            let _ = done.send(());
+        }
+    }
+}
+
+// Writes next to the file and renames it over the old one, so a crash never leaves half a file
+fn write_file(path: &Path, write: PendingWrite, fsync: FsyncPolicy) -> Result<(), anyhow::Error> {
+    let contents = match write.contents {
+        Contents::Ready(contents) => contents,
+        Contents::Deferred(make) => make()?,
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let mut file =
+        File::create(&tmp_path).with_context(|| format!("creating {:?} failed", tmp_path))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if write.private {
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    file.write_all(&contents)
+        .with_context(|| format!("writing {:?} failed", tmp_path))?;
+    if fsync == FsyncPolicy::Always {
+        file.sync_all()
+            .with_context(|| format!("syncing {:?} failed", tmp_path))?;
+    }
+    fs::rename(&tmp_path, path).with_context(|| format!("replacing {:?} failed", path))?;
+
+    Ok(())
+}
+
+fn remove_file(path: &Path) -> Result<(), anyhow::Error> {
+    if path.exists() {
+        fs::remove_file(path).with_context(|| format!("removing {:?} failed", path))?;
+    }
+    Ok(())
+}
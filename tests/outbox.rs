@@ -0,0 +1,41 @@
+use futures::StreamExt;
+use p2pchat::app::App;
+use p2pchat::config::Config;
+use p2pchat::connection::{self, ConnectionCommand};
+use p2pchat::input::InputTask;
+use p2pchat::profile;
+
+// Commands pushed to the outbox only change the swarm once the event loop takes them from the
+// queue, in the order they were pushed
+#[tokio::test]
+async fn queued_commands_are_run_in_order() {
+    // Keeps the test away from the profiles of the user running it, and from the network
+    let config_dir = std::env::temp_dir().join(format!("p2pchat-test-{}", std::process::id()));
+    profile::set_base_dir(config_dir.clone());
+    profile::set(Some(String::from("outbox-test"))).unwrap();
+    let config = Config {
+        outbound_only: true,
+        port_mapping: false,
+        ..Config::default()
+    };
+
+    let mut app = App::new(config).await.unwrap();
+    app.connection
+        .outbox
+        .push(ConnectionCommand::Subscribe(String::from("outbox-test")));
+    app.connection.outbox.push(ConnectionCommand::Shutdown);
+    assert!(!app.connection.topics.is_subscribed("outbox-test"));
+
+    // What the command branch of the event loop does
+    let mut handled = 0;
+    while let Some(command) = app.connection.outbox.rx.next().await {
+        handled += 1;
+        if let InputTask::Quit = connection::handle_connection_command(command, &mut app) {
+            break;
+        }
+    }
+
+    assert_eq!(handled, 2);
+    assert!(app.connection.topics.is_subscribed("outbox-test"));
+    let _ = std::fs::remove_dir_all(config_dir);
+}